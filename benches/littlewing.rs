@@ -11,6 +11,7 @@ use littlewing::fen::FEN;
 use littlewing::game::Game;
 use littlewing::piece_move_generator::PieceMoveGenerator;
 use littlewing::piece_move_notation::PieceMoveNotation;
+use littlewing::positions::test_suite;
 use littlewing::search::Search;
 
 #[bench]
@@ -98,6 +99,14 @@ fn bench_perft(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn bench_perft_kiwipete(b: &mut Bencher) {
+    let mut game = Game::from_fen(test_suite::KIWIPETE).unwrap();
+    b.iter(|| {
+        game.perft(2)
+    });
+}
+
 #[bench]
 fn bench_move_from_lan(b: &mut Bencher) {
     let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
@@ -2,11 +2,20 @@ extern crate littlewing;
 extern crate colored;
 extern crate getopts;
 extern crate atty;
+extern crate dirs;
 
 use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::panic;
+use std::process;
+use std::time::Instant;
 use atty::Stream;
 use getopts::Options;
 use colored::Colorize;
+use littlewing::crash;
+use littlewing::options::Options as EngineOptions;
+use littlewing::options::DEFAULT_PROFILE;
 use littlewing::protocols::cli::CLI;
 
 fn print_usage(opts: Options) {
@@ -36,7 +45,40 @@ fn print_banner(mut board: String) {
     println!("{}", board);
 }
 
+// Print the context of the search to stderr and append it to a log file
+// before exiting on a panic, for bug reports from GUIs hiding the output
+fn set_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let mut report = format!("{}\n", littlewing::version());
+        report.push_str(&format!("panic: {}\n", info));
+        if let Some(context) = crash::context() {
+            report.push_str(&context.to_string());
+        }
+        eprint!("{}", report);
+
+        if let Some(dir) = dirs::data_dir().map(|dir| dir.join("littlewing")) {
+            let path = dir.join("crash.log");
+            let file = fs::create_dir_all(&dir).and_then(|_| {
+                OpenOptions::new().create(true).append(true).open(&path)
+            });
+            if let Ok(mut file) = file {
+                if writeln!(file, "{}", report).is_ok() {
+                    eprintln!("crash report saved to '{}'", path.display());
+                }
+            }
+        }
+
+        // Exit now instead of waiting for a panicking search thread
+        process::exit(101);
+    }));
+}
+
 fn main() {
+    set_panic_hook();
+
     let mut cli = CLI::new();
 
     if !atty::is(Stream::Stdout) {
@@ -49,6 +91,7 @@ fn main() {
     opts.optflag("h", "help",    "print this message");
     opts.optflag("s", "silent",  "display less output");
     opts.optflag("v", "version", "print version");
+    opts.optflag("w", "warmup",  "print initialization time");
 
     let args: Vec<String> = env::args().collect();
     let matches = match opts.parse(&args) {
@@ -66,6 +109,15 @@ fn main() {
         return;
     }
 
+    // Initialize the tables now to avoid slowing down the first search
+    let started_at = Instant::now();
+    littlewing::init();
+    if matches.opt_present("w") {
+        let time = started_at.elapsed().as_secs_f64() * 1000.0;
+        println!("initialization: {:.2} ms", time);
+        return;
+    }
+
     if !matches.opt_present("s") {
         cli.show_board = true;
         cli.game.show_coordinates = true;
@@ -76,6 +128,10 @@ fn main() {
         cli.game.is_debug = true;
     }
 
+    if let Err(e) = cli.game.load_options(DEFAULT_PROFILE) {
+        println!("# {} {}", "error:".bold().red(), e.to_string().to_lowercase());
+    }
+
     if matches.opt_present("t") {
         if let Some(size) = matches.opt_str("t") {
             let memory = size.parse::<usize>().unwrap() << 20;
@@ -1,11 +1,16 @@
+use std::sync::LazyLock;
+
 use color::*;
 use piece::*;
 use square::*;
 use common::*;
-use bitboard::{Bitboard, BitboardExt};
+use bitboard::{Bitboard, BitboardExt, BitboardIterator};
 use game::Game;
-use hyperbola::bishop_attacks;
-use hyperbola::rook_attacks;
+use piece_move::PieceMove;
+use magic::bishop_attacks;
+use magic::rook_attacks;
+//use hyperbola::bishop_attacks;
+//use hyperbola::rook_attacks;
 //use dumb7fill::bishop_attacks;
 //use dumb7fill::rook_attacks;
 
@@ -13,52 +18,36 @@ pub trait Attack {
     fn is_check(&self, side: Color) -> bool;
     fn is_attacked(&self, square: Square, side: Color) -> bool;
     fn attacks_to(&self, square: Square, occupied: Bitboard) -> Bitboard;
+    fn attacks_by(&self, side: Color, occupied: Bitboard) -> Bitboard;
+    fn is_legal_en_passant(&self, m: PieceMove) -> bool;
+    fn gives_check(&self, m: PieceMove) -> bool;
 }
 
 impl Attack for Game {
     fn is_check(&self, side: Color) -> bool {
-        let king = self.bitboards[(side | KING) as usize];
-        king == 0 || self.is_attacked(king.scan() as Square, side)
-    }
-
-    fn is_attacked(&self, square: Square, side: Color) -> bool {
-        let bbs = &self.bitboards;
-
-        let occupied = bbs[WHITE as usize] | bbs[BLACK as usize];
-
-        let pawns = bbs[(side ^ 1 | PAWN) as usize];
-        let attacks = PAWN_ATTACKS[side as usize][square as usize];
-        if attacks & pawns > 0 {
-            return true;
-        }
-
-        let knights = bbs[(side ^ 1 | KNIGHT) as usize];
-        let attacks = PIECE_MASKS[KNIGHT as usize][square as usize];
-        if attacks & knights > 0 {
-            return true;
+        if !self.variant.has_royal_king() {
+            return false;
         }
 
-        let king = bbs[(side ^ 1 | KING) as usize];
-        let attacks = PIECE_MASKS[KING as usize][square as usize];
-        if attacks & king > 0 {
+        let king = self.bitboards[(side | KING) as usize];
+        if king == 0 {
             return true;
         }
 
-        let queens = bbs[(side ^ 1 | QUEEN) as usize];
-
-        let bishops = bbs[(side ^ 1 | BISHOP) as usize];
-        let attacks = bishop_attacks(square, occupied);
-        if attacks & (bishops | queens) > 0 {
-            return true;
+        // In Atomic the king cannot be captured next to the other king, and
+        // the game is won when the other king explodes
+        if self.variant.has_explosions() {
+            let opponent_king = self.bitboards[(side ^ 1 | KING) as usize];
+            if opponent_king == 0 || PIECE_MASKS[KING as usize][king.scan() as usize] & opponent_king != 0 {
+                return false;
+            }
         }
 
-        let rooks = bbs[(side ^ 1 | ROOK) as usize];
-        let attacks = rook_attacks(square, occupied);
-        if attacks & (rooks | queens) > 0 {
-            return true;
-        }
+        self.is_attacked(king.scan() as Square, side)
+    }
 
-        false
+    fn is_attacked(&self, square: Square, side: Color) -> bool {
+        is_attacked_on(&self.bitboards, square, side)
     }
 
     fn attacks_to(&self, square: Square, occupied: Bitboard) -> Bitboard {
@@ -80,6 +69,148 @@ impl Attack for Game {
         ((queens | bishops) & piece_attacks(BISHOP,     square, occupied)) |
         ((queens | rooks)   & piece_attacks(ROOK,       square, occupied))
     }
+
+    /// Get the squares attacked by the pieces of the given side
+    fn attacks_by(&self, side: Color, occupied: Bitboard) -> Bitboard {
+        let mut attacks = 0;
+        for &kind in &PIECES {
+            let piece = side | kind;
+            let mut pieces = self.bitboards[piece as usize];
+            while let Some(square) = pieces.next() {
+                attacks |= piece_attacks(piece, square, occupied);
+            }
+        }
+        attacks
+    }
+
+    /// Check if the given en passant capture leaves the king of the side to
+    /// move out of check, without playing it and without the explosions
+    /// of Atomic
+    ///
+    /// Both pawns leave the rank of the capture at once, which can expose
+    /// the king to a rook or a queen on that rank even when none of the two
+    /// pawns is pinned on its own.
+    fn is_legal_en_passant(&self, m: PieceMove) -> bool {
+        let side = self.side();
+        let king = self.bitboards[(side | KING) as usize];
+        if king == 0 || !self.variant.has_royal_king() {
+            return true;
+        }
+
+        let captured = m.to() ^ 8;
+        let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
+        let occupied = (occupied & !(1 << m.from()) & !(1 << captured)) | (1 << m.to());
+        let attackers = self.attacks_to(king.scan() as Square, occupied) & !(1 << captured);
+
+        attackers & self.bitboards[(side ^ 1) as usize] == 0
+    }
+
+    /// Check if the given move of the side to move gives check, without
+    /// playing it, by updating a copy of the bitboards
+    fn gives_check(&self, m: PieceMove) -> bool {
+        if m.is_null() || !self.variant.has_royal_king() {
+            return false;
+        }
+
+        let side = self.side();
+        let mut bbs = self.bitboards;
+        let to = m.to();
+        if m.is_drop() {
+            let p = side | m.drop_kind();
+            bbs[p as usize].set(to);
+            bbs[side as usize].set(to);
+        } else {
+            let from = m.from();
+            let piece = self.board[from as usize];
+            let capture = if m.is_castle() { EMPTY } else { self.board[to as usize] };
+            bbs[piece as usize].reset(from);
+            bbs[side as usize].reset(from);
+            if capture != EMPTY {
+                bbs[capture as usize].reset(to);
+                bbs[(side ^ 1) as usize].reset(to);
+            }
+            if m.is_en_passant() {
+                let sq = to ^ 8; // The captured pawn is behind the destination
+                bbs[(side ^ 1 | PAWN) as usize].reset(sq);
+                bbs[(side ^ 1) as usize].reset(sq);
+            }
+            if m.is_castle() {
+                let rook = side | ROOK;
+                let rook_from = self.positions.top().castling_rook(side, m.castle_kind());
+                let rook_to = if m.castle_kind() == KING { F1 } else { D1 }.flip(side);
+                bbs[rook as usize].reset(rook_from);
+                bbs[side as usize].reset(rook_from);
+                bbs[rook as usize].set(rook_to);
+                bbs[side as usize].set(rook_to);
+            }
+            let p = if m.is_promotion() { side | m.promotion_kind() } else { piece };
+            bbs[p as usize].set(to);
+            bbs[side as usize].set(to);
+
+            // The capturing piece explodes with the pieces around except
+            // the pawns in Atomic
+            if self.variant.has_explosions() && (capture != EMPTY || m.is_en_passant()) {
+                let occupied = bbs[WHITE as usize] | bbs[BLACK as usize];
+                let pawns = bbs[WHITE_PAWN as usize] | bbs[BLACK_PAWN as usize];
+                let around = PIECE_MASKS[KING as usize][to as usize] & occupied & !pawns;
+                let exploded = around | (1 << to);
+                for bb in bbs.iter_mut() {
+                    *bb &= !exploded;
+                }
+            }
+        }
+
+        // Same rules as `is_check` for the kings after the move
+        let king = bbs[(side ^ 1 | KING) as usize];
+        if king == 0 {
+            return true;
+        }
+        if self.variant.has_explosions() && PIECE_MASKS[KING as usize][king.scan() as usize] & bbs[(side | KING) as usize] != 0 {
+            return false;
+        }
+
+        is_attacked_on(&bbs, king.scan() as Square, side ^ 1)
+    }
+}
+
+// Check if the given square is attacked by the opponent of the given side
+// on the given bitboards
+fn is_attacked_on(bbs: &[Bitboard; 14], square: Square, side: Color) -> bool {
+    let occupied = bbs[WHITE as usize] | bbs[BLACK as usize];
+
+    let pawns = bbs[(side ^ 1 | PAWN) as usize];
+    let attacks = PAWN_ATTACKS[side as usize][square as usize];
+    if attacks & pawns > 0 {
+        return true;
+    }
+
+    let knights = bbs[(side ^ 1 | KNIGHT) as usize];
+    let attacks = PIECE_MASKS[KNIGHT as usize][square as usize];
+    if attacks & knights > 0 {
+        return true;
+    }
+
+    let king = bbs[(side ^ 1 | KING) as usize];
+    let attacks = PIECE_MASKS[KING as usize][square as usize];
+    if attacks & king > 0 {
+        return true;
+    }
+
+    let queens = bbs[(side ^ 1 | QUEEN) as usize];
+
+    let bishops = bbs[(side ^ 1 | BISHOP) as usize];
+    let attacks = bishop_attacks(square, occupied);
+    if attacks & (bishops | queens) > 0 {
+        return true;
+    }
+
+    let rooks = bbs[(side ^ 1 | ROOK) as usize];
+    let attacks = rook_attacks(square, occupied);
+    if attacks & (rooks | queens) > 0 {
+        return true;
+    }
+
+    false
 }
 
 /// Return the attacks bitboard of a piece attacks to a square
@@ -95,28 +226,28 @@ pub fn piece_attacks(piece: Piece, square: Square, occupied: Bitboard) -> Bitboa
     }
 }
 
-lazy_static! {
-    pub static ref PAWN_ATTACKS: [[Bitboard; 64]; 2] = {
-        let xdirs = [LEFT, RIGHT];
-        let ydirs = [DOWN, UP];
-        let files = [FILE_H, FILE_A];
-        let mut attacks = [[0; 64]; 2];
-        for side in 0..2 {
-            for square in 0..64 {
-                for i in 0..2 {
-                    let dir = ydirs[side ^ 1] + xdirs[i];
-                    attacks[side][square] |= (1 << square).shift(dir) & !files[i];
-                }
+pub static PAWN_ATTACKS: LazyLock<[[Bitboard; 64]; 2]> = LazyLock::new(|| {
+    let xdirs = [LEFT, RIGHT];
+    let ydirs = [DOWN, UP];
+    let files = [FILE_H, FILE_A];
+    let mut attacks = [[0; 64]; 2];
+    for side in 0..2 {
+        for square in 0..64 {
+            for i in 0..2 {
+                let dir = ydirs[side ^ 1] + xdirs[i];
+                attacks[side][square] |= (1 << square).shift(dir) & !files[i];
             }
         }
-        attacks
-    };
-}
+    }
+    attacks
+});
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use variant::Variant;
     use fen::FEN;
+    use piece_move_generator::PieceMoveGenerator;
 
     #[test]
     fn test_piece_attacks() {
@@ -150,6 +281,95 @@ mod tests {
         assert_eq!(defended.scan() as Square, C2);
     }
 
+    #[test]
+    fn test_is_check_atomic() {
+        let mut game = Game::new();
+        game.variant = Variant::Atomic;
+
+        // Kings next to each other
+        game.load_fen("8/8/8/8/8/3k4/3K3r/8 w - - 0 1").unwrap();
+        assert!(!game.is_check(WHITE));
+        game.variant = Variant::Standard;
+        assert!(game.is_check(WHITE));
+        game.variant = Variant::Atomic;
+
+        // Exploded kings
+        game.load_fen("8/8/8/8/8/8/3K3r/8 w - - 0 1").unwrap();
+        assert!(!game.is_check(WHITE));
+        assert!(game.is_check(BLACK));
+    }
+
+    #[test]
+    fn test_is_legal_en_passant() {
+        let m = PieceMove::new(B5, C6, EN_PASSANT);
+
+        // Both pawns leaving the rank of the king and the rook
+        let game = Game::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(!game.is_legal_en_passant(m));
+        let game = Game::from_fen("8/8/8/qPp3K1/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(!game.is_legal_en_passant(m));
+        let game = Game::from_fen("8/8/8/KPp1p2r/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(game.is_legal_en_passant(m)); // Another blocker
+
+        // Captured pawn pinned on a diagonal
+        let game = Game::from_fen("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1").unwrap();
+        assert!(game.is_legal_en_passant(PieceMove::new(C4, D3, EN_PASSANT)));
+        let game = Game::from_fen("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1").unwrap();
+        assert!(!game.is_legal_en_passant(PieceMove::new(C5, D6, EN_PASSANT)));
+
+        // Capture of the checking pawn
+        let game = Game::from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        assert!(game.is_legal_en_passant(PieceMove::new(E4, D3, EN_PASSANT)));
+    }
+
+    #[test]
+    fn test_gives_check() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",      // Check by castling
+            "8/8/8/K2pP2q/8/8/8/7k w - d6 0 1",    // Discovered en passant
+            "3k4/1P6/8/8/8/8/8/4K3 w - - 0 1",     // Check by promotion
+            "3k4/8/8/8/3B4/8/3R4/4K3 w - - 0 1",   // Discovered checks
+            "3k4/8/8/8/8/8/8/4K3[QNp] w - - 0 1"   // Checks by drops
+        ];
+        for &variant in &[Variant::Standard, Variant::Atomic, Variant::Crazyhouse] {
+            for fen in &fens {
+                if fen.contains('[') && !variant.has_drops() {
+                    continue;
+                }
+                let mut game = Game::new();
+                game.variant = variant;
+                game.load_fen(fen).unwrap();
+                let side = game.side();
+                game.moves.clear();
+                while let Some(m) = game.next_move() {
+                    let gives_check = game.gives_check(m);
+                    game.make_move(m);
+                    if !game.is_check(side) {
+                        assert_eq!(gives_check, game.is_check(side ^ 1), "{} {}", fen, m.to_lan());
+                    }
+                    game.undo_move(m);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_attacks_by() {
+        let game = Game::from_fen("7k/8/8/8/8/8/1p6/N3r2K w - - 0 1").unwrap();
+        let occupied = game.bitboards[WHITE as usize] | game.bitboards[BLACK as usize];
+        let pawn = (1 << A1) | (1 << C1);
+        let king = (1 << G8) | (1 << G7) | (1 << H7);
+        let rook = (0xFF & !(1 << E1)) | 0x1010101010101000;
+        assert_eq!(game.attacks_by(BLACK, occupied), pawn | king | rook);
+
+        let knight = (1 << B3) | (1 << C2);
+        let king = (1 << G1) | (1 << G2) | (1 << H2);
+        assert_eq!(game.attacks_by(WHITE, occupied), knight | king);
+    }
+
     #[test]
     fn test_bishop_attacks() {
         let fen = "r1bqk1nr/ppppbppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
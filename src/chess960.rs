@@ -0,0 +1,107 @@
+use std::error::Error;
+
+use color::*;
+use piece::*;
+use piece::PieceChar;
+
+/// Number of start positions in Chess960
+pub const CHESS960_POSITIONS_COUNT: u16 = 960;
+
+/// Scharnagl number of the start position of standard chess
+pub const STANDARD_ID: u16 = 518;
+
+// Placements of the knights on the five squares left empty by the bishops
+// and the queen
+const KNIGHTS_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)
+];
+
+/// Get the pieces of the first rank, from file A to file H, of the Chess960
+/// start position with the given Scharnagl number
+pub fn back_rank(id: u16) -> Option<[Piece; 8]> {
+    if id >= CHESS960_POSITIONS_COUNT {
+        return None;
+    }
+
+    let mut rank = [EMPTY; 8];
+    let mut n = id as usize;
+
+    rank[2 * (n % 4) + 1] = BISHOP; // Light square
+    n /= 4;
+    rank[2 * (n % 4)] = BISHOP; // Dark square
+    n /= 4;
+    put_on_empty(&mut rank, n % 6, QUEEN);
+    n /= 6;
+
+    // The knights are placed together to count the empty squares before
+    // any of them is put on the rank
+    let (i, j) = KNIGHTS_PLACEMENTS[n];
+    put_on_empty(&mut rank, j, KNIGHT);
+    put_on_empty(&mut rank, i, KNIGHT);
+
+    // The king goes between the rooks on the three squares left
+    put_on_empty(&mut rank, 0, ROOK);
+    put_on_empty(&mut rank, 0, KING);
+    put_on_empty(&mut rank, 0, ROOK);
+
+    Some(rank)
+}
+
+// Put the piece on the nth empty square of the rank
+fn put_on_empty(rank: &mut [Piece; 8], n: usize, piece: Piece) {
+    let file = (0..8).filter(|&file| rank[file] == EMPTY).nth(n).unwrap();
+    rank[file] = piece;
+}
+
+/// Get the Shredder-FEN of the Double Fischer Random Chess start position
+/// with the given Scharnagl numbers for each side, identical for Chess960
+pub fn start_fen(white_id: u16, black_id: u16) -> Result<String, Box<dyn Error>> {
+    let white_rank = back_rank(white_id).ok_or_else(|| format!("invalid chess960 id '{}'", white_id))?;
+    let black_rank = back_rank(black_id).ok_or_else(|| format!("invalid chess960 id '{}'", black_id))?;
+
+    let white_pieces: String = white_rank.iter().map(|&p| (WHITE | p).to_char()).collect();
+    let black_pieces: String = black_rank.iter().map(|&p| (BLACK | p).to_char()).collect();
+
+    // Castling rights with the files of the rooks, king side first
+    let mut castling = String::new();
+    for (rank, offset) in [(white_rank, b'A'), (black_rank, b'a')].iter() {
+        for file in (0..8).rev().filter(|&file| rank[file] == ROOK) {
+            castling.push((offset + file as u8) as char);
+        }
+    }
+
+    Ok(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w {} - 0 1", black_pieces, white_pieces, castling))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_rank() {
+        assert_eq!(back_rank(STANDARD_ID), Some([ROOK, KNIGHT, BISHOP, QUEEN, KING, BISHOP, KNIGHT, ROOK]));
+        assert_eq!(back_rank(0), Some([BISHOP, BISHOP, QUEEN, KNIGHT, KNIGHT, ROOK, KING, ROOK]));
+        assert_eq!(back_rank(959), Some([ROOK, KING, ROOK, KNIGHT, KNIGHT, QUEEN, BISHOP, BISHOP]));
+        assert_eq!(back_rank(960), None);
+
+        for id in 0..CHESS960_POSITIONS_COUNT {
+            let rank = back_rank(id).unwrap();
+            let files = |piece| (0..8).filter(|&file| rank[file] == piece).collect::<Vec<usize>>();
+            let bishops = files(BISHOP);
+            let rooks = files(ROOK);
+            let king = files(KING)[0];
+            assert_eq!(bishops.len(), 2);
+            assert_ne!(bishops[0] % 2, bishops[1] % 2);
+            assert!(rooks[0] < king && king < rooks[1]);
+            assert_eq!(files(KNIGHT).len(), 2);
+            assert_eq!(files(QUEEN).len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_start_fen() {
+        assert_eq!(start_fen(STANDARD_ID, STANDARD_ID).unwrap(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+        assert_eq!(start_fen(0, 959).unwrap(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFca - 0 1");
+        assert!(start_fen(0, 960).is_err());
+    }
+}
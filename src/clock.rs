@@ -1,7 +1,14 @@
+use std::error::Error;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Instant;
 
+use color::*;
+
+/// Remaining time (in ms) under which the search only looks at a few moves
+/// to play immediately
+pub const TIME_PRESSURE: u64 = 100;
+
 #[derive(Clone)]
 pub struct Clock {
     pub polling_nodes_count: u64,
@@ -10,7 +17,12 @@ pub struct Clock {
     moves_remaining: u16,
     time_remaining: u64,
     last_nodes_count: u64,
+    nodes_limit: Option<u64>,
     is_finished: Arc<AtomicBool>,
+    is_pondering: Arc<AtomicBool>,
+    ponder_time: Arc<AtomicU64>, // Time elapsed (in ms) before the ponderhit
+    is_infinite: bool,
+    is_fixed_time: bool,
     is_level: bool // TODO: find a better name
 }
 
@@ -23,7 +35,12 @@ impl Clock {
             moves_remaining: moves,
             time_remaining: time,
             last_nodes_count: 0,
+            nodes_limit: None,
             is_finished: Arc::new(AtomicBool::new(false)),
+            is_pondering: Arc::new(AtomicBool::new(false)),
+            ponder_time: Arc::new(AtomicU64::new(0)),
+            is_infinite: false,
+            is_fixed_time: false,
             is_level: true
         }
     }
@@ -47,14 +64,75 @@ impl Clock {
         self.is_finished.store(true, Ordering::Relaxed);
     }
 
+    /// Stop the search after the given number of nodes
+    pub fn set_nodes_limit(&mut self, nodes_count: u64) {
+        self.nodes_limit = Some(nodes_count);
+    }
+
     pub fn disable_level(&mut self) {
         self.is_level = false;
     }
 
+    /// Search until the clock is explicitly stopped
+    pub fn enable_infinite(&mut self) {
+        self.is_infinite = true;
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.is_infinite
+    }
+
+    /// Search for the given time instead of the remaining time of the game
+    pub fn enable_fixed_time(&mut self) {
+        self.is_fixed_time = true;
+    }
+
+    /// Search on the time of the opponent until the ponderhit, without
+    /// being stopped by the clock
+    pub fn enable_ponder(&mut self) {
+        self.is_pondering.store(true, Ordering::Relaxed);
+        self.ponder_time.store(0, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.is_pondering.load(Ordering::Relaxed)
+    }
+
+    /// Continue the search started by pondering on our own time
+    ///
+    /// The time already spent pondering is credited to the search: it counts
+    /// in the time allocated to the move, which is thus not given again in
+    /// full after the ponderhit.
+    pub fn ponderhit(&mut self) {
+        if self.is_pondering.swap(false, Ordering::Relaxed) {
+            self.ponder_time.store(self.elapsed_time(), Ordering::Relaxed);
+        }
+    }
+
+    /// Get the time spent on our own clock since the start of the search,
+    /// which excludes the time spent pondering
+    pub fn used_time(&self) -> u64 {
+        if self.is_pondering() {
+            0
+        } else {
+            self.elapsed_time().saturating_sub(self.ponder_time.load(Ordering::Relaxed))
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_finished.load(Ordering::Relaxed)
+    }
+
     pub fn set_time(&mut self, time: u64) {
         self.time_remaining = time;
     }
 
+    /// Check if the remaining time of the game is too low for a regular
+    /// search
+    pub fn is_time_pressure(&self) -> bool {
+        !self.is_infinite && !self.is_fixed_time && !self.is_pondering() && self.time_remaining < TIME_PRESSURE
+    }
+
     pub fn allocated_time(&self) -> u64 {
         self.time_remaining / self.moves_remaining as u64
     }
@@ -64,9 +142,15 @@ impl Clock {
     }
 
     pub fn poll(&mut self, nodes_count: u64) -> bool {
+        if let Some(nodes_limit) = self.nodes_limit {
+            if nodes_count >= nodes_limit {
+                self.is_finished.store(true, Ordering::Relaxed);
+            }
+        }
+
         // We do the real computation only every `polling_nodes_count` nodes
         // TODO: do we need this?
-        if nodes_count - self.last_nodes_count > self.polling_nodes_count {
+        if nodes_count - self.last_nodes_count > self.polling_nodes_count && !self.is_infinite && !self.is_pondering() {
             self.last_nodes_count = nodes_count;
 
             // A certain amount of time pass between two polls,
@@ -83,3 +167,313 @@ impl Clock {
         self.is_finished.load(Ordering::Relaxed)
     }
 }
+
+/// A period of a time control
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeControl {
+    pub moves: u16, // Number of moves in the period or 0 until the end
+    pub time: u64, // Time added at the start of the period (in ms)
+    pub increment: u64, // Time added after each move (in ms)
+    pub delay: u64 // Time not counted at the start of each move (in ms)
+}
+
+impl TimeControl {
+    pub fn new(moves: u16, time: u64, increment: u64) -> TimeControl {
+        TimeControl { moves, time, increment, delay: 0 }
+    }
+
+    /// Parse time control periods from the value of a PGN `TimeControl`
+    /// tag, like `40/7200:3600` or `300+2`
+    pub fn from_pgn(s: &str) -> Result<Vec<TimeControl>, Box<dyn Error>> {
+        let mut controls = Vec::new();
+        for period in s.split(':') {
+            let (moves, period) = match period.find('/') {
+                Some(i) => (period[..i].parse()?, &period[(i + 1)..]),
+                None => (0, period)
+            };
+            let (time, increment) = match period.find('+') {
+                Some(i) => (period[..i].parse::<u64>()?, period[(i + 1)..].parse::<u64>()?),
+                None => (period.parse::<u64>()?, 0)
+            };
+            controls.push(TimeControl::new(moves, time * 1000, increment * 1000));
+        }
+        Ok(controls)
+    }
+}
+
+/// A chess clock keeping the time of both players
+#[derive(Clone, Debug)]
+pub struct GameClock {
+    controls: [Vec<TimeControl>; 2],
+    periods: [usize; 2],
+    moves: [u16; 2], // Moves played in the current period
+    remaining: [u64; 2],
+    is_flagged: [bool; 2]
+}
+
+impl GameClock {
+    /// Create a clock with the given time control periods, the last period
+    /// being repeated when it is over
+    pub fn new(controls: &[TimeControl]) -> GameClock {
+        GameClock::with_odds(controls, controls)
+    }
+
+    /// Create a clock with different time control periods for each side,
+    /// to give time odds
+    pub fn with_odds(white_controls: &[TimeControl], black_controls: &[TimeControl]) -> GameClock {
+        assert!(!white_controls.is_empty() && !black_controls.is_empty());
+        GameClock {
+            controls: [white_controls.to_vec(), black_controls.to_vec()],
+            periods: [0; 2],
+            moves: [0; 2],
+            remaining: [white_controls[0].time, black_controls[0].time],
+            is_flagged: [false; 2]
+        }
+    }
+
+    /// Restart the clock at the beginning of the first period of each side
+    pub fn reset(&mut self) {
+        self.periods = [0; 2];
+        self.moves = [0; 2];
+        self.remaining = [self.controls[WHITE as usize][0].time, self.controls[BLACK as usize][0].time];
+        self.is_flagged = [false; 2];
+    }
+
+    /// Get the time control periods of the given side
+    pub fn controls(&self, side: Color) -> &[TimeControl] {
+        &self.controls[side as usize]
+    }
+
+    /// Get the current time control period of the given side
+    pub fn control(&self, side: Color) -> TimeControl {
+        let controls = &self.controls[side as usize];
+        let i = self.periods[side as usize].min(controls.len() - 1);
+        controls[i]
+    }
+
+    /// Get the remaining time of the given side (in ms)
+    pub fn remaining_time(&self, side: Color) -> u64 {
+        self.remaining[side as usize]
+    }
+
+    /// Set the remaining time of the given side (in ms), when it is given
+    /// by an external clock
+    pub fn set_remaining_time(&mut self, side: Color, time: u64) {
+        self.remaining[side as usize] = time;
+    }
+
+    /// Get the number of moves to play by the given side before the next
+    /// period, or 0 if the period lasts until the end of the game
+    pub fn moves_to_go(&self, side: Color) -> u16 {
+        let control = self.control(side);
+        if control.moves > 0 {
+            control.moves - self.moves[side as usize]
+        } else {
+            0
+        }
+    }
+
+    /// Check if the given side has run out of time
+    pub fn is_flagged(&self, side: Color) -> bool {
+        self.is_flagged[side as usize]
+    }
+
+    /// Stop the clock of the given side after a move played in the given
+    /// time (in ms) and return false if the side ran out of time
+    pub fn press(&mut self, side: Color, elapsed: u64) -> bool {
+        let control = self.control(side);
+        let i = side as usize;
+
+        let used = elapsed.saturating_sub(control.delay);
+        if used > self.remaining[i] {
+            self.remaining[i] = 0;
+            self.is_flagged[i] = true;
+            return false;
+        }
+        self.remaining[i] -= used;
+        self.remaining[i] += control.increment;
+        self.moves[i] += 1;
+
+        if control.moves > 0 && self.moves[i] == control.moves {
+            self.periods[i] += 1;
+            self.moves[i] = 0;
+            self.remaining[i] += self.control(side).time;
+        }
+
+        true
+    }
+
+    /// Get a search clock for the given side to move
+    pub fn search_clock(&self, side: Color) -> Clock {
+        let moves = match self.moves_to_go(side) {
+            0 => 40, // Expected number of moves until the end of the game
+            n => n
+        };
+        let mut clock = Clock::new(moves, self.remaining_time(side));
+        clock.disable_level();
+        clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_clock_time_pressure() {
+        let mut clock = Clock::new(1, 5000);
+        assert!(!clock.is_time_pressure());
+
+        clock.set_time(50);
+        assert!(clock.is_time_pressure());
+
+        clock.enable_infinite();
+        assert!(!clock.is_time_pressure());
+
+        // A short time to move is not a low clock
+        let mut clock = Clock::new(1, 50);
+        clock.enable_fixed_time();
+        assert!(!clock.is_time_pressure());
+
+        let mut clock = Clock::new(1, 50);
+        clock.enable_ponder();
+        assert!(!clock.is_time_pressure());
+    }
+
+    #[test]
+    fn test_clock_nodes_limit() {
+        let mut clock = Clock::new(1, 10_000);
+        clock.enable_infinite();
+        clock.set_nodes_limit(500);
+        clock.start(1);
+        assert!(!clock.poll(499));
+        assert!(clock.poll(500));
+        assert!(clock.is_stopped());
+    }
+
+    #[test]
+    fn test_clock_ponderhit() {
+        let polling = 1000;
+        let mut clock = Clock::new(1, 10_000);
+        clock.polling_nodes_count = polling;
+        clock.disable_level();
+        clock.start(1);
+
+        // The clock is not stopped while pondering
+        clock.enable_ponder();
+        clock.started_at -= Duration::from_millis(4_000);
+        assert!(!clock.poll(polling + 1));
+        assert_eq!(clock.used_time(), 0);
+
+        // The time spent pondering is not charged to our clock but counts in
+        // the time allocated to the move
+        clock.ponderhit();
+        assert!(!clock.is_pondering());
+        assert!(clock.used_time() < 100);
+        assert!(!clock.poll(2 * polling + 2));
+        clock.started_at -= Duration::from_millis(6_000);
+        assert!(clock.used_time() >= 6_000);
+        assert!(clock.poll(3 * polling + 3));
+
+        // The search stops at the ponderhit after pondering longer than the
+        // time allocated to the move
+        let mut clock = Clock::new(1, 10_000);
+        clock.polling_nodes_count = polling;
+        clock.disable_level();
+        clock.start(1);
+        clock.enable_ponder();
+        clock.started_at -= Duration::from_millis(12_000);
+        assert!(!clock.poll(polling + 1));
+        clock.ponderhit();
+        assert!(clock.poll(2 * polling + 2));
+        assert!(clock.used_time() < 100);
+
+        // A second ponderhit does not change the credited time
+        clock.ponderhit();
+        assert!(clock.used_time() < 100);
+    }
+
+    #[test]
+    fn test_time_control_from_pgn() {
+        let controls = TimeControl::from_pgn("40/7200:3600").unwrap();
+        assert_eq!(controls, vec![
+            TimeControl::new(40, 7200 * 1000, 0),
+            TimeControl::new(0, 3600 * 1000, 0)
+        ]);
+
+        let controls = TimeControl::from_pgn("300+2").unwrap();
+        assert_eq!(controls, vec![TimeControl::new(0, 300 * 1000, 2000)]);
+
+        assert!(TimeControl::from_pgn("?").is_err());
+    }
+
+    #[test]
+    fn test_game_clock_periods() {
+        let controls = TimeControl::from_pgn("2/60:30+1").unwrap();
+        let mut clock = GameClock::new(&controls);
+        assert_eq!(clock.moves_to_go(WHITE), 2);
+
+        assert!(clock.press(WHITE, 10_000));
+        assert!(clock.press(BLACK, 5_000));
+        assert_eq!(clock.remaining_time(WHITE), 50_000);
+        assert_eq!(clock.moves_to_go(WHITE), 1);
+
+        assert!(clock.press(WHITE, 10_000));
+        assert_eq!(clock.remaining_time(WHITE), 40_000 + 30_000);
+        assert_eq!(clock.moves_to_go(WHITE), 0);
+
+        assert!(clock.press(WHITE, 10_000));
+        assert_eq!(clock.remaining_time(WHITE), 60_000 + 1_000);
+        assert_eq!(clock.remaining_time(BLACK), 55_000);
+    }
+
+    #[test]
+    fn test_game_clock_delay() {
+        let mut control = TimeControl::new(0, 10_000, 0);
+        control.delay = 2_000;
+        let mut clock = GameClock::new(&[control]);
+
+        assert!(clock.press(BLACK, 1_000));
+        assert_eq!(clock.remaining_time(BLACK), 10_000);
+        assert!(clock.press(BLACK, 5_000));
+        assert_eq!(clock.remaining_time(BLACK), 7_000);
+
+        assert!(!clock.press(BLACK, 10_000));
+        assert!(clock.is_flagged(BLACK));
+        assert!(!clock.is_flagged(WHITE));
+        assert_eq!(clock.remaining_time(BLACK), 0);
+
+        clock.reset();
+        assert!(!clock.is_flagged(BLACK));
+        assert_eq!(clock.remaining_time(BLACK), 10_000);
+    }
+
+    #[test]
+    fn test_game_clock_search_clock() {
+        let controls = TimeControl::from_pgn("40/7200:3600").unwrap();
+        let mut clock = GameClock::new(&controls);
+        clock.set_remaining_time(WHITE, 4000);
+        assert_eq!(clock.search_clock(WHITE).allocated_time(), 100);
+
+        // The moves of the game are expected to last 40 moves without a
+        // number of moves in the period
+        let mut clock = GameClock::new(&TimeControl::from_pgn("60+1").unwrap());
+        assert!(clock.press(WHITE, 20_000));
+        assert_eq!(clock.search_clock(WHITE).allocated_time(), 41_000 / 40);
+    }
+
+    #[test]
+    fn test_game_clock_odds() {
+        let white_controls = TimeControl::from_pgn("300+2").unwrap();
+        let black_controls = TimeControl::from_pgn("60").unwrap();
+        let mut clock = GameClock::with_odds(&white_controls, &black_controls);
+        assert_eq!(clock.remaining_time(WHITE), 300_000);
+        assert_eq!(clock.remaining_time(BLACK), 60_000);
+
+        assert!(clock.press(WHITE, 10_000));
+        assert!(clock.press(BLACK, 10_000));
+        assert_eq!(clock.remaining_time(WHITE), 292_000);
+        assert_eq!(clock.remaining_time(BLACK), 50_000);
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 
+use std::sync::LazyLock;
+
 use piece::*;
-use square::*;
 use bitboard::Bitboard;
 
 pub type Shift = i8;
@@ -12,6 +13,9 @@ pub type Depth = i8;
 
 pub const INF: Score = 29999;
 
+pub const PHASE_MAX: Score = 256;
+pub const PHASE_MATERIAL: Score = 24; // Non-pawn material in the opening
+
 pub const UP:    Shift = 8;
 pub const DOWN:  Shift = -8;
 pub const LEFT:  Shift = -1;
@@ -117,6 +121,7 @@ pub const QUEEN_CASTLE:             PieceMoveType = 0b0011; // 3
 pub const CAPTURE:                  PieceMoveType = 0b0100; // 4
 pub const EN_PASSANT:               PieceMoveType = 0b0101; // 5
 pub const NULL_MOVE:                PieceMoveType = 0b0110; // 6
+pub const DROP:                     PieceMoveType = 0b0111; // 7
 pub const KNIGHT_PROMOTION:         PieceMoveType = 0b1000; // 8
 pub const BISHOP_PROMOTION:         PieceMoveType = 0b1001; // 9
 pub const ROOK_PROMOTION:           PieceMoveType = 0b1010; // 10
@@ -134,11 +139,6 @@ pub const PROMOTION_KIND_MASK:      PieceMoveType = 0b1100;
 
 pub const PROMOTION_KINDS: [Piece; 4] = [KNIGHT, BISHOP, ROOK, QUEEN];
 
-pub const CASTLING_MASKS: [[Bitboard; 2]; 2] = [
-    [1 << F1 | 1 << G1, 1 << B1 | 1 << C1 | 1 << D1],
-    [1 << F8 | 1 << G8, 1 << B8 | 1 << C8 | 1 << D8]
-];
-
 pub const DEFAULT_FEN: &str =
     "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
@@ -156,67 +156,72 @@ pub const SQUARES: [Square; 64] = [
 */
 
 pub const MAX_PLY: usize = 128;
-pub const MAX_MOVES: usize = 256;
+pub const MAX_MOVES: usize = 512; // Drops can exceed 256 moves in Crazyhouse
+pub const MAX_HAND: usize = 16;
 pub const MAX_KILLERS: usize = 2;
 
 pub const TT_SIZE: usize = 8 << 20; // 8 Mb
 
+#[cfg(feature = "verify-search")]
+pub const VERIFY_TT_SIZE: usize = 64 << 10; // 64 Kb
+
+pub const PAWN_TABLE_SIZE: usize = 512 << 10; // 512 Kb
+
 pub const XSHIFTS: [Shift; 2] = [LEFT, RIGHT];
 pub const YSHIFTS: [Shift; 2] = [UP, DOWN];
 pub const END_FILES: [Bitboard; 2] = [FILE_A, FILE_H];
 pub const SEC_RANKS: [Bitboard; 2] = [RANK_3, RANK_6];
 pub const END_RANKS: [Bitboard; 2] = [RANK_8, RANK_1];
 
-lazy_static! {
-    pub static ref PIECE_MASKS: [[Bitboard; 64]; 14] = { // TODO: s/12/5/
-        let mut piece_masks = [[0u64; 64]; 14];
-
-        let deltas = [-2, -1, 0, 1, 2];
-        for x in 0..8 {
-            for y in 0..8 {
-                let from = 8 * x + y;
-                for &i in &deltas {
-                    for &j in &deltas {
-                        for k in 1..7 {
-                            let dx = x + i * k;
-                            let dy = y + j * k;
-                            let to = 8 * dx + dy;
-                            if to == from {
-                                break;
-                            }
-                            if dx as u8 >= 8 || dy as u8 >= 8 {
-                                break; // Out of board
-                            }
-                            if i == -2 || j == -2 || i == 2 || j == 2 {
-                                if i == -1 || j == -1 || i == 1 || j == 1 {
-                                    piece_masks[KNIGHT as usize][from as usize] |= 1 << to;
-                                }
-                                break;
-                            }
-                            if k == 1 {
-                                piece_masks[KING as usize][from as usize] |= 1 << to;
-                            }
-                            if (dx + i) as u8 >= 8 || (dy + j) as u8 >= 8 {
-                                break; // Edge of the board
-                            }
-                            if i == 0 || j == 0 {
-                                piece_masks[ROOK as usize][from as usize] |= 1 << to;
-                            } else {
-                                piece_masks[BISHOP as usize][from as usize] |= 1 << to;
+pub static PIECE_MASKS: LazyLock<[[Bitboard; 64]; 14]> = LazyLock::new(|| { // TODO: s/12/5/
+    let mut piece_masks = [[0u64; 64]; 14];
+
+    let deltas = [-2, -1, 0, 1, 2];
+    for x in 0..8 {
+        for y in 0..8 {
+            let from = 8 * x + y;
+            for &i in &deltas {
+                for &j in &deltas {
+                    for k in 1..7 {
+                        let dx = x + i * k;
+                        let dy = y + j * k;
+                        let to = 8 * dx + dy;
+                        if to == from {
+                            break;
+                        }
+                        if dx as u8 >= 8 || dy as u8 >= 8 {
+                            break; // Out of board
+                        }
+                        if i == -2 || j == -2 || i == 2 || j == 2 {
+                            if i == -1 || j == -1 || i == 1 || j == 1 {
+                                piece_masks[KNIGHT as usize][from as usize] |= 1 << to;
                             }
-                            piece_masks[QUEEN as usize][from as usize] |= 1 << to;
+                            break;
+                        }
+                        if k == 1 {
+                            piece_masks[KING as usize][from as usize] |= 1 << to;
+                        }
+                        if (dx + i) as u8 >= 8 || (dy + j) as u8 >= 8 {
+                            break; // Edge of the board
                         }
+                        if i == 0 || j == 0 {
+                            piece_masks[ROOK as usize][from as usize] |= 1 << to;
+                        } else {
+                            piece_masks[BISHOP as usize][from as usize] |= 1 << to;
+                        }
+                        piece_masks[QUEEN as usize][from as usize] |= 1 << to;
                     }
                 }
             }
-        };
-
-        piece_masks
+        }
     };
-}
+
+    piece_masks
+});
 
 #[cfg(test)]
 mod tests {
+    use square::*;
     use super::*;
 
     #[test]
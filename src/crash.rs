@@ -0,0 +1,114 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use common::*;
+use fen::FEN;
+use game::Game;
+use piece_move::PieceMove;
+use protocols::Protocol;
+
+/// Context of a search kept to be printed in crash reports
+#[derive(Clone, Debug)]
+pub struct CrashContext {
+    pub protocol: Protocol,
+    pub variant: String,
+    pub starting_fen: String,
+    pub moves: Vec<String>, // Moves played from the starting position in LAN
+    pub fen: String,
+    pub depths: Range<Depth>,
+    pub allocated_time: Option<u64>, // Time of the search (in ms) if limited
+    pub threads_count: usize,
+    pub depth: Depth, // Deepest iteration completed
+    pub nodes_count: u64,
+    pub best_move: String
+}
+
+impl CrashContext {
+    /// Create the context of a search starting on the given game
+    pub fn new(game: &Game, depths: Range<Depth>) -> CrashContext {
+        CrashContext {
+            protocol: game.protocol,
+            variant: game.variant.name().into(),
+            starting_fen: game.starting_fen.clone(),
+            moves: game.history.iter().map(|m| m.to_lan()).collect(),
+            fen: game.to_fen(),
+            depths,
+            allocated_time: if game.clock.is_infinite() { None } else { Some(game.clock.allocated_time()) },
+            threads_count: game.threads_count,
+            depth: 0,
+            nodes_count: 0,
+            best_move: String::new()
+        }
+    }
+}
+
+impl fmt::Display for CrashContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "protocol: {:?}", self.protocol)?;
+        writeln!(f, "variant: {}", self.variant)?;
+        writeln!(f, "starting fen: {}", self.starting_fen)?;
+        writeln!(f, "moves: {}", self.moves.join(" "))?;
+        writeln!(f, "fen: {}", self.fen)?;
+        writeln!(f, "depths: {}..{}", self.depths.start, self.depths.end)?;
+        match self.allocated_time {
+            Some(time) => writeln!(f, "time: {} ms", time)?,
+            None => writeln!(f, "time: infinite")?
+        }
+        writeln!(f, "threads: {}", self.threads_count)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        writeln!(f, "nodes: {}", self.nodes_count)?;
+        writeln!(f, "best move: {}", self.best_move)?;
+        Ok(())
+    }
+}
+
+static CONTEXT: Mutex<Option<CrashContext>> = Mutex::new(None);
+
+/// Record the context of a search starting on the given game
+pub fn record_search(game: &Game, depths: Range<Depth>) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        *context = Some(CrashContext::new(game, depths));
+    }
+}
+
+/// Record the progress of the search after an iteration, keeping the
+/// deepest one of every thread
+pub fn record_depth(depth: Depth, nodes_count: u64, best_move: PieceMove) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        if let Some(ref mut context) = *context {
+            if depth >= context.depth {
+                context.depth = depth;
+                context.nodes_count = nodes_count;
+                context.best_move = best_move.to_lan();
+            }
+        }
+    }
+}
+
+/// Get the context of the last search recorded, without waiting for it if
+/// the crash happened while it was being recorded
+pub fn context() -> Option<CrashContext> {
+    CONTEXT.try_lock().ok().and_then(|context| context.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piece_move_generator::PieceMoveGenerator;
+    use piece_move_notation::PieceMoveNotation;
+
+    #[test]
+    fn test_crash_context() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        let m = game.move_from_lan("e2e4");
+        game.make_move(m);
+        game.history.push(m);
+
+        let context = CrashContext::new(&game, 1..20);
+        let report = context.to_string();
+        assert!(report.contains("moves: e2e4\n"));
+        assert!(report.contains("fen: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n"));
+        assert!(report.contains("depths: 1..20\n"));
+    }
+}
@@ -0,0 +1,120 @@
+use std::mem;
+use std::sync::{Arc, LazyLock};
+
+use color::*;
+use piece::*;
+use square::*;
+use attack::piece_attacks;
+use bitboard::BitboardIterator;
+use zobrist::Zobrist;
+
+// Number of entries of the table, with two of them for each key
+const CUCKOO_SIZE: usize = 8192;
+
+/// A reversible move of a piece, other than a pawn, between two squares
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CuckooEntry {
+    pub key: u64, // Difference between the hashes before and after the move
+    pub piece: Piece,
+    pub from: Square,
+    pub to: Square
+}
+
+/// A cuckoo hash table of every reversible move on an empty board indexed
+/// by the difference it makes to the hash of a position, to find the move
+/// going from a position to a previous one (Marcel van Kervinck's upcoming
+/// repetition detection)
+pub struct Cuckoo {
+    entries: Vec<CuckooEntry>
+}
+
+pub static CUCKOO: LazyLock<Arc<Cuckoo>> = LazyLock::new(|| {
+    Arc::new(Cuckoo::new(&Zobrist::new()))
+});
+
+impl Cuckoo {
+    /// Create the table of the moves with the given Zobrist keys
+    pub fn new(zobrist: &Zobrist) -> Cuckoo {
+        let null = CuckooEntry { key: 0, piece: EMPTY, from: OUT, to: OUT };
+        let mut cuckoo = Cuckoo {
+            entries: vec![null; CUCKOO_SIZE]
+        };
+
+        for &c in &COLORS {
+            for &kind in &[KNIGHT, BISHOP, ROOK, QUEEN, KING] {
+                let piece = c | kind;
+                for from in 0..64 {
+                    let mut targets = piece_attacks(piece, from, 0);
+                    while let Some(to) = targets.next() {
+                        if to > from {
+                            let key = zobrist.pieces[piece as usize][from as usize] ^
+                                zobrist.pieces[piece as usize][to as usize] ^
+                                zobrist.side;
+                            cuckoo.insert(CuckooEntry { key, piece, from, to });
+                        }
+                    }
+                }
+            }
+        }
+
+        cuckoo
+    }
+
+    // Put the entry in one of its two slots, moving the entry already
+    // there to its other slot until an empty one is found
+    fn insert(&mut self, mut entry: CuckooEntry) {
+        let mut i = h1(entry.key);
+        loop {
+            mem::swap(&mut self.entries[i], &mut entry);
+            if entry.key == 0 {
+                return;
+            }
+            i = if i == h1(entry.key) { h2(entry.key) } else { h1(entry.key) };
+        }
+    }
+
+    /// Get the move making the given difference to the hash of a position
+    pub fn get(&self, key: u64) -> Option<CuckooEntry> {
+        let e = self.entries[h1(key)];
+        if e.key == key {
+            return Some(e);
+        }
+        let e = self.entries[h2(key)];
+        if e.key == key {
+            return Some(e);
+        }
+        None
+    }
+}
+
+fn h1(key: u64) -> usize {
+    (key & (CUCKOO_SIZE as u64 - 1)) as usize
+}
+
+fn h2(key: u64) -> usize {
+    ((key >> 16) & (CUCKOO_SIZE as u64 - 1)) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cuckoo() {
+        let zobrist = Zobrist::new();
+        let cuckoo = Cuckoo::new(&zobrist);
+        assert_eq!(cuckoo.entries.iter().filter(|e| e.key != 0).count(), 3668);
+
+        let key = zobrist.pieces[WHITE_KNIGHT as usize][G1 as usize] ^
+            zobrist.pieces[WHITE_KNIGHT as usize][F3 as usize] ^
+            zobrist.side;
+        let e = cuckoo.get(key).unwrap();
+        assert_eq!((e.piece, e.from, e.to), (WHITE_KNIGHT, G1, F3));
+
+        // No pawn moves
+        let key = zobrist.pieces[WHITE_PAWN as usize][E2 as usize] ^
+            zobrist.pieces[WHITE_PAWN as usize][E3 as usize] ^
+            zobrist.side;
+        assert_eq!(cuckoo.get(key), None);
+    }
+}
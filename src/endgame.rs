@@ -0,0 +1,178 @@
+use std::cmp;
+
+use color::*;
+use piece::*;
+use square::*;
+use common::*;
+use bitboard::BitboardExt;
+use eval::QUEEN_VALUE;
+use game::Game;
+use kpk;
+use piece::PieceAttr;
+use square::SquareExt;
+
+/// Maximum number of pieces of the positions in the KPK bitbase
+pub const KPK_MAX_PIECES: usize = 3;
+
+/// Maximum number of pieces of the positions known by the recognizers
+pub const RECOGNIZERS_MAX_PIECES: usize = 3;
+
+/// Maximum number of pieces of the positions guided by the heuristics
+pub const GUIDANCE_MAX_PIECES: usize = 4;
+
+const GUIDANCE_EDGE: Score = 20; // Weak king away from the center
+const GUIDANCE_CORNER: Score = 20; // Weak king near a corner of the bishop
+const GUIDANCE_KINGS: Score = 10; // Strong king near the weak king
+const GUIDANCE_KNIGHT: Score = 10; // Weak knight away from its king
+
+/// Probe the exact score of the endgame of the game from the point of view
+/// of the side to move, from the most to the least precise source having
+/// positions with its number of pieces
+pub fn probe(game: &Game) -> Option<Score> {
+    // The endgames are known only with the standard rules
+    if !game.variant.has_standard_endgames() {
+        return None;
+    }
+
+    let n = pieces_count(game);
+
+    // Piece-count gate
+    if n > cmp::max(KPK_MAX_PIECES, RECOGNIZERS_MAX_PIECES) {
+        return None;
+    }
+
+    if n <= KPK_MAX_PIECES {
+        if let Some(score) = probe_kpk(game) {
+            return Some(score);
+        }
+    }
+
+    if n <= RECOGNIZERS_MAX_PIECES && is_insufficient_material(game) {
+        return Some(0); // Draw
+    }
+
+    None
+}
+
+/// Get a score guiding the search toward the conversion of endgames won
+/// but hard to convert without tablebases, like KQvKR, KRvKB, KRvKN, and
+/// KBNvK, from the point of view of the side to move
+pub fn guidance(game: &Game) -> Score {
+    if !game.variant.has_standard_endgames() || pieces_count(game) > GUIDANCE_MAX_PIECES {
+        return 0;
+    }
+
+    for &strong in &COLORS {
+        let weak = strong ^ 1;
+        let has = |c: Color, pieces: &[Piece]| {
+            let mut bb = *game.bitboard(c | KING);
+            for &p in pieces {
+                bb |= game.bitboard(c | p);
+            }
+            bb == *game.bitboard(c) && pieces.iter().all(|&p| game.bitboard(c | p).count() == 1)
+        };
+        let strong_king = game.bitboard(strong | KING).scan() as Square;
+        let weak_king = game.bitboard(weak | KING).scan() as Square;
+        let kings = GUIDANCE_KINGS * (7 - distance(strong_king, weak_king) as Score);
+
+        let score = if has(weak, &[]) && has(strong, &[BISHOP, KNIGHT]) {
+            // Mate is only possible in a corner of the color of the bishop
+            let bishop = game.bitboard(strong | BISHOP).scan() as Square;
+            let corners = if is_dark(bishop) { [A1, H8] } else { [A8, H1] };
+            let corner = corners.iter().map(|&sq| distance(weak_king, sq) as Score).min().unwrap();
+            GUIDANCE_CORNER * (7 - corner) + kings
+        } else if has(strong, &[QUEEN]) && has(weak, &[ROOK]) || has(strong, &[ROOK]) && has(weak, &[BISHOP]) {
+            GUIDANCE_EDGE * center_distance(weak_king) + kings
+        } else if has(strong, &[ROOK]) && has(weak, &[KNIGHT]) {
+            let knight = game.bitboard(weak | KNIGHT).scan() as Square;
+            GUIDANCE_EDGE * center_distance(weak_king) + kings + GUIDANCE_KNIGHT * distance(weak_king, knight) as Score
+        } else {
+            continue;
+        };
+
+        return if strong == game.side() { score } else { -score };
+    }
+
+    0
+}
+
+// Get the number of king moves from the center to the given square
+fn center_distance(sq: Square) -> Score {
+    let file = sq.file() as Score;
+    let rank = sq.rank() as Score;
+    cmp::max(cmp::max(3 - file, file - 4), cmp::max(3 - rank, rank - 4))
+}
+
+// Check if the given square has the color of A1
+fn is_dark(sq: Square) -> bool {
+    (sq.file() + sq.rank()) & 1 == 0
+}
+
+fn pieces_count(game: &Game) -> usize {
+    (game.bitboard(WHITE) | game.bitboard(BLACK)).count() as usize
+}
+
+// King and pawn versus king
+fn probe_kpk(game: &Game) -> Option<Score> {
+    let pawns = game.bitboard(WHITE | PAWN) | game.bitboard(BLACK | PAWN);
+    if pawns.count() != 1 || pieces_count(game) != 3 {
+        return None;
+    }
+
+    let side = game.side();
+    let pawn = pawns.scan() as Square;
+    let strong = game.board[pawn as usize].color();
+    let king = game.bitboard(strong | KING).scan() as Square;
+    let opponent_king = game.bitboard((strong ^ 1) | KING).scan() as Square;
+    if kpk::probe(strong, side, king, pawn, opponent_king) {
+        let score = QUEEN_VALUE + 10 * pawn.flip(strong).rank() as Score;
+        Some(if strong == side { score } else { -score }) // Win
+    } else {
+        Some(0) // Draw
+    }
+}
+
+// Kings with at most a minor piece
+fn is_insufficient_material(game: &Game) -> bool {
+    let occupied = game.bitboard(WHITE) | game.bitboard(BLACK);
+    let kings = game.bitboard(WHITE | KING) | game.bitboard(BLACK | KING);
+    let knights = game.bitboard(WHITE | KNIGHT) | game.bitboard(BLACK | KNIGHT);
+    let bishops = game.bitboard(WHITE | BISHOP) | game.bitboard(BLACK | BISHOP);
+    (kings | knights | bishops) == occupied && occupied.count() <= 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fen::FEN;
+
+    #[test]
+    fn test_probe() {
+        let game = Game::from_fen("8/8/4k3/8/4N3/4K3/8/8 w - - 0 1").unwrap();
+        assert_eq!(probe(&game), Some(0));
+
+        let game = Game::from_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(probe(&game), Some(-QUEEN_VALUE - 40));
+
+        let game = Game::from_fen("4k3/8/4K3/4P3/8/8/4p3/8 w - - 0 1").unwrap();
+        assert_eq!(probe(&game), None);
+    }
+
+    #[test]
+    fn test_guidance() {
+        // KBNvK with the king driven to a corner of the color of the bishop
+        let right_corner = Game::from_fen("7k/8/5K2/8/8/8/8/4BN2 w - - 0 1").unwrap();
+        let wrong_corner = Game::from_fen("k7/8/2K5/8/8/8/8/4BN2 w - - 0 1").unwrap();
+        assert!(guidance(&right_corner) > guidance(&wrong_corner));
+        assert!(guidance(&wrong_corner) > 0);
+
+        // KQvKR with the king driven to the edge
+        let edge = Game::from_fen("8/8/8/8/8/2K5/1Q6/k6r b - - 0 1").unwrap();
+        let center = Game::from_fen("8/8/8/3k4/8/2K5/1Q6/7r b - - 0 1").unwrap();
+        assert!(guidance(&edge) < guidance(&center));
+        assert!(guidance(&center) < 0);
+
+        let game = Game::from_fen("8/8/8/3k4/8/2K5/1Q6/7R b - - 0 1").unwrap();
+        assert_eq!(guidance(&game), 0);
+    }
+}
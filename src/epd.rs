@@ -0,0 +1,71 @@
+use attack::Attack;
+use fen::FEN;
+use game::Game;
+use piece_move::PieceMove;
+use piece_move_generator::PieceMoveGenerator;
+use piece_move_notation::PieceMoveNotation;
+use search::Search;
+
+/// Extended Position Description support
+pub trait EPD {
+    /// Get the EPD record of the current position, with the opcodes `bm`,
+    /// `ce`, `acd`, and `acn` of the last search if it was done from there
+    fn to_epd(&mut self) -> String;
+}
+
+impl EPD for Game {
+    fn to_epd(&mut self) -> String {
+        // The four fields of the position without the move counters
+        let fen = self.to_fen();
+        let mut epd = fen.split(' ').take(4).collect::<Vec<&str>>().join(" ");
+
+        let hash = self.positions.top().hash;
+        if let Some(res) = self.search_result.filter(|res| res.hash == hash) {
+            epd.push_str(&format!(" bm {};", san_with_check(self, res.best_move)));
+            epd.push_str(&format!(" ce {};", res.score));
+            epd.push_str(&format!(" acd {};", res.depth));
+            epd.push_str(&format!(" acn {};", res.nodes_count));
+        }
+
+        epd
+    }
+}
+
+// Get the SAN of the move with `+` or `#` when it gives a check or a mate
+fn san_with_check(game: &mut Game, m: PieceMove) -> String {
+    let mut san = game.move_to_san(m);
+    let side = game.side();
+    game.make_move(m);
+    if game.is_check(side ^ 1) {
+        san.push(if game.is_mate() { '#' } else { '+' });
+    }
+    game.undo_move(m);
+    san
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::*;
+    use clock::Clock;
+
+    #[test]
+    fn test_to_epd() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(game.to_epd(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
+
+        // Back rank mate
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 5 * 1000);
+        game.search(1..4);
+        let nodes_count = game.search_result.unwrap().nodes_count;
+        let epd = format!("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#; ce {}; acd 3; acn {};", INF - 1, nodes_count);
+        assert_eq!(game.to_epd(), epd);
+
+        // The result is not given for another position
+        let m = game.move_from_san("Rd8").unwrap();
+        game.make_move(m);
+        assert_eq!(game.to_epd(), "3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - -");
+    }
+}
@@ -1,4 +1,5 @@
 use std::cmp;
+use std::sync::LazyLock;
 
 use color::*;
 use piece::*;
@@ -7,8 +8,10 @@ use common::*;
 use attack::Attack;
 use attack::piece_attacks;
 use bitboard::{Bitboard, BitboardExt, BitboardIterator};
-use bitboard::filefill;
+use bitboard::{filefill, upfill, downfill};
 use game::Game;
+use endgame;
+use pawn_table::PawnEval;
 use piece_move::PieceMove;
 use piece_square_table::PST;
 
@@ -20,31 +23,49 @@ pub const QUEEN_VALUE:      Score =  1000; // R + B + P + bonus bishop pair
 pub const KING_VALUE:       Score = 10000;
 
 const BONUS_BISHOP_PAIR:    Score =    50;
+const BONUS_CHECKS: [Score; 4] = [0, 150, 400, 0]; // Checks given in Three-check
+
+// Shelter of the king by the closest pawn in front of it on each of the
+// three files around it, indexed by relative rank (0 without pawn)
+const MALUS_SHELTER: [Score; 8] = [-30, 0, 0, -10, -20, -25, -25, -25];
+
+// Storm of the closest opponent pawn in front of the king on each of the
+// three files around it, indexed by relative rank (0 without pawn), halved
+// when blocked by a pawn of the king
+const MALUS_STORM: [Score; 8] = [0, 0, -10, -40, -25, -10, -5, 0];
+
+// Doubled, isolated, and passed pawns in the opening and the ending, the
+// latter indexed by relative rank
+const MALUS_DOUBLED_PAWN: [Score; 2] = [-10, -20];
+const MALUS_ISOLATED_PAWN: [Score; 2] = [-10, -15];
+const BONUS_PASSED_PAWN: [[Score; 2]; 8] = [
+    [0, 0], [5, 10], [5, 15], [10, 25], [20, 45], [35, 75], [60, 120], [0, 0]
+];
+
+// Scaling of the score (out of 64) in positions locked by the pawns
+const SCALE_BLOCKED: i32 = 32;
 //const BONUS_HALF_OPEN_FILE: Score =     5;
 //const BONUS_KNIGHT_PAWNS:   Score =     5;
 //const BONUS_ROOK_OPEN_FILE: Score =    20;
 //const BONUS_ROOK_PAWNS:     Score =     5;
-//const MALUS_DOUBLED_PAWN:   Score =   -10;
-
-lazy_static! {
-    static ref PIECE_VALUES: [Score; 14] = {
-        let mut piece_values = [0; 14];
-
-        piece_values[PAWN   as usize] = PAWN_VALUE;
-        piece_values[KNIGHT as usize] = KNIGHT_VALUE;
-        piece_values[BISHOP as usize] = BISHOP_VALUE;
-        piece_values[ROOK   as usize] = ROOK_VALUE;
-        piece_values[QUEEN  as usize] = QUEEN_VALUE;
-        piece_values[KING   as usize] = KING_VALUE;
-
-        for i in 0..7 {
-            let j = i * 2;
-            piece_values[j + 1] = piece_values[j];
-        }
 
-        piece_values
-    };
-}
+static PIECE_VALUES: LazyLock<[Score; 14]> = LazyLock::new(|| {
+    let mut piece_values = [0; 14];
+
+    piece_values[PAWN   as usize] = PAWN_VALUE;
+    piece_values[KNIGHT as usize] = KNIGHT_VALUE;
+    piece_values[BISHOP as usize] = BISHOP_VALUE;
+    piece_values[ROOK   as usize] = ROOK_VALUE;
+    piece_values[QUEEN  as usize] = QUEEN_VALUE;
+    piece_values[KING   as usize] = KING_VALUE;
+
+    for i in 0..7 {
+        let j = i * 2;
+        piece_values[j + 1] = piece_values[j];
+    }
+
+    piece_values
+});
 
 /// Evaluation algorithms
 pub trait Eval {
@@ -56,10 +77,24 @@ pub trait Eval {
 
     /// Static Exchange Evaluation
     fn see(&self, capture: PieceMove) -> Score;
+
+    /// Get the sequence of captures on the given square, starting with the
+    /// side to move and using the least valuable piece each time, with the
+    /// material gained so far after each capture from the point of view of
+    /// the side to move
+    fn exchange_sequence(&self, square: Square) -> Vec<(Piece, Score)>;
+
+    /// Check if the pawns are fully locked, without any pawn able to move
+    /// or capture, and form a wall that the kings cannot cross
+    fn is_blocked(&self) -> bool;
 }
 
 trait EvalExt {
     fn eval_ending(&self, c: Color) -> Option<Score>;
+    fn eval_antichess(&self, c: Color) -> Score;
+    fn eval_pawns(&self, with_shelters: bool) -> PawnEval;
+    fn eval_pawn_structure(&self) -> [Score; 2];
+    fn eval_pawn_storm(&self, c: Color) -> Score;
     fn lvp(&self, side: Color, attacks: Bitboard, occupied: Bitboard) -> Square;
 }
 
@@ -68,6 +103,10 @@ impl Eval for Game {
         let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
         let side = self.side();
 
+        if self.variant.has_losing_goal() {
+            return self.eval_antichess(side);
+        }
+
         // Look for win/loss/draw
         if let Some(score) = self.eval_ending(side) {
             return score;
@@ -82,8 +121,9 @@ impl Eval for Game {
                 let piece = c | p;
                 let mut pieces = self.bitboards[piece as usize];
                 let n = pieces.count() as Score;
-                material[c as usize] += n * PIECE_VALUES[piece as usize];
-                if p == BISHOP && n > 1 { // FIXME: Slows eval from 1250ns to 1350ns
+                let in_hand = self.positions.top().hand(piece) as Score; // Crazyhouse
+                material[c as usize] += (n + in_hand) * PIECE_VALUES[piece as usize];
+                if p == BISHOP && n > 1 && self.eval_params.bishop_pair { // FIXME: Slows eval from 1250ns to 1350ns
                     material[c as usize] += BONUS_BISHOP_PAIR;
                 }
                 while let Some(square) = pieces.next() {
@@ -101,29 +141,80 @@ impl Eval for Game {
         let c = side as usize;
 
         // Linear interpolation between opening and ending scores
-        // based on the number of pieces on the board
-        let x0 = 32; // Max
-        let x1 = 2; // Min
-        let x = occupied.count() as Score; // Current
-
-        let y0 = position[c][0];
-        let y1 = position[c][1];
-        position_score += (y0 * (x1 - x) + y1 * (x - x0)) / (x1 - x0);
+        // based on the phase of the game
+        let x = self.phase() as i32;
+        let x1 = PHASE_MAX as i32;
+
+        let y0 = position[c][0] as i32;
+        let y1 = position[c][1] as i32;
+        position_score += ((y0 * (x1 - x) + y1 * x) / x1) as Score;
         material_score += material[c];
         mobility_score += mobility[c];
 
-        let y0 = position[c ^ 1][0];
-        let y1 = position[c ^ 1][1];
-        position_score -= (y0 * (x1 - x) + y1 * (x - x0)) / (x1 - x0);
+        let y0 = position[c ^ 1][0] as i32;
+        let y1 = position[c ^ 1][1] as i32;
+        position_score -= ((y0 * (x1 - x) + y1 * x) / x1) as Score;
         material_score -= material[c ^ 1];
         mobility_score -= mobility[c ^ 1];
 
-        let score = position_score + material_score + mobility_score;
+        if !self.eval_params.position {
+            position_score = 0;
+        }
+        if !self.eval_params.mobility {
+            mobility_score = 0;
+        }
+
+        let pos = self.positions.top();
+        let checks_score = BONUS_CHECKS[pos.checks(side) as usize] - BONUS_CHECKS[pos.checks(side ^ 1) as usize];
+
+        let mut pawns_score = 0;
+        let mut storm_score = 0;
+        let with_shelters = self.eval_params.pawn_storm && self.variant.has_royal_king();
+        if self.eval_params.pawns || with_shelters {
+            let pawns = self.eval_pawns(with_shelters);
+
+            if self.eval_params.pawns {
+                let y0 = pawns.structure[0] as i32;
+                let y1 = pawns.structure[1] as i32;
+                pawns_score = ((y0 * (x1 - x) + y1 * x) / x1) as Score;
+                if side == BLACK {
+                    pawns_score = -pawns_score;
+                }
+            }
+
+            // Pawn shelters and storms only matter when there are enough
+            // pieces left to attack the kings
+            if with_shelters {
+                let storm = pawns.shelters[c] - pawns.shelters[c ^ 1];
+                storm_score = ((storm as i32 * (x1 - x)) / x1) as Score;
+            }
+        }
+
+        let guidance_score = endgame::guidance(self);
+
+        let mut score = position_score + material_score + mobility_score + pawns_score + checks_score + storm_score + guidance_score;
+
+        // No progress can be made without sacrificing material
+        let is_blocked = self.eval_params.blocked && self.is_blocked();
+        if is_blocked {
+            score = (score as i32 * SCALE_BLOCKED / 64) as Score;
+        }
 
         if self.is_eval_verbose {
             println!("material: {:>5.2}", 0.01 * material_score as f64);
             println!("position: {:>5.2}", 0.01 * position_score as f64);
             println!("mobility: {:>5.2}", 0.01 * mobility_score as f64);
+            println!("pawns:    {:>5.2}", 0.01 * pawns_score as f64);
+            println!("storm:    {:>5.2}", 0.01 * storm_score as f64);
+            if self.variant.has_checks_limit() {
+                println!("checks:   {:>5.2}", 0.01 * checks_score as f64);
+            }
+            if guidance_score != 0 {
+                println!("endgame:  {:>5.2}", 0.01 * guidance_score as f64);
+            }
+            if is_blocked {
+                println!("blocked:  x{:.2}", SCALE_BLOCKED as f64 / 64.0);
+            }
             println!("total:    {:>5.2}", 0.01 * score as f64);
         }
 
@@ -149,7 +240,7 @@ impl Eval for Game {
         for &p in &PIECES {
             let piece = c | p;
             let pieces = self.bitboards[piece as usize];
-            let n = pieces.count() as Score;
+            let n = pieces.count() as Score + self.positions.top().hand(piece) as Score;
             score += n * PIECE_VALUES[piece as usize];
 
             /*
@@ -212,12 +303,77 @@ impl Eval for Game {
 
         gains[0]
     }
+
+    fn exchange_sequence(&self, square: Square) -> Vec<(Piece, Score)> {
+        let mut occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+        let mut side = self.side();
+        let mut target = self.board[square as usize];
+        let mut score = 0;
+        let mut sequence = Vec::new();
+
+        if target != EMPTY && target.color() == side {
+            return sequence;
+        }
+
+        loop {
+            let attacks = self.attacks_to(square, occupied);
+            let sq = self.lvp(side, attacks, occupied);
+            if sq == OUT {
+                break;
+            }
+            occupied.reset(sq);
+
+            // The king cannot capture a defended piece
+            let piece = self.board[sq as usize];
+            if piece.kind() == KING && self.lvp(side ^ 1, attacks, occupied) != OUT {
+                break;
+            }
+
+            let value = PIECE_VALUES[target as usize];
+            score += if side == self.side() { value } else { -value };
+            sequence.push((piece, score));
+
+            target = piece;
+            side ^= 1;
+        }
+
+        sequence
+    }
+
+    fn is_blocked(&self) -> bool {
+        if !self.variant.has_royal_king() || self.variant.has_drops() {
+            return false;
+        }
+
+        let white_pawns = *self.bitboard(WHITE | PAWN);
+        let black_pawns = *self.bitboard(BLACK | PAWN);
+        if white_pawns == 0 || black_pawns == 0 {
+            return false;
+        }
+
+        // Every pawn is stopped by an opponent pawn
+        if white_pawns.shift(UP) != black_pawns || black_pawns.shift(DOWN) != white_pawns {
+            return false;
+        }
+
+        // No pawn can capture
+        let white_attacks = pawn_attacks(white_pawns, WHITE);
+        let black_attacks = pawn_attacks(black_pawns, BLACK);
+        if white_attacks & black_pawns != 0 || black_attacks & white_pawns != 0 {
+            return false;
+        }
+
+        // The kings are kept on their own side of the pawns
+        let white_king = *self.bitboard(WHITE | KING);
+        let black_king = *self.bitboard(BLACK | KING);
+        let white_area = king_fill(white_king, !white_pawns & !black_attacks);
+        let black_area = king_fill(black_king, !black_pawns & !white_attacks);
+        white_area & black_area == 0
+    }
 }
 
 impl EvalExt for Game {
     fn eval_ending(&self, side: Color) -> Option<Score> {
-        let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
-
         let kings = self.bitboard(WHITE | KING) | self.bitboard(BLACK | KING);
         if kings.count() < 2 {
             if self.bitboard(side | KING).count() == 0 {
@@ -227,16 +383,141 @@ impl EvalExt for Game {
             }
         }
 
-        // Draw by insufficient material
-        if occupied.count() < 4 {
-            let knights = self.bitboard(WHITE | KNIGHT) | self.bitboard(BLACK | KNIGHT);
-            let bishops = self.bitboard(WHITE | BISHOP) | self.bitboard(BLACK | BISHOP);
-            if (kings | knights | bishops) == occupied {
-                return Some(0); // Draw
+        // Draw by insufficient material and KPK bitbase
+        endgame::probe(self)
+    }
+
+    // The pieces are a liability and the king is an ordinary piece
+    fn eval_antichess(&self, side: Color) -> Score {
+        let n = self.bitboard(side).count() as Score;
+        if n == 0 {
+            return INF; // Win
+        }
+        let score = (self.bitboard(side ^ 1).count() as Score - n) * PAWN_VALUE;
+
+        if self.is_eval_verbose {
+            println!("material: {:>5.2}", 0.01 * score as f64);
+            println!("total:    {:>5.2}", 0.01 * score as f64);
+        }
+
+        score
+    }
+
+    // Get the evaluation of the pawns from the pawn hash table, or evaluate
+    // them and store it, with the shelters of the kings if they moved since
+    fn eval_pawns(&self, with_shelters: bool) -> PawnEval {
+        let hash = self.positions.top().pawn_hash;
+        let (mut pawns, mut is_stored) = match self.pawn_table.get(hash) {
+            Some(pawns) => (pawns, true),
+            None => (PawnEval::new(self.eval_pawn_structure()), false)
+        };
+
+        if with_shelters {
+            for &c in &COLORS {
+                let kings = self.bitboard(c | KING);
+                let king = if kings.count() == 1 { kings.scan() as Square } else { OUT };
+                if pawns.kings[c as usize] != king {
+                    pawns.kings[c as usize] = king;
+                    pawns.shelters[c as usize] = self.eval_pawn_storm(c);
+                    is_stored = false;
+                }
+            }
+        }
+
+        if !is_stored {
+            self.pawn_table.set(hash, pawns);
+        }
+
+        pawns
+    }
+
+    // Evaluate the doubled, isolated, and passed pawns of both sides from
+    // the point of view of white, in the opening and in the ending
+    fn eval_pawn_structure(&self) -> [Score; 2] {
+        let mut score = [0; 2];
+        for &c in &COLORS {
+            let sign = if c == WHITE { 1 } else { -1 };
+            let pawns = *self.bitboard(c | PAWN);
+            let opponent_pawns = *self.bitboard((c ^ 1) | PAWN);
+
+            let doubled = pawns.count() as Score - (filefill(pawns) & RANK_1).count() as Score;
+            for i in 0..2 {
+                score[i] += sign * doubled * MALUS_DOUBLED_PAWN[i];
+            }
+
+            let mut squares = pawns;
+            while let Some(sq) = squares.next() {
+                let file = sq.file() as usize;
+                let mut adjacent_files = 0;
+                if file > 0 {
+                    adjacent_files |= FILES[file - 1];
+                }
+                if file < 7 {
+                    adjacent_files |= FILES[file + 1];
+                }
+                if pawns & adjacent_files == 0 {
+                    for i in 0..2 {
+                        score[i] += sign * MALUS_ISOLATED_PAWN[i];
+                    }
+                }
+
+                // Neither another pawn of the side in front of the pawn nor
+                // an opponent pawn in front of it or able to capture it
+                let front = if c == WHITE { upfill(1 << sq) << 8 } else { downfill(1 << sq) >> 8 };
+                let span = front | (front.shift(LEFT) & !FILE_H) | (front.shift(RIGHT) & !FILE_A);
+                let is_passed = front & pawns == 0 && span & opponent_pawns == 0;
+                if is_passed {
+                    let rank = sq.flip(c).rank() as usize;
+                    for i in 0..2 {
+                        score[i] += sign * BONUS_PASSED_PAWN[rank][i];
+                    }
+                }
             }
         }
 
-        None
+        score
+    }
+
+    // Evaluate the pawns in front of the king of the given side, on its
+    // file and the adjacent ones, for the shelter of its own pawns and the
+    // storm of the opponent pawns
+    fn eval_pawn_storm(&self, side: Color) -> Score {
+        let kings = self.bitboard(side | KING);
+        if kings.count() != 1 {
+            return 0;
+        }
+        let king = kings.scan() as Square;
+        let king_rank = king.flip(side).rank();
+
+        // The shelter of a king on an edge is on the same files as the
+        // shelter of a king next to it
+        let center = king.file().clamp(1, 6);
+
+        let mut score = 0;
+        for file in (center - 1)..(center + 2) {
+            let front = |pawns: Bitboard| {
+                let mut closest = 0;
+                let mut pawns = pawns & FILES[file as usize];
+                while let Some(sq) = pawns.next() {
+                    let rank = sq.flip(side).rank();
+                    if rank >= king_rank && (closest == 0 || rank < closest) {
+                        closest = rank;
+                    }
+                }
+                closest
+            };
+            let shelter = front(*self.bitboard(side | PAWN));
+            let storm = front(*self.bitboard((side ^ 1) | PAWN));
+
+            score += MALUS_SHELTER[shelter as usize];
+            if storm > 0 && storm == shelter + 1 {
+                score += MALUS_STORM[storm as usize] / 2; // Blocked
+            } else {
+                score += MALUS_STORM[storm as usize];
+            }
+        }
+
+        score
     }
 
     // Get square of least valuable piece
@@ -255,6 +536,29 @@ impl EvalExt for Game {
     }
 }
 
+// Get the squares attacked by the given pawns of the given side
+fn pawn_attacks(pawns: Bitboard, side: Color) -> Bitboard {
+    let dirs = if side == WHITE { [NORTHWEST, NORTHEAST] } else { [SOUTHWEST, SOUTHEAST] };
+    dirs.iter().fold(0, |attacks, &dir| {
+        attacks | (pawns.shift(DIRECTION_SHIFTS[dir]) & DIRECTION_MASKS[dir])
+    })
+}
+
+// Get the squares reachable by a king from the given squares by moving
+// through the given free squares
+fn king_fill(mut area: Bitboard, free: Bitboard) -> Bitboard {
+    loop {
+        let mut next = area;
+        for i in 0..8 {
+            next |= area.shift(DIRECTION_SHIFTS[i]) & DIRECTION_MASKS[i] & free;
+        }
+        if next == area {
+            return area;
+        }
+        area = next;
+    }
+}
+
 #[allow(dead_code)]
 fn closed_files(white_pawns: Bitboard, black_pawns: Bitboard) -> Bitboard {
     filefill(white_pawns) & filefill(black_pawns)
@@ -279,6 +583,81 @@ mod tests {
     use fen::FEN;
     use game::Game;
     use piece_move::PieceMove;
+    use piece_move_generator::PieceMoveGenerator;
+    use piece_move_notation::PieceMoveNotation;
+    use variant::Variant;
+
+    #[test]
+    fn test_eval_pawn_storm() {
+        // Intact shelter
+        let game = Game::from_fen("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_storm(WHITE), 0);
+
+        // Shelter with an advanced pawn
+        let game = Game::from_fen("4k3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_storm(WHITE), MALUS_SHELTER[2]);
+
+        // Storm against a king without shelter on the file of the pawn
+        let game = Game::from_fen("4k3/8/8/8/6p1/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_storm(WHITE), MALUS_SHELTER[0] + MALUS_STORM[3]);
+
+        // Blocked storm
+        let game = Game::from_fen("4k3/8/8/8/8/6p1/5PPP/6K1 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_storm(WHITE), MALUS_STORM[2] / 2);
+
+        // Storm of black pawns against the king of white castled on the
+        // other side
+        let game = Game::from_fen("1kr5/ppp5/8/8/8/5ppp/5PPP/6K1 b - - 0 1").unwrap();
+        assert!(game.eval_pawn_storm(WHITE) < game.eval_pawn_storm(BLACK));
+    }
+
+    #[test]
+    fn test_eval_pawn_structure() {
+        let game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(game.eval_pawn_structure(), [0, 0]);
+
+        // Isolated and passed pawn
+        let game = Game::from_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_structure(), [
+            MALUS_ISOLATED_PAWN[0] + BONUS_PASSED_PAWN[1][0],
+            MALUS_ISOLATED_PAWN[1] + BONUS_PASSED_PAWN[1][1]
+        ]);
+
+        // Doubled pawns with only the one in front passed
+        let game = Game::from_fen("4k3/8/8/8/8/P7/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_structure(), [
+            MALUS_DOUBLED_PAWN[0] + 2 * MALUS_ISOLATED_PAWN[0] + BONUS_PASSED_PAWN[2][0],
+            MALUS_DOUBLED_PAWN[1] + 2 * MALUS_ISOLATED_PAWN[1] + BONUS_PASSED_PAWN[2][1]
+        ]);
+
+        // Passed pawns side by side
+        let game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_structure(), [
+            BONUS_PASSED_PAWN[4][0] - BONUS_PASSED_PAWN[3][0],
+            BONUS_PASSED_PAWN[4][1] - BONUS_PASSED_PAWN[3][1]
+        ]);
+
+        // Pawns stopping each other on adjacent files
+        let game = Game::from_fen("4k3/8/8/8/3p4/4P3/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.eval_pawn_structure(), [0, 0]);
+    }
+
+    #[test]
+    fn test_eval_pawn_table() {
+        let fen = "r1bq1rk1/pp3ppp/2n1pn2/2pp4/3P4/2PBPN2/P4PPP/R1BQ1RK1 w - - 0 9";
+        let mut game = Game::from_fen(fen).unwrap();
+
+        // The kings and the pawns move after the evaluation of the pawns
+        // is stored in the table
+        for lan in ["g1h1", "g8h8", "c3c4", "c5d4", "e3d4"].iter() {
+            game.eval();
+            let m = game.move_from_lan(lan);
+            game.make_move(m);
+            let copy = Game::from_fen(&game.to_fen()).unwrap();
+            assert_eq!(game.eval(), copy.eval());
+            assert!(game.pawn_table.get(game.positions.top().pawn_hash).is_some());
+        }
+    }
 
     #[test]
     fn test_draw() {
@@ -294,6 +673,95 @@ mod tests {
         assert_eq!(game.eval(), 0);
     }
 
+    #[test]
+    fn test_kpk() {
+        let mut game = Game::new();
+
+        game.load_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").unwrap();
+        assert!(game.eval() >= QUEEN_VALUE);
+
+        game.load_fen("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1").unwrap();
+        assert!(game.eval() <= -QUEEN_VALUE);
+
+        game.load_fen("k7/8/1K6/P7/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(game.eval(), 0);
+
+        game.load_fen("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(game.eval(), 0);
+    }
+
+    #[test]
+    fn test_eval_params() {
+        let mut game = Game::new();
+
+        game.load_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert_ne!(game.eval(), 2 * BISHOP_VALUE + BONUS_BISHOP_PAIR);
+
+        game.eval_params.position = false;
+        game.eval_params.mobility = false;
+        assert_eq!(game.eval(), 2 * BISHOP_VALUE + BONUS_BISHOP_PAIR);
+
+        game.eval_params.bishop_pair = false;
+        assert_eq!(game.eval(), 2 * BISHOP_VALUE);
+    }
+
+    #[test]
+    fn test_is_blocked() {
+        let mut game = Game::new();
+
+        // Locked chain across the board
+        game.load_fen("8/4k3/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/4K3/8 w - - 0 1").unwrap();
+        assert!(game.is_blocked());
+
+        // Same structure with an extra knight scaled toward a draw
+        game.load_fen("8/4k3/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/4K3/6N1 w - - 0 1").unwrap();
+        assert!(game.is_blocked());
+        let score = game.eval();
+        game.eval_params.blocked = false;
+        assert!(0 < score && score < game.eval());
+        game.eval_params.blocked = true;
+
+        // A pawn can still move
+        game.load_fen("8/4k3/8/1p1p1p2/pPpPpPpP/P1P1P1P1/4K3/8 w - - 0 1").unwrap();
+        assert!(!game.is_blocked());
+
+        // A pawn can capture
+        game.load_fen("4k3/8/8/3pp3/3PP3/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!game.is_blocked());
+
+        // The kings can go around the pawns
+        game.load_fen("8/4k3/8/3p1p1p/2pPpPpP/2P1P1P1/4K3/8 w - - 0 1").unwrap();
+        assert!(!game.is_blocked());
+
+        // Pieces can be dropped in Crazyhouse
+        game.variant = Variant::Crazyhouse;
+        game.load_fen("8/4k3/8/1p1p1p1p/pPpPpPpP/P1P1P1P1/4K3/8[] w - - 0 1").unwrap();
+        assert!(!game.is_blocked());
+    }
+
+    #[test]
+    fn test_exchange_sequence() {
+        let fen = "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.exchange_sequence(E5), vec![
+            (WHITE_KNIGHT, PAWN_VALUE),
+            (BLACK_KNIGHT, PAWN_VALUE - KNIGHT_VALUE),
+            (WHITE_ROOK, PAWN_VALUE),
+            (BLACK_BISHOP, PAWN_VALUE - ROOK_VALUE),
+            (WHITE_QUEEN, PAWN_VALUE - ROOK_VALUE + BISHOP_VALUE),
+            (BLACK_QUEEN, PAWN_VALUE - ROOK_VALUE + BISHOP_VALUE - QUEEN_VALUE)
+        ]);
+
+        // The king can only capture an undefended piece
+        let game = Game::from_fen("8/8/3k4/4p3/3P4/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.exchange_sequence(E5), vec![(WHITE_PAWN, PAWN_VALUE), (BLACK_KING, 0)]);
+        let game = Game::from_fen("8/8/3k4/4p3/3P4/5N2/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.exchange_sequence(E5), vec![(WHITE_PAWN, PAWN_VALUE)]);
+
+        // No capture of a piece of the side to move
+        assert!(game.exchange_sequence(D4).is_empty());
+    }
+
     #[test]
     fn test_see() {
         let mut game = Game::new();
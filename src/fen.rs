@@ -1,5 +1,7 @@
 use std;
+use std::cmp;
 use std::error::Error;
+use std::fmt;
 
 use color::*;
 use piece::*;
@@ -10,124 +12,81 @@ use game::Game;
 use piece::PieceChar;
 use square::SquareExt;
 use positions::Position;
+use variant::CHECKS_LIMIT;
+
+/// Error returned when loading an invalid FEN string
+#[derive(Clone, PartialEq, Debug)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPiece(char),
+    InvalidRankCount(usize),
+    InvalidRankLength(u8),
+    InvalidSide(String),
+    InvalidCastling(char),
+    InvalidEnPassant(String),
+    InvalidCounter(String),
+    InvalidChecks(String),
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    PawnOnBackRank(Square)
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let color = |c: &Color| if *c == WHITE { "white" } else { "black" };
+        match self {
+            FenError::MissingField(field)   => write!(f, "missing {} field in fen", field),
+            FenError::InvalidPiece(c)       => write!(f, "invalid piece '{}' in fen", c),
+            FenError::InvalidRankCount(n)   => write!(f, "invalid number of ranks {} in fen", n),
+            FenError::InvalidRankLength(r)  => write!(f, "invalid length of rank {} in fen", r),
+            FenError::InvalidSide(s)        => write!(f, "invalid side '{}' in fen", s),
+            FenError::InvalidCastling(c)    => write!(f, "invalid castling right '{}' in fen", c),
+            FenError::InvalidEnPassant(s)   => write!(f, "invalid en passant square '{}' in fen", s),
+            FenError::InvalidCounter(s)     => write!(f, "invalid move counter '{}' in fen", s),
+            FenError::InvalidChecks(s)      => write!(f, "invalid remaining checks '{}' in fen", s),
+            FenError::MissingKing(c)        => write!(f, "missing {} king in fen", color(c)),
+            FenError::TooManyKings(c)       => write!(f, "too many {} kings in fen", color(c)),
+            FenError::TooManyPawns(c)       => write!(f, "too many {} pawns in fen", color(c)),
+            FenError::TooManyPieces(c)      => write!(f, "too many {} pieces in fen", color(c)),
+            FenError::PawnOnBackRank(sq)    => write!(f, "invalid pawn on {} in fen", sq.to_coord())
+        }
+    }
+}
+
+impl Error for FenError {}
 
 /// Forsyth–Edwards Notation support
 pub trait FEN {
     /// Create `Game` from a given FEN string
-    fn from_fen(fen: &str) -> Result<Game, Box<dyn Error>>;
+    fn from_fen(fen: &str) -> Result<Game, FenError>;
 
-    /// Load game state from a given FEN string
-    fn load_fen(&mut self, fen: &str) -> Result<(), Box<dyn Error>>;
+    /// Load game state from a given FEN string, or the starting position
+    /// if the string is invalid
+    fn load_fen(&mut self, fen: &str) -> Result<(), FenError>;
 
-    /// Export game state to a FEN string
+    /// Export game state to a FEN string, or to a Shredder-FEN string if
+    /// `is_shredder_fen` is set
     fn to_fen(&self) -> String;
+
+    /// Export game state to a Shredder-FEN string, with castling rights
+    /// given by the files of the rooks
+    fn to_shredder_fen(&self) -> String;
 }
 
 impl FEN for Game {
-    fn from_fen(fen: &str) -> Result<Game, Box<dyn Error>> {
+    fn from_fen(fen: &str) -> Result<Game, FenError> {
         let mut game = Game::new();
         game.load_fen(fen)?;
         Ok(game)
     }
 
-    // TODO: Return error if loading fail
-    fn load_fen(&mut self, fen: &str) -> Result<(), Box<dyn Error>> {
-        self.clear();
-        self.starting_fen = String::from(fen);
-        let mut position = Position::new();
-
-        let mut fields = fen.split_whitespace();
-
-        let mut sq = A8;
-        if let Some(field) = fields.next() {
-            for c in field.chars() {
-                let dir = match c {
-                    '/' => {
-                        2 * DOWN
-                    },
-                    '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' => {
-                        c.to_digit(10).unwrap() as Shift
-                    },
-                    'P' | 'N' | 'B' | 'R' | 'Q' | 'K' |
-                    'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
-                        let p = PieceChar::from_char(c);
-                        self.board[sq as usize] = p;
-                        self.bitboards[(p) as usize].set(sq);
-                        self.bitboards[(p & 1) as usize].set(sq); // TODO: p.color()
-                        position.hash ^= self.zobrist.pieces[p as usize][sq as usize];
-                        1
-                    },
-                    _ => {
-                        self.load_fen(DEFAULT_FEN)?;
-                        return Err("invalid fen string".into());
-                    }
-                };
-                //sq += dir;
-                sq = ((sq as i8) + dir) as Square;
-            }
-        } else {
+    fn load_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        if let Err(e) = parse_fen(self, fen) {
             self.load_fen(DEFAULT_FEN)?;
-            return Err("invalid fen string".into());
-        }
-
-        position.side = match fields.next() {
-            Some("w") => WHITE,
-            Some("b") => BLACK,
-            _ => {
-                self.load_fen(DEFAULT_FEN)?;
-                return Err("invalid fen string".into());
-            }
-        };
-
-        if position.side == BLACK {
-            position.hash ^= self.zobrist.side;
+            return Err(e);
         }
-
-        if let Some(field) = fields.next() {
-            for c in field.chars() {
-                match c {
-                    'K' => {
-                        position.set_castling_right(WHITE, KING);
-                        position.hash ^= self.zobrist.castling_right(WHITE, KING);
-                    }
-                    'Q' => {
-                        position.set_castling_right(WHITE, QUEEN);
-                        position.hash ^= self.zobrist.castling_right(WHITE, QUEEN);
-                    }
-                    'k' => {
-                        position.set_castling_right(BLACK, KING);
-                        position.hash ^= self.zobrist.castling_right(BLACK, KING);
-                    }
-                    'q' => {
-                        position.set_castling_right(BLACK, QUEEN);
-                        position.hash ^= self.zobrist.castling_right(BLACK, QUEEN);
-                    }
-                    _   => break
-                }
-            }
-        }
-
-        if let Some(ep) = fields.next() {
-            if ep != "-" {
-                position.en_passant = SquareExt::from_coord(ep.into()); // TODO: check square
-                position.hash ^= self.zobrist.en_passant[position.en_passant as usize];
-            }
-        };
-
-        self.positions.push(position);
-
-        if let Some(hm) = fields.next() {
-            if let Ok(n) = hm.parse::<u8>() {
-                self.positions.set_halfmoves(n);
-            }
-        };
-
-        if let Some(fm) = fields.next() {
-            if let Ok(n) = fm.parse::<u8>() {
-                self.positions.set_fullmoves(n);
-            }
-        };
-
         Ok(())
     }
 
@@ -147,9 +106,16 @@ impl FEN for Game {
                     n = 0;
                 }
                 fen.push(p.to_char());
+                if self.variant.has_drops() && self.positions.top().promoted.get(sq) {
+                    fen.push('~');
+                }
             }
 
             if sq == H1 {
+                if n > 0 {
+                    let c = std::char::from_digit(n, 10).unwrap();
+                    fen.push(c);
+                }
                 break;
             }
 
@@ -168,6 +134,20 @@ impl FEN for Game {
             sq = ((sq as i8) + RIGHT) as Square; // 0 <= sq <= 64
         }
 
+        // Pieces in hand in Crazyhouse
+        if self.variant.has_drops() {
+            let pos = self.positions.top();
+            fen.push('[');
+            for &c in &COLORS {
+                for &p in &[QUEEN, ROOK, BISHOP, KNIGHT, PAWN] {
+                    for _ in 0..pos.hand(c | p) {
+                        fen.push((c | p).to_char());
+                    }
+                }
+            }
+            fen.push(']');
+        }
+
         fen.push(' ');
         if self.side() == WHITE {
             fen.push('w');
@@ -176,24 +156,7 @@ impl FEN for Game {
         }
 
         fen.push(' ');
-        let &pos = self.positions.top();
-        let mut castles = String::new();
-        if pos.castling_right(WHITE, KING) {
-            castles.push('K');
-        }
-        if pos.castling_right(WHITE, QUEEN) {
-            castles.push('Q');
-        }
-        if pos.castling_right(BLACK, KING) {
-            castles.push('k');
-        }
-        if pos.castling_right(BLACK, QUEEN) {
-            castles.push('q');
-        }
-        if castles.is_empty() {
-            castles.push('-');
-        }
-        fen.push_str(&castles);
+        fen.push_str(&castling_field(self, self.is_shredder_fen));
 
         fen.push(' ');
         // TODO: implement `square.is_out()`
@@ -204,6 +167,13 @@ impl FEN for Game {
             fen.push('-');
         }
 
+        if self.variant.has_checks_limit() {
+            let pos = self.positions.top();
+            let white = CHECKS_LIMIT - pos.checks(WHITE);
+            let black = CHECKS_LIMIT - pos.checks(BLACK);
+            fen.push_str(&format!(" {}+{}", white, black));
+        }
+
         fen.push(' ');
         let hm = self.positions.halfmoves();
         let fm = self.positions.fullmoves();
@@ -211,15 +181,245 @@ impl FEN for Game {
 
         fen
     }
+
+    fn to_shredder_fen(&self) -> String {
+        let mut fields: Vec<String> = self.to_fen().split(' ').map(String::from).collect();
+        fields[2] = castling_field(self, true);
+        fields.join(" ")
+    }
+}
+
+fn parse_fen(game: &mut Game, fen: &str) -> Result<(), FenError> {
+    game.clear();
+    game.starting_fen = String::from(fen);
+    let mut position = Position::new();
+
+    let mut fields = fen.split_whitespace().peekable();
+
+    let field = fields.next().ok_or(FenError::MissingField("board"))?;
+
+    // Pieces in hand in Crazyhouse, like `[Qp]`
+    let (field, hand) = match field.find('[') {
+        Some(i) if field.ends_with(']') && game.variant.has_drops() => (&field[..i], Some(&field[(i + 1)..(field.len() - 1)])),
+        Some(_) => return Err(FenError::InvalidPiece('[')),
+        None => (field, None)
+    };
+    if let Some(hand) = hand {
+        for c in hand.chars() {
+            let p = match c {
+                'P' | 'N' | 'B' | 'R' | 'Q' |
+                'p' | 'n' | 'b' | 'r' | 'q' => PieceChar::from_char(c),
+                _ => return Err(FenError::InvalidPiece(c))
+            };
+            let n = position.hand(p);
+            if n as usize >= MAX_HAND {
+                return Err(FenError::TooManyPieces(p.color()));
+            }
+            position.hash ^= game.zobrist.hand(p, n);
+            position.hands[p as usize] += 1;
+        }
+    }
+
+    let ranks: Vec<&str> = field.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::InvalidRankCount(ranks.len()));
+    }
+    for (i, rank) in ranks.iter().enumerate() {
+        let r = 7 - i as u8;
+        let mut f = 0;
+        for c in rank.chars() {
+            match c {
+                '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' => {
+                    f += c.to_digit(10).unwrap() as u8;
+                },
+                'P' | 'N' | 'B' | 'R' | 'Q' | 'K' |
+                'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
+                    if f < 8 {
+                        let sq = 8 * r + f;
+                        let p = PieceChar::from_char(c);
+                        game.board[sq as usize] = p;
+                        game.bitboards[p as usize].set(sq);
+                        game.bitboards[p.color() as usize].set(sq);
+                        position.hash ^= game.zobrist.pieces[p as usize][sq as usize];
+                        if p.kind() == PAWN {
+                            position.pawn_hash ^= game.zobrist.pieces[p as usize][sq as usize];
+                        }
+                    }
+                    f += 1;
+                },
+                '~' if f > 0 && game.board[(8 * r + f - 1) as usize] != EMPTY => {
+                    position.promoted.set(8 * r + f - 1);
+                },
+                _ => {
+                    return Err(FenError::InvalidPiece(c));
+                }
+            }
+        }
+        if f != 8 {
+            return Err(FenError::InvalidRankLength(r + 1));
+        }
+    }
+
+    for &c in &COLORS {
+        // Kings can be captured in Antichess and exploded in Atomic
+        let has_king = game.bitboard(c | KING).count() > 0;
+        if !has_king && game.variant.has_royal_king() && !game.variant.has_explosions() {
+            return Err(FenError::MissingKing(c));
+        }
+        if game.bitboard(c | KING).count() > 1 {
+            return Err(FenError::TooManyKings(c));
+        }
+        // Dropped pieces can exceed the numbers of the starting position
+        if hand.is_none() && game.bitboard(c | PAWN).count() > 8 {
+            return Err(FenError::TooManyPawns(c));
+        }
+        if hand.is_none() && game.bitboard(c).count() > 16 {
+            return Err(FenError::TooManyPieces(c));
+        }
+        let pawns = game.bitboard(c | PAWN) & (RANK_1 | RANK_8);
+        if pawns != 0 {
+            return Err(FenError::PawnOnBackRank(pawns.scan() as Square));
+        }
+    }
+
+    position.side = match fields.next() {
+        Some("w") => WHITE,
+        Some("b") => BLACK,
+        Some(s) => return Err(FenError::InvalidSide(s.to_string())),
+        None => return Err(FenError::MissingField("side"))
+    };
+
+    if position.side == BLACK {
+        position.hash ^= game.zobrist.side;
+    }
+
+    // Castling rights in FEN, X-FEN, or Shredder-FEN
+    let field = fields.next().ok_or(FenError::MissingField("castling"))?;
+    if field != "-" {
+        for c in field.chars() {
+            let side = if c.is_ascii_uppercase() { WHITE } else { BLACK };
+            let k = king_file(game, side).ok_or(FenError::InvalidCastling(c))?;
+            let (wing, file) = match c.to_ascii_lowercase() {
+                'k' => (KING, outermost_rook_file(game, side, KING)),
+                'q' => (QUEEN, outermost_rook_file(game, side, QUEEN)),
+                'a'..='h' => {
+                    let file = c.to_ascii_lowercase() as u8 - b'a';
+                    let wing = if file > k { KING } else { QUEEN };
+                    (wing, Some(file).filter(|&f| is_rook_file(game, side, f)))
+                },
+                _ => return Err(FenError::InvalidCastling(c))
+            };
+            let file = file.ok_or(FenError::InvalidCastling(c))?;
+            if !position.castling_right(side, wing) {
+                position.set_castling_right(side, wing);
+                position.hash ^= game.zobrist.castling_right(side, wing);
+            }
+            position.set_castling_file(side, wing, file);
+        }
+    }
+
+    let field = fields.next().ok_or(FenError::MissingField("en passant"))?;
+    if field != "-" {
+        let bytes = field.as_bytes();
+        let rank = if position.side == WHITE { b'6' } else { b'3' };
+        if bytes.len() != 2 || bytes[0] < b'a' || bytes[0] > b'h' || bytes[1] != rank {
+            return Err(FenError::InvalidEnPassant(field.to_string()));
+        }
+        position.en_passant = SquareExt::from_coord(field);
+        position.hash ^= game.zobrist.en_passant[position.en_passant as usize];
+    }
+
+    // Remaining checks in Three-check, like `3+3`
+    if let Some(field) = fields.peek().cloned().filter(|field| field.contains('+')) {
+        fields.next();
+        let counts: Vec<u8> = field.split('+').filter_map(|n| n.parse().ok()).collect();
+        if !game.variant.has_checks_limit() || counts.len() != 2 || counts.iter().any(|&n| n > CHECKS_LIMIT) {
+            return Err(FenError::InvalidChecks(field.to_string()));
+        }
+        for (&c, &n) in COLORS.iter().zip(&counts) {
+            while position.checks(c) < CHECKS_LIMIT - n {
+                position.hash ^= game.zobrist.check(c, position.checks(c));
+                position.add_check(c);
+            }
+        }
+    }
+
+    game.positions.push(position);
+
+    // The move counters are optional, like in EPD, and the halfmoves beyond
+    // the fifty-move rule are clamped
+    if let Some(hm) = fields.next() {
+        let n = hm.parse::<u32>().map_err(|_| FenError::InvalidCounter(hm.to_string()))?;
+        game.positions.set_halfmoves(cmp::min(n, u8::MAX as u32) as u8);
+    }
+
+    if let Some(fm) = fields.next() {
+        let n = fm.parse::<u16>().map_err(|_| FenError::InvalidCounter(fm.to_string()))?;
+        game.positions.set_fullmoves(n);
+    }
+
+    Ok(())
+}
+
+// Get the file of the king of the given side on its first rank
+fn king_file(game: &Game, side: Color) -> Option<u8> {
+    (0..8).find(|&f| game.board[(f as Square).flip(side) as usize] == side | KING)
+}
+
+// Check if a rook of the given side is on the given file of its first rank
+fn is_rook_file(game: &Game, side: Color, file: u8) -> bool {
+    game.board[(file as Square).flip(side) as usize] == side | ROOK
+}
+
+// Get the file of the outermost rook of the given side on the given wing of
+// its first rank
+fn outermost_rook_file(game: &Game, side: Color, wing: Piece) -> Option<u8> {
+    let is_rook = |f: &u8| is_rook_file(game, side, *f);
+    let k = king_file(game, side)?;
+    if wing == KING {
+        (k + 1..8).rev().find(is_rook)
+    } else {
+        (0..k).find(is_rook)
+    }
+}
+
+// Get the castling field of the game, using the file of the rooks when
+// they are not the outermost ones like in X-FEN, or always like in
+// Shredder-FEN
+fn castling_field(game: &Game, is_shredder: bool) -> String {
+    let pos = game.positions.top();
+    let mut castles = String::new();
+    for &side in &COLORS {
+        for &wing in &[KING, QUEEN] {
+            if !pos.castling_right(side, wing) {
+                continue;
+            }
+            let file = pos.castling_file(side, wing);
+            let (c, default_file) = if wing == KING { ('k', 7) } else { ('q', 0) };
+            let c = if !is_shredder && outermost_rook_file(game, side, wing).unwrap_or(default_file) == file {
+                c
+            } else {
+                (b'a' + file) as char
+            };
+            castles.push(if side == WHITE { c.to_ascii_uppercase() } else { c });
+        }
+    }
+    if castles.is_empty() {
+        castles.push('-');
+    }
+    castles
 }
 
 #[cfg(test)]
 mod tests {
+    use color::*;
     use piece::*;
     use square::*;
     use common::*;
-    use fen::FEN;
+    use bitboard::BitboardExt;
+    use fen::{FEN, FenError};
     use game::Game;
+    use variant::Variant;
 
     #[test]
     fn test_from_fen() {
@@ -241,4 +441,129 @@ mod tests {
             assert_eq!(&game.to_fen(), fen);
         }
     }
+
+    #[test]
+    fn test_fen_errors() {
+        let errors = [
+            ("", FenError::MissingField("board")),
+            ("4k3/8/8/8/8/8/8/4K3", FenError::MissingField("side")),
+            ("4k3/8/8/8/8/8/8/4K3 w", FenError::MissingField("castling")),
+            ("4k3/8/8/8/8/8/8/4X3 w - - 0 1", FenError::InvalidPiece('X')),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::InvalidRankCount(7)),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::InvalidRankLength(1)),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidSide("x".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w X - 0 1", FenError::InvalidCastling('X')),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::InvalidCastling('K')),
+            ("4k3/8/8/8/8/8/4K3/R6R w Q - 0 1", FenError::InvalidCastling('Q')),
+            (&format!("{} w KkQqABCDEFGH - 0 1", DEFAULT_FEN.split(' ').next().unwrap()), FenError::InvalidCastling('B')),
+            ("4k3/8/8/8/8/8/8/4K3 w - e3 0 1", FenError::InvalidEnPassant("e3".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::InvalidCounter("x".into())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 2+3 0 1", FenError::InvalidChecks("2+3".into())),
+            ("4k3/8/8/8/8/8/8/4K3[Q] w - - 0 1", FenError::InvalidPiece('[')),
+            ("8/8/8/8/8/8/8/8 w - - 0 1", FenError::MissingKing(WHITE)),
+            ("4k3/8/8/8/8/8/8/R7 w - - 0 1", FenError::MissingKing(WHITE)),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::TooManyKings(WHITE)),
+            ("4k3/pppppppp/p7/8/8/8/8/4K3 w - - 0 1", FenError::TooManyPawns(BLACK)),
+            ("4k3/8/8/8/8/8/8/4K2p w - - 0 1", FenError::PawnOnBackRank(H1))
+        ];
+        for (fen, error) in errors.iter() {
+            assert_eq!(Game::from_fen(fen).err(), Some(error.clone()));
+        }
+
+        // Kings can be captured in Antichess
+        let mut game = Game::new();
+        game.variant = Variant::Antichess;
+        assert!(game.load_fen("4k3/8/8/8/8/8/8/R7 w - - 0 1").is_ok());
+
+        // The move counters of long games are kept
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 300";
+        assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.load_fen("4k3/8/8/8/8/8/8/4K3 w - -").is_ok()); // EPD
+        assert!(game.load_fen("4k3 w - - 0 1").is_err());
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+    }
+
+    #[test]
+    fn test_castling_files() {
+        // X-FEN with an inner rook on the king side
+        let fen = "4k2r/8/8/8/8/8/8/R3KR1R w Fk - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        let pos = game.positions.top();
+        assert!(pos.castling_right(WHITE, KING));
+        assert!(!pos.castling_right(WHITE, QUEEN));
+        assert_eq!(pos.castling_rook(WHITE, KING), F1);
+        assert_eq!(&game.to_fen(), fen);
+        assert_eq!(&game.to_shredder_fen(), "4k2r/8/8/8/8/8/8/R3KR1R w Fh - 0 1");
+
+        // Shredder-FEN of a Chess960 position
+        let fen = "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1";
+        let game = Game::from_fen(fen).unwrap();
+        let pos = game.positions.top();
+        assert_eq!(pos.castling_rook(WHITE, KING), C1);
+        assert_eq!(pos.castling_rook(BLACK, QUEEN), A8);
+        assert_eq!(&game.to_shredder_fen(), fen);
+        assert_eq!(&game.to_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+
+        let game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(&game.to_shredder_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1");
+    }
+
+    #[test]
+    fn test_shredder_fen_option() {
+        let shredder_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1";
+        for fen in &[DEFAULT_FEN, shredder_fen] {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert_eq!(&game.to_fen(), DEFAULT_FEN);
+            game.is_shredder_fen = true;
+            assert_eq!(&game.to_fen(), shredder_fen);
+        }
+
+        // The option is kept when loading another position
+        let mut game = Game::new();
+        game.is_shredder_fen = true;
+        game.load_fen("4k2r/8/8/8/8/8/8/R3KR1R w Fk - 0 1").unwrap();
+        assert_eq!(&game.to_fen(), "4k2r/8/8/8/8/8/8/R3KR1R w Fh - 0 1");
+        assert_eq!(game.to_fen(), game.to_shredder_fen());
+    }
+
+    #[test]
+    fn test_crazyhouse_fen() {
+        let fen = "r1bqk2r/pppp1ppp/2n2n2/4p3/1b2P3/2NP1N2/PPP2PPP/R1BQ~KB1R[Bp] w KQkq - 0 6";
+        let mut game = Game::new();
+        game.variant = Variant::Crazyhouse;
+        game.load_fen(fen).unwrap();
+        let pos = *game.positions.top();
+        assert_eq!(pos.hand(WHITE_BISHOP), 1);
+        assert_eq!(pos.hand(BLACK_PAWN), 1);
+        assert_eq!(pos.hand(WHITE_PAWN), 0);
+        assert!(pos.promoted.get(D1));
+        assert_eq!(&game.to_fen(), fen);
+
+        // The hands are part of the hash
+        game.load_fen("r1bqk2r/pppp1ppp/2n2n2/4p3/1b2P3/2NP1N2/PPP2PPP/R1BQ~KB1R[Pb] w KQkq - 0 6").unwrap();
+        assert_ne!(game.positions.top().hash, pos.hash);
+
+        assert_eq!(game.load_fen("4k3/8/8/8/8/8/8/4K3[K] w - - 0 1").err(), Some(FenError::InvalidPiece('K')));
+        assert_eq!(game.load_fen("4k3/8/8/8/8/8/8/4K3[ w - - 0 1").err(), Some(FenError::InvalidPiece('[')));
+    }
+
+    #[test]
+    fn test_three_check_fen() {
+        let fen = "rnbqkbnr/ppp2ppp/8/1B1pp3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 2+3 1 3";
+        let mut game = Game::new();
+        game.variant = Variant::ThreeCheck;
+        game.load_fen(fen).unwrap();
+        let pos = *game.positions.top();
+        assert_eq!(pos.checks(WHITE), 1);
+        assert_eq!(pos.checks(BLACK), 0);
+        assert_eq!(&game.to_fen(), fen);
+
+        // The checks are part of the hash
+        game.load_fen("rnbqkbnr/ppp2ppp/8/1B1pp3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 3+3 1 3").unwrap();
+        assert_ne!(game.positions.top().hash, pos.hash);
+
+        assert_eq!(game.load_fen("4k3/8/8/8/8/8/8/4K3 w - - 4+3 0 1").err(), Some(FenError::InvalidChecks("4+3".into())));
+    }
 }
@@ -1,18 +1,40 @@
+use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
 use colored::Colorize;
 
+use attack::piece_attacks;
 use board;
 use color::*;
 use piece::*;
+use square::*;
 use common::*;
-use bitboard::Bitboard;
+use bitboard::{Bitboard, BitboardExt, BitboardIterator};
+use chess960;
 use clock::Clock;
+use cuckoo::{Cuckoo, CUCKOO};
+use fen::FEN;
+use learning::Learning;
+use material::MaterialSignature;
+use options::DEFAULT_PROFILE;
+use odds;
+use odds::Odds;
+use pawn_table::PawnTable;
+use params::{ClearPolicy, EvalParams, SearchParams};
 use piece_move::PieceMove;
+use piece_move_generator::PieceMoveGenerator;
 use piece_move_list::PieceMoveList;
+use piece_move_notation::{PieceMoveNotation, PromotionPiece};
 use positions::Positions;
+use pv_table::PvTable;
 use protocols::Protocol;
+use search::{RootOrdering, Search, SearchResult, SearchStats, ThreadStats};
+use trace::SearchTrace;
 use transposition_table::TranspositionTable;
-use zobrist::Zobrist;
+use variant::{Variant, CHECKS_LIMIT};
+use random::Random;
+use zobrist::{Zobrist, POLYGLOT_KEYS_COUNT};
 use piece::{PieceAttr, PieceChar};
 
 /// A `Game` type to store the state of a chess game
@@ -24,16 +46,42 @@ pub struct Game {
     pub is_eval_verbose: bool, // Print thinking in eval
     pub is_search_verbose: bool, // Print thinking in search
     pub show_coordinates: bool,
+    pub show_san_pv: bool, // Print PV in SAN in UCI info strings
+    pub show_ponder: bool, // Print the move to ponder on after the best move in UCI
+    pub is_chess960: bool, // Castle by moving the king to its rook in LAN
+    pub is_shredder_fen: bool, // Give castling rights with the files of the rooks in FEN
+    pub variant: Variant,
     pub threads_count: usize,
     pub nodes_count: u64,
+    pub seldepth: usize, // Deepest ply reached by the current iteration of the search
+    pub search_stats: SearchStats, // Stats of the nodes of the last search
+    pub search_result: Option<SearchResult>,
+    pub threads_stats: Vec<ThreadStats>, // Stats of each thread of the last search
+    pub threads_nodes: Arc<Vec<AtomicU64>>, // Nodes searched by each thread during SMP search
+    pub thread_index: usize,
+    pub search_params: SearchParams,
+    pub eval_params: EvalParams,
+    pub learning: Learning,
+    pub trace: SearchTrace, // Nodes of the search written to a file for debugging
+    pub root_ordering: Option<Arc<RootOrdering>>, // Reordering of the root moves before the search
+    pub auto_queen: bool, // Promote to a queen when a move is entered without the promotion
+    pub promotion_piece: Option<Arc<PromotionPiece>>, // Choice of the promotion otherwise
+    pub options_profile: String,
     pub clock: Clock,
     pub bitboards: [Bitboard; 14],
     pub board: [Piece; 64],
     pub moves: PieceMoveList,
+    pub pv: PvTable, // Principal variations found by the search
     pub positions: Positions,
     pub zobrist: Zobrist,
+    pub cuckoo: Arc<Cuckoo>, // Reversible moves indexed by their hash difference
+    pub rng: Random,
     pub history: Vec<PieceMove>,
-    pub tt: TranspositionTable
+    pub explosions: Vec<(Square, Piece)>, // Pieces to restore when undoing captures in Atomic
+    pub tt: TranspositionTable,
+    #[cfg(feature = "verify-search")]
+    pub verify_tt: TranspositionTable, // Written by the searches verifying the zero-window ones
+    pub pawn_table: PawnTable
 }
 
 impl Game {
@@ -46,16 +94,42 @@ impl Game {
             is_eval_verbose: false,
             is_search_verbose: false,
             show_coordinates: false,
+            show_san_pv: false,
+            show_ponder: false,
+            is_chess960: false,
+            is_shredder_fen: false,
+            variant: Variant::Standard,
             threads_count: 0,
             nodes_count: 0,
+            seldepth: 0,
+            search_stats: SearchStats::default(),
+            search_result: None,
+            threads_stats: Vec::new(),
+            threads_nodes: Arc::new(Vec::new()),
+            thread_index: 0,
+            search_params: SearchParams::new(),
+            eval_params: EvalParams::new(),
+            learning: Learning::new(),
+            trace: SearchTrace::new(),
+            root_ordering: None,
+            auto_queen: false,
+            promotion_piece: None,
+            options_profile: String::from(DEFAULT_PROFILE),
             clock: Clock::new(40, 5 * 60),
             bitboards: [0; 14],
             board: [EMPTY; 64],
             moves: PieceMoveList::new(),
+            pv: PvTable::new(),
             positions: Positions::new(),
             zobrist: Zobrist::new(),
+            cuckoo: Arc::clone(&CUCKOO),
+            rng: Random::from_entropy(),
             history: Vec::new(),
-            tt: TranspositionTable::with_memory(TT_SIZE)
+            explosions: Vec::new(),
+            tt: TranspositionTable::with_memory(TT_SIZE),
+            #[cfg(feature = "verify-search")]
+            verify_tt: TranspositionTable::with_memory(VERIFY_TT_SIZE),
+            pawn_table: PawnTable::with_memory(PAWN_TABLE_SIZE)
         }
     }
 
@@ -67,17 +141,54 @@ impl Game {
     /// Resize the transposition table at the given size in byte or the next
     /// power of two
     pub fn tt_resize(&mut self, memory: usize) {
+        let huge_pages = self.tt.has_huge_pages();
         self.tt = TranspositionTable::with_memory(memory);
+        if huge_pages {
+            self.tt.set_huge_pages(true);
+        }
     }
 
-    /// Clear the current game state
+    /// Clear the current game state, keeping the search tables that are
+    /// handled by `clear_search`
     pub fn clear(&mut self) {
         self.bitboards = [0; 14];
         self.board = [EMPTY; 64];
-        self.moves.clear_all();
+        self.moves.clear_plies();
         self.positions.clear();
         self.history.clear();
-        self.tt.clear();
+        self.explosions.clear();
+    }
+
+    /// Apply the given policy to the transposition table, the killer moves
+    /// and the history heuristic
+    pub fn clear_search(&mut self, policy: ClearPolicy) {
+        match policy {
+            ClearPolicy::Keep => {
+            },
+            ClearPolicy::Age => {
+                self.tt.reset();
+                self.moves.clear_killers();
+                self.moves.age_history();
+            },
+            ClearPolicy::Clear => {
+                self.tt.clear();
+                self.moves.clear_killers();
+                self.moves.clear_history();
+            }
+        }
+    }
+
+    /// Clear the game state and the search tables before a new game
+    pub fn new_game(&mut self) {
+        self.clear();
+        self.clear_search(self.search_params.clear_between_games);
+
+        // Nothing found by the searches of the previous game is kept
+        self.positions = Positions::new();
+        self.pv = PvTable::new();
+        self.search_result = None;
+        self.search_stats = SearchStats::default();
+        self.seldepth = 0;
     }
 
     /// Get a bitboard representation of the given piece in the game
@@ -90,6 +201,233 @@ impl Game {
     pub fn side(&self) -> Color {
         self.positions.top().side
     }
+
+    /// Get the game phase, from `0` in the opening to `PHASE_MAX` in the
+    /// ending, based on the non-pawn material left on the board
+    pub fn phase(&self) -> Score {
+        let mut material = 0;
+        for &c in &COLORS {
+            material += self.bitboard(c | KNIGHT).count();
+            material += self.bitboard(c | BISHOP).count();
+            material += self.bitboard(c | ROOK).count() * 2;
+            material += self.bitboard(c | QUEEN).count() * 4;
+        }
+        let material = (material as Score).min(PHASE_MATERIAL);
+
+        (PHASE_MATERIAL - material) * PHASE_MAX / PHASE_MATERIAL
+    }
+
+    /// Get the number of pieces of the given side attacking each square,
+    /// to display a heatmap of the board or to debug the king safety
+    pub fn attack_map(&self, side: Color) -> [u8; 64] {
+        let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+        let mut map = [0; 64];
+        for &kind in &PIECES {
+            let piece = side | kind;
+            let mut pieces = *self.bitboard(piece);
+            while let Some(square) = pieces.next() {
+                let mut targets = piece_attacks(piece, square, occupied);
+                while let Some(target) = targets.next() {
+                    map[target as usize] += 1;
+                }
+            }
+        }
+        map
+    }
+
+    /// Check if the opponent of the side to move has given the last check
+    /// allowed in Three-check
+    pub fn is_checks_limit_reached(&self) -> bool {
+        self.variant.has_checks_limit() && self.positions.top().checks(self.side() ^ 1) >= CHECKS_LIMIT
+    }
+
+    /// Create a new `Game` from the Double Fischer Random Chess start
+    /// position with the given Scharnagl numbers for each side, or from a
+    /// Chess960 one with the same number twice
+    pub fn from_960_id(white_id: u16, black_id: u16) -> Result<Game, Box<dyn Error>> {
+        let fen = chess960::start_fen(white_id, black_id)?;
+        let mut game = Game::from_fen(&fen)?;
+        game.is_chess960 = true;
+        Ok(game)
+    }
+
+    /// Create a new `Game` from the start position with the given material
+    /// odds given by the given side
+    pub fn from_odds(odds: Odds, side: Color) -> Game {
+        Game::from_fen(&odds::start_fen(odds, side)).unwrap()
+    }
+
+    /// Get the material signature of the game, like `KRPPvKRP` with the
+    /// white pieces first
+    pub fn material_signature(&self) -> String {
+        MaterialSignature::from_game(self).to_string()
+    }
+
+    /// Rebuild the board and the bitboards of the colors from the
+    /// bitboards of the pieces, and recompute the hashes of the position
+    pub fn rebuild_from_bitboards(&mut self) {
+        self.board = [EMPTY; 64];
+        self.bitboards[WHITE as usize] = 0;
+        self.bitboards[BLACK as usize] = 0;
+        for &c in &COLORS {
+            for &kind in &PIECES {
+                let p = c | kind;
+                let mut pieces = self.bitboards[p as usize];
+                while let Some(sq) = pieces.next() {
+                    self.board[sq as usize] = p;
+                }
+                self.bitboards[c as usize] |= self.bitboards[p as usize];
+            }
+        }
+        self.positions.set_hash(self.compute_hash());
+        self.positions.set_pawn_hash(self.compute_pawn_hash());
+    }
+
+    /// Rebuild the bitboards from the board, and recompute the hashes of
+    /// the position
+    pub fn rebuild_from_board(&mut self) {
+        self.bitboards = [0; 14];
+        for sq in 0..64 {
+            let p = self.board[sq as usize];
+            if p != EMPTY {
+                self.bitboards[p as usize].set(sq);
+                self.bitboards[p.color() as usize].set(sq);
+            }
+        }
+        self.positions.set_hash(self.compute_hash());
+        self.positions.set_pawn_hash(self.compute_pawn_hash());
+    }
+
+    /// Get the hash of the current position
+    pub fn hash(&self) -> u64 {
+        self.positions.top().hash
+    }
+
+    /// Compute the hash of the current position from scratch instead of
+    /// updating it incrementally
+    pub fn compute_hash(&self) -> u64 {
+        let position = self.positions.top();
+        let mut hash = 0;
+        for sq in 0..64 {
+            let p = self.board[sq];
+            if p != EMPTY {
+                hash ^= self.zobrist.pieces[p as usize][sq];
+            }
+        }
+        for &c in &COLORS {
+            for &kind in &PIECES {
+                for n in 0..position.hand(c | kind) {
+                    hash ^= self.zobrist.hand(c | kind, n);
+                }
+            }
+            for &wing in &[KING, QUEEN] {
+                if position.castling_right(c, wing) {
+                    hash ^= self.zobrist.castling_right(c, wing);
+                }
+            }
+            for n in 0..position.checks(c) {
+                hash ^= self.zobrist.check(c, n);
+            }
+        }
+        if position.side == BLACK {
+            hash ^= self.zobrist.side;
+        }
+        if position.en_passant != OUT {
+            hash ^= self.zobrist.en_passant[position.en_passant as usize];
+        }
+        hash
+    }
+
+    /// Compute the hash of the pawns of the current position from scratch
+    pub fn compute_pawn_hash(&self) -> u64 {
+        let mut hash = 0;
+        for &c in &COLORS {
+            let p = c | PAWN;
+            let mut pawns = self.bitboards[p as usize];
+            while let Some(sq) = pawns.next() {
+                hash ^= self.zobrist.pieces[p as usize][sq as usize];
+            }
+        }
+        hash
+    }
+
+    /// Use the keys of the Polyglot opening books, given by their random
+    /// array, to hash the positions loaded afterward
+    pub fn set_polyglot_keys(&mut self, randoms: &[u64; POLYGLOT_KEYS_COUNT]) {
+        self.zobrist = Zobrist::from_polyglot(randoms);
+        self.cuckoo = Arc::new(Cuckoo::new(&self.zobrist));
+    }
+
+    /// Get the Polyglot key of the current position from its hash, when
+    /// the positions are hashed with the keys of Polyglot
+    pub fn polyglot_key(&self) -> u64 {
+        let position = self.positions.top();
+
+        // Polyglot hashes the side to move when it is white
+        let mut key = position.hash ^ self.zobrist.side;
+
+        // And the en passant file only when a pawn can capture
+        let ep = position.en_passant;
+        if ep != OUT {
+            let side = position.side;
+            let attackers = piece_attacks((side ^ 1) | PAWN, ep, 0) & self.bitboards[(side | PAWN) as usize];
+            if attackers == 0 {
+                key ^= self.zobrist.en_passant[ep as usize];
+            }
+        }
+
+        key
+    }
+
+    /// Check that the board and the bitboards describe the same position,
+    /// and that the hashes of the position are up to date
+    pub fn is_consistent(&self) -> bool {
+        let mut bitboards = [0; 14];
+        for sq in 0..64 {
+            let p = self.board[sq as usize];
+            if p != EMPTY {
+                bitboards[p as usize].set(sq);
+                bitboards[p.color() as usize].set(sq);
+            }
+        }
+        bitboards == self.bitboards &&
+            self.positions.top().hash == self.compute_hash() &&
+            self.positions.top().pawn_hash == self.compute_pawn_hash()
+    }
+
+    /// Take back up to the given number of moves of the history, restoring
+    /// the positions kept for the detection of repetitions, and return the
+    /// number of moves taken back
+    pub fn takeback(&mut self, n: usize) -> usize {
+        let mut i = 0;
+        while i < n {
+            match self.history.pop() {
+                Some(m) => self.undo_move(m),
+                None => break
+            }
+            i += 1;
+        }
+        i
+    }
+
+    /// Check if a premove of the side not to move, given in coordinate or
+    /// algebraic notation, would be legal after the given opponent move,
+    /// without changing the game
+    pub fn would_be_legal_after(&mut self, opponent_move: &str, my_move: &str) -> bool {
+        let m = match self.parse_move(opponent_move) {
+            Some(m) if self.get_moves().contains(&m) => m,
+            _ => return false
+        };
+
+        self.make_move(m);
+        let is_legal = match self.parse_move(my_move) {
+            Some(premove) => self.get_moves().contains(&premove),
+            None => false
+        };
+        self.undo_move(m);
+
+        is_legal
+    }
 }
 
 impl fmt::Display for Game {
@@ -128,4 +466,215 @@ mod tests {
         game.tt_resize(size);
         assert_eq!(game.tt_size(), size);
     }
+
+    #[test]
+    fn test_new_game() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.search(1..6);
+        let hash = game.positions.top().hash;
+        assert!(game.tt.get(hash).is_some());
+        assert!(game.search_result.is_some());
+
+        game.new_game();
+        game.load_fen(DEFAULT_FEN).unwrap();
+        assert!(game.tt.get(hash).is_none());
+        assert!(game.search_result.is_none());
+        assert_eq!(game.search_stats.nodes_count(), 0);
+        for m in game.get_moves() {
+            assert_eq!(game.moves.get_history(m), 0);
+        }
+    }
+
+    #[test]
+    fn test_phase() {
+        let game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(game.phase(), 0);
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.phase(), PHASE_MAX);
+
+        let game = Game::from_fen("3qk3/pppppppp/8/8/8/8/PPPPPPPP/3QK3 w - - 0 1").unwrap();
+        assert_eq!(game.phase(), PHASE_MAX * 2 / 3);
+    }
+
+    #[test]
+    fn test_attack_map() {
+        let game = Game::from_fen("7k/8/8/8/8/8/1p6/N3r2K w - - 0 1").unwrap();
+        let map = game.attack_map(BLACK);
+        assert_eq!(map[A1 as usize], 2); // Pawn and rook
+        assert_eq!(map[E4 as usize], 1); // Rook
+        assert_eq!(map[G7 as usize], 1); // King
+        assert_eq!(map[A2 as usize], 0);
+        assert_eq!(map.iter().map(|&n| n as u32).sum::<u32>(), 2 + 3 + 14);
+
+        let game = Game::from_fen(DEFAULT_FEN).unwrap();
+        let map = game.attack_map(WHITE);
+        assert_eq!(map[F3 as usize], 3); // Pawns on e2 and g2, knight on g1
+        assert_eq!(map[D2 as usize], 4); // Queen, king, bishop, and knight
+        assert_eq!(map[E4 as usize], 0);
+    }
+
+    #[test]
+    fn test_would_be_legal_after() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+
+        assert!(game.would_be_legal_after("e7e5", "g1f3"));
+        assert!(game.would_be_legal_after("e5", "Nf3"));
+        assert!(!game.would_be_legal_after("e7e5", "e4e5")); // Blocked pawn
+        assert!(!game.would_be_legal_after("e7e4", "g1f3")); // Illegal reply
+        assert!(!game.would_be_legal_after("d7d5", "d1d3")); // Blocked queen
+        assert!(game.would_be_legal_after("d7d5", "e4d5"));
+        assert!(!game.would_be_legal_after("none", "g1f3"));
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_takeback() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        for s in &["g1f3", "g8f6", "f3g1", "f6g8", "e2e4"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+            game.history.push(m);
+        }
+        assert_eq!(game.takeback(1), 1);
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4 3");
+        assert_eq!(game.positions.len(), 5);
+        assert!(game.positions.is_repetition()); // Of the start position
+
+        let m = game.move_from_lan("g1f3");
+        game.make_move(m);
+        game.history.push(m);
+        assert_eq!(game.takeback(2), 2);
+        assert_eq!(game.history.len(), 3);
+        assert_eq!(game.side(), BLACK);
+
+        assert_eq!(game.takeback(4), 3);
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+        assert_eq!(game.positions.len(), 1);
+        assert_eq!(game.takeback(1), 0);
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        for s in &["a2a4", "b4a3", "e1g1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        assert!(game.is_consistent());
+        let hash = game.positions.top().hash;
+        assert_eq!(game.compute_hash(), hash);
+
+        // Remove the knight on e5 from the board only
+        game.board[E5 as usize] = EMPTY;
+        assert!(!game.is_consistent());
+        game.rebuild_from_board();
+        assert!(game.is_consistent());
+        assert_eq!(game.bitboard(WHITE_KNIGHT).count(), 1);
+        assert_ne!(game.positions.top().hash, hash);
+
+        // Put it back in the bitboard of the knights only
+        game.bitboards[WHITE_KNIGHT as usize].set(E5);
+        assert!(!game.is_consistent());
+        game.rebuild_from_bitboards();
+        assert!(game.is_consistent());
+        assert_eq!(game.board[E5 as usize], WHITE_KNIGHT);
+        assert_eq!(game.positions.top().hash, hash);
+
+        // Remove the pawn on d5
+        let pawn_hash = game.positions.top().pawn_hash;
+        game.board[D5 as usize] = EMPTY;
+        game.rebuild_from_board();
+        assert!(game.is_consistent());
+        assert_ne!(game.positions.top().pawn_hash, pawn_hash);
+    }
+
+    #[test]
+    fn test_polyglot_key() {
+        let mut rng = Random::new(42);
+        let mut randoms = [0; POLYGLOT_KEYS_COUNT];
+        for k in randoms.iter_mut() {
+            *k = rng.next_u64();
+        }
+
+        // Key construction of Polyglot from the FEN of the position
+        let polyglot_key = |fen: &str| {
+            let fields: Vec<&str> = fen.split(' ').collect();
+            let mut key = 0;
+            for (i, rank) in fields[0].split('/').enumerate() {
+                let mut file = 0;
+                for c in rank.chars() {
+                    match "pPnNbBrRqQkK".find(c) {
+                        Some(k) => {
+                            key ^= randoms[64 * k + 8 * (7 - i) + file];
+                            file += 1;
+                        },
+                        None => {
+                            file += c.to_digit(10).unwrap() as usize;
+                        }
+                    }
+                }
+            }
+            for (k, c) in "KQkq".chars().enumerate() {
+                if fields[2].contains(c) {
+                    key ^= randoms[768 + k];
+                }
+            }
+            if fields[3] != "-" {
+                let file = fields[3].chars().next().unwrap() as usize - 'a' as usize;
+                key ^= randoms[772 + file];
+            }
+            if fields[1] == "w" {
+                key ^= randoms[780];
+            }
+            key
+        };
+
+        let mut game = Game::new();
+        game.set_polyglot_keys(&randoms);
+        game.load_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(game.polyglot_key(), polyglot_key(DEFAULT_FEN));
+
+        // En passant without a pawn able to capture
+        let m = game.move_from_lan("e2e4");
+        game.make_move(m);
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(game.polyglot_key(), polyglot_key(fen));
+
+        // En passant with a pawn able to capture, and castling rights lost
+        for lan in &["d7d5", "e4e5", "f7f5"] {
+            let m = game.move_from_lan(lan);
+            game.make_move(m);
+        }
+        let m = game.move_from_lan("e1e2");
+        game.make_move(m);
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3";
+        assert_eq!(game.polyglot_key(), polyglot_key(fen));
+        game.undo_move(m);
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(game.polyglot_key(), polyglot_key(fen));
+    }
+
+    #[test]
+    fn test_from_960_id() {
+        let game = Game::from_960_id(518, 518).unwrap();
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+
+        let mut game = Game::from_960_id(0, 959).unwrap();
+        assert_eq!(game.to_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert!(game.is_chess960);
+        assert_eq!(game.perft(2), 400);
+
+        assert!(Game::from_960_id(960, 0).is_err());
+    }
+
+    #[test]
+    fn test_from_odds() {
+        let game = Game::from_odds(Odds::Knight, BLACK);
+        assert_eq!(game.material_signature(), "KQRRBBNNPPPPPPPPvKQRRBBNPPPPPPPP");
+        assert_eq!(game.board[B8 as usize], EMPTY);
+    }
 }
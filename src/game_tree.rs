@@ -0,0 +1,383 @@
+use std::error::Error;
+
+use color::*;
+use common::*;
+use attack::Attack;
+use fen::FEN;
+use game::Game;
+use pgn::{PGN, Token, tokenize};
+use piece_move::PieceMove;
+use piece_move_generator::PieceMoveGenerator;
+use piece_move_notation::PieceMoveNotation;
+use search::Search;
+
+/// Index of a node in a `GameTree`
+pub type NodeId = usize;
+
+/// A node of a `GameTree` storing the move leading to its position
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub piece_move: PieceMove, // Null move for the root node
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>, // The first child continues the main line
+    pub comment: Option<String>,
+    pub nags: Vec<u8>
+}
+
+/// A `GameTree` type to store an annotated game with its variations
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    pub starting_fen: String,
+    pub headers: Vec<(String, String)>,
+    pub result: String,
+    nodes: Vec<Node>
+}
+
+impl GameTree {
+    /// Create a new `GameTree` from the given starting position
+    pub fn new(fen: &str) -> GameTree {
+        let root = Node {
+            piece_move: PieceMove::new_null(),
+            parent: None,
+            children: Vec::new(),
+            comment: None,
+            nags: Vec::new()
+        };
+
+        GameTree {
+            starting_fen: fen.to_string(),
+            headers: Vec::new(),
+            result: "*".to_string(),
+            nodes: vec![root]
+        }
+    }
+
+    /// Get the root node of the tree
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    /// Get the given node
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    /// Add a move after the given node, as a new variation if the node
+    /// already has a continuation, and return the node of the move
+    pub fn add_move(&mut self, parent: NodeId, m: PieceMove) -> NodeId {
+        for &child in &self.nodes[parent].children {
+            if self.nodes[child].piece_move == m {
+                return child;
+            }
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            piece_move: m,
+            parent: Some(parent),
+            children: Vec::new(),
+            comment: None,
+            nags: Vec::new()
+        });
+        self.nodes[parent].children.push(id);
+
+        id
+    }
+
+    /// Remove the given node and its continuations from the tree
+    pub fn remove(&mut self, id: NodeId) {
+        if let Some(parent) = self.nodes[id].parent {
+            self.nodes[parent].children.retain(|&child| child != id);
+        }
+    }
+
+    /// Set the comment following the move of the given node
+    pub fn set_comment(&mut self, id: NodeId, comment: &str) {
+        self.nodes[id].comment = Some(comment.to_string());
+    }
+
+    /// Add a Numeric Annotation Glyph to the move of the given node
+    pub fn add_nag(&mut self, id: NodeId, nag: u8) {
+        self.nodes[id].nags.push(nag);
+    }
+
+    /// Promote the variation of the given node one level up
+    pub fn promote(&mut self, id: NodeId) {
+        let mut child = id;
+        while let Some(parent) = self.nodes[child].parent {
+            let children = &mut self.nodes[parent].children;
+            let i = children.iter().position(|&c| c == child).unwrap();
+            if i > 0 {
+                children.swap(i - 1, i);
+                return;
+            }
+            child = parent;
+        }
+    }
+
+    /// Promote the variation of the given node to the main line
+    pub fn promote_to_main_line(&mut self, id: NodeId) {
+        let mut child = id;
+        while let Some(parent) = self.nodes[child].parent {
+            let children = &mut self.nodes[parent].children;
+            let i = children.iter().position(|&c| c == child).unwrap();
+            let c = children.remove(i);
+            children.insert(0, c);
+            child = parent;
+        }
+    }
+
+    /// Get the moves leading to the given node
+    pub fn line(&self, id: NodeId) -> Vec<PieceMove> {
+        let mut moves = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            moves.push(self.nodes[node].piece_move);
+            node = parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// Get the moves of the main line
+    pub fn main_line(&self) -> Vec<PieceMove> {
+        let mut moves = Vec::new();
+        let mut node = self.root();
+        while let Some(&child) = self.nodes[node].children.first() {
+            moves.push(self.nodes[child].piece_move);
+            node = child;
+        }
+        moves
+    }
+
+    /// Create a `GameTree` from a PGN with its variations and annotations
+    pub fn from_pgn(pgn: &PGN) -> Result<GameTree, Box<dyn Error>> {
+        let fen = pgn.header("FEN").unwrap_or_else(|| DEFAULT_FEN.to_string());
+        let mut tree = GameTree::new(&fen);
+        tree.headers = pgn.headers().into_iter().
+            filter(|(key, _)| key != "FEN" && key != "SetUp" && key != "Result").
+            collect();
+        tree.result = pgn.result();
+
+        let mut game = Game::from_fen(&fen)?;
+        let mut node = tree.root();
+        let mut variations = Vec::new();
+
+        for token in tokenize(pgn.body()) {
+            match token {
+                Token::Move(san) => {
+                    match game.move_from_san(&san) {
+                        Some(m) => {
+                            game.make_move(m);
+                            node = tree.add_move(node, m);
+                        },
+                        None => {
+                            return Err(format!("invalid move '{}'", san).into());
+                        }
+                    }
+                },
+                Token::VariationStart => {
+                    // The variation replaces the last move played
+                    let parent = match tree.nodes[node].parent {
+                        Some(parent) => parent,
+                        None => return Err("invalid variation".into())
+                    };
+                    game.undo_move(tree.nodes[node].piece_move);
+                    variations.push(node);
+                    node = parent;
+                },
+                Token::VariationEnd => {
+                    node = match variations.pop() {
+                        Some(node) => node,
+                        None => return Err("invalid variation".into())
+                    };
+                    tree.replay(&mut game, node)?;
+                },
+                Token::Comment(comment) => {
+                    let comment = match tree.nodes[node].comment.take() {
+                        Some(c) => format!("{} {}", c, comment),
+                        None => comment
+                    };
+                    tree.set_comment(node, &comment);
+                },
+                Token::Nag(nag) => {
+                    tree.add_nag(node, nag);
+                },
+                Token::Result(result) => {
+                    tree.result = result;
+                },
+                Token::MoveNumber(_) => {
+                }
+            }
+        }
+
+        Ok(tree)
+    }
+
+    /// Export the tree to PGN with its variations and annotations
+    pub fn to_pgn(&self) -> Result<PGN, Box<dyn Error>> {
+        let mut pgn = PGN::new();
+        for (key, val) in &self.headers {
+            pgn.set_header(key, val);
+        }
+        if self.starting_fen != DEFAULT_FEN {
+            pgn.set_fen(&self.starting_fen);
+        }
+        pgn.set_result(&self.result);
+
+        let mut game = Game::from_fen(&self.starting_fen)?;
+        let mut words = Vec::new();
+        if let Some(ref comment) = self.nodes[self.root()].comment {
+            words.push(format!("{{{}}}", comment));
+        }
+        self.write_moves(&mut game, self.root(), true, &mut words);
+        words.push(self.result.clone());
+
+        // Split movetext over multiple lines of 80 chars max
+        let mut body = String::new();
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + word.len() >= 80 {
+                body.push_str(&format!("{}\n", line));
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        body.push_str(&format!("{}\n", line));
+        pgn.set_body(&body);
+
+        Ok(pgn)
+    }
+
+    // Play the moves leading to the given node from the starting position
+    fn replay(&self, game: &mut Game, id: NodeId) -> Result<(), Box<dyn Error>> {
+        game.load_fen(&self.starting_fen)?;
+        for m in self.line(id) {
+            game.make_move(m);
+        }
+        Ok(())
+    }
+
+    // Write the continuations of the given node
+    fn write_moves(&self, game: &mut Game, id: NodeId, is_numbered: bool, words: &mut Vec<String>) {
+        let children = &self.nodes[id].children;
+        if children.is_empty() {
+            return;
+        }
+
+        let main = children[0];
+        self.write_move(game, main, is_numbered, words);
+
+        for &child in &children[1..] {
+            let i = words.len();
+            self.write_move(game, child, true, words);
+            let m = self.nodes[child].piece_move;
+            game.make_move(m);
+            self.write_moves(game, child, self.nodes[child].comment.is_some(), words);
+            game.undo_move(m);
+            words[i].insert(0, '(');
+            words.last_mut().unwrap().push(')');
+        }
+
+        let m = self.nodes[main].piece_move;
+        let is_numbered = children.len() > 1 || self.nodes[main].comment.is_some();
+        game.make_move(m);
+        self.write_moves(game, main, is_numbered, words);
+        game.undo_move(m);
+    }
+
+    // Write the move of the given node with its annotations
+    fn write_move(&self, game: &mut Game, id: NodeId, is_numbered: bool, words: &mut Vec<String>) {
+        let node = &self.nodes[id];
+        let m = node.piece_move;
+
+        let fm = game.positions.fullmoves();
+        let mut word = if game.side() == WHITE {
+            format!("{}. ", fm)
+        } else if is_numbered {
+            format!("{}... ", fm)
+        } else {
+            String::new()
+        };
+
+        word.push_str(&game.move_to_san(m));
+        game.make_move(m);
+        if game.is_mate() {
+            word.push('#');
+        } else if game.is_check(game.side()) {
+            word.push('+');
+        }
+        game.undo_move(m);
+        words.push(word);
+
+        for nag in &node.nags {
+            words.push(format!("${}", nag));
+        }
+        if let Some(ref comment) = node.comment {
+            words.push(format!("{{{}}}", comment));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_tree_from_pgn() {
+        let body = "1. e4 {best by test} 1... e5 $1 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 Nc6 *";
+        let mut pgn = PGN::new();
+        pgn.set_white("Kasparov");
+        pgn.set_body(body);
+
+        let tree = GameTree::from_pgn(&pgn).unwrap();
+        let e4 = tree.node(tree.root()).children[0];
+        assert_eq!(tree.node(e4).comment, Some("best by test".to_string()));
+        assert_eq!(tree.node(e4).children.len(), 2);
+        let e5 = tree.node(e4).children[0];
+        assert_eq!(tree.node(e5).nags, vec![1]);
+        assert_eq!(tree.main_line().len(), 4);
+
+        let c5 = tree.node(e4).children[1];
+        let nf3 = tree.node(c5).children[0];
+        assert_eq!(tree.node(c5).children.len(), 2);
+        assert_eq!(tree.line(nf3).len(), 3);
+
+        let pgn = tree.to_pgn().unwrap();
+        assert_eq!(pgn.white(), "Kasparov");
+        assert_eq!(pgn.body(), format!("{}\n", body));
+    }
+
+    #[test]
+    fn test_game_tree_promote() {
+        let mut pgn = PGN::new();
+        pgn.set_body("1. d4 (1. e4 e5 (1... c5)) (1. c4) 1... d5 *");
+        let mut tree = GameTree::from_pgn(&pgn).unwrap();
+
+        let root = tree.root();
+        let c4 = tree.node(root).children[2];
+        tree.promote(c4);
+        assert_eq!(tree.to_pgn().unwrap().body(), "1. d4 (1. c4) (1. e4 e5 (1... c5)) 1... d5 *\n");
+
+        let e4 = tree.node(root).children[2];
+        let c5 = tree.node(e4).children[1];
+        tree.promote_to_main_line(c5);
+        assert_eq!(tree.to_pgn().unwrap().body(), "1. e4 (1. d4 d5) (1. c4) 1... c5 (1... e5) *\n");
+
+        tree.remove(c5);
+        assert_eq!(tree.main_line().len(), 2);
+    }
+
+    #[test]
+    fn test_game_tree_invalid_pgn() {
+        let mut pgn = PGN::new();
+        pgn.set_body("1. e4 e5 2. Ke3 *");
+        assert!(GameTree::from_pgn(&pgn).is_err());
+
+        pgn.set_body("1. e4 e5) 2. Nf3 *");
+        assert!(GameTree::from_pgn(&pgn).is_err());
+    }
+}
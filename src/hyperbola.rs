@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use common::*;
 use color::*;
 use square::*;
@@ -39,42 +41,46 @@ fn rank_attacks(occupied: Bitboard, sq: Square) -> Bitboard {
     FIRST_RANK_ATTACKS[o as usize][f as usize] << r
 }
 
-lazy_static! {
-    static ref HYPERBOLA_MASKS: [[Bitboard; 4]; 64] = {
-        let mut hyperbola_masks = [[0; 4]; 64];
-        for sq in 0..64 {
-            let mask = &mut hyperbola_masks[sq as usize];
-            mask[HyperbolaMask::File as usize] = generate_mask(NORTH,     sq) | generate_mask(SOUTH,     sq);
-            mask[HyperbolaMask::Rank as usize] = generate_mask(EAST,      sq) | generate_mask(WEST,      sq);
-            mask[HyperbolaMask::Diag as usize] = generate_mask(NORTHEAST, sq) | generate_mask(SOUTHWEST, sq);
-            mask[HyperbolaMask::Anti as usize] = generate_mask(NORTHWEST, sq) | generate_mask(SOUTHEAST, sq);
-        }
-        hyperbola_masks
-    };
+static HYPERBOLA_MASKS: LazyLock<[[Bitboard; 4]; 64]> = LazyLock::new(|| {
+    let mut hyperbola_masks = [[0; 4]; 64];
+    for sq in 0..64 {
+        let mask = &mut hyperbola_masks[sq as usize];
+        mask[HyperbolaMask::File as usize] = generate_mask(NORTH,     sq) | generate_mask(SOUTH,     sq);
+        mask[HyperbolaMask::Rank as usize] = generate_mask(EAST,      sq) | generate_mask(WEST,      sq);
+        mask[HyperbolaMask::Diag as usize] = generate_mask(NORTHEAST, sq) | generate_mask(SOUTHWEST, sq);
+        mask[HyperbolaMask::Anti as usize] = generate_mask(NORTHWEST, sq) | generate_mask(SOUTHEAST, sq);
+    }
+    hyperbola_masks
+});
 
-    static ref FIRST_RANK_ATTACKS: [[Bitboard; 8]; 64] = {
-        let mut first_rank_attacks = [[0; 8]; 64];
-        for o in 0..64 {
-            for f in 0..8 {
-                first_rank_attacks[o][f] = 0;
+static FIRST_RANK_ATTACKS: LazyLock<[[Bitboard; 8]; 64]> = LazyLock::new(|| {
+    let mut first_rank_attacks = [[0; 8]; 64];
+    for o in 0..64 {
+        for f in 0..8 {
+            first_rank_attacks[o][f] = 0;
 
-                for i in (f + 1)..8 {
-                    first_rank_attacks[o][f] |= 1 << i;
-                    if (o << 1) & (1 << i) > 0 {
-                        break;
-                    }
+            for i in (f + 1)..8 {
+                first_rank_attacks[o][f] |= 1 << i;
+                if (o << 1) & (1 << i) > 0 {
+                    break;
                 }
-                for i in (0..f).rev() {
-                    first_rank_attacks[o][f] |= 1 << i;
-                    if (o << 1) & (1 << i) > 0 {
-                        break;
-                    }
+            }
+            for i in (0..f).rev() {
+                first_rank_attacks[o][f] |= 1 << i;
+                if (o << 1) & (1 << i) > 0 {
+                    break;
                 }
             }
         }
+    }
+
+    first_rank_attacks
+});
 
-        first_rank_attacks
-    };
+/// Initialize the masks and the attacks on the first rank
+pub fn init_tables() {
+    LazyLock::force(&HYPERBOLA_MASKS);
+    LazyLock::force(&FIRST_RANK_ATTACKS);
 }
 
 fn is_out_rank(dir: Direction, sq: Square) -> bool {
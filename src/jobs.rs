@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+
+/// Get the default number of jobs run concurrently, one per available core
+pub fn default_jobs_count() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Run the jobs on a pool of threads, one per worker, taking the next job
+/// waiting when done with the previous one, and call `output` with the
+/// result of each job in the order of the jobs as soon as it is available
+pub fn run<W, J, R, F, O>(workers: Vec<W>, jobs: Vec<J>, work: F, mut output: O)
+    where W: Send, J: Send, R: Send, F: Fn(&mut W, J) -> R + Sync, O: FnMut(R)
+{
+    let queue = Mutex::new(jobs.into_iter().enumerate());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for (i, mut worker) in workers.into_iter().enumerate() {
+            let sender = sender.clone();
+            let queue = &queue;
+            let work = &work;
+            let builder = thread::Builder::new().
+                name(format!("job_{}", i)).
+                stack_size(4 << 20);
+
+            builder.spawn_scoped(scope, move || {
+                loop {
+                    // The lock is released before working on the job
+                    let job = queue.lock().unwrap().next();
+                    match job {
+                        Some((j, job)) => {
+                            if sender.send((j, work(&mut worker, job))).is_err() {
+                                break;
+                            }
+                        },
+                        None => break
+                    }
+                }
+            }).unwrap();
+        }
+        drop(sender);
+
+        // Results are kept until the results of all the previous jobs
+        // have been given to `output`
+        let mut results = BTreeMap::new();
+        let mut next = 0;
+        for (j, result) in receiver {
+            results.insert(j, result);
+            while let Some(result) = results.remove(&next) {
+                output(result);
+                next += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_run() {
+        let workers = vec![0; 4];
+        let jobs: Vec<u64> = (0..20).collect();
+        let mut results = Vec::new();
+        run(workers, jobs, |count, job| {
+            // The first jobs finish last
+            thread::sleep(Duration::from_millis(20 - job));
+            *count += 1;
+            job * job
+        }, |result| results.push(result));
+
+        let expected: Vec<u64> = (0..20).map(|job| job * job).collect();
+        assert_eq!(results, expected);
+    }
+}
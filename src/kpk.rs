@@ -0,0 +1,179 @@
+use std::sync::LazyLock;
+
+use color::*;
+use piece::*;
+use square::*;
+use common::*;
+use attack::PAWN_ATTACKS;
+use bitboard::{Bitboard, BitboardExt, BitboardIterator};
+use square::SquareExt;
+
+// Number of positions with the white king, the white pawn on the files A to
+// D and the ranks 2 to 7, the black king, and the side to move.
+const KPK_SIZE: usize = 2 * 24 * 64 * 64;
+
+const INVALID: u8 = 0b000;
+const UNKNOWN: u8 = 0b001;
+const DRAW:    u8 = 0b010;
+const WIN:     u8 = 0b100;
+
+// Bitbase of the positions won by White
+static KPK_BITBASE: LazyLock<Vec<u64>> = LazyLock::new(generate);
+
+/// Initialize the bitbase
+pub fn init_tables() {
+    LazyLock::force(&KPK_BITBASE);
+}
+
+/// Check if the given king and pawn versus king position is won by the side
+/// having the pawn
+pub fn probe(strong: Color, side: Color, king: Square, pawn: Square, opponent_king: Square) -> bool {
+    // Normalize the position with White having a pawn on the files A to D
+    let mirror = if pawn.flip(strong).file() > 3 { 7 } else { 0 };
+    let wk = king.flip(strong) ^ mirror;
+    let wp = pawn.flip(strong) ^ mirror;
+    let bk = opponent_king.flip(strong) ^ mirror;
+    let side = if strong == WHITE { side } else { side ^ 1 };
+
+    let i = index(side, wk, wp, bk);
+    KPK_BITBASE[i / 64].get((i % 64) as Square)
+}
+
+fn index(side: Color, wk: Square, wp: Square, bk: Square) -> usize {
+    let p = 4 * (6 - wp.rank() as usize) + wp.file() as usize;
+    (((p * 2 + side as usize) * 64 + bk as usize) * 64) + wk as usize
+}
+
+fn unindex(i: usize) -> (Color, Square, Square, Square) {
+    let wk = (i % 64) as Square;
+    let bk = ((i / 64) % 64) as Square;
+    let side = ((i / 64 / 64) % 2) as Color;
+    let p = i / 64 / 64 / 2;
+    let wp = (8 * (6 - p / 4) + p % 4) as Square;
+    (side, wk, wp, bk)
+}
+
+fn king_moves(sq: Square) -> Bitboard {
+    PIECE_MASKS[KING as usize][sq as usize]
+}
+
+// Classify the positions that can be decided without looking at the moves
+fn init(side: Color, wk: Square, wp: Square, bk: Square) -> u8 {
+    let pawn_attacks = PAWN_ATTACKS[WHITE as usize][wp as usize];
+    let promotion = wp + 8;
+
+    if distance(wk, bk) <= 1 || wk == wp || bk == wp ||
+        (side == WHITE && pawn_attacks.get(bk)) { // Black in check
+        INVALID
+    } else if side == WHITE && wp.rank() == 6 && wk != promotion && bk != promotion &&
+        (distance(bk, promotion) > 1 || distance(wk, promotion) == 1) {
+        WIN // The pawn can be promoted without being captured
+    } else if side == BLACK && (
+        king_moves(bk) & !(king_moves(wk) | pawn_attacks) == 0 || // Stalemate
+        (king_moves(bk) & !king_moves(wk)).get(wp) // The pawn can be captured
+    ) {
+        DRAW
+    } else {
+        UNKNOWN
+    }
+}
+
+// Classify a position from the results of the positions after its moves
+fn classify(db: &[u8], side: Color, wk: Square, wp: Square, bk: Square) -> u8 {
+    let (good, bad) = if side == WHITE { (WIN, DRAW) } else { (DRAW, WIN) };
+
+    let mut r = INVALID;
+    if side == WHITE {
+        let mut targets = king_moves(wk);
+        while let Some(to) = targets.next() {
+            r |= db[index(BLACK, to, wp, bk)];
+        }
+        if wp.rank() < 6 {
+            r |= db[index(BLACK, wk, wp + 8, bk)];
+        }
+        if wp.rank() == 1 && wp + 8 != wk && wp + 8 != bk {
+            r |= db[index(BLACK, wk, wp + 16, bk)];
+        }
+    } else {
+        let mut targets = king_moves(bk);
+        while let Some(to) = targets.next() {
+            r |= db[index(WHITE, wk, wp, to)];
+        }
+    }
+
+    if r & good != 0 {
+        good
+    } else if r & UNKNOWN != 0 {
+        UNKNOWN
+    } else {
+        bad
+    }
+}
+
+// Generate the bitbase by retrograde analysis from the positions that can
+// be decided immediately
+fn generate() -> Vec<u64> {
+    let mut db = vec![UNKNOWN; KPK_SIZE];
+    for (i, result) in db.iter_mut().enumerate() {
+        let (side, wk, wp, bk) = unindex(i);
+        *result = init(side, wk, wp, bk);
+    }
+
+    let mut is_changed = true;
+    while is_changed {
+        is_changed = false;
+        for i in 0..KPK_SIZE {
+            if db[i] == UNKNOWN {
+                let (side, wk, wp, bk) = unindex(i);
+                let result = classify(&db, side, wk, wp, bk);
+                if result != UNKNOWN {
+                    db[i] = result;
+                    is_changed = true;
+                }
+            }
+        }
+    }
+
+    let mut bitbase = vec![0; KPK_SIZE / 64];
+    for (i, &result) in db.iter().enumerate() {
+        if result == WIN {
+            bitbase[i / 64].set((i % 64) as Square);
+        }
+    }
+    bitbase
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        for &i in &[0, 1, 4095, 4096, 8191, 8192, KPK_SIZE - 1] {
+            let (side, wk, wp, bk) = unindex(i);
+            assert_eq!(index(side, wk, wp, bk), i);
+        }
+    }
+
+    #[test]
+    fn test_probe() {
+        // King on the 6th rank in front of the pawn
+        assert!(probe(WHITE, WHITE, E6, E5, E8));
+        assert!(probe(WHITE, BLACK, E6, E5, E8));
+        assert!(probe(BLACK, WHITE, D3, D4, D1));
+
+        // King in front of the pawn with the opposition
+        assert!(!probe(WHITE, WHITE, E5, E4, E7));
+        assert!(probe(WHITE, BLACK, E5, E4, E7));
+        assert!(!probe(BLACK, BLACK, F4, F5, F2));
+        assert!(probe(BLACK, WHITE, F4, F5, F2));
+
+        // Rook pawn with the opponent king in the corner
+        assert!(!probe(WHITE, WHITE, B6, A5, A8));
+        assert!(!probe(WHITE, BLACK, G6, H5, H8));
+
+        // Pawn outside the square of the opponent king
+        assert!(probe(WHITE, WHITE, E1, E2, A2));
+        assert!(!probe(WHITE, WHITE, E1, E2, E3));
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use color::*;
+use common::*;
+use game::Game;
+
+/// Number of plies from the starting position where learning is used
+pub const LEARNING_MAX_PLY: usize = 20;
+
+const LEARNING_PENALTY: Score = 25;
+const LEARNING_MAX_PENALTY: Score = 100;
+
+/// Results of the games that went through a position
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+/// Learning of the results of the opening lines played
+///
+/// The results are stored by position from the point of view of the side
+/// that played the move leading to it, in a small text file with one
+/// position per line.
+#[derive(Clone, Debug)]
+pub struct Learning {
+    pub is_enabled: bool,
+    pub path: Option<PathBuf>,
+    records: HashMap<u64, Record>
+}
+
+impl Learning {
+    pub fn new() -> Learning {
+        Learning {
+            is_enabled: false,
+            path: dirs::data_dir().map(|dir| dir.join("littlewing").join("learning")),
+            records: HashMap::new()
+        }
+    }
+
+    /// Get the record of the given position
+    pub fn get(&self, hash: u64) -> Record {
+        self.records.get(&hash).cloned().unwrap_or_default()
+    }
+
+    /// Get the penalty of a move leading to the given position, increasing
+    /// with the number of games lost more than won after it
+    pub fn penalty(&self, hash: u64) -> Score {
+        let record = self.get(hash);
+        if record.losses > record.wins + 1 {
+            let n = (record.losses - record.wins - 1) as Score;
+            (n * LEARNING_PENALTY).min(LEARNING_MAX_PENALTY)
+        } else {
+            0
+        }
+    }
+
+    /// Record the result of the given game in the opening positions
+    pub fn record(&mut self, game: &Game, result: &str) {
+        let n = game.positions.len().min(LEARNING_MAX_PLY + 1);
+        for i in 1..n {
+            let side = game.positions[i - 1].side;
+            let record = self.records.entry(game.positions[i].hash).or_default();
+            match (result, side) {
+                ("1-0", WHITE) | ("0-1", BLACK) => record.wins += 1,
+                ("1-0", BLACK) | ("0-1", WHITE) => record.losses += 1,
+                ("1/2-1/2", _)                  => record.draws += 1,
+                _                               => {}
+            }
+        }
+    }
+
+    /// Load records from the learning file
+    pub fn load(&mut self) -> Result<(), Box<dyn Error>> {
+        self.records.clear();
+        let path = match self.path {
+            Some(ref path) if path.exists() => path,
+            _ => return Ok(())
+        };
+        for line in fs::read_to_string(path)?.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 4 {
+                return Err(format!("invalid learning record '{}'", line).into());
+            }
+            let hash = u64::from_str_radix(fields[0], 16)?;
+            let record = Record {
+                wins: fields[1].parse()?,
+                draws: fields[2].parse()?,
+                losses: fields[3].parse()?
+            };
+            self.records.insert(hash, record);
+        }
+        Ok(())
+    }
+
+    /// Save records to the learning file
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Err("no learning file".into())
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut hashes: Vec<&u64> = self.records.keys().collect();
+        hashes.sort();
+        let mut content = String::new();
+        for hash in hashes {
+            let r = self.records[hash];
+            content.push_str(&format!("{:016X} {} {} {}\n", hash, r.wins, r.draws, r.losses));
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+impl Default for Learning {
+    fn default() -> Self {
+        Learning::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use fen::FEN;
+    use piece_move_generator::PieceMoveGenerator;
+    use piece_move_notation::PieceMoveNotation;
+
+    fn play(moves: &[&str]) -> Game {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        for s in moves {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+            game.history.push(m);
+        }
+        game
+    }
+
+    #[test]
+    fn test_learning() {
+        let mut learning = Learning::new();
+        learning.path = Some(env::temp_dir().join("littlewing_test_learning"));
+
+        let game = play(&["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let hash = game.positions[1].hash; // After 1. f3
+
+        learning.record(&game, "0-1");
+        assert_eq!(learning.get(hash), Record { wins: 0, draws: 0, losses: 1 });
+        assert_eq!(learning.penalty(hash), 0);
+
+        learning.record(&game, "0-1");
+        assert_eq!(learning.penalty(hash), LEARNING_PENALTY);
+
+        learning.record(&game, "0-1");
+        assert_eq!(learning.penalty(hash), 2 * LEARNING_PENALTY);
+
+        let hash = game.positions[2].hash; // After 1... e5
+        assert_eq!(learning.get(hash), Record { wins: 3, draws: 0, losses: 0 });
+        assert_eq!(learning.penalty(hash), 0);
+
+        learning.save().unwrap();
+        let records = learning.records.clone();
+        learning.load().unwrap();
+        assert_eq!(learning.records, records);
+        fs::remove_file(learning.path.unwrap()).unwrap();
+    }
+}
@@ -33,8 +33,6 @@
 //! }
 //! ```
 
-#[macro_use]
-extern crate lazy_static;
 extern crate colored;
 extern crate dirs;
 extern crate rand;
@@ -42,16 +40,26 @@ extern crate rand_xorshift;
 extern crate regex;
 extern crate rustyline;
 extern crate rustyline_derive;
+#[cfg(feature = "network")]
+extern crate ureq;
+#[cfg(target_os = "linux")]
+extern crate libc;
+
+use std::sync::LazyLock;
 
 mod attack;
 mod board;
 mod common;
+mod cuckoo;
 mod dumb7fill;
 mod hyperbola;
+mod kpk;
+mod magic;
 mod piece_move;
 mod piece_move_list;
-mod positions;
+mod pawn_table;
 mod piece_square_table;
+mod pv_table;
 mod transposition;
 mod transposition_table;
 mod zobrist;
@@ -59,12 +67,24 @@ mod zobrist;
 /// Bitboard type
 pub mod bitboard;
 
+/// Chess960 start positions
+pub mod chess960;
+
 /// Clock controls
 pub mod clock;
 
 /// Color type
 pub mod color;
 
+/// Context of the search for crash reports
+pub mod crash;
+
+/// Endgame knowledge
+pub mod endgame;
+
+/// Extended Position Description support
+pub mod epd;
+
 /// Evaluation algorithms
 pub mod eval;
 
@@ -74,9 +94,39 @@ pub mod fen;
 /// Game engine
 pub mod game;
 
+/// Game tree with variations and annotations
+pub mod game_tree;
+
+/// Job scheduler processing positions concurrently
+pub mod jobs;
+
 /// Portable Game Notation support
 pub mod pgn;
 
+/// Opening learning
+pub mod learning;
+
+/// Material signatures
+pub mod material;
+
+/// Games from online chess servers
+pub mod online;
+
+/// Material odds
+pub mod odds;
+
+/// Engine options
+pub mod options;
+
+/// Move ordering quality metrics
+pub mod ordering;
+
+/// Search parameters
+pub mod params;
+
+/// Perft hash table
+pub mod perft_table;
+
 /// Piece move generator
 pub mod piece_move_generator;
 
@@ -86,15 +136,41 @@ pub mod piece_move_notation;
 /// Piece type
 pub mod piece;
 
+/// Game positions and test positions
+pub mod positions;
+
 /// Communication protocols
 pub mod protocols;
 
+/// Seedable random numbers
+pub mod random;
+
 /// Search algorithms
 pub mod search;
 
 /// Square type
 pub mod square;
 
+/// Search tracing
+pub mod trace;
+
+/// Chess variants
+pub mod variant;
+
+/// Initialize the tables precomputed by the engine, like the attacks of the
+/// pieces and the KPK bitbase, that would otherwise be initialized during
+/// the first search using them
+pub fn init() {
+    LazyLock::force(&common::PIECE_MASKS);
+    LazyLock::force(&attack::PAWN_ATTACKS);
+    LazyLock::force(&piece_move_generator::MVV_LVA_SCORES);
+    LazyLock::force(&piece_square_table::PST);
+    hyperbola::init_tables();
+    magic::init_tables();
+    kpk::init_tables();
+    LazyLock::force(&cuckoo::CUCKOO); // Using the attacks of the pieces
+}
+
 /// Return Little Wing's version
 pub fn version() -> String {
     let ver = String::from("v") + env!("CARGO_PKG_VERSION");
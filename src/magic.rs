@@ -0,0 +1,185 @@
+use std::sync::LazyLock;
+
+use common::*;
+use square::*;
+use bitboard::{Bitboard, BitboardExt};
+use hyperbola;
+
+// Magic numbers found by trial and error with sparse random numbers for the
+// number of relevant occupancy bits of each square
+const ROOK_MAGICS: [u64; 64] = [
+    0x0080068051E04000, 0x0040001000402000, 0x0080100020008008, 0x4E000A0010208440,
+    0x4200040802002010, 0x0100010008020400, 0x9080608019000600, 0x8100020080204100,
+    0x4103800480400020, 0x8015004004802100, 0x000200108A002040, 0x0801000821001000,
+    0x0015000500080070, 0x0120800400800200, 0x0109000432001100, 0x020080055B000080,
+    0x0080004000402002, 0x5260848020004008, 0x2402020014402080, 0x3000808010000802,
+    0x0304018004810800, 0x0000808004000200, 0x0002040001500248, 0x0012020000408401,
+    0x8440008080004020, 0x0804200840100040, 0x0820008080201000, 0x2080100100082100,
+    0x0001000500100800, 0x00A1000900028400, 0x0100100400C80102, 0x000001120000A044,
+    0x800080C004800620, 0x4040081000202000, 0x0D08802008801000, 0x1000800800801004,
+    0x1004000801010010, 0x0402800400800200, 0x0004080204008110, 0x0000404082000401,
+    0x00C0118861408000, 0x1100220081020048, 0x09A0430420050010, 0x0000082200420010,
+    0x2110080004008080, 0x2004201040680104, 0x1106001451820008, 0x0002224104820014,
+    0x00800C8044210500, 0x02A0200040100040, 0x040100A0001E4100, 0x00204023108A0200,
+    0x2400080080040080, 0x1289008400020900, 0x0002088250010400, 0x0001006084010200,
+    0x0001023480002141, 0x0006400021810015, 0x8400100840200101, 0x40003000A1000825,
+    0x1002011008200402, 0x100D000400080201, 0x0020048806102904, 0x8401000020804201,
+];
+const BISHOP_MAGICS: [u64; 64] = [
+    0x4C40240122060016, 0x8048110404004A80, 0x8004440410414020, 0x021C410060405000,
+    0x80CD1040D0480812, 0x0002021104000082, 0x08440082A8200001, 0x00202A0800841002,
+    0x0200C40810842088, 0x60C0081000C08901, 0x00A3D0040042510C, 0x1C00110400808541,
+    0x0400820211084005, 0x0000008860080800, 0x002002020202C000, 0x0400344E08040A81,
+    0x812800102098A080, 0x00202010823A2040, 0x4086400800830201, 0x5008012A22004000,
+    0x0004801C00A00000, 0x0000400200505400, 0x0480408401080820, 0x8000400029082824,
+    0x0008880804501000, 0x0001600048084100, 0x0108220624040400, 0x0008080000820002,
+    0xC804040010410041, 0x01080A0040208400, 0x2018030480A88800, 0x4040410020410810,
+    0x1108044010100210, 0x084A100400029800, 0x0801080100820C00, 0x8010400808108200,
+    0x0084008400020500, 0x0002004200290481, 0x0010150200032090, 0x8404042220404102,
+    0x0302080308004008, 0x1200420820000408, 0x0802002024200800, 0x4020824208000084,
+    0x000002020C008200, 0x2C40208081000882, 0x2082223441000401, 0x8804080081101020,
+    0x4401011002220808, 0x81020C4202100000, 0x4005004404040308, 0x0820400C42020001,
+    0x0020206421820010, 0x0150401001424008, 0x02A20242020C0608, 0x5020110109011200,
+    0x2050840108410401, 0x0100090880842108, 0x220008960142187A, 0x1111028880208820,
+    0x4400200042028200, 0x4400010802084206, 0x0000400242040100, 0x0002201104010944,
+];
+
+pub fn bishop_attacks(from: Square, occupied: Bitboard) -> Bitboard {
+    debug_assert!(from < OUT);
+    let tables = &*MAGIC_TABLES;
+    tables.attacks[tables.bishops[from as usize].index(occupied, tables.has_pext)]
+}
+
+pub fn rook_attacks(from: Square, occupied: Bitboard) -> Bitboard {
+    debug_assert!(from < OUT);
+    let tables = &*MAGIC_TABLES;
+    tables.attacks[tables.rooks[from as usize].index(occupied, tables.has_pext)]
+}
+
+// Parallel bits extract of BMI2, giving directly the index of the relevant
+// occupancy in the attacks of a square without the magic multiplication
+#[cfg(all(feature = "pext", target_arch = "x86_64"))]
+mod pext {
+    use std::arch::x86_64::_pext_u64;
+
+    pub fn is_available() -> bool {
+        is_x86_feature_detected!("bmi2")
+    }
+
+    #[target_feature(enable = "bmi2")]
+    pub unsafe fn pext(bb: u64, mask: u64) -> u64 {
+        _pext_u64(bb, mask)
+    }
+}
+
+#[cfg(all(feature = "pext", target_arch = "x86_64"))]
+fn has_pext() -> bool {
+    pext::is_available()
+}
+
+#[cfg(not(all(feature = "pext", target_arch = "x86_64")))]
+fn has_pext() -> bool {
+    false
+}
+
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: Bitboard, // Relevant occupancy without the edges of the board
+    magic: u64,
+    shift: u32,
+    offset: usize // Index of the first attacks of the square in the table
+}
+
+impl Magic {
+    #[inline]
+    fn index(&self, occupied: Bitboard, has_pext: bool) -> usize {
+        #[cfg(all(feature = "pext", target_arch = "x86_64"))]
+        {
+            if has_pext {
+                return self.offset + unsafe { pext::pext(occupied, self.mask) } as usize;
+            }
+        }
+        let _ = has_pext;
+        let hash = (occupied & self.mask).wrapping_mul(self.magic) >> self.shift;
+        self.offset + hash as usize
+    }
+}
+
+struct MagicTables {
+    bishops: [Magic; 64],
+    rooks: [Magic; 64],
+    attacks: Vec<Bitboard>, // Shared by every square of both pieces
+    has_pext: bool // Index the attacks with PEXT instead of the magics
+}
+
+// Fancy magic bitboards, with the attacks of each square and occupancy
+// computed once with Hyperbola Quintessence, and stored at the index given
+// by either the magics or PEXT
+fn init_magics(magics: &mut [Magic; 64], numbers: &[u64; 64], attacks: &mut Vec<Bitboard>, has_pext: bool, slider: fn(Square, Bitboard) -> Bitboard) {
+    for sq in 0..64 {
+        let rank_edges = (RANK_1 | RANK_8) & !(RANK_1 << (8 * (sq / 8)));
+        let file_edges = (FILE_A | FILE_H) & !(FILE_A << (sq % 8));
+        let mask = slider(sq, 0) & !rank_edges & !file_edges;
+        let magic = Magic {
+            mask,
+            magic: numbers[sq as usize],
+            shift: 64 - mask.count(),
+            offset: attacks.len()
+        };
+        attacks.resize(attacks.len() + (1 << mask.count()), 0);
+
+        // Enumerate the subsets of the mask with the Carry-Rippler trick
+        let mut occupied: Bitboard = 0;
+        loop {
+            let i = magic.index(occupied, has_pext);
+            debug_assert!(attacks[i] == 0 || attacks[i] == slider(sq, occupied));
+            attacks[i] = slider(sq, occupied);
+            occupied = occupied.wrapping_sub(mask) & mask;
+            if occupied == 0 {
+                break;
+            }
+        }
+
+        magics[sq as usize] = magic;
+    }
+}
+
+static MAGIC_TABLES: LazyLock<MagicTables> = LazyLock::new(|| {
+    let mut tables = MagicTables {
+        bishops: [Magic::default(); 64],
+        rooks: [Magic::default(); 64],
+        attacks: Vec::with_capacity(5248 + 102400),
+        has_pext: has_pext()
+    };
+    let has_pext = tables.has_pext;
+    init_magics(&mut tables.bishops, &BISHOP_MAGICS, &mut tables.attacks, has_pext, hyperbola::bishop_attacks);
+    init_magics(&mut tables.rooks, &ROOK_MAGICS, &mut tables.attacks, has_pext, hyperbola::rook_attacks);
+    tables
+});
+
+/// Initialize the attacks of the sliding pieces
+pub fn init_tables() {
+    LazyLock::force(&MAGIC_TABLES);
+}
+
+#[cfg(test)]
+mod tests {
+    use random::Random;
+
+    use super::*;
+
+    #[test]
+    fn test_magic_attacks() {
+        assert_eq!(MAGIC_TABLES.attacks.len(), 5248 + 102400);
+        assert_eq!(MAGIC_TABLES.has_pext, has_pext());
+
+        let mut rng = Random::new(42);
+        for _ in 0..1000 {
+            let occupied: Bitboard = rng.next_u64() & rng.next_u64();
+            for sq in 0..64 {
+                assert_eq!(bishop_attacks(sq, occupied), hyperbola::bishop_attacks(sq, occupied));
+                assert_eq!(rook_attacks(sq, occupied), hyperbola::rook_attacks(sq, occupied));
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use color::*;
+use piece::*;
+use bitboard::BitboardExt;
+use game::Game;
+use piece::PieceChar;
+
+const SIGNATURE_PIECES: [Piece; 6] = [KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN];
+
+/// Material of a position given by the number of pieces of each kind of
+/// both sides, written like `KRPPvKRP` with the white pieces first
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialSignature {
+    pieces: [u8; 14] // Indexed like the bitboards
+}
+
+impl MaterialSignature {
+    /// Get the material signature of the given game
+    pub fn from_game(game: &Game) -> MaterialSignature {
+        let mut pieces = [0; 14];
+        for &c in &COLORS {
+            for &p in &SIGNATURE_PIECES {
+                pieces[(c | p) as usize] = game.bitboard(c | p).count() as u8;
+            }
+        }
+        MaterialSignature { pieces }
+    }
+
+    /// Get the number of the given piece
+    pub fn count(&self, piece: Piece) -> u8 {
+        self.pieces[piece as usize]
+    }
+
+    /// Get the signature with the colors of the pieces swapped
+    pub fn flip(&self) -> MaterialSignature {
+        let mut pieces = [0; 14];
+        for &p in &SIGNATURE_PIECES {
+            pieces[(WHITE | p) as usize] = self.count(BLACK | p);
+            pieces[(BLACK | p) as usize] = self.count(WHITE | p);
+        }
+        MaterialSignature { pieces }
+    }
+
+    /// Check if the given game has this material for any of its sides, to
+    /// filter positions by material class
+    pub fn matches(&self, game: &Game) -> bool {
+        let signature = MaterialSignature::from_game(game);
+        signature == *self || signature == self.flip()
+    }
+}
+
+impl FromStr for MaterialSignature {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<MaterialSignature, Self::Err> {
+        let sides: Vec<&str> = s.split('v').collect();
+        if sides.len() != 2 {
+            return Err(format!("invalid material signature '{}'", s).into());
+        }
+
+        let mut pieces = [0; 14];
+        for (&c, side) in COLORS.iter().zip(sides) {
+            for ch in side.chars() {
+                let p = match PieceChar::from_char(ch.to_ascii_uppercase()) {
+                    EMPTY => return Err(format!("invalid piece '{}' in material signature", ch).into()),
+                    p => p
+                };
+                pieces[(c | p.kind()) as usize] += 1;
+            }
+            if pieces[(c | KING) as usize] != 1 {
+                return Err(format!("invalid number of kings in material signature '{}'", s).into());
+            }
+        }
+
+        Ok(MaterialSignature { pieces })
+    }
+}
+
+impl fmt::Display for MaterialSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &c in &COLORS {
+            if c == BLACK {
+                write!(f, "v")?;
+            }
+            for &p in &SIGNATURE_PIECES {
+                for _ in 0..self.count(c | p) {
+                    write!(f, "{}", (WHITE | p).to_char())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fen::FEN;
+
+    #[test]
+    fn test_material_signature() {
+        let game = Game::from_fen("8/5k2/5p2/4r3/8/1P2R3/P7/6K1 w - - 0 1").unwrap();
+        assert_eq!(game.material_signature(), "KRPPvKRP");
+
+        let signature: MaterialSignature = "KRPPvKRP".parse().unwrap();
+        assert_eq!(signature.count(WHITE_PAWN), 2);
+        assert_eq!(signature.count(BLACK_ROOK), 1);
+        assert_eq!(signature.to_string(), "KRPPvKRP");
+        assert_eq!(signature.flip().to_string(), "KRPvKRPP");
+        assert!(signature.matches(&game));
+        assert!(signature.flip().matches(&game));
+        assert!(!"KRPvKR".parse::<MaterialSignature>().unwrap().matches(&game));
+
+        // Pieces in any order and case
+        let signature: MaterialSignature = "kpprvkrp".parse().unwrap();
+        assert!(signature.matches(&game));
+
+        assert!("KRPP".parse::<MaterialSignature>().is_err());
+        assert!("KXvK".parse::<MaterialSignature>().is_err());
+        assert!("KRvR".parse::<MaterialSignature>().is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use color::*;
+use square::*;
+use common::*;
+use square::SquareExt;
+
+/// Material handicap given by removing pieces from the start position
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Odds {
+    Pawn, // Pawn on file F
+    Knight, // Knight on file B
+    Rook, // Rook on file A
+    Queen
+}
+
+pub const ODDS: [Odds; 4] = [Odds::Pawn, Odds::Knight, Odds::Rook, Odds::Queen];
+
+impl Odds {
+    /// Get the name of the odds
+    pub fn name(self) -> &'static str {
+        match self {
+            Odds::Pawn   => "pawn",
+            Odds::Knight => "knight",
+            Odds::Rook   => "rook",
+            Odds::Queen  => "queen"
+        }
+    }
+
+    /// Get the odds with the given name
+    pub fn from_name(name: &str) -> Option<Odds> {
+        let name = name.to_lowercase();
+        ODDS.iter().cloned().find(|odds| odds.name() == name)
+    }
+
+    /// Get the square of the piece removed from the start position of white
+    pub fn square(self) -> Square {
+        match self {
+            Odds::Pawn   => F2,
+            Odds::Knight => B1,
+            Odds::Rook   => A1,
+            Odds::Queen  => D1
+        }
+    }
+}
+
+/// Get the FEN of the start position with the piece of the odds removed
+/// from the side giving them
+pub fn start_fen(odds: Odds, side: Color) -> String {
+    let square = odds.square().flip(side);
+    let mut fields: Vec<String> = DEFAULT_FEN.split(' ').map(String::from).collect();
+
+    // Ranks are written from the eighth to the first in FEN
+    let mut ranks: Vec<String> = fields[0].split('/').map(String::from).collect();
+    let i = 7 - square.rank() as usize;
+    let mut rank: Vec<char> = ranks[i].chars().collect();
+    rank[square.file() as usize] = '1';
+    ranks[i] = rank.into_iter().collect();
+    fields[0] = ranks.join("/");
+
+    if odds == Odds::Rook {
+        let c = if side == WHITE { 'Q' } else { 'q' };
+        fields[2] = fields[2].replace(c, "");
+    }
+
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_fen() {
+        assert_eq!(start_fen(Odds::Knight, WHITE), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1");
+        assert_eq!(start_fen(Odds::Pawn, BLACK), "rnbqkbnr/ppppp1pp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(start_fen(Odds::Rook, WHITE), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w Kkq - 0 1");
+        assert_eq!(start_fen(Odds::Rook, BLACK), "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1");
+        assert_eq!(Odds::from_name("Queen"), Some(Odds::Queen));
+        assert_eq!(Odds::from_name("king"), None);
+    }
+}
@@ -0,0 +1,98 @@
+use std::error::Error;
+
+use pgn::PGN;
+
+/// Game hosted by an online chess server
+///
+/// Chess.com games are not supported because its public API only exports
+/// the games of a player by month, not a game by its ID.
+#[derive(Clone, PartialEq, Debug)]
+pub enum OnlineGame {
+    Lichess(String)
+}
+
+impl OnlineGame {
+    /// Parse the URL of a game, or the ID of a Lichess game prefixed by
+    /// `lichess:`
+    pub fn parse(s: &str) -> Option<OnlineGame> {
+        let s = s.trim();
+        if let Some(id) = s.strip_prefix("lichess:") {
+            return lichess_id(id).map(OnlineGame::Lichess);
+        }
+
+        let s = s.split(['#', '?']).next().unwrap();
+        let s = s.trim_start_matches("https://").trim_start_matches("http://");
+        let s = s.trim_start_matches("www.");
+
+        let segments: Vec<&str> = s.split('/').filter(|segment| !segment.is_empty()).collect();
+        match segments[..] {
+            // Game seen by a spectator or by one of the players
+            ["lichess.org", id] | ["lichess.org", id, "white" | "black"] => {
+                lichess_id(id).map(OnlineGame::Lichess)
+            },
+            _ => None
+        }
+    }
+
+    /// Get the URL of the public API exporting the game to PGN
+    pub fn export_url(&self) -> String {
+        match self {
+            OnlineGame::Lichess(id) => format!("https://lichess.org/game/export/{}?clocks=false&evals=false", id)
+        }
+    }
+
+    /// Download the game in PGN
+    pub fn fetch(&self) -> Result<PGN, Box<dyn Error>> {
+        download(&self.export_url()).map(PGN::from)
+    }
+}
+
+// Get the ID of a Lichess game, ignoring the suffix of the player URLs
+fn lichess_id(s: &str) -> Option<String> {
+    if (s.len() == 8 || s.len() == 12) && s.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Some(s[0..8].to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "network")]
+fn download(url: &str) -> Result<String, Box<dyn Error>> {
+    let res = ureq::get(url).set("Accept", "application/x-chess-pgn").call()?;
+    Ok(res.into_string()?)
+}
+
+#[cfg(not(feature = "network"))]
+fn download(_url: &str) -> Result<String, Box<dyn Error>> {
+    Err("network support disabled, build with `--features network`".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let game = Some(OnlineGame::Lichess("q7ZvsdUF".into()));
+        assert_eq!(OnlineGame::parse("https://lichess.org/q7ZvsdUF"), game);
+        assert_eq!(OnlineGame::parse("https://lichess.org/q7ZvsdUFzbDn"), game);
+        assert_eq!(OnlineGame::parse("lichess.org/q7ZvsdUF/black#32"), game);
+        assert_eq!(OnlineGame::parse("lichess:q7ZvsdUF"), game);
+
+        assert_eq!(OnlineGame::parse("https://www.chess.com/game/live/123456789"), None);
+        assert_eq!(OnlineGame::parse("https://example.com/q7ZvsdUF"), None);
+        assert_eq!(OnlineGame::parse("https://lichess.org/@/someone"), None);
+        assert_eq!(OnlineGame::parse("https://lichess.org/q7ZvsdUF/analysis"), None);
+        assert_eq!(OnlineGame::parse("https://lichess.org/training/61cT1"), None);
+        assert_eq!(OnlineGame::parse("https://lichess.org/study/q7ZvsdUF"), None);
+        assert_eq!(OnlineGame::parse("lichess:someone"), None);
+        assert_eq!(OnlineGame::parse("q7ZvsdUF"), None);
+        assert_eq!(OnlineGame::parse("game.pgn"), None);
+    }
+
+    #[test]
+    fn test_export_url() {
+        let game = OnlineGame::Lichess("q7ZvsdUF".into());
+        assert_eq!(game.export_url(), "https://lichess.org/game/export/q7ZvsdUF?clocks=false&evals=false");
+    }
+}
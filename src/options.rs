@@ -0,0 +1,520 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use common::*;
+use params::{ClearPolicy, CLEAR_POLICIES, GAME_PHASES};
+use game::Game;
+use random::Random;
+use variant::{Variant, VARIANTS};
+
+/// Name of the options profile loaded at startup
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Type of an engine option
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionType {
+    Check,
+    Spin(i64, i64),
+    Combo(Vec<String>),
+    String,
+    Button
+}
+
+/// Engine option with its current value
+#[derive(Clone, PartialEq, Debug)]
+pub struct EngineOption {
+    pub name: String,
+    pub kind: OptionType,
+    pub value: String
+}
+
+impl EngineOption {
+    fn check(name: &str, value: bool) -> EngineOption {
+        EngineOption { name: name.into(), kind: OptionType::Check, value: value.to_string() }
+    }
+
+    fn spin(name: &str, value: i64, min: i64, max: i64) -> EngineOption {
+        EngineOption { name: name.into(), kind: OptionType::Spin(min, max), value: value.to_string() }
+    }
+
+    fn combo(name: &str, value: &str, values: Vec<String>) -> EngineOption {
+        EngineOption { name: name.into(), kind: OptionType::Combo(values), value: value.into() }
+    }
+
+    fn string(name: &str, value: &str) -> EngineOption {
+        EngineOption { name: name.into(), kind: OptionType::String, value: value.into() }
+    }
+
+    fn button(name: &str) -> EngineOption {
+        EngineOption { name: name.into(), kind: OptionType::Button, value: String::new() }
+    }
+
+    // Options set by the GUI for each game are not saved in the profiles,
+    // nor the seed drawn at startup that is only set for debugging
+    fn is_saved(&self) -> bool {
+        self.kind != OptionType::Button && self.name != "OptionsProfile" && self.name != "Seed" && !self.name.starts_with("UCI_")
+    }
+}
+
+/// Get the path of the given options profile in the config directory, or
+/// the profile itself if it is the path of a TOML file
+pub fn profile_path(profile: &str) -> Option<PathBuf> {
+    if profile.ends_with(".toml") {
+        return Some(PathBuf::from(profile));
+    }
+    dirs::config_dir().map(|dir| dir.join("littlewing").join(format!("{}.toml", profile)))
+}
+
+/// Engine options
+pub trait Options {
+    /// Get the list of options with their current values
+    fn options(&self) -> Vec<EngineOption>;
+
+    /// Get the current value of the given option
+    fn get_option(&self, name: &str) -> Option<String>;
+
+    /// Set the given option to the given value
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Save the current options to the given profile in TOML
+    fn save_options(&self, profile: &str) -> Result<PathBuf, Box<dyn Error>>;
+
+    /// Load the options from the given profile, returning false if it does
+    /// not exist
+    fn load_options(&mut self, profile: &str) -> Result<bool, Box<dyn Error>>;
+}
+
+impl Options for Game {
+    fn options(&self) -> Vec<EngineOption> {
+        let mut options = vec![
+            EngineOption::spin("Hash", (self.tt_size() >> 20) as i64, 1, 65536),
+            EngineOption::check("Huge Pages", self.tt.has_huge_pages()),
+            EngineOption::button("Clear Hash"),
+            EngineOption::spin("Threads", self.threads_count.max(1) as i64, 1, 256),
+            EngineOption::spin("Contempt", self.search_params.contempt as i64, -100, 100),
+            EngineOption::spin("Contempt Ending", self.search_params.contempt_ending as i64, -100, 100),
+            EngineOption::spin("Repetition Adjustment", self.search_params.repetition as i64, 0, 100),
+            EngineOption::spin("Aspiration Window", self.search_params.aspiration as i64, 0, 500),
+            EngineOption::spin("NullMove Reduction", self.search_params.nmp_reduction as i64, 1, 6),
+            EngineOption::spin("NullMove Depth Divisor", self.search_params.nmp_depth_divisor as i64, 1, 16),
+            EngineOption::spin("NullMove Eval Divisor", self.search_params.nmp_eval_divisor as i64, 0, 1000),
+            EngineOption::spin("NullMove Eval Max", self.search_params.nmp_eval_max as i64, 0, 6),
+            EngineOption::spin("Futility Depth", self.search_params.fp_depth as i64, 0, 10),
+            EngineOption::spin("Futility Margin", self.search_params.fp_margin as i64, 0, 1000),
+            EngineOption::spin("Razoring Depth", self.search_params.razor_depth as i64, 0, 10),
+            EngineOption::spin("Razoring Margin", self.search_params.razor_margin as i64, 0, 1000),
+            EngineOption::spin("LMP Depth", self.search_params.lmp_depth as i64, 0, 10),
+            EngineOption::spin("SEE Depth", self.search_params.see_depth as i64, 0, 10),
+            EngineOption::spin("SEE Quiet Margin", self.search_params.see_quiet_margin as i64, 0, 1000),
+            EngineOption::spin("SEE Capture Margin", self.search_params.see_capture_margin as i64, 0, 1000),
+            EngineOption::spin("IID Depth", self.search_params.iid_depth as i64, 0, 20),
+            EngineOption::spin("IIR Depth", self.search_params.iir_depth as i64, 0, 20),
+            EngineOption::check("NullMove Pruning", self.search_params.features.nmp),
+            EngineOption::check("Late Move Reduction", self.search_params.features.lmr),
+            EngineOption::check("Late Move Pruning", self.search_params.features.lmp),
+            EngineOption::check("Futility Pruning", self.search_params.features.futility),
+            EngineOption::check("SEE Pruning", self.search_params.features.see),
+            EngineOption::check("Transposition Table", self.search_params.features.tt),
+            EngineOption::combo("Clear Between Moves", self.search_params.clear_between_moves.name(), CLEAR_POLICIES.iter().map(|p| p.name().to_string()).collect()),
+            EngineOption::combo("Clear Between Games", self.search_params.clear_between_games.name(), CLEAR_POLICIES.iter().map(|p| p.name().to_string()).collect()),
+            EngineOption::check("SAN PV", self.show_san_pv),
+            EngineOption::check("Ponder", self.show_ponder),
+            EngineOption::check("UCI_Chess960", self.is_chess960),
+            EngineOption::check("Shredder FEN", self.is_shredder_fen),
+            EngineOption::combo("UCI_Variant", self.variant.name(), VARIANTS.iter().map(|v| v.name().to_string()).collect()),
+            EngineOption::check("Learning", self.learning.is_enabled),
+            EngineOption::string("LearningFile", &self.learning.path.as_ref().map_or(String::new(), |path| path.display().to_string())),
+            EngineOption::string("TraceFile", &self.trace.path.as_ref().map_or(String::new(), |path| path.display().to_string())),
+            EngineOption::spin("Trace Depth", self.trace.max_ply as i64, 0, MAX_PLY as i64),
+            EngineOption::spin("Seed", self.rng.seed() as i64, 0, i64::MAX),
+            EngineOption::string("OptionsProfile", &self.options_profile),
+            EngineOption::button("SaveOptions"),
+            EngineOption::button("LoadOptions"),
+        ];
+
+        for &phase in &GAME_PHASES {
+            let params = self.search_params.phase(phase);
+            options.push(EngineOption::check(&format!("NullMove {}", phase.name()), params.nmp));
+            options.push(EngineOption::spin(&format!("LMR {}", phase.name()), params.lmr as i64, 0, 4));
+        }
+
+        options
+    }
+
+    fn get_option(&self, name: &str) -> Option<String> {
+        self.options().into_iter().
+            find(|option| option.name.eq_ignore_ascii_case(name)).
+            map(|option| option.value)
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let option = match self.options().into_iter().find(|option| option.name.eq_ignore_ascii_case(name)) {
+            Some(option) => option,
+            None => return Err(format!("unknown option '{}'", name).into())
+        };
+
+        let (check, spin) = match option.kind {
+            OptionType::Check => {
+                (value.parse::<bool>()?, 0)
+            },
+            OptionType::Spin(min, max) => {
+                let spin = value.parse::<i64>()?;
+                if spin < min || max < spin {
+                    return Err(format!("option '{}' should be between {} and {}", option.name, min, max).into());
+                }
+                (false, spin)
+            },
+            OptionType::Combo(ref values) => {
+                if !values.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+                    return Err(format!("option '{}' should be one of {}", option.name, values.join(", ")).into());
+                }
+                (false, 0)
+            },
+            _ => {
+                (false, 0)
+            }
+        };
+
+        match option.name.as_str() {
+            "Hash" => {
+                self.tt_resize((spin as usize) << 20);
+            },
+            "Huge Pages" => {
+                self.tt.set_huge_pages(check);
+            },
+            "Clear Hash" => {
+                self.clear_search(ClearPolicy::Clear);
+            },
+            "Threads" => {
+                self.threads_count = spin as usize;
+            },
+            "Contempt" => {
+                self.search_params.contempt = spin as Score;
+            },
+            "Contempt Ending" => {
+                self.search_params.contempt_ending = spin as Score;
+            },
+            "Repetition Adjustment" => {
+                self.search_params.repetition = spin as Score;
+            },
+            "Aspiration Window" => {
+                self.search_params.aspiration = spin as Score;
+            },
+            "NullMove Reduction" => {
+                self.search_params.nmp_reduction = spin as Depth;
+            },
+            "NullMove Depth Divisor" => {
+                self.search_params.nmp_depth_divisor = spin as Depth;
+            },
+            "NullMove Eval Divisor" => {
+                self.search_params.nmp_eval_divisor = spin as Score;
+            },
+            "NullMove Eval Max" => {
+                self.search_params.nmp_eval_max = spin as Depth;
+            },
+            "Futility Depth" => {
+                self.search_params.fp_depth = spin as Depth;
+            },
+            "Futility Margin" => {
+                self.search_params.fp_margin = spin as Score;
+            },
+            "Razoring Depth" => {
+                self.search_params.razor_depth = spin as Depth;
+            },
+            "Razoring Margin" => {
+                self.search_params.razor_margin = spin as Score;
+            },
+            "LMP Depth" => {
+                self.search_params.lmp_depth = spin as Depth;
+            },
+            "SEE Depth" => {
+                self.search_params.see_depth = spin as Depth;
+            },
+            "SEE Quiet Margin" => {
+                self.search_params.see_quiet_margin = spin as Score;
+            },
+            "SEE Capture Margin" => {
+                self.search_params.see_capture_margin = spin as Score;
+            },
+            "IID Depth" => {
+                self.search_params.iid_depth = spin as Depth;
+            },
+            "IIR Depth" => {
+                self.search_params.iir_depth = spin as Depth;
+            },
+            "NullMove Pruning" => {
+                self.search_params.features.nmp = check;
+            },
+            "Late Move Reduction" => {
+                self.search_params.features.lmr = check;
+            },
+            "Late Move Pruning" => {
+                self.search_params.features.lmp = check;
+            },
+            "Futility Pruning" => {
+                self.search_params.features.futility = check;
+            },
+            "SEE Pruning" => {
+                self.search_params.features.see = check;
+            },
+            "Transposition Table" => {
+                self.search_params.features.tt = check;
+            },
+            "Clear Between Moves" => {
+                self.search_params.clear_between_moves = ClearPolicy::from_name(value).unwrap();
+            },
+            "Clear Between Games" => {
+                self.search_params.clear_between_games = ClearPolicy::from_name(value).unwrap();
+            },
+            "SAN PV" => {
+                self.show_san_pv = check;
+            },
+            "Ponder" => {
+                self.show_ponder = check;
+            },
+            "UCI_Chess960" => {
+                self.is_chess960 = check;
+            },
+            "Shredder FEN" => {
+                self.is_shredder_fen = check;
+            },
+            "UCI_Variant" => {
+                self.variant = Variant::from_name(value).unwrap_or_default();
+            },
+            "Learning" => {
+                self.learning.is_enabled = check;
+                if check {
+                    self.learning.load()?;
+                }
+            },
+            "LearningFile" => {
+                self.learning.path = if value.is_empty() { None } else { Some(value.into()) };
+                if self.learning.is_enabled {
+                    self.learning.load()?;
+                }
+            },
+            "TraceFile" => {
+                self.trace.path = if value.is_empty() || value == "<empty>" { None } else { Some(value.into()) };
+                self.trace.open()?;
+            },
+            "Trace Depth" => {
+                self.trace.max_ply = spin as usize;
+            },
+            "Seed" => {
+                self.rng = Random::new(spin as u64);
+            },
+            "OptionsProfile" => {
+                self.options_profile = if value.is_empty() || value == "<empty>" { DEFAULT_PROFILE.into() } else { value.into() };
+            },
+            "SaveOptions" => {
+                let profile = self.options_profile.clone();
+                self.save_options(&profile)?;
+            },
+            "LoadOptions" => {
+                let profile = self.options_profile.clone();
+                if !self.load_options(&profile)? {
+                    return Err(format!("unknown options profile '{}'", profile).into());
+                }
+            },
+            name => {
+                for &phase in &GAME_PHASES {
+                    let params = self.search_params.phase_mut(phase);
+                    if name == format!("NullMove {}", phase.name()) {
+                        params.nmp = check;
+                    } else if name == format!("LMR {}", phase.name()) {
+                        params.lmr = spin as Depth;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_options(&self, profile: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let path = match profile_path(profile) {
+            Some(path) => path,
+            None => return Err("no config directory".into())
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut content = String::new();
+        for option in self.options().into_iter().filter(|option| option.is_saved()) {
+            let value = match option.kind {
+                OptionType::Check | OptionType::Spin(_, _) => option.value,
+                _ => toml_string(&option.value)
+            };
+            content.push_str(&format!("{} = {}\n", toml_string(&option.name), value));
+        }
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    fn load_options(&mut self, profile: &str) -> Result<bool, Box<dyn Error>> {
+        let path = match profile_path(profile) {
+            Some(ref path) if path.exists() => path.clone(),
+            _ => return Ok(false)
+        };
+        for line in fs::read_to_string(&path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = match parse_toml_pair(line) {
+                Some(pair) => pair,
+                None => return Err(format!("invalid line '{}' in '{}'", line, path.display()).into())
+            };
+            self.set_option(&name, &value)?;
+        }
+        self.options_profile = profile.into();
+        Ok(true)
+    }
+}
+
+// Quote and escape a TOML basic string
+fn toml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Parse a TOML basic string or a bare key or value, returning the rest of
+// the line after it
+fn parse_toml_token(s: &str) -> Option<(String, &str)> {
+    let s = s.trim_start();
+    if !s.starts_with('"') {
+        let end = s.find(&['=', '#'][..]).unwrap_or(s.len());
+        let token = s[..end].trim_end();
+        return if token.is_empty() { None } else { Some((token.into(), &s[end..])) };
+    }
+    let mut token = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((token, &s[(i + 1)..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => token.push('\n'),
+                Some((_, 't')) => token.push('\t'),
+                Some((_, c)) => token.push(c),
+                None => return None
+            },
+            c => token.push(c)
+        }
+    }
+    None
+}
+
+// Parse a `key = value` line of a TOML table without nested values
+fn parse_toml_pair(line: &str) -> Option<(String, String)> {
+    let (key, rest) = parse_toml_token(line)?;
+    let rest = rest.trim_start();
+    if !rest.starts_with('=') {
+        return None;
+    }
+    let (value, rest) = parse_toml_token(&rest[1..])?;
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use params::GamePhase;
+    use piece_move::*;
+    use square::*;
+    use transposition::Bound;
+
+    #[test]
+    fn test_set_option() {
+        let mut game = Game::new();
+
+        assert!(game.set_option("Hash", "4").is_ok());
+        assert_eq!(game.tt_size(), 4 << 20);
+        assert_eq!(game.get_option("hash"), Some("4".into()));
+
+        assert!(game.set_option("Huge Pages", "true").is_ok());
+        assert!(game.tt.has_huge_pages());
+        assert!(game.set_option("Hash", "8").is_ok());
+        assert!(game.tt.has_huge_pages());
+
+        game.tt.set(42, 1, 0, PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH), Bound::Exact);
+        assert!(game.set_option("Clear Hash", "").is_ok());
+        assert!(game.tt.get(42).is_none());
+
+        assert!(game.set_option("SAN PV", "true").is_ok());
+        assert!(game.show_san_pv);
+
+        assert!(game.set_option("nullmove pawn ending", "true").is_ok());
+        assert!(game.search_params.phase(GamePhase::PawnEnding).nmp);
+
+        assert!(game.set_option("LMR Late Ending", "2").is_ok());
+        assert_eq!(game.search_params.phase(GamePhase::LateEnding).lmr, 2);
+
+        assert!(game.set_option("LMR Late Ending", "5").is_err());
+        assert!(game.set_option("NullMove Ending", "yes").is_err());
+        assert!(game.set_option("SyzygyPath", "").is_err());
+
+        assert!(game.set_option("Trace Depth", "6").is_ok());
+        assert_eq!(game.trace.max_ply, 6);
+        assert!(game.set_option("TraceFile", "").is_ok());
+        assert!(!game.trace.is_enabled());
+
+        assert!(game.set_option("UCI_Variant", "crazyhouse").is_ok());
+        assert_eq!(game.variant, Variant::Crazyhouse);
+        assert!(game.set_option("UCI_Variant", "shogi").is_err());
+
+        assert!(game.set_option("Clear Between Moves", "keep").is_ok());
+        assert_eq!(game.search_params.clear_between_moves, ClearPolicy::Keep);
+        assert_eq!(game.get_option("Clear Between Games"), Some("clear".into()));
+        assert!(game.set_option("Clear Between Games", "never").is_err());
+
+        assert!(game.set_option("Seed", "42").is_ok());
+        assert_eq!(game.rng.seed(), 42);
+        assert_eq!(game.rng.next_u64(), Random::new(42).next_u64());
+        assert!(game.set_option("Seed", "-1").is_err());
+
+        assert!(game.set_option("Foo", "bar").is_err());
+    }
+
+    #[test]
+    fn test_save_options() {
+        let path = env::temp_dir().join("littlewing_test_options.toml");
+        let profile = path.to_str().unwrap();
+
+        let mut game = Game::new();
+        assert!(game.set_option("Hash", "2").is_ok());
+        assert!(game.set_option("Contempt", "-15").is_ok());
+        assert!(game.set_option("LearningFile", "/tmp/a \"b\"\\c").is_ok());
+        assert!(game.set_option("UCI_Variant", "atomic").is_ok());
+        assert!(game.set_option("Seed", "42").is_ok());
+        assert_eq!(game.save_options(profile).unwrap(), path);
+
+        let mut game = Game::new();
+        assert!(game.load_options(profile).unwrap());
+        assert_eq!(game.tt_size(), 2 << 20);
+        assert_eq!(game.search_params.contempt, -15);
+        assert_eq!(game.get_option("LearningFile"), Some("/tmp/a \"b\"\\c".into()));
+        assert_eq!(game.variant, Variant::Standard);
+        assert_ne!(game.rng.seed(), 42);
+        assert_eq!(game.options_profile, profile);
+
+        fs::remove_file(&path).unwrap();
+        assert!(!game.load_options(profile).unwrap());
+        assert!(game.set_option("LoadOptions", "").is_err());
+    }
+
+    #[test]
+    fn test_parse_toml_pair() {
+        assert_eq!(parse_toml_pair("Hash = 64"), Some(("Hash".into(), "64".into())));
+        assert_eq!(parse_toml_pair("\"SAN PV\" = true # comment"), Some(("SAN PV".into(), "true".into())));
+        assert_eq!(parse_toml_pair("TraceFile=\"C:\\\\trace.txt\""), Some(("TraceFile".into(), "C:\\trace.txt".into())));
+        assert_eq!(parse_toml_pair("Hash 64"), None);
+        assert_eq!(parse_toml_pair("Hash = \"64"), None);
+    }
+}
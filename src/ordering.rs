@@ -0,0 +1,143 @@
+use std::error::Error;
+
+use common::*;
+use attack::Attack;
+use clock::Clock;
+use fen::FEN;
+use game::Game;
+use params::ClearPolicy;
+use piece_move::PieceMove;
+use piece_move_generator::PieceMoveGenerator;
+use piece_move_notation::PieceMoveNotation;
+use search::{Search, SearchStats};
+
+/// Quality of the move ordering measured on positions with known best moves
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct OrderingStats {
+    /// Number of positions measured
+    pub positions_count: u64,
+
+    /// Sum of the indexes of the best moves in the order of the picker
+    pub best_move_indexes_sum: u64,
+
+    /// Number of positions with the best move picked first
+    pub best_move_first_count: u64,
+
+    /// Number of beta cutoffs during the shallow searches
+    pub cutoffs_count: u64,
+
+    /// Number of beta cutoffs produced by the first legal move searched
+    pub first_move_cutoffs_count: u64
+}
+
+impl OrderingStats {
+    /// Get the average index of the best moves in the order of the picker,
+    /// starting at zero
+    pub fn average_best_move_index(&self) -> f64 {
+        if self.positions_count == 0 {
+            return 0.0;
+        }
+        self.best_move_indexes_sum as f64 / self.positions_count as f64
+    }
+
+    /// Get the rate of beta cutoffs produced by the first move searched
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        if self.cutoffs_count == 0 {
+            return 0.0;
+        }
+        self.first_move_cutoffs_count as f64 / self.cutoffs_count as f64
+    }
+}
+
+/// Move ordering quality metrics
+pub trait MoveOrdering {
+    /// Measure the move ordering on the given positions in FEN with their
+    /// best move in SAN or LAN, using a search at the given depth to count
+    /// the cutoffs
+    fn ordering_stats(&mut self, positions: &[(&str, &str)], depth: Depth) -> Result<OrderingStats, Box<dyn Error>>;
+
+    /// Get the index of the given move in the order of the picker at the
+    /// current position, counting only the legal moves
+    fn move_order_index(&mut self, m: PieceMove) -> Option<usize>;
+}
+
+impl MoveOrdering for Game {
+    fn ordering_stats(&mut self, positions: &[(&str, &str)], depth: Depth) -> Result<OrderingStats, Box<dyn Error>> {
+        let mut stats = OrderingStats::default();
+
+        for &(fen, best_move) in positions {
+            self.load_fen(fen)?;
+            self.clear_search(ClearPolicy::Clear);
+
+            let m = match self.parse_move(best_move) {
+                Some(m) => m,
+                None => return Err(format!("invalid best move '{}' in '{}'", best_move, fen).into())
+            };
+            let i = match self.move_order_index(m) {
+                Some(i) => i,
+                None => return Err(format!("illegal best move '{}' in '{}'", best_move, fen).into())
+            };
+            stats.positions_count += 1;
+            stats.best_move_indexes_sum += i as u64;
+            if i == 0 {
+                stats.best_move_first_count += 1;
+            }
+
+            // Shallow search in the current thread to keep its counters
+            self.clock = Clock::new(1, u64::MAX);
+            self.clock.enable_infinite();
+            self.clock.start(self.positions.len());
+            self.nodes_count = 0;
+            self.search_stats = SearchStats::default();
+            self.moves.clear_plies();
+            self.search_root(1..(depth + 1));
+            stats.cutoffs_count += self.search_stats.cutoffs_count;
+            stats.first_move_cutoffs_count += self.search_stats.first_move_cutoffs_count;
+        }
+
+        Ok(stats)
+    }
+
+    fn move_order_index(&mut self, m: PieceMove) -> Option<usize> {
+        let side = self.side();
+        let mut i = 0;
+        self.moves.clear();
+        while let Some(next_move) = self.next_move() {
+            self.make_move(next_move);
+            let is_legal = !self.is_check(side);
+            self.undo_move(next_move);
+            if is_legal {
+                if next_move == m {
+                    return Some(i);
+                }
+                i += 1;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering_stats() {
+        let mut game = Game::new();
+        let positions = [
+            // Winning capture of the queen
+            ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", "Rxd5"),
+            // Back rank mate
+            ("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "a1a8"),
+        ];
+        let stats = game.ordering_stats(&positions, 3).unwrap();
+        assert_eq!(stats.positions_count, 2);
+        assert_eq!(stats.best_move_first_count, 1); // The capture
+        assert!(stats.average_best_move_index() > 0.0);
+        assert!(stats.cutoffs_count > 0);
+        assert!(stats.first_move_cutoff_rate() > 0.0);
+        assert!(stats.first_move_cutoff_rate() <= 1.0);
+
+        assert!(game.ordering_stats(&[("4k3/8/8/8/8/8/8/4K3 w - - 0 1", "Qd1")], 1).is_err());
+    }
+}
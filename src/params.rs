@@ -0,0 +1,572 @@
+use std::cmp;
+
+use piece::*;
+use common::*;
+use bitboard::BitboardExt;
+use game::Game;
+
+/// Phases of the game having their own set of search parameters
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GamePhase {
+    Middlegame,
+    Ending,
+    LateEnding,
+    PawnEnding
+}
+
+pub const GAME_PHASES: [GamePhase; 4] = [
+    GamePhase::Middlegame,
+    GamePhase::Ending,
+    GamePhase::LateEnding,
+    GamePhase::PawnEnding
+];
+
+impl GamePhase {
+    /// Get the phase of the given game from the point of view of the side
+    /// to move
+    pub fn from_game(game: &Game) -> GamePhase {
+        let side = game.side();
+        let pieces_count = game.bitboard(side).count();
+        let pawns_count = game.bitboard(side | PAWN).count();
+
+        if pieces_count == pawns_count + 1 { // pawns + king
+            return GamePhase::PawnEnding;
+        }
+
+        let phase = game.phase();
+        if phase >= PHASE_MAX * 5 / 6 {
+            GamePhase::LateEnding
+        } else if phase >= PHASE_MAX / 2 {
+            GamePhase::Ending
+        } else {
+            GamePhase::Middlegame
+        }
+    }
+
+    /// Get the name of the phase
+    pub fn name(self) -> &'static str {
+        match self {
+            GamePhase::Middlegame => "Middlegame",
+            GamePhase::Ending     => "Ending",
+            GamePhase::LateEnding => "Late Ending",
+            GamePhase::PawnEnding => "Pawn Ending"
+        }
+    }
+}
+
+/// Policy applied to the transposition table, the killer moves and the
+/// history heuristic before a new search or a new game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClearPolicy {
+    /// Keep everything from the previous searches
+    Keep,
+
+    /// Make the previous entries replaceable and halve the history
+    Age,
+
+    /// Start from empty tables
+    Clear
+}
+
+pub const CLEAR_POLICIES: [ClearPolicy; 3] = [
+    ClearPolicy::Keep,
+    ClearPolicy::Age,
+    ClearPolicy::Clear
+];
+
+impl ClearPolicy {
+    /// Get the name of the policy
+    pub fn name(self) -> &'static str {
+        match self {
+            ClearPolicy::Keep  => "keep",
+            ClearPolicy::Age   => "age",
+            ClearPolicy::Clear => "clear"
+        }
+    }
+
+    /// Get the policy with the given name
+    pub fn from_name(name: &str) -> Option<ClearPolicy> {
+        CLEAR_POLICIES.iter().cloned().find(|p| p.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Search parameters specific to a phase of the game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PhaseParams {
+    /// Allow null move pruning
+    pub nmp: bool,
+
+    /// Additional late move reduction
+    pub lmr: Depth
+}
+
+/// Features of the search that can be disabled to measure their impact
+pub const SEARCH_FEATURES: [&str; 6] = ["nmp", "lmr", "lmp", "futility", "see", "tt"];
+
+/// Search features
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchFeatures {
+    /// Null move pruning
+    pub nmp: bool,
+
+    /// Late move reduction
+    pub lmr: bool,
+
+    /// Late move pruning
+    pub lmp: bool,
+
+    /// Futility pruning, and delta pruning in quiescence search
+    pub futility: bool,
+
+    /// Static exchange evaluation pruning
+    pub see: bool,
+
+    /// Probing of the transposition table in the search, the entries being
+    /// still stored to get the principal variation
+    pub tt: bool
+}
+
+impl SearchFeatures {
+    /// Create the default search features with every feature enabled
+    pub fn new() -> SearchFeatures {
+        SearchFeatures {
+            nmp: true,
+            lmr: true,
+            lmp: true,
+            futility: true,
+            see: true,
+            tt: true
+        }
+    }
+
+    /// Get the mutable toggle of the feature with the given name
+    pub fn feature_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "nmp"      => Some(&mut self.nmp),
+            "lmr"      => Some(&mut self.lmr),
+            "lmp"      => Some(&mut self.lmp),
+            "futility" => Some(&mut self.futility),
+            "see"      => Some(&mut self.see),
+            "tt"       => Some(&mut self.tt),
+            _          => None
+        }
+    }
+
+    /// Get the list of the features prefixed by `+` when enabled or by `-`
+    /// when disabled
+    pub fn signature(&self) -> String {
+        let toggles = [self.nmp, self.lmr, self.lmp, self.futility, self.see, self.tt];
+        SEARCH_FEATURES.iter().zip(toggles.iter()).map(|(name, &enabled)| {
+            format!("{}{}", if enabled { '+' } else { '-' }, name)
+        }).collect::<Vec<String>>().join(" ")
+    }
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        SearchFeatures::new()
+    }
+}
+
+/// Search parameters
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchParams {
+    pub phases: [PhaseParams; 4],
+
+    /// Features enabled in the search
+    pub features: SearchFeatures,
+
+    /// Score of a draw for the opponent of the side to move at the root
+    /// in the middlegame
+    pub contempt: Score,
+
+    /// Contempt in the ending, interpolated with the middlegame one by the
+    /// material left on the board
+    pub contempt_ending: Score,
+
+    /// Root score adjustment of moves allowing an immediate repetition
+    pub repetition: Score,
+
+    /// Initial half-width of the root window around the score of the
+    /// previous iteration, or zero to search with a full window
+    pub aspiration: Score,
+
+    /// Null move reduction at the shallowest depths
+    pub nmp_reduction: Depth,
+
+    /// Plies of depth adding one ply to the null move reduction
+    pub nmp_depth_divisor: Depth,
+
+    /// Margin of the static evaluation above beta adding one ply to the
+    /// null move reduction, or zero to ignore the evaluation
+    pub nmp_eval_divisor: Score,
+
+    /// Maximum number of plies added to the null move reduction by the
+    /// static evaluation
+    pub nmp_eval_max: Depth,
+
+    /// Depth below which quiet moves are pruned when the static evaluation
+    /// plus the futility margin cannot reach alpha, or zero to disable it
+    pub fp_depth: Depth,
+
+    /// Futility margin added by each ply of depth left
+    pub fp_margin: Score,
+
+    /// Depth below which a node is pruned when the static evaluation plus
+    /// the razoring margin and then the quiescence search cannot reach
+    /// alpha, or zero to disable it
+    pub razor_depth: Depth,
+
+    /// Razoring margin added by each ply of depth left
+    pub razor_margin: Score,
+
+    /// Depth below which the quiet moves coming after a number of legal
+    /// moves growing with the depth are pruned when the static evaluation
+    /// is not improving, or zero to disable it
+    pub lmp_depth: Depth,
+
+    /// Depth below which the moves losing more material in the exchanges
+    /// on their destination square than the SEE margin are pruned, or zero
+    /// to disable it
+    pub see_depth: Depth,
+
+    /// SEE margin of the quiet moves added by each ply of depth left
+    pub see_quiet_margin: Score,
+
+    /// SEE margin of the captures added by each ply of depth left
+    pub see_capture_margin: Score,
+
+    /// Depth from which the PV nodes without a move from the transposition
+    /// table are first searched at half the depth to get one, or zero to
+    /// disable it
+    pub iid_depth: Depth,
+
+    /// Depth from which the other nodes without a move from the
+    /// transposition table are searched one ply shallower, or zero to
+    /// disable it
+    pub iir_depth: Depth,
+
+    /// Policy for the search tables between the moves of a game
+    pub clear_between_moves: ClearPolicy,
+
+    /// Policy for the search tables between games
+    pub clear_between_games: ClearPolicy
+}
+
+impl SearchParams {
+    /// Create the default search parameters
+    pub fn new() -> SearchParams {
+        SearchParams {
+            phases: [
+                PhaseParams { nmp: true, lmr: 0 },  // Middlegame
+                PhaseParams { nmp: true, lmr: 0 },  // Ending
+                PhaseParams { nmp: true, lmr: 1 },  // Late ending
+                PhaseParams { nmp: false, lmr: 0 }, // Pawn ending (zugzwang)
+            ],
+            features: SearchFeatures::new(),
+            contempt: 0,
+            contempt_ending: 0,
+            repetition: 0,
+            aspiration: 25,
+            nmp_reduction: 3,
+            nmp_depth_divisor: 4,
+            nmp_eval_divisor: 200,
+            nmp_eval_max: 3,
+            fp_depth: 6,
+            fp_margin: 100,
+            razor_depth: 4,
+            razor_margin: 200,
+            lmp_depth: 4,
+            see_depth: 4,
+            see_quiet_margin: 60,
+            see_capture_margin: 100,
+            iid_depth: 4,
+            iir_depth: 4,
+            clear_between_moves: ClearPolicy::Age,
+            clear_between_games: ClearPolicy::Clear
+        }
+    }
+
+    /// Get the parameters of the given phase
+    pub fn phase(&self, phase: GamePhase) -> &PhaseParams {
+        &self.phases[phase as usize]
+    }
+
+    /// Get the contempt at the given game phase, from the middlegame one at
+    /// `0` to the ending one at `PHASE_MAX`
+    pub fn contempt(&self, phase: Score) -> Score {
+        let phase = phase.clamp(0, PHASE_MAX) as i32;
+        let mg = self.contempt as i32 * (PHASE_MAX as i32 - phase);
+        let eg = self.contempt_ending as i32 * phase;
+        ((mg + eg) / PHASE_MAX as i32) as Score
+    }
+
+    /// Get the score of a draw at the given ply from the root and the given
+    /// game phase
+    pub fn draw_score(&self, ply: usize, phase: Score) -> Score {
+        let contempt = self.contempt(phase);
+        if ply & 1 == 0 { -contempt } else { contempt }
+    }
+
+    /// Get the null move reduction at the given depth with the given margin
+    /// of the static evaluation above beta
+    pub fn null_move_reduction(&self, depth: Depth, margin: Score) -> Depth {
+        let mut r = self.nmp_reduction + depth / self.nmp_depth_divisor;
+        if self.nmp_eval_divisor > 0 && margin > 0 {
+            r += cmp::min(margin / self.nmp_eval_divisor, self.nmp_eval_max as Score) as Depth;
+        }
+        r
+    }
+
+    /// Get the futility margin at the given depth, if futility pruning is
+    /// allowed there
+    pub fn futility_margin(&self, depth: Depth) -> Option<Score> {
+        if depth < self.fp_depth {
+            Some(self.fp_margin * depth as Score)
+        } else {
+            None
+        }
+    }
+
+    /// Get the razoring margin at the given depth, if razoring is allowed
+    /// there
+    pub fn razoring_margin(&self, depth: Depth) -> Option<Score> {
+        if depth < self.razor_depth {
+            Some(self.razor_margin * depth as Score)
+        } else {
+            None
+        }
+    }
+
+    /// Get the number of legal moves searched at the given depth before
+    /// pruning the quiet moves, if late move pruning is allowed there
+    pub fn late_move_count(&self, depth: Depth) -> Option<usize> {
+        if depth < self.lmp_depth {
+            Some(3 + (depth as usize) * (depth as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Get the SEE score below which a quiet move or a capture is pruned at
+    /// the given depth, if SEE pruning is allowed there
+    pub fn see_threshold(&self, depth: Depth, is_capture: bool) -> Option<Score> {
+        if depth < self.see_depth {
+            let margin = if is_capture { self.see_capture_margin } else { self.see_quiet_margin };
+            Some(-margin * depth as Score)
+        } else {
+            None
+        }
+    }
+
+    /// Get the depth of the internal iterative deepening search at the
+    /// given depth, if it is allowed there
+    pub fn iid_search_depth(&self, depth: Depth) -> Option<Depth> {
+        if self.iid_depth > 0 && depth >= self.iid_depth {
+            Some(depth / 2)
+        } else {
+            None
+        }
+    }
+
+    /// Get the internal iterative reduction at the given depth
+    pub fn iir_reduction(&self, depth: Depth) -> Depth {
+        if self.iir_depth > 0 && depth >= self.iir_depth { 1 } else { 0 }
+    }
+
+    /// Get the mutable parameters of the given phase
+    pub fn phase_mut(&mut self, phase: GamePhase) -> &mut PhaseParams {
+        &mut self.phases[phase as usize]
+    }
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams::new()
+    }
+}
+
+/// Terms of the evaluation that can be disabled to measure their impact
+pub const EVAL_TERMS: [&str; 6] = ["bishop_pair", "position", "mobility", "pawns", "pawn_storm", "blocked"];
+
+/// Evaluation parameters
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EvalParams {
+    /// Bonus for having the two bishops
+    pub bishop_pair: bool,
+
+    /// Piece-square tables interpolated between the phases of the game
+    pub position: bool,
+
+    /// Number of squares attacked by the pieces
+    pub mobility: bool,
+
+    /// Isolated, doubled, and passed pawns
+    pub pawns: bool,
+
+    /// Pawns sheltering the kings and storming them in the middlegame
+    pub pawn_storm: bool,
+
+    /// Scaling toward a draw of the positions locked by the pawns
+    pub blocked: bool
+}
+
+impl EvalParams {
+    /// Create the default evaluation parameters with every term enabled
+    pub fn new() -> EvalParams {
+        EvalParams {
+            bishop_pair: true,
+            position: true,
+            mobility: true,
+            pawns: true,
+            pawn_storm: true,
+            blocked: true
+        }
+    }
+
+    /// Get the mutable toggle of the term with the given name
+    pub fn term_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "bishop_pair" => Some(&mut self.bishop_pair),
+            "position"    => Some(&mut self.position),
+            "mobility"    => Some(&mut self.mobility),
+            "pawns"       => Some(&mut self.pawns),
+            "pawn_storm"  => Some(&mut self.pawn_storm),
+            "blocked"     => Some(&mut self.blocked),
+            _             => None
+        }
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fen::FEN;
+
+    #[test]
+    fn test_game_phase() {
+        let game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(GamePhase::from_game(&game), GamePhase::Middlegame);
+
+        let game = Game::from_fen("4k3/pp3ppp/2n5/8/8/2B5/PP3PPP/R3K3 w - - 0 1").unwrap();
+        assert_eq!(GamePhase::from_game(&game), GamePhase::LateEnding);
+
+        let game = Game::from_fen("4k3/pp3ppp/8/8/8/8/PP3PPP/R3K3 b - - 0 1").unwrap();
+        assert_eq!(GamePhase::from_game(&game), GamePhase::PawnEnding);
+    }
+
+    #[test]
+    fn test_contempt() {
+        let mut params = SearchParams::new();
+        params.contempt = 40;
+        params.contempt_ending = 0;
+        assert_eq!(params.contempt(0), 40);
+        assert_eq!(params.contempt(PHASE_MAX / 2), 20);
+        assert_eq!(params.contempt(PHASE_MAX), 0);
+        assert_eq!(params.draw_score(0, 0), -40);
+        assert_eq!(params.draw_score(1, 0), 40);
+    }
+
+    #[test]
+    fn test_null_move_reduction() {
+        let params = SearchParams::new();
+        assert_eq!(params.null_move_reduction(1, 0), 3);
+        assert_eq!(params.null_move_reduction(8, 0), 5);
+        assert_eq!(params.null_move_reduction(8, -100), 5);
+        assert_eq!(params.null_move_reduction(8, 199), 5);
+        assert_eq!(params.null_move_reduction(8, 400), 7);
+        assert_eq!(params.null_move_reduction(8, 2000), 8);
+
+        let mut params = SearchParams::new();
+        params.nmp_eval_divisor = 0;
+        assert_eq!(params.null_move_reduction(8, 2000), 5);
+    }
+
+    #[test]
+    fn test_futility_margin() {
+        let mut params = SearchParams::new();
+        assert_eq!(params.futility_margin(1), Some(100));
+        assert_eq!(params.futility_margin(5), Some(500));
+        assert_eq!(params.futility_margin(6), None);
+
+        params.fp_depth = 0;
+        assert_eq!(params.futility_margin(1), None);
+    }
+
+    #[test]
+    fn test_razoring_margin() {
+        let mut params = SearchParams::new();
+        assert_eq!(params.razoring_margin(1), Some(200));
+        assert_eq!(params.razoring_margin(3), Some(600));
+        assert_eq!(params.razoring_margin(4), None);
+
+        params.razor_depth = 0;
+        assert_eq!(params.razoring_margin(1), None);
+    }
+
+    #[test]
+    fn test_late_move_count() {
+        let mut params = SearchParams::new();
+        assert_eq!(params.late_move_count(1), Some(4));
+        assert_eq!(params.late_move_count(3), Some(12));
+        assert_eq!(params.late_move_count(4), None);
+
+        params.lmp_depth = 0;
+        assert_eq!(params.late_move_count(1), None);
+    }
+
+    #[test]
+    fn test_see_threshold() {
+        let mut params = SearchParams::new();
+        assert_eq!(params.see_threshold(1, false), Some(-60));
+        assert_eq!(params.see_threshold(3, false), Some(-180));
+        assert_eq!(params.see_threshold(3, true), Some(-300));
+        assert_eq!(params.see_threshold(4, true), None);
+
+        params.see_depth = 0;
+        assert_eq!(params.see_threshold(1, false), None);
+    }
+
+    #[test]
+    fn test_internal_iterative_depth() {
+        let mut params = SearchParams::new();
+        assert_eq!(params.iid_search_depth(3), None);
+        assert_eq!(params.iid_search_depth(4), Some(2));
+        assert_eq!(params.iid_search_depth(9), Some(4));
+        assert_eq!(params.iir_reduction(3), 0);
+        assert_eq!(params.iir_reduction(4), 1);
+
+        params.iid_depth = 0;
+        params.iir_depth = 0;
+        assert_eq!(params.iid_search_depth(9), None);
+        assert_eq!(params.iir_reduction(9), 0);
+    }
+
+    #[test]
+    fn test_search_features() {
+        let mut features = SearchFeatures::new();
+        assert_eq!(features.signature(), "+nmp +lmr +lmp +futility +see +tt");
+
+        *features.feature_mut("lmr").unwrap() = false;
+        *features.feature_mut("tt").unwrap() = false;
+        assert!(!features.lmr && !features.tt);
+        assert_eq!(features.signature(), "+nmp -lmr +lmp +futility +see -tt");
+        assert_eq!(features.feature_mut("iid"), None);
+    }
+
+    #[test]
+    fn test_eval_params() {
+        let mut params = EvalParams::new();
+        for name in &EVAL_TERMS {
+            *params.term_mut(name).unwrap() = false;
+        }
+        assert!(!params.bishop_pair && !params.position && !params.mobility && !params.pawns && !params.pawn_storm && !params.blocked);
+        assert_eq!(params.term_mut("material"), None);
+    }
+}
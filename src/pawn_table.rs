@@ -0,0 +1,173 @@
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use common::*;
+use square::*;
+
+/// Evaluation of the pawns of a position, depending only on the pawns for
+/// their structure, and also on the kings for their shelters
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PawnEval {
+    pub structure: [Score; 2], // Score of white in the opening and the ending
+    pub kings: [Square; 2], // Squares of the kings of the shelters (OUT if not evaluated)
+    pub shelters: [Score; 2] // Shelter and storm of the king of each side
+}
+
+impl PawnEval {
+    /// Create the evaluation of the given structure without the shelters
+    pub fn new(structure: [Score; 2]) -> PawnEval {
+        PawnEval {
+            structure,
+            kings: [OUT; 2],
+            shelters: [0; 2]
+        }
+    }
+
+    fn structure_data(&self) -> u64 {
+        (self.structure[0] as u16 as u64) |
+        (self.structure[1] as u16 as u64) << 16
+    }
+
+    fn shelters_data(&self) -> u64 {
+        (self.shelters[0] as u16 as u64) |
+        (self.shelters[1] as u16 as u64) << 16 |
+        (self.kings[0] as u64) << 32 |
+        (self.kings[1] as u64) << 40
+    }
+
+    fn from_data(structure: u64, shelters: u64) -> PawnEval {
+        PawnEval {
+            structure: [structure as u16 as Score, (structure >> 16) as u16 as Score],
+            kings: [(shelters >> 32) as u8 as Square, (shelters >> 40) as u8 as Square],
+            shelters: [shelters as u16 as Score, (shelters >> 16) as u16 as Score]
+        }
+    }
+}
+
+// An entry shared by the threads of the search without lock, with the hash
+// XORed with the data to detect concurrent writes like in the transposition
+// table
+struct PawnEntry {
+    key: AtomicU64,
+    structure: AtomicU64,
+    shelters: AtomicU64
+}
+
+impl PawnEntry {
+    fn new() -> PawnEntry {
+        // An empty entry is the evaluation of a position without pawns
+        let e = PawnEval::new([0; 2]);
+        PawnEntry {
+            key: AtomicU64::new(e.structure_data() ^ e.shelters_data()),
+            structure: AtomicU64::new(e.structure_data()),
+            shelters: AtomicU64::new(e.shelters_data())
+        }
+    }
+
+    fn load(&self) -> (u64, PawnEval) {
+        let key = self.key.load(Ordering::Relaxed);
+        let structure = self.structure.load(Ordering::Relaxed);
+        let shelters = self.shelters.load(Ordering::Relaxed);
+        (key ^ structure ^ shelters, PawnEval::from_data(structure, shelters))
+    }
+
+    fn store(&self, hash: u64, e: PawnEval) {
+        let structure = e.structure_data();
+        let shelters = e.shelters_data();
+        self.key.store(hash ^ structure ^ shelters, Ordering::Relaxed);
+        self.structure.store(structure, Ordering::Relaxed);
+        self.shelters.store(shelters, Ordering::Relaxed);
+    }
+}
+
+/// A table of the evaluations of the pawns of the positions already seen,
+/// using the Zobrist hash of their pawns, shared by the threads of the
+/// search
+#[derive(Clone)]
+pub struct PawnTable {
+    entries: Arc<Vec<PawnEntry>>
+}
+
+impl PawnTable {
+    /// Create a table with the given number of entries or the next power
+    /// of two
+    pub fn with_capacity(capacity: usize) -> PawnTable {
+        let n = capacity.next_power_of_two();
+        PawnTable {
+            entries: Arc::new((0..n).map(|_| PawnEntry::new()).collect())
+        }
+    }
+
+    /// Create a table using the given size in byte or less
+    pub fn with_memory(memory: usize) -> PawnTable {
+        let capacity = memory / mem::size_of::<PawnEntry>();
+        let n = if capacity.is_power_of_two() {
+            capacity
+        } else {
+            capacity.next_power_of_two() / 2
+        };
+
+        PawnTable::with_capacity(n)
+    }
+
+    /// Get the evaluation of the pawns with the given hash
+    pub fn get(&self, hash: u64) -> Option<PawnEval> {
+        let (key, e) = self.entries[self.index(hash)].load();
+        if key == hash {
+            Some(e)
+        } else {
+            None
+        }
+    }
+
+    /// Set the evaluation of the pawns with the given hash, always
+    /// replacing the previous entry
+    pub fn set(&self, hash: u64, e: PawnEval) {
+        self.entries[self.index(hash)].store(hash, e);
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Get the size of the table in byte
+    pub fn memory(&self) -> usize {
+        self.len() * mem::size_of::<PawnEntry>()
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash & (self.len() as u64 - 1)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pawn_table() {
+        assert_eq!(PawnTable::with_capacity(24).len(), 32);
+        assert!(PawnTable::with_memory(PAWN_TABLE_SIZE).memory() <= PAWN_TABLE_SIZE);
+
+        let table = PawnTable::with_capacity(32);
+        let e = PawnEval {
+            structure: [-25, 110],
+            kings: [G1, OUT],
+            shelters: [-40, 0]
+        };
+        let hash = 0x463B96181691FC9C;
+        assert_eq!(table.get(hash), None);
+        table.set(hash, e);
+        assert_eq!(table.get(hash), Some(e));
+
+        // Another structure in the same entry
+        let other = hash ^ (1 << 40);
+        assert_eq!(table.get(other), None);
+        table.set(other, PawnEval::new([0, 0]));
+        assert_eq!(table.get(hash), None);
+
+        // Positions without pawns are already in the table
+        assert_eq!(table.get(0), Some(PawnEval::new([0, 0])));
+    }
+}
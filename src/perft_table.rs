@@ -0,0 +1,90 @@
+use std::mem;
+
+use common::*;
+
+#[derive(Clone, Copy)]
+struct PerftEntry {
+    hash: u64,
+    nodes: u64,
+    depth: Depth
+}
+
+/// A table of the number of nodes found by perft in the positions already
+/// counted at the same depth, using the Zobrist hash of the positions
+#[derive(Clone)]
+pub struct PerftTable {
+    entries: Vec<PerftEntry>
+}
+
+impl PerftTable {
+    /// Create a table with the given number of entries or the next power
+    /// of two
+    pub fn with_capacity(capacity: usize) -> PerftTable {
+        let n = capacity.next_power_of_two();
+        let null = PerftEntry { hash: 0, nodes: 0, depth: 0 };
+        PerftTable {
+            entries: vec![null; n]
+        }
+    }
+
+    /// Create a table using the given size in byte or less
+    pub fn with_memory(memory: usize) -> PerftTable {
+        let capacity = memory / mem::size_of::<PerftEntry>();
+        let n = if capacity.is_power_of_two() {
+            capacity
+        } else {
+            capacity.next_power_of_two() / 2
+        };
+
+        PerftTable::with_capacity(n)
+    }
+
+    /// Get the number of nodes of the given position at the given depth
+    pub fn get(&self, hash: u64, depth: Depth) -> Option<u64> {
+        let e = &self.entries[self.index(hash, depth)];
+        if e.hash == hash && e.depth == depth {
+            Some(e.nodes)
+        } else {
+            None
+        }
+    }
+
+    /// Set the number of nodes of the given position at the given depth,
+    /// always replacing the previous entry
+    pub fn set(&mut self, hash: u64, depth: Depth, nodes: u64) {
+        let k = self.index(hash, depth);
+        self.entries[k] = PerftEntry { hash, nodes, depth };
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Get the size of the table in byte
+    pub fn memory(&self) -> usize {
+        self.len() * mem::size_of::<PerftEntry>()
+    }
+
+    // The same position at different depths goes to different entries
+    fn index(&self, hash: u64, depth: Depth) -> usize {
+        (hash.wrapping_add(depth as u64) & (self.len() as u64 - 1)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perft_table() {
+        assert_eq!(PerftTable::with_capacity(24).len(), 32);
+        assert_eq!(PerftTable::with_memory(24 * 32).len(), 32);
+        assert_eq!(PerftTable::with_memory(24 * 40).len(), 32);
+
+        let mut table = PerftTable::with_capacity(32);
+        table.set(42, 3, 8902);
+        assert_eq!(table.get(42, 3), Some(8902));
+        assert_eq!(table.get(42, 4), None);
+        assert_eq!(table.get(42 + 32, 3), None);
+    }
+}
@@ -1,7 +1,8 @@
-use regex::Regex;
-
 use std::collections::BTreeMap;
+use std::error::Error;
 use std::fmt;
+use std::io::{self, BufRead};
+use std::sync::LazyLock;
 
 use attack::*;
 use color::*;
@@ -20,18 +21,19 @@ pub struct PGN {
     body: String,
 }
 
-lazy_static! {
-    static ref DEFAULT_HEADERS: Vec<(String, String)> = vec![
-        ("1Event".to_string(), "?".to_string()),
-        ("2Site".to_string(), "?".to_string()),
-        ("3White".to_string(), "?".to_string()),
-        ("4Black".to_string(), "?".to_string()),
-        ("5Result".to_string(), "*".to_string()),
-    ];
-}
+static DEFAULT_HEADERS: LazyLock<Vec<(String, String)>> = LazyLock::new(|| vec![
+    // Seven Tag Roster
+    ("1Event".to_string(), "?".to_string()),
+    ("2Site".to_string(), "?".to_string()),
+    ("3Date".to_string(), "????.??.??".to_string()),
+    ("4Round".to_string(), "?".to_string()),
+    ("5White".to_string(), "?".to_string()),
+    ("6Black".to_string(), "?".to_string()),
+    ("7Result".to_string(), "*".to_string()),
+]);
 
 impl PGN {
-    fn new() -> PGN {
+    pub fn new() -> PGN {
         PGN {
             headers: DEFAULT_HEADERS.clone().into_iter().collect(),
             body: "".to_string(),
@@ -39,27 +41,43 @@ impl PGN {
     }
 
     pub fn white(&self) -> String {
-        self.headers["3White"].clone()
+        self.headers["5White"].clone()
     }
 
     pub fn black(&self) -> String {
-        self.headers["4Black"].clone()
+        self.headers["6Black"].clone()
     }
 
     pub fn result(&self) -> String {
-        self.headers["5Result"].clone()
+        self.headers["7Result"].clone()
     }
 
     pub fn set_white(&mut self, white: &str) {
-        self.headers.insert("3White".to_string(), white.to_string());
+        self.headers.insert("5White".to_string(), white.to_string());
     }
 
     pub fn set_black(&mut self, black: &str) {
-        self.headers.insert("4Black".to_string(), black.to_string());
+        self.headers.insert("6Black".to_string(), black.to_string());
     }
 
     pub fn set_result(&mut self, result: &str) {
-        self.headers.insert("5Result".to_string(), result.to_string());
+        self.headers.insert("7Result".to_string(), result.to_string());
+    }
+
+    pub fn set_event(&mut self, event: &str) {
+        self.headers.insert("1Event".to_string(), event.to_string());
+    }
+
+    pub fn set_site(&mut self, site: &str) {
+        self.headers.insert("2Site".to_string(), site.to_string());
+    }
+
+    pub fn set_date(&mut self, date: &str) {
+        self.headers.insert("3Date".to_string(), date.to_string());
+    }
+
+    pub fn set_round(&mut self, round: &str) {
+        self.headers.insert("4Round".to_string(), round.to_string());
     }
 
     pub fn set_fen(&mut self, fen: &str) {
@@ -67,8 +85,56 @@ impl PGN {
         self.headers.insert("SetUp".to_string(), "1".to_string());
     }
 
+    /// Set the header with the given tag name
     pub fn set_header(&mut self, key: &str, val: &str) {
-        self.headers.insert(key.to_string(), val.to_string());
+        self.headers.insert(header_key(key), val.to_string());
+    }
+
+    /// Get the header with the given tag name
+    pub fn header(&self, key: &str) -> Option<String> {
+        self.headers.get(&header_key(key)).cloned()
+    }
+
+    /// Get the headers in the order of the PGN export
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.headers.iter().map(|(key, val)| {
+            (key.trim_start_matches(char::is_numeric).to_string(), val.clone())
+        }).collect()
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn set_body(&mut self, body: &str) {
+        self.body = body.to_string();
+    }
+
+    /// Get the moves of the main line in SAN format
+    pub fn moves(&self) -> Vec<String> {
+        let mut moves = Vec::new();
+        let mut variation_level = 0;
+        for token in Tokens::new(&self.body) {
+            match token {
+                Token::VariationStart => {
+                    variation_level += 1;
+                },
+                Token::VariationEnd => {
+                    variation_level -= 1;
+                },
+                Token::Move(san) if variation_level == 0 => {
+                    moves.push(san);
+                },
+                _ => {}
+            }
+        }
+        moves
+    }
+}
+
+impl Default for PGN {
+    fn default() -> Self {
+        PGN::new()
     }
 }
 
@@ -85,41 +151,230 @@ impl fmt::Display for PGN {
 
 impl From<String> for PGN {
     fn from(s: String) -> PGN {
-        lazy_static! {
-            static ref RE: Regex = Regex::new("\\[(?P<key>\\w+) \"(?P<val>.*)\"\\]").unwrap();
+        // Keep only the last game
+        PgnReader::new(s.as_bytes()).filter_map(Result::ok).last().unwrap_or_default()
+    }
+}
+
+/// Reader of the games of a PGN database, parsed one at a time from a
+/// buffered source to handle files too large to fit in memory
+pub struct PgnReader<R> {
+    reader: R,
+    line: String,
+    header: Option<(String, String)> // First header of the next game
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader,
+            line: String::new(),
+            header: None
         }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = io::Result<PGN>;
+
+    fn next(&mut self) -> Option<io::Result<PGN>> {
         let mut pgn = PGN::new();
-        for line in s.lines() {
-            match RE.captures(line) {
-                Some(header) => {
-                    // Keep only the last game
-                    if !pgn.body.is_empty() {
-                        pgn = PGN::new();
-                    }
+        let mut is_empty = true;
+        if let Some((key, val)) = self.header.take() {
+            pgn.set_header(&key, &val);
+            is_empty = false;
+        }
 
-                    let key = header["key"].to_string();
-                    let val = header["val"].to_string();
-                    let mut is_default_header = false;
-                    for (k, _) in DEFAULT_HEADERS.iter() {
-                        if key == k.trim_start_matches(char::is_numeric) {
-                            pgn.set_header(k, &val);
-                            is_default_header = true;
-                            break;
-                        }
-                    }
-                    if !is_default_header {
-                        pgn.set_header(&key, &val);
-                    }
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => {
+                    return if is_empty { None } else { Some(Ok(pgn)) };
+                },
+                Ok(_) => {},
+                Err(e) => {
+                    return Some(Err(e));
                 }
-                None => {
-                    if !line.is_empty() {
-                        pgn.body.push_str(line);
-                        pgn.body.push('\n');
+            }
+
+            let line = self.line.trim_end();
+            if let Some((key, val)) = parse_header(line) {
+                // A header after the movetext starts the next game
+                if !pgn.body.is_empty() {
+                    self.header = Some((key.to_string(), val.to_string()));
+                    return Some(Ok(pgn));
+                }
+                pgn.set_header(key, val);
+                is_empty = false;
+            } else if !line.is_empty() && !line.starts_with('%') {
+                pgn.body.push_str(line);
+                pgn.body.push('\n');
+                is_empty = false;
+            }
+        }
+    }
+}
+
+// Parse a header line like `[Event "?"]` into its key and value
+fn parse_header(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if !line.starts_with('[') || !line.ends_with(']') {
+        return None;
+    }
+    let line = &line[1..(line.len() - 1)];
+    let i = line.find(' ')?;
+    let (key, val) = line.split_at(i);
+    let val = val.trim_start();
+    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    if val.len() < 2 || !val.starts_with('"') || !val.ends_with('"') {
+        return None;
+    }
+    Some((key, &val[1..(val.len() - 1)]))
+}
+
+// Header keys of the Seven Tag Roster are prefixed with their position
+fn header_key(key: &str) -> String {
+    for (k, _) in DEFAULT_HEADERS.iter() {
+        if key == k.trim_start_matches(char::is_numeric) {
+            return k.clone();
+        }
+    }
+    key.to_string()
+}
+
+/// Token of the movetext section of a PGN
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    MoveNumber(u16),
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+/// Split the movetext section of a PGN into tokens
+pub fn tokenize(movetext: &str) -> Vec<Token> {
+    Tokens::new(movetext).collect()
+}
+
+/// Iterator over the tokens of the movetext section of a PGN, scanning it
+/// lazily without copying the parts that are skipped
+pub struct Tokens<'a> {
+    movetext: &'a str,
+    nag: Option<u8> // Suffix annotation of the last move
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(movetext: &'a str) -> Tokens<'a> {
+        Tokens { movetext, nag: None }
+    }
+
+    // Consume the movetext until the given delimiter and return the part
+    // before it
+    fn take_until(&mut self, delimiter: char) -> &'a str {
+        let i = self.movetext.find(delimiter).unwrap_or(self.movetext.len());
+        let (text, rest) = self.movetext.split_at(i);
+        self.movetext = rest.get(1..).unwrap_or("");
+        text
+    }
+
+    // Consume the next word, splitting move numbers from moves written
+    // like `1.e4`
+    fn take_word(&mut self) -> &'a str {
+        let mut end = self.movetext.len();
+        let mut prev = ' ';
+        for (i, c) in self.movetext.char_indices() {
+            if c.is_whitespace() || "{}();".contains(c) || (c != '.' && prev == '.') {
+                end = i;
+                break;
+            }
+            prev = c;
+        }
+        let (word, rest) = self.movetext.split_at(end);
+        self.movetext = rest;
+        word
+    }
+
+    fn tokenize_word(&mut self, word: &str) -> Option<Token> {
+        match word {
+            "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                Some(Token::Result(word.to_string()))
+            },
+            _ if word.starts_with('$') => {
+                word[1..].parse().ok().map(Token::Nag)
+            },
+            _ if word.starts_with(|c: char| c.is_ascii_digit()) && word.ends_with('.') => {
+                word.trim_end_matches('.').parse().ok().map(Token::MoveNumber)
+            },
+            _ => {
+                // Suffix annotations are converted into their NAG equivalent
+                let i = word.find(|c| "!?".contains(c)).unwrap_or(word.len());
+                let (san, suffix) = word.split_at(i);
+                let san = san.trim_end_matches(|c| "+#".contains(c));
+                self.nag = match suffix {
+                    "!"  => Some(1),
+                    "?"  => Some(2),
+                    "!!" => Some(3),
+                    "??" => Some(4),
+                    "!?" => Some(5),
+                    "?!" => Some(6),
+                    _    => None,
+                };
+                Some(Token::Move(san.to_string()))
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if let Some(nag) = self.nag.take() {
+            return Some(Token::Nag(nag));
+        }
+
+        loop {
+            self.movetext = self.movetext.trim_start();
+            let c = self.movetext.chars().next()?;
+            match c {
+                '{' => {
+                    self.movetext = &self.movetext[1..];
+                    let comment = self.take_until('}');
+                    let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                    return Some(Token::Comment(comment));
+                },
+                ';' => {
+                    self.movetext = &self.movetext[1..];
+                    let comment = self.take_until('\n');
+                    return Some(Token::Comment(comment.trim().to_string()));
+                },
+                '%' => { // Escape mechanism
+                    self.take_until('\n');
+                },
+                '(' => {
+                    self.movetext = &self.movetext[1..];
+                    return Some(Token::VariationStart);
+                },
+                ')' => {
+                    self.movetext = &self.movetext[1..];
+                    return Some(Token::VariationEnd);
+                },
+                '}' => { // Unbalanced comment
+                    self.movetext = &self.movetext[1..];
+                },
+                _ => {
+                    let word = self.take_word();
+                    if let Some(token) = self.tokenize_word(word) {
+                        return Some(token);
                     }
                 }
             }
         }
-        pgn
     }
 }
 
@@ -146,6 +401,8 @@ impl ToPGN for Game {
             } else {
                 "1/2-1/2"
             }
+        } else if self.positions.is_draw() {
+            "1/2-1/2"
         } else {
             "*"
         };
@@ -192,35 +449,28 @@ impl ToPGN for Game {
 /// Portable Game Notation import
 pub trait LoadPGN {
     /// Load PGN
-    fn load_pgn(&mut self, pgn: PGN);
+    fn load_pgn(&mut self, pgn: PGN) -> Result<(), Box<dyn Error>>;
 }
 
 impl LoadPGN for Game {
-    fn load_pgn(&mut self, pgn: PGN) {
+    fn load_pgn(&mut self, pgn: PGN) -> Result<(), Box<dyn Error>> {
         self.clear();
-        let starting_fen = pgn.headers.get("FEN").map_or(DEFAULT_FEN, String::as_str);
-        self.load_fen(starting_fen).unwrap();
-        let mut comment_level = 0;
-        let mut variation_level = 0;
-        for line in pgn.body.lines() {
-            for word in line.split(" ") {
-                if word.starts_with(";") {
-                    break;
-                }
-                comment_level += word.matches("{").count();
-                comment_level -= word.matches("}").count();
-                variation_level += word.matches("(").count();
-                variation_level -= word.matches(")").count();
-                if comment_level > 0 || variation_level > 0 {
-                    continue;
-                }
-
-                if let Some(m) = self.parse_move(word) {
+        let starting_fen = pgn.header("FEN").unwrap_or_else(|| DEFAULT_FEN.to_string());
+        self.load_fen(&starting_fen)?;
+        for san in pgn.moves() {
+            match self.move_from_san(&san) {
+                Some(m) => {
                     self.make_move(m);
                     self.history.push(m);
+                },
+                None => {
+                    let fm = self.positions.fullmoves();
+                    let dots = if self.side() == WHITE { "." } else { "..." };
+                    return Err(format!("invalid move '{}{} {}'", fm, dots, san).into());
                 }
             }
         }
+        Ok(())
     }
 }
 
@@ -261,6 +511,35 @@ mod tests {
         let pgn = PGN::from(content.clone());
         assert_eq!(pgn.to_string(), content);
         assert_eq!(pgn.result(), "0-1".to_string());
+
+        let content = fs::read_to_string("tests/zukertort_vs_steinitz_1886.pgn").unwrap();
+        let pgn = PGN::from(content.clone());
+        let content = pgn.to_string();
+        let headers: Vec<&str> = content.lines().take(7).collect();
+        assert_eq!(headers, vec![
+            "[Event \"Steinitz - Zukertort World Championship Match\"]",
+            "[Site \"New Orleans, LA USA\"]",
+            "[Date \"1886.03.24\"]",
+            "[Round \"19\"]",
+            "[White \"Johannes Zukertort\"]",
+            "[Black \"Wilhelm Steinitz\"]",
+            "[Result \"0-1\"]",
+        ]);
+    }
+
+    #[test]
+    fn test_game_to_pgn_draw() {
+        // Fischer vs Petrosian (1971)
+        let fen = "8/pp3p1k/2p2q1p/3r1P1Q/5R2/7P/P1P2P2/7K w - - 1 30";
+        let mut game = Game::from_fen(fen).unwrap();
+        for s in &["h5e2", "f6e5", "e2h5", "e5f6", "h5e2"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+            game.history.push(m);
+        }
+        let pgn = game.to_pgn();
+        assert_eq!(pgn.result(), "1/2-1/2");
+        assert!(pgn.to_string().ends_with("30. Qe2 Qe5 31. Qh5 Qf6 32. Qe2 1/2-1/2\n"));
     }
 
     #[test]
@@ -269,16 +548,66 @@ mod tests {
 
         let s1 = fs::read_to_string("tests/fool.pgn").unwrap();
         let pgn = PGN::from(s1.clone());
-        game.load_pgn(pgn);
+        game.load_pgn(pgn).unwrap();
         assert_eq!(game.history.len(), 4);
 
         let s2 = fs::read_to_string("tests/zukertort_vs_steinitz_1886.pgn").unwrap();
         let pgn = PGN::from(s2.clone());
-        game.load_pgn(pgn);
+        game.load_pgn(pgn).unwrap();
         assert_eq!(game.history.len(), 58);
 
         let pgn = PGN::from(format!("{}\n{}", s1, s2));
-        game.load_pgn(pgn);
+        game.load_pgn(pgn).unwrap();
         assert_eq!(game.history.len(), 58);
+
+        let pgn = PGN::from("1.e4 e5 2.Nf3 Nc6 3.Bc4 Nf6 4.0-0 {castle} Nxe4 *".to_string());
+        game.load_pgn(pgn).unwrap();
+        assert_eq!(game.history.len(), 8);
+
+        let pgn = PGN::from("1. e4 e5 2. Ke3 *".to_string());
+        let res = game.load_pgn(pgn);
+        assert_eq!(res.unwrap_err().to_string(), "invalid move '2. Ke3'");
+    }
+
+    #[test]
+    fn test_pgn_reader() {
+        let s1 = fs::read_to_string("tests/fool.pgn").unwrap();
+        let s2 = fs::read_to_string("tests/zukertort_vs_steinitz_1886.pgn").unwrap();
+        let content = format!("{}\n{}\n%comment\n[Event \"?\"]\n1. d4 d5 *\n", s1, s2);
+
+        let pgns: Vec<PGN> = PgnReader::new(content.as_bytes()).map(Result::unwrap).collect();
+        assert_eq!(pgns.len(), 3);
+        assert_eq!(pgns[0].to_string(), s1);
+        assert_eq!(pgns[1].white(), "Johannes Zukertort");
+        assert_eq!(pgns[1].moves().len(), 58);
+        assert_eq!(pgns[2].moves(), vec!["d4", "d5"]);
+
+        assert_eq!(PgnReader::new("".as_bytes()).count(), 0);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("1.e4 {best by test} e5!? (1... c5 $14) 2. Nf3 Nc6?! ; end\n1/2-1/2");
+        assert_eq!(tokens, vec![
+            Token::MoveNumber(1),
+            Token::Move("e4".into()),
+            Token::Comment("best by test".into()),
+            Token::Move("e5".into()),
+            Token::Nag(5),
+            Token::VariationStart,
+            Token::MoveNumber(1),
+            Token::Move("c5".into()),
+            Token::Nag(14),
+            Token::VariationEnd,
+            Token::MoveNumber(2),
+            Token::Move("Nf3".into()),
+            Token::Move("Nc6".into()),
+            Token::Nag(6),
+            Token::Comment("end".into()),
+            Token::Result("1/2-1/2".into()),
+        ]);
+
+        let pgn = PGN::from("1. d4 (1. e4 e5 (1... c5)) 1... d5 2. c4+ *".to_string());
+        assert_eq!(pgn.moves(), vec!["d4", "d5", "c4"]);
     }
 }
@@ -9,7 +9,11 @@ use square::SquareExt;
 
 pub const BEST_MOVE_SCORE:    u8 = 255;
 pub const KILLER_MOVE_SCORE:  u8 = 254;
-pub const GOOD_CAPTURE_SCORE: u8 = 64;
+pub const GOOD_CAPTURE_SCORE: u8 = 128;
+pub const EVASION_CAPTURE_SCORE: u8 = 128;
+pub const EVASION_BLOCK_SCORE:   u8 = 64;
+pub const EVASION_KING_SCORE:    u8 = 1;
+pub const ESCAPE_MOVE_SCORE:  u8 = 32;
 pub const QUIET_MOVE_SCORE:   u8 = 0;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -27,6 +31,12 @@ impl PieceMove {
         PieceMove(0)
     }
 
+    /// Create a drop of a piece of the given kind from the hand, with the
+    /// kind stored in place of the origin square
+    pub fn new_drop(kind: Piece, to: Square) -> PieceMove {
+        PieceMove::new(kind as Square, to, DROP)
+    }
+
     pub fn from(self) -> Square {
         (self.0 >> 10) as Square
     }
@@ -52,6 +62,14 @@ impl PieceMove {
         self.kind() == EN_PASSANT
     }
 
+    pub fn is_drop(self) -> bool {
+        self.kind() == DROP
+    }
+
+    pub fn drop_kind(self) -> Piece {
+        self.from() as Piece
+    }
+
     pub fn is_castle(self) -> bool {
         self.kind() == KING_CASTLE || self.kind() == QUEEN_CASTLE
     }
@@ -76,6 +94,12 @@ impl PieceMove {
 impl fmt::Display for PieceMove {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut out = String::new();
+        if self.is_drop() {
+            out.push((WHITE | self.drop_kind()).to_char());
+            out.push('@');
+            out.push_str(self.to().to_coord().as_str());
+            return write!(f, "{}", out);
+        }
         out.push_str(self.from().to_coord().as_str());
         out.push_str(self.to().to_coord().as_str());
         if self.is_promotion() {
@@ -118,6 +142,17 @@ mod tests {
         assert_eq!(PieceMove::new(E7, D8, QUEEN_PROMOTION_CAPTURE).is_promotion(), true);
     }
 
+    #[test]
+    fn test_move_drop() {
+        let m = PieceMove::new_drop(KNIGHT, F3);
+        assert!(m.is_drop());
+        assert!(!m.is_capture());
+        assert!(!m.is_promotion());
+        assert_eq!(m.drop_kind(), KNIGHT);
+        assert_eq!(m.to(), F3);
+        assert_eq!(m.to_lan(), "N@f3");
+    }
+
     #[test]
     fn test_move_promotion_kind() {
         assert_eq!(PieceMove::new(E7, E8, QUEEN_PROMOTION).promotion_kind(), QUEEN);
@@ -1,37 +1,57 @@
+use std::cmp;
+use std::sync::LazyLock;
+
 use color::*;
 use piece::*;
 use square::*;
 use common::*;
 use attack::Attack;
 use attack::piece_attacks;
-use bitboard::BitboardExt;
+use magic::{bishop_attacks, rook_attacks};
+use bitboard::{Bitboard, BitboardExt, BitboardIterator};
 use game::Game;
 use piece_move::*;
 use piece_move_list::PieceMoveListStage;
 use piece::PieceAttr;
 use square::SquareExt;
 use eval::Eval;
-
-lazy_static! {
-    // PxP =  7, PxN = 15, PxB = 23, PxR = 31, PxQ = 39, PxK = 47
-    // NxP =  6, NxN = 14, NxB = 22, NxR = 30, NxQ = 38, NxK = 46
-    // BxP =  5, BxN = 13, BxB = 21, BxR = 29, BxQ = 37, BxK = 45
-    // RxP =  4, RxN = 12, RxB = 20, RxR = 28, RxQ = 36, RxK = 44
-    // QxP =  3, QxN = 11, QxB = 19, QxR = 27, QxQ = 35, QxK = 43
-    // KxP =  2, KxN = 10, KxB = 18, KxR = 26, KxQ = 34, KxK = 42
-    pub static ref MVV_LVA_SCORES: [[u8; 13]; 13] = {
-        let pieces = vec![EMPTY, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING];
-        let mut mvv_lva_scores = [[0; 13]; 13];
-        for i in 1..7 {
-            for j in 1..7 {
-                let a = pieces[i as usize];
-                let v = pieces[j as usize];
-                mvv_lva_scores[a as usize][v as usize] = (8 * j) - i;
-            }
-        }
-        mvv_lva_scores
-    };
-}
+use variant::CHECKS_LIMIT;
+
+// PxP =  7, PxN = 15, PxB = 23, PxR = 31, PxQ = 39, PxK = 47
+// NxP =  6, NxN = 14, NxB = 22, NxR = 30, NxQ = 38, NxK = 46
+// BxP =  5, BxN = 13, BxB = 21, BxR = 29, BxQ = 37, BxK = 45
+// RxP =  4, RxN = 12, RxB = 20, RxR = 28, RxQ = 36, RxK = 44
+// QxP =  3, QxN = 11, QxB = 19, QxR = 27, QxQ = 35, QxK = 43
+// KxP =  2, KxN = 10, KxB = 18, KxR = 26, KxQ = 34, KxK = 42
+pub static MVV_LVA_SCORES: LazyLock<[[u8; 13]; 13]> = LazyLock::new(|| {
+    let pieces = vec![EMPTY, PAWN, KNIGHT, BISHOP, ROOK, QUEEN, KING];
+    let mut mvv_lva_scores = [[0; 13]; 13];
+    for i in 1..7 {
+        for j in 1..7 {
+            let a = pieces[i as usize];
+            let v = pieces[j as usize];
+            mvv_lva_scores[a as usize][v as usize] = (8 * j) - i;
+        }
+    }
+    mvv_lva_scores
+});
+
+// Capture history from which a capture is ordered before the other captures
+// with the same MVV/LVA score
+const CAPTURE_HISTORY_MIN: u32 = 64;
+
+// Squares of the first rank between two files included
+static RANK_SPANS: LazyLock<[[Bitboard; 8]; 8]> = LazyLock::new(|| {
+    let mut spans = [[0; 8]; 8];
+    for a in 0..8 {
+        for b in 0..8 {
+            let (a, b) = (cmp::min(a, b), cmp::max(a, b));
+            spans[a][b] = (a..(b + 1)).fold(0, |bb, sq| bb | 1 << sq);
+            spans[b][a] = spans[a][b];
+        }
+    }
+    spans
+});
 
 /// PieceMoveList generator
 pub trait PieceMoveGenerator {
@@ -44,22 +64,51 @@ pub trait PieceMoveGenerator {
     /// Get the next capture from the moves list (for quiescence search)
     fn next_capture(&mut self) -> Option<PieceMove>;
 
+    /// Get the next capture of a piece worth at least the given kind of
+    /// piece, like `next_capture` but without generating the others (for
+    /// pruning captures of low value pieces)
+    fn next_capture_of(&mut self, victim: Piece) -> Option<PieceMove>;
+
+    /// Get the next capture, then the next quiet move giving a check, from
+    /// the moves list (for the first ply of quiescence search)
+    fn next_capture_or_check(&mut self) -> Option<PieceMove>;
+
     /// Get the next move from the moves list (for regular search)
     fn next_move(&mut self) -> Option<PieceMove>;
 
+    /// Get the piece moved or dropped by the given move of the side to move
+    fn moved_piece(&self, m: PieceMove) -> Piece;
+
+    /// Get the kind of piece captured by the given move
+    fn captured_kind(&self, m: PieceMove) -> Piece;
+
     /// Make the given move and update the game state
     fn make_move(&mut self, m: PieceMove);
 
     /// Undo the given move and update the game state
     fn undo_move(&mut self, m: PieceMove);
+
+    /// Get the legal moves from the current game position, filtered with
+    /// the pins and the checks on the king instead of playing each move
+    fn generate_legal_moves(&mut self) -> Vec<PieceMove>;
+
+    /// Count the legal moves from the current game position, like
+    /// `generate_legal_moves` but without collecting them
+    fn count_legal_moves(&mut self) -> u64;
 }
 
 trait PieceMoveGeneratorExt {
+    fn sort_evasions(&mut self, checkers: Bitboard);
+    fn sort_quiet_moves(&mut self);
     fn is_legal_move(&mut self, m: PieceMove) -> bool;
     fn mvv_lva(&self, m: PieceMove) -> u8;
-    fn can_king_castle(&mut self, side: Color) -> bool;
-    fn can_queen_castle(&mut self, side: Color) -> bool;
     fn can_castle_on(&mut self, side: Color, wing: Piece) -> bool;
+    fn castling_king_path(&self, side: Color, wing: Piece) -> Option<Bitboard>;
+    fn can_capture(&self, side: Color) -> bool;
+    fn king_blockers(&self, king_side: Color, side: Color) -> Bitboard;
+    fn remove_illegal_en_passant(&mut self);
+    fn is_leaving_king_in_check(&mut self, m: PieceMove) -> bool;
+    fn legal_moves_with<F>(&mut self, f: F) where F: FnMut(PieceMove);
 }
 
 impl PieceMoveGenerator for Game {
@@ -67,6 +116,8 @@ impl PieceMoveGenerator for Game {
         match self.moves.stage() {
             PieceMoveListStage::KillerPieceMove => {
                 if !self.moves.skip_killers {
+                    // The killer moves are quiet and thus filtered by
+                    // `is_legal_move` when captures are forced
                     for i in 0..MAX_KILLERS {
                         let m = self.moves.get_killer_move(i);
                         if self.is_legal_move(m) {
@@ -80,23 +131,152 @@ impl PieceMoveGenerator for Game {
                 let side = position.side;
                 let ep = position.en_passant;
 
+                // Quiet moves are not allowed when a capture is possible
+                let is_quiet_stage = self.moves.stage() == PieceMoveListStage::QuietPieceMove;
+                if is_quiet_stage && self.variant.has_forced_captures() && self.can_capture(side) {
+                    return;
+                }
+
                 self.moves.add_pawns_moves(&self.bitboards, side, ep);
                 self.moves.add_knights_moves(&self.bitboards, side);
-                self.moves.add_king_moves(&self.bitboards, side);
+                // Kings cannot capture in Atomic
+                if !self.variant.has_explosions() || self.moves.stage() == PieceMoveListStage::QuietPieceMove {
+                    self.moves.add_king_moves(&self.bitboards, side);
+                }
                 self.moves.add_bishops_moves(&self.bitboards, side);
                 self.moves.add_rooks_moves(&self.bitboards, side);
                 self.moves.add_queens_moves(&self.bitboards, side);
+                if self.variant.has_drops() && self.moves.stage() == PieceMoveListStage::QuietPieceMove {
+                    self.moves.add_drops(&self.bitboards, &position.hands, side);
+                }
 
-                if self.moves.stage() == PieceMoveListStage::Capture {
+                if ep != OUT && !self.variant.has_explosions() {
+                    self.remove_illegal_en_passant();
+                }
+
+                let kings = *self.bitboard(side | KING);
+                let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+                let checkers = if kings == 0 || !self.variant.has_royal_king() {
+                    0 // Some test positions have no king
+                } else {
+                    self.attacks_to(kings.scan() as Square, occupied) & self.bitboard(side ^ 1)
+                };
+
+                if checkers != 0 {
+                    if !self.moves.skip_ordering {
+                        self.sort_evasions(checkers);
+                    }
+                } else if self.moves.stage() == PieceMoveListStage::Capture {
                     if !self.moves.skip_ordering {
                         self.sort_moves();
                     }
                 } else { // Castlings
-                    if self.can_king_castle(side) {
-                        self.moves.add_king_castle(side);
+                    // The attacks of the opponent are computed at most once
+                    // for both wings, and only if a castling is possible
+                    let mut attacks = None;
+                    for &wing in &[KING, QUEEN] {
+                        if let Some(path) = self.castling_king_path(side, wing) {
+                            let attacks = *attacks.get_or_insert_with(|| {
+                                self.attacks_by(side ^ 1, occupied)
+                            });
+                            if path & attacks == 0 {
+                                self.moves.add_castle(side, kings.scan() as Square, wing);
+                            }
+                        }
+                    }
+                    if !self.moves.skip_ordering {
+                        self.sort_quiet_moves();
+                    }
+                }
+            },
+            PieceMoveListStage::Evasion => {
+                let &position = self.positions.top();
+                let side = position.side;
+                let ep = position.en_passant;
+                let king = self.bitboard(side | KING).scan() as Square;
+                let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+                let checkers = self.attacks_to(king, occupied) & self.bitboard(side ^ 1);
+
+                // Only the king can get out of a double check, otherwise the
+                // moves not capturing or blocking the checker are removed by
+                // `sort_evasions`. The moves are generated with the stages
+                // of the captures and the quiet moves, without skipping the
+                // killer moves that were not tried before.
+                let skip_killers = self.moves.skip_killers;
+                self.moves.skip_killers = true;
+                for &stage in &[PieceMoveListStage::Capture, PieceMoveListStage::QuietPieceMove] {
+                    self.moves.set_stage(stage);
+                    self.moves.add_king_moves(&self.bitboards, side);
+                    if checkers.count() > 1 {
+                        continue;
+                    }
+                    self.moves.add_pawns_moves(&self.bitboards, side, ep);
+                    self.moves.add_knights_moves(&self.bitboards, side);
+                    self.moves.add_bishops_moves(&self.bitboards, side);
+                    self.moves.add_rooks_moves(&self.bitboards, side);
+                    self.moves.add_queens_moves(&self.bitboards, side);
+                    if self.variant.has_drops() && stage == PieceMoveListStage::QuietPieceMove {
+                        self.moves.add_drops(&self.bitboards, &position.hands, side);
                     }
-                    if self.can_queen_castle(side) {
-                        self.moves.add_queen_castle(side);
+                }
+                self.moves.skip_killers = skip_killers;
+                self.moves.set_stage(PieceMoveListStage::Evasion);
+
+                if ep != OUT {
+                    self.remove_illegal_en_passant();
+                }
+                self.sort_evasions(checkers);
+            },
+            PieceMoveListStage::QuietCheck => {
+                let side = self.side();
+                let kings = self.bitboards[(side ^ 1 | KING) as usize];
+                if kings.count() != 1 || !self.variant.has_royal_king() {
+                    return;
+                }
+                let king = kings.scan() as Square;
+                let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
+
+                // Squares from where each kind of piece gives a direct check,
+                // found with the attacks of the same piece from the king
+                let mut check_squares = [0; 14];
+                for &p in &[PAWN, KNIGHT, BISHOP, ROOK, QUEEN] {
+                    check_squares[p as usize] = piece_attacks(side ^ 1 | p, king, occupied);
+                }
+                let discoverers = self.king_blockers(side ^ 1, side);
+
+                // The quiet moves are generated without the drops and the
+                // castlings, and without skipping the killer moves, then
+                // the moves not giving a check are removed
+                let skip_killers = self.moves.skip_killers;
+                self.moves.skip_killers = true;
+                self.moves.set_stage(PieceMoveListStage::QuietPieceMove);
+                self.moves.add_pawns_moves(&self.bitboards, side, OUT);
+                self.moves.add_knights_moves(&self.bitboards, side);
+                if discoverers != 0 {
+                    self.moves.add_king_moves(&self.bitboards, side);
+                }
+                self.moves.add_bishops_moves(&self.bitboards, side);
+                self.moves.add_rooks_moves(&self.bitboards, side);
+                self.moves.add_queens_moves(&self.bitboards, side);
+                self.moves.skip_killers = skip_killers;
+                self.moves.set_stage(PieceMoveListStage::QuietCheck);
+
+                let mut i = self.moves.index();
+                while i < self.moves.len() {
+                    let m = self.moves[i].item;
+                    let (from, to) = (m.from(), m.to());
+                    let is_direct_check = if m.is_promotion() {
+                        // The pawn could be hiding the king from its square
+                        let p = side | m.promotion_kind();
+                        piece_attacks(p, to, occupied & !(1 << from)).get(king)
+                    } else {
+                        check_squares[self.board[from as usize].kind() as usize].get(to)
+                    };
+                    let is_discovered_check = discoverers.get(from) && !line_through(king, from).get(to);
+                    if is_direct_check || is_discovered_check {
+                        i += 1;
+                    } else {
+                        self.moves.remove(i);
                     }
                 }
             },
@@ -109,9 +289,16 @@ impl PieceMoveGenerator for Game {
         let a = if self.moves[0].score == BEST_MOVE_SCORE { 1 } else { 0 };
         let b = self.moves.len();
         for i in a..b {
-            if self.moves[i].item.is_capture() {
-                self.moves[i].score = self.mvv_lva(self.moves[i].item);
-                if self.see(self.moves[i].item) >= 0 {
+            let m = self.moves[i].item;
+            if m.is_capture() {
+                // Captures with the same MVV/LVA score are ordered by their
+                // capture history
+                self.moves[i].score = 2 * self.mvv_lva(m);
+                let history = self.moves.get_capture_history(self.moved_piece(m), m.to(), self.captured_kind(m));
+                if history >= CAPTURE_HISTORY_MIN {
+                    self.moves[i].score += 1;
+                }
+                if self.see(m) >= 0 {
                     self.moves[i].score += GOOD_CAPTURE_SCORE;
                 }
                 debug_assert!(self.moves[i].score < BEST_MOVE_SCORE);
@@ -130,6 +317,17 @@ impl PieceMoveGenerator for Game {
         // Staged moves generation
         while next_move.is_none() && !self.moves.is_last_stage() {
             self.moves.next_stage();
+
+            // Only the moves getting out of check are generated when in
+            // check, except in Atomic where exploding the checker is enough
+            if self.moves.stage() == PieceMoveListStage::Capture {
+                let side = self.side();
+                let has_king = self.bitboard(side | KING).count() == 1;
+                if has_king && !self.variant.has_explosions() && self.is_check(side) {
+                    self.moves.set_stage(PieceMoveListStage::Evasion);
+                }
+            }
+
             self.generate_moves();
             next_move = self.moves.next();
         }
@@ -157,16 +355,87 @@ impl PieceMoveGenerator for Game {
         self.moves.next()
     }
 
+    fn next_capture_of(&mut self, victim: Piece) -> Option<PieceMove> {
+        if self.moves.stage() == PieceMoveListStage::BestPieceMove {
+            let side = self.side();
+            let mut victims = 0;
+            for &kind in &PIECES {
+                if piece_rank(kind) >= piece_rank(victim) {
+                    victims |= self.bitboards[(side ^ 1 | kind) as usize];
+                }
+            }
+            self.moves.set_victims(victims);
+        }
+
+        self.next_capture()
+    }
+
+    fn next_capture_or_check(&mut self) -> Option<PieceMove> {
+        if self.moves.stage() != PieceMoveListStage::QuietCheck {
+            if let Some(m) = self.next_capture() {
+                return Some(m);
+            }
+
+            // Skip bad captures before generating the checks
+            self.moves.truncate();
+            self.moves.set_stage(PieceMoveListStage::QuietCheck);
+            self.generate_moves();
+        }
+
+        self.moves.next()
+    }
+
+    fn moved_piece(&self, m: PieceMove) -> Piece {
+        if m.is_drop() {
+            self.side() | m.drop_kind()
+        } else {
+            self.board[m.from() as usize]
+        }
+    }
+
+    fn captured_kind(&self, m: PieceMove) -> Piece {
+        if m.is_en_passant() {
+            PAWN
+        } else {
+            self.board[m.to() as usize].kind()
+        }
+    }
+
     fn make_move(&mut self, m: PieceMove) {
+        if m.is_null() {
+            self.moves.set_played(EMPTY, OUT);
+        } else {
+            self.moves.set_played(self.moved_piece(m), m.to());
+        }
+
         let mut position = *self.positions.top();
         let side = position.side;
+        position.explosions_count = 0;
 
         let piece = self.board[m.from() as usize];
-        let capture = self.board[m.to() as usize]; // TODO: En passant
+        let capture = if m.is_castle() {
+            EMPTY // The king can go to the square of its rook in Chess960
+        } else {
+            self.board[m.to() as usize] // TODO: En passant
+        };
 
         position.halfmoves_count += 1;
 
-        if !m.is_null() {
+        if m.is_drop() {
+            let p = side | m.drop_kind();
+            debug_assert!(position.hand(p) > 0);
+            position.hands[p as usize] -= 1;
+            position.hash ^= self.zobrist.hand(p, position.hand(p));
+            position.capture = EMPTY;
+
+            self.board[m.to() as usize] = p;
+            self.bitboards[p as usize].toggle(m.to());
+            self.bitboards[side as usize].toggle(m.to());
+            position.hash ^= self.zobrist.pieces[p as usize][m.to() as usize];
+            if p.kind() == PAWN {
+                position.pawn_hash ^= self.zobrist.pieces[p as usize][m.to() as usize];
+            }
+        } else if !m.is_null() {
             self.bitboards[side as usize].toggle(m.from());
             self.bitboards[side as usize].toggle(m.to());
             self.bitboards[piece as usize].toggle(m.from());
@@ -177,16 +446,17 @@ impl PieceMoveGenerator for Game {
 
             if piece.kind() == PAWN {
                 position.halfmoves_count = 0;
+                position.pawn_hash ^= self.zobrist.pieces[piece as usize][m.from() as usize];
             }
 
-            if piece.kind() == KING || (piece.kind() == ROOK && m.from() == H1.flip(side)) {
+            if piece.kind() == KING || (piece.kind() == ROOK && m.from() == position.castling_rook(side, KING)) {
                 if position.castling_right(side, KING) {
                     position.reset_castling_right(side, KING);
                     position.hash ^= self.zobrist.castling_right(side, KING);
                 }
             }
 
-            if piece.kind() == KING || (piece.kind() == ROOK && m.from() == A1.flip(side)) {
+            if piece.kind() == KING || (piece.kind() == ROOK && m.from() == position.castling_rook(side, QUEEN)) {
                 if position.castling_right(side, QUEEN) {
                     position.reset_castling_right(side, QUEEN);
                     position.hash ^= self.zobrist.castling_right(side, QUEEN);
@@ -197,6 +467,9 @@ impl PieceMoveGenerator for Game {
             self.board[m.to() as usize] = p;
             self.bitboards[p as usize].toggle(m.to());
             position.hash ^= self.zobrist.pieces[p as usize][m.to() as usize];
+            if p.kind() == PAWN {
+                position.pawn_hash ^= self.zobrist.pieces[p as usize][m.to() as usize];
+            }
 
             if m.is_en_passant() {
                 let sq = (((m.to().flip(side) as Shift) + DOWN) as Square).flip(side);
@@ -205,21 +478,25 @@ impl PieceMoveGenerator for Game {
                 self.bitboards[pawn as usize].toggle(sq);
                 self.bitboards[(side ^ 1) as usize].toggle(sq);
                 position.hash ^= self.zobrist.pieces[pawn as usize][sq as usize];
+                position.pawn_hash ^= self.zobrist.pieces[pawn as usize][sq as usize];
             } else if capture != EMPTY {
                 position.halfmoves_count = 0;
                 self.bitboards[capture as usize].toggle(m.to());
                 self.bitboards[(side ^ 1) as usize].toggle(m.to());
                 position.hash ^= self.zobrist.pieces[capture as usize][m.to() as usize];
+                if capture.kind() == PAWN {
+                    position.pawn_hash ^= self.zobrist.pieces[capture as usize][m.to() as usize];
+                }
 
                 // Update opponent's castling rights on rook capture
                 if capture.kind() == ROOK {
-                    if m.to() == H1.flip(side ^ 1) {
-                        if position.castling_right(side, KING) {
+                    if m.to() == position.castling_rook(side ^ 1, KING) {
+                        if position.castling_right(side ^ 1, KING) {
                             position.reset_castling_right(side ^ 1, KING);
                             position.hash ^= self.zobrist.castling_right(side ^ 1, KING);
                         }
-                    } else if m.to() == A1.flip(side ^ 1) {
-                        if position.castling_right(side, QUEEN) {
+                    } else if m.to() == position.castling_rook(side ^ 1, QUEEN) {
+                        if position.castling_right(side ^ 1, QUEEN) {
                             position.reset_castling_right(side ^ 1, QUEEN);
                             position.hash ^= self.zobrist.castling_right(side ^ 1, QUEEN);
                         }
@@ -228,14 +505,13 @@ impl PieceMoveGenerator for Game {
             } else if m.is_castle() {
                 let rook = side | ROOK;
 
-                let (rook_from, rook_to) = if m.castle_kind() == KING {
-                    (H1.flip(side), F1.flip(side))
-                } else {
-                    (A1.flip(side), D1.flip(side))
-                };
+                let rook_from = position.castling_rook(side, m.castle_kind());
+                let rook_to = if m.castle_kind() == KING { F1 } else { D1 }.flip(side);
 
+                // The king and the rook can swap their squares in Chess960
                 self.board[rook_from as usize] = EMPTY;
                 self.board[rook_to as usize] = rook;
+                self.board[m.to() as usize] = piece;
                 self.bitboards[rook as usize].toggle(rook_from);
                 self.bitboards[rook as usize].toggle(rook_to);
                 self.bitboards[side as usize].toggle(rook_from);
@@ -243,6 +519,66 @@ impl PieceMoveGenerator for Game {
                 position.hash ^= self.zobrist.pieces[rook as usize][rook_from as usize];
                 position.hash ^= self.zobrist.pieces[rook as usize][rook_to as usize];
             }
+
+            if self.variant.has_drops() {
+                // The captured piece goes into the hand, as a pawn if it
+                // was promoted
+                let captured = if m.is_en_passant() { (side ^ 1) | PAWN } else { capture };
+                if captured != EMPTY {
+                    let kind = if position.promoted.get(m.to()) { PAWN } else { captured.kind() };
+                    let p = side | kind;
+                    position.hash ^= self.zobrist.hand(p, position.hand(p));
+                    position.hands[p as usize] += 1;
+                }
+
+                let is_promoted = m.is_promotion() || position.promoted.get(m.from());
+                position.promoted.reset(m.from());
+                if is_promoted {
+                    position.promoted.set(m.to());
+                } else {
+                    position.promoted.reset(m.to());
+                }
+            }
+
+            if self.variant.has_explosions() && (capture != EMPTY || m.is_en_passant()) {
+                // The capturing piece explodes with the pieces around except
+                // the pawns
+                let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
+                let pawns = self.bitboards[WHITE_PAWN as usize] | self.bitboards[BLACK_PAWN as usize];
+                let around = PIECE_MASKS[KING as usize][m.to() as usize] & occupied & !pawns;
+                let mut squares = around | (1 << m.to());
+                while let Some(sq) = squares.next() {
+                    let p = self.board[sq as usize];
+                    let c = p.color();
+                    self.board[sq as usize] = EMPTY;
+                    self.bitboards[p as usize].toggle(sq);
+                    self.bitboards[c as usize].toggle(sq);
+                    position.hash ^= self.zobrist.pieces[p as usize][sq as usize];
+                    if p.kind() == PAWN {
+                        position.pawn_hash ^= self.zobrist.pieces[p as usize][sq as usize];
+                    }
+
+                    for &wing in &[KING, QUEEN] {
+                        let is_castling_piece = p.kind() == KING ||
+                            (p.kind() == ROOK && sq == position.castling_rook(c, wing));
+                        if is_castling_piece && position.castling_right(c, wing) {
+                            position.reset_castling_right(c, wing);
+                            position.hash ^= self.zobrist.castling_right(c, wing);
+                        }
+                    }
+
+                    self.explosions.push((sq, p));
+                    position.explosions_count += 1;
+                }
+            }
+        }
+
+        if self.variant.has_checks_limit() && !m.is_null() {
+            let n = position.checks(side);
+            if n < CHECKS_LIMIT && self.is_check(side ^ 1) {
+                position.hash ^= self.zobrist.check(side, n);
+                position.add_check(side);
+            }
         }
 
         if position.en_passant != OUT {
@@ -264,9 +600,32 @@ impl PieceMoveGenerator for Game {
 
         self.positions.push(position);
         self.moves.inc();
+
+        debug_assert_eq!(self.hash(), self.compute_hash(), "incremental hash after {}", m.to_lan());
+        debug_assert_eq!(self.positions.top().pawn_hash, self.compute_pawn_hash(), "incremental pawn hash after {}", m.to_lan());
+    }
+
+    fn generate_legal_moves(&mut self) -> Vec<PieceMove> {
+        let mut moves = Vec::new();
+        self.legal_moves_with(|m| moves.push(m));
+        moves
+    }
+
+    fn count_legal_moves(&mut self) -> u64 {
+        let mut n = 0;
+        self.legal_moves_with(|_| n += 1);
+        n
     }
 
     fn undo_move(&mut self, m: PieceMove) {
+        // Put back the pieces exploded in Atomic
+        for _ in 0..self.positions.top().explosions_count {
+            let (sq, p) = self.explosions.pop().unwrap();
+            self.board[sq as usize] = p;
+            self.bitboards[p as usize].toggle(sq);
+            self.bitboards[p.color() as usize].toggle(sq);
+        }
+
         let piece = self.board[m.to() as usize];
         let capture = self.positions.top().capture;
 
@@ -280,6 +639,13 @@ impl PieceMoveGenerator for Game {
         let &position = self.positions.top();
         let side = position.side;
 
+        if m.is_drop() {
+            self.board[m.to() as usize] = EMPTY;
+            self.bitboards[piece as usize].toggle(m.to());
+            self.bitboards[side as usize].toggle(m.to());
+            return;
+        }
+
         let p = if m.is_promotion() { side | PAWN } else { piece };
         self.board[m.from() as usize] = p;
         self.bitboards[p as usize].toggle(m.from());
@@ -301,14 +667,13 @@ impl PieceMoveGenerator for Game {
         } else if m.is_castle() {
             let rook = side | ROOK;
 
-            let (rook_from, rook_to) = if m.castle_kind() == KING {
-                (H1.flip(side), F1.flip(side))
-            } else {
-                (A1.flip(side), D1.flip(side))
-            };
+            let rook_from = position.castling_rook(side, m.castle_kind());
+            let rook_to = if m.castle_kind() == KING { F1 } else { D1 }.flip(side);
 
-            self.board[rook_from as usize] = rook;
+            // The king and the rook can swap their squares in Chess960
             self.board[rook_to as usize] = EMPTY;
+            self.board[rook_from as usize] = rook;
+            self.board[m.from() as usize] = piece;
             self.bitboards[side as usize].toggle(rook_from);
             self.bitboards[side as usize].toggle(rook_to);
             self.bitboards[rook as usize].toggle(rook_from);
@@ -317,41 +682,329 @@ impl PieceMoveGenerator for Game {
     }
 }
 
+// Get the rank of the given kind of piece by value, with the same rank for
+// the knight and the bishop
+fn piece_rank(kind: Piece) -> u8 {
+    match kind.kind() {
+        PAWN            => 0,
+        KNIGHT | BISHOP => 1,
+        ROOK            => 2,
+        QUEEN           => 3,
+        KING            => 4,
+        _               => unreachable!()
+    }
+}
+
+// Get the squares of the rank of the given squares between them included
+fn rank_span(a: Square, b: Square) -> Bitboard {
+    debug_assert_eq!(a.rank(), b.rank());
+    RANK_SPANS[a.file() as usize][b.file() as usize] << (8 * a.rank())
+}
+
+// Get the squares between the given squares excluded, or nothing if they
+// are not on the same rank, file, or diagonal
+fn squares_between(a: Square, b: Square) -> Bitboard {
+    let (bb_a, bb_b) = (1 << a, 1 << b);
+    if rook_attacks(a, bb_b).get(b) {
+        rook_attacks(a, bb_b) & rook_attacks(b, bb_a)
+    } else if bishop_attacks(a, bb_b).get(b) {
+        bishop_attacks(a, bb_b) & bishop_attacks(b, bb_a)
+    } else {
+        0
+    }
+}
+
+// Get the squares of the line going through the given aligned squares,
+// without them
+fn line_through(a: Square, b: Square) -> Bitboard {
+    if rook_attacks(a, 0).get(b) {
+        rook_attacks(a, 0) & rook_attacks(b, 0)
+    } else {
+        bishop_attacks(a, 0) & bishop_attacks(b, 0)
+    }
+}
+
 impl PieceMoveGeneratorExt for Game {
-    fn can_castle_on(&mut self, side: Color, wing: Piece) -> bool {
-        match wing {
-            QUEEN => self.can_queen_castle(side),
-            KING  => self.can_king_castle(side),
-            _     => unreachable!()
+    // Remove the en passant captures of the current stage exposing the king,
+    // because both pawns leave the rank at once when none of them is pinned
+    fn remove_illegal_en_passant(&mut self) {
+        let mut i = self.moves.index();
+        while i < self.moves.len() {
+            let m = self.moves[i].item;
+            if m.is_en_passant() && !self.is_legal_en_passant(m) {
+                self.moves.remove(i);
+            } else {
+                i += 1;
+            }
         }
     }
 
-    fn can_king_castle(&mut self, side: Color) -> bool {
-        let &position = self.positions.top();
+    // Get the pieces of the given side alone between the king of the given
+    // side and a sliding piece of its opponent: the pinned pieces when both
+    // sides are the same, or the pieces giving a discovered check by moving
+    // out of the line otherwise
+    fn king_blockers(&self, king_side: Color, side: Color) -> Bitboard {
+        let king = self.bitboards[(king_side | KING) as usize].scan() as Square;
+        let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
+        let queens = self.bitboards[(king_side ^ 1 | QUEEN) as usize];
+        let rooks = self.bitboards[(king_side ^ 1 | ROOK) as usize] | queens;
+        let bishops = self.bitboards[(king_side ^ 1 | BISHOP) as usize] | queens;
+
+        let mut king_blockers = 0;
+        let mut snipers = (rook_attacks(king, 0) & rooks) | (bishop_attacks(king, 0) & bishops);
+        while let Some(sniper) = snipers.next() {
+            let blockers = squares_between(king, sniper) & occupied;
+            if blockers.count() == 1 && blockers & self.bitboards[side as usize] != 0 {
+                king_blockers |= blockers;
+            }
+        }
+        king_blockers
+    }
+
+    // Check if the given move leaves the king in check by playing it
+    fn is_leaving_king_in_check(&mut self, m: PieceMove) -> bool {
+        let side = self.side();
+        self.make_move(m);
+        let is_check = self.is_check(side);
+        self.undo_move(m);
+        is_check
+    }
+
+    // Call the given function with each legal move of the current position,
+    // filtered with the pins and the checks on the king
+    fn legal_moves_with<F>(&mut self, mut f: F) where F: FnMut(PieceMove) {
+        let side = self.side();
+        let kings = self.bitboards[(side | KING) as usize];
         let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
-        let mask = CASTLING_MASKS[side as usize][(KING >> 3) as usize];
+        let opponent = self.bitboards[(side ^ 1) as usize];
+
+        // Every move is legal without a royal king, and the explosions of
+        // Atomic are only known after playing the move
+        let has_masks = kings.count() == 1 && self.variant.has_royal_king() && !self.variant.has_explosions();
+
+        let king = kings.scan() as Square;
+        let (checks, pinned) = if has_masks {
+            // Squares where a piece other than the king can go when in check
+            let checkers = self.attacks_to(king, occupied) & opponent;
+            let checks = match checkers.count() {
+                0 => !0,
+                1 => checkers | squares_between(king, checkers.scan() as Square),
+                _ => 0
+            };
+            (checks, self.king_blockers(side, side))
+        } else {
+            (!0, 0)
+        };
+
+        self.moves.clear();
+        while let Some(m) = self.next_move() {
+            let is_legal = if !has_masks {
+                !self.is_leaving_king_in_check(m)
+            } else if m.is_castle() {
+                // The castling rook could be shielding the king in Chess960
+                !self.is_leaving_king_in_check(m)
+            } else if m.is_drop() {
+                checks.get(m.to())
+            } else if m.from() == king {
+                let without_king = occupied & !(1 << king);
+                self.attacks_to(m.to(), without_king) & opponent & !(1 << m.to()) == 0
+            } else if m.is_en_passant() {
+                self.is_legal_en_passant(m)
+            } else {
+                checks.get(m.to()) && (!pinned.get(m.from()) || line_through(king, m.from()).get(m.to()))
+            };
+            if is_legal {
+                f(m);
+            }
+        }
+    }
+
+    // Sort the moves of the current stage when the king is in check: first
+    // the captures of the checker by SEE, then the interpositions by SEE and
+    // history, then the king moves by history. The king moves to squares
+    // still attacked are removed.
+    fn sort_evasions(&mut self, checkers: Bitboard) {
+        let side = self.side();
+        let king = self.bitboard(side | KING).scan() as Square;
+        let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+
+        // Squares between a single sliding checker and the king
+        let checker = checkers.scan() as Square;
+        let blocks = if checkers.count() > 1 {
+            0
+        } else if rook_attacks(king, occupied).get(checker) {
+            rook_attacks(king, occupied) & rook_attacks(checker, occupied)
+        } else if bishop_attacks(king, occupied).get(checker) {
+            bishop_attacks(king, occupied) & bishop_attacks(checker, occupied)
+        } else {
+            0
+        };
+
+        let mut i = self.moves.index();
+        while i < self.moves.len() {
+            let m = self.moves[i].item;
+            let history = self.moves.get_history(m);
+            let score = if !m.is_drop() && m.from() == king {
+                let without_king = occupied & !(1 << king);
+                let is_attacked = self.attacks_to(m.to(), without_king) & self.bitboard(side ^ 1) != 0;
+                if is_attacked && !self.variant.has_explosions() {
+                    self.moves.remove(i);
+                    continue;
+                }
+                if checkers.get(m.to()) {
+                    EVASION_CAPTURE_SCORE
+                } else {
+                    EVASION_KING_SCORE + cmp::min(history, 62) as u8
+                }
+            } else if checkers.count() > 1 {
+                QUIET_MOVE_SCORE
+            } else if checkers.get(m.to()) || (m.is_en_passant() && checkers.get(m.to() ^ 8)) {
+                let see = (self.see(m) / 16).clamp(-62, 62);
+                EVASION_CAPTURE_SCORE + (62 + see) as u8
+            } else if blocks.get(m.to()) {
+                let bonus = if !m.is_drop() && self.see(m) >= 0 { 32 } else { 0 };
+                EVASION_BLOCK_SCORE + bonus + cmp::min(history, 31) as u8
+            } else if self.moves.stage() == PieceMoveListStage::Evasion {
+                self.moves.remove(i); // Illegal move
+                continue;
+            } else {
+                QUIET_MOVE_SCORE // Illegal move
+            };
+            self.moves[i].score = score;
+            i += 1;
+        }
+
+        // Sort the scored moves
+        let a = self.moves.index();
+        let b = self.moves.len();
+        for i in a..b {
+            for j in a..i {
+                if self.moves[j].score < self.moves[i].score {
+                    self.moves.swap(i, j);
+                }
+            }
+        }
+    }
+
+    // Sort the quiet moves of the current stage to try first the moves of
+    // the pieces attacked by a cheaper piece to a square where they are not,
+    // the most valuable pieces first, then the moves with the highest
+    // continuation history
+    fn sort_quiet_moves(&mut self) {
+        let a = self.moves.index();
+        let b = self.moves.len();
+        let mut is_scored = false;
 
-        !occupied & mask == mask &&
-        self.board[E1.flip(side) as usize] == side | KING &&
-        self.board[H1.flip(side) as usize] == side | ROOK &&
-        position.castling_right(side, KING) &&
-        !self.is_attacked(E1.flip(side), side) &&
-        !self.is_attacked(F1.flip(side), side) &&
-        !self.is_attacked(G1.flip(side), side) // TODO: Duplicate with is_check() ?
+        // The continuation history is scored on a logarithmic scale below
+        // the escapes
+        for i in a..b {
+            let m = self.moves[i].item;
+            let history = self.moves.get_continuation_history(self.moved_piece(m), m.to());
+            if history > 0 {
+                let score = cmp::min(32 - history.leading_zeros(), (ESCAPE_MOVE_SCORE - 1) as u32);
+                self.moves[i].score = QUIET_MOVE_SCORE + score as u8;
+                is_scored = true;
+            }
+        }
+
+        let side = self.side();
+        let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+
+        // Squares attacked by the pieces of the opponent cheaper than the
+        // pieces of each rank, from the attacks of each kind of piece
+        let mut cheaper = [0; 4];
+        for &kind in &[PAWN, KNIGHT, BISHOP, ROOK] {
+            let piece = side ^ 1 | kind;
+            let mut pieces = self.bitboards[piece as usize];
+            let mut attacks = 0;
+            while let Some(sq) = pieces.next() {
+                attacks |= piece_attacks(piece, sq, occupied);
+            }
+            for rank in (piece_rank(kind) + 1)..4 {
+                cheaper[rank as usize] |= attacks;
+            }
+        }
+
+        let mut threatened = 0;
+        for &kind in &[KNIGHT, BISHOP, ROOK, QUEEN] {
+            threatened |= self.bitboard(side | kind) & cheaper[piece_rank(kind) as usize];
+        }
+        if threatened == 0 && !is_scored {
+            return;
+        }
+
+        for i in a..b {
+            let m = self.moves[i].item;
+            if m.is_drop() || !threatened.get(m.from()) {
+                continue;
+            }
+            let rank = piece_rank(self.board[m.from() as usize].kind());
+            if !m.is_castle() && !cheaper[rank as usize].get(m.to()) {
+                self.moves[i].score = ESCAPE_MOVE_SCORE + rank;
+            }
+        }
+
+        // Only the few scored moves are sorted after being moved first
+        let mut n = a;
+        for i in a..b {
+            if self.moves[i].score > QUIET_MOVE_SCORE {
+                self.moves.swap(i, n);
+                n += 1;
+            }
+        }
+        for i in a..n {
+            for j in a..i {
+                if self.moves[j].score < self.moves[i].score {
+                    self.moves.swap(i, j);
+                }
+            }
+        }
     }
 
-    fn can_queen_castle(&mut self, side: Color) -> bool {
+    // Check if the king can castle on the given wing, with the king and the
+    // rook on any files of the first rank like in Chess960
+    fn can_castle_on(&mut self, side: Color, wing: Piece) -> bool {
+        match self.castling_king_path(side, wing) {
+            Some(path) => {
+                let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
+                path & self.attacks_by(side ^ 1, occupied) == 0
+            },
+            None => false
+        }
+    }
+
+    // Get the squares traveled by the king, its own square included, if
+    // the castling is allowed without taking the attacks into account,
+    // with the king and the rook on any squares of the first rank in
+    // Chess960
+    fn castling_king_path(&self, side: Color, wing: Piece) -> Option<Bitboard> {
         let &position = self.positions.top();
+        if !position.castling_right(side, wing) || !self.variant.has_royal_king() {
+            return None;
+        }
+
+        let king = self.bitboards[(side | KING) as usize];
+        let rook_from = position.castling_rook(side, wing);
+        if king.count() != 1 || self.board[rook_from as usize] != side | ROOK {
+            return None;
+        }
+        let king_from = king.scan() as Square;
+        if king_from.flip(side).rank() != 0 {
+            return None;
+        }
+        let (king_to, rook_to) = if wing == KING { (G1, F1) } else { (C1, D1) };
+        let king_to = king_to.flip(side);
+        let rook_to = rook_to.flip(side);
+
+        // The squares traveled by the king and the rook must be empty
         let occupied = self.bitboards[WHITE as usize] | self.bitboards[BLACK as usize];
-        let mask = CASTLING_MASKS[side as usize][(QUEEN >> 3) as usize];
+        let path = (rank_span(king_from, king_to) | rank_span(rook_from, rook_to)) &
+            !(1 << king_from) & !(1 << rook_from);
+        if occupied & path != 0 {
+            return None;
+        }
 
-        !occupied & mask == mask &&
-        self.board[E1.flip(side) as usize] == side | KING &&
-        self.board[A1.flip(side) as usize] == side | ROOK &&
-        position.castling_right(side, QUEEN) &&
-        !self.is_attacked(E1.flip(side), side) &&
-        !self.is_attacked(D1.flip(side), side) &&
-        !self.is_attacked(C1.flip(side), side)
+        Some(rank_span(king_from, king_to))
     }
 
     // Pseudo legal move checker (limited to moves generated by the engine)
@@ -363,6 +1016,17 @@ impl PieceMoveGeneratorExt for Game {
         let &position = self.positions.top();
         let side = position.side;
 
+        if m.is_drop() {
+            let kind = m.drop_kind();
+            if !self.variant.has_drops() || kind == KING || !PIECES.contains(&kind) {
+                return false;
+            }
+            if kind == PAWN && (RANK_1 | RANK_8).get(m.to()) {
+                return false;
+            }
+            return position.hand(side | kind) > 0 && self.board[m.to() as usize] == EMPTY;
+        }
+
         let p = self.board[m.from() as usize];
 
         // There must be a piece to play
@@ -373,6 +1037,11 @@ impl PieceMoveGeneratorExt for Game {
             return false;
         }
 
+        let is_capture = m.is_capture() || m.is_en_passant();
+        if !is_capture && self.variant.has_forced_captures() && self.can_capture(side) {
+            return false;
+        }
+
         if m.is_promotion() || m.kind() == DOUBLE_PAWN_PUSH {
             if p.kind() != PAWN {
                 return false;
@@ -393,8 +1062,9 @@ impl PieceMoveGeneratorExt for Game {
 
         if m.is_castle() {
             let wing = m.castle_kind();
+            let to = if wing == KING { G1 } else { C1 };
 
-            return self.can_castle_on(side, wing);
+            return p.kind() == KING && m.to() == to.flip(side) && self.can_castle_on(side, wing);
         }
 
         // The piece must be able to reach its destination
@@ -433,13 +1103,30 @@ impl PieceMoveGeneratorExt for Game {
         }
     }
 
+    fn can_capture(&self, side: Color) -> bool {
+        let pieces = self.bitboards[side as usize];
+        let targets = self.bitboards[(side ^ 1) as usize];
+        let occupied = pieces | targets;
+
+        let ep = self.positions.top().en_passant;
+        let pawn_targets = if ep == OUT { targets } else { targets | (1 << ep) };
+
+        let mut squares = pieces;
+        while let Some(sq) = squares.next() {
+            let p = self.board[sq as usize];
+            let attacks = piece_attacks(p, sq, occupied);
+            let t = if p.kind() == PAWN { pawn_targets } else { targets };
+            if attacks & t != 0 {
+                return true;
+            }
+        }
+
+        false
+    }
+
     fn mvv_lva(&self, m: PieceMove) -> u8 {
         let a = self.board[m.from() as usize].kind();
-        let v = if m.is_en_passant() {
-            PAWN
-        } else {
-            self.board[m.to() as usize].kind()
-        };
+        let v = self.captured_kind(m);
 
         MVV_LVA_SCORES[a as usize][v as usize]
     }
@@ -454,6 +1141,9 @@ mod tests {
     use fen::FEN;
     use game::Game;
     use piece_move_notation::PieceMoveNotation;
+    use positions::test_suite;
+    use search::Search;
+    use variant::Variant;
     use super::*;
 
     fn perft(fen: &str) -> usize {
@@ -482,20 +1172,20 @@ mod tests {
         let fen = "8/8/4k3/4p3/3P4/3K4/8/8 w - -";
         assert_eq!(perft(fen), 9);
 
-        // Pawn left capture
-        let fen = "8/8/2p5/2p1P3/1p1P4/3P4/8/8 w - -";
-        assert_eq!(perft(fen), 3);
+        // Pawn left capture, with 3 king moves
+        let fen = "k7/8/2p5/2p1P3/1p1P4/3P4/8/7K w - -";
+        assert_eq!(perft(fen), 3 + 3);
 
-        let fen = "8/8/2p5/2p1P3/1p1P4/3P4/8/8 b - -";
-        assert_eq!(perft(fen), 3);
+        let fen = "k7/8/2p5/2p1P3/1p1P4/3P4/8/7K b - -";
+        assert_eq!(perft(fen), 3 + 3);
 
         // Bishop
-        let fen = "8/8/8/8/3B4/8/8/8 w - -";
-        assert_eq!(perft(fen), 13);
+        let fen = "k7/8/8/8/3B4/8/8/7K w - -";
+        assert_eq!(perft(fen), 13 + 3);
 
         // Rook
-        let fen = "8/8/8/8/1r1R4/8/8/8 w - -";
-        assert_eq!(perft(fen), 13);
+        let fen = "k7/8/8/8/1r1R4/8/8/7K w - -";
+        assert_eq!(perft(fen), 13 + 3);
     }
 
     #[test]
@@ -528,6 +1218,41 @@ mod tests {
         assert_eq!(game.positions.top().hash, hash2);
     }
 
+    #[test]
+    fn test_make_move_rook_capture() {
+        // The castling right of the captured rook is lost even when the
+        // side capturing it has none
+        let mut game = Game::from_fen("r3k2r/8/8/8/8/8/8/4K2R w k - 0 1").unwrap();
+        let m = game.move_from_lan("h1h8");
+        game.make_move(m);
+        assert!(!game.positions.top().castling_right(BLACK, KING));
+        assert_eq!(game.to_fen(), "r3k2R/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(game.hash(), game.compute_hash());
+    }
+
+    #[test]
+    fn test_make_move_pawn_hash() {
+        let fen = "r3k3/1P6/8/3pP3/8/8/8/R3K3 w Qq d6 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        let pawn_hash = game.positions.top().pawn_hash;
+
+        // En passant, promotion, capture of a pawn, and castling
+        let moves = ["e5d6", "e8d7", "b7a8q", "d7d6", "e1c1"];
+        for lan in moves.iter() {
+            let m = game.move_from_lan(lan);
+            game.make_move(m);
+            let copy = Game::from_fen(&game.to_fen()).unwrap();
+            assert_eq!(copy.positions.top().pawn_hash, game.positions.top().pawn_hash);
+        }
+        assert_eq!(game.positions.top().pawn_hash, 0);
+
+        for lan in moves.iter().rev() {
+            let m = game.move_from_lan(lan);
+            game.undo_move(m);
+        }
+        assert_eq!(game.positions.top().pawn_hash, pawn_hash);
+    }
+
     #[test]
     fn test_make_undo_move_hash() {
         let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
@@ -720,6 +1445,145 @@ mod tests {
         assert_eq!(game.next_capture(), None);
     }
 
+    #[test]
+    fn test_next_capture_of() {
+        let fen = "k1K5/8/2p1N3/1p6/2rp1n2/1P2P3/3Q4/8 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.next_capture_of(KNIGHT), Some(PieceMove::new(B3, C4, CAPTURE)));
+        assert_eq!(game.next_capture_of(KNIGHT), Some(PieceMove::new(E3, F4, CAPTURE)));
+        assert_eq!(game.next_capture_of(KNIGHT), Some(PieceMove::new(E6, F4, CAPTURE)));
+        assert_eq!(game.next_capture_of(KNIGHT), None);
+
+        game.moves.clear();
+        assert_eq!(game.next_capture_of(ROOK), Some(PieceMove::new(B3, C4, CAPTURE)));
+        assert_eq!(game.next_capture_of(ROOK), None);
+
+        // The victims are reset with the list
+        game.moves.clear();
+        assert_eq!(game.next_capture(), Some(PieceMove::new(B3, C4, CAPTURE)));
+        assert_eq!(game.next_capture(), Some(PieceMove::new(E3, F4, CAPTURE)));
+
+        // En passant captures are captures of pawns, generated but skipped
+        // like the bad captures
+        let fen = "k1K5/8/8/2Pp4/8/8/8/8 w - d6 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.next_capture_of(KNIGHT), None);
+        assert_eq!(game.moves.len(), 0);
+        game.moves.clear();
+        assert_eq!(game.next_capture_of(PAWN), None);
+        assert_eq!(game.moves.len(), 1);
+    }
+
+    #[test]
+    fn test_next_capture_or_check() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/1P6/8/8/8/8/8/1k2K3 w - - 0 1", // Promotion checking through its square
+        ];
+        for fen in &fens {
+            let mut game = Game::from_fen(fen).unwrap();
+            let side = game.side();
+
+            let mut expected = Vec::new();
+            game.moves.clear();
+            while let Some(m) = game.next_move() {
+                if m.is_capture() || m.is_castle() {
+                    continue;
+                }
+                game.make_move(m);
+                if game.is_check(side ^ 1) {
+                    expected.push(m);
+                }
+                game.undo_move(m);
+            }
+
+            let mut checks = Vec::new();
+            game.moves.clear();
+            while let Some(m) = game.next_capture_or_check() {
+                if !m.is_capture() {
+                    checks.push(m);
+                }
+            }
+            assert_eq!(game.moves.stage(), PieceMoveListStage::QuietCheck);
+
+            expected.sort_by_key(|m| m.to_lan());
+            checks.sort_by_key(|m| m.to_lan());
+            assert_eq!(checks, expected, "{}", fen);
+            if game.to_fen() == fens[5] {
+                assert_eq!(checks.len(), 2); // Queen and rook promotions
+            }
+        }
+    }
+
+    fn legal_perft(game: &mut Game, depth: Depth) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut r = 0;
+        for m in game.generate_legal_moves() {
+            game.make_move(m);
+            r += legal_perft(game, depth - 1);
+            game.undo_move(m);
+        }
+        r
+    }
+
+    #[test]
+    fn test_generate_legal_moves() {
+        let fens = [
+            DEFAULT_FEN,
+            test_suite::KIWIPETE,
+            test_suite::PERFT_POSITION_3,
+            test_suite::PERFT_POSITION_4,
+            test_suite::PERFT_POSITION_5,
+            "8/8/8/KPp4r/8/8/8/7k w - c6 0 1", // En passant pin
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1", // En passant evasion
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", // Chess960
+            "8/8/8/8/8/8/6k1/rR2K3 w B - 0 1" // Castling rook shielding the king
+        ];
+        for fen in &fens {
+            let mut game = Game::from_fen(fen).unwrap();
+            assert_eq!(legal_perft(&mut game, 3), game.perft(3), "perft 3 of {}", fen);
+        }
+
+        // Pinned knight
+        let mut game = Game::from_fen("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+        let moves = game.generate_legal_moves();
+        assert_eq!(moves.len(), 4);
+        assert!(moves.iter().all(|m| m.from() == E1));
+    }
+
+    #[test]
+    fn test_evasion_stage() {
+        // Check by the bishop: blocks and king moves
+        let mut game = Game::from_fen("4k3/8/8/8/1b6/8/5PPP/RN2K3 w Q - 0 1").unwrap();
+        let mut moves = Vec::new();
+        game.moves.clear();
+        while let Some(m) = game.next_move() {
+            assert_eq!(game.moves.stage(), PieceMoveListStage::Evasion);
+            moves.push(m);
+        }
+        moves.sort_by_key(|m| m.to_lan());
+        let mut legal_moves = game.generate_legal_moves();
+        legal_moves.sort_by_key(|m| m.to_lan());
+        assert_eq!(moves, legal_moves);
+        assert_eq!(moves.len(), 5); // Nc3, Nd2, Kd1, Ke2, Kf1
+
+        // Double check: only king moves
+        let mut game = Game::from_fen("4k3/8/8/8/1b6/5n2/6PP/R3K3 w Q - 0 1").unwrap();
+        let mut n = 0;
+        game.moves.clear();
+        while let Some(m) = game.next_move() {
+            assert_eq!(m.from(), E1);
+            n += 1;
+        }
+        assert_eq!(n, 4); // Kd1, Ke2, Kf1, Kf2
+    }
+
     #[test]
     fn test_is_legal_move() {
         let fen = "k1K5/8/8/8/8/1p6/2P5/N7 w - - 0 1";
@@ -764,6 +1628,64 @@ mod tests {
         assert_eq!(n, 31);
     }
 
+    #[test]
+    fn test_moves_order_with_escapes() {
+        // The rook is attacked by the bishop and the knight by the pawn
+        let fen = "k7/8/8/4p3/3Nb3/8/P7/K6R w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+
+        game.moves.clear();
+        let mut moves = Vec::new();
+        while let Some(m) = game.next_move() {
+            moves.push(m);
+        }
+        assert!(moves[0..11].iter().all(|m| m.from() == H1));
+        assert!(moves[11..19].iter().all(|m| m.from() == D4));
+        assert!(moves[19..].iter().all(|m| m.from() != D4));
+
+        // Moves to squares still attacked by the bishop come later
+        let h1h7 = game.move_from_lan("h1h7");
+        assert!(moves[19..].contains(&h1h7));
+    }
+
+    #[test]
+    fn test_moves_order_with_continuation_history() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        for lan in &["e2e4", "e7e5"] {
+            let m = game.move_from_lan(lan);
+            game.make_move(m);
+        }
+
+        // The knight often refuted the replies to `e4` and the bishop less
+        game.moves.add_continuation_history(WHITE_KNIGHT, F3, 4);
+        game.moves.add_continuation_history(WHITE_BISHOP, C4, 2);
+
+        game.moves.clear();
+        let mut moves = Vec::new();
+        while let Some(m) = game.next_move() {
+            moves.push(m.to_lan());
+        }
+        assert_eq!(moves[0], "g1f3");
+        assert_eq!(moves[1], "f1c4");
+        assert_eq!(moves.len(), 29);
+    }
+
+    #[test]
+    fn test_moves_order_with_capture_history() {
+        let fen = "4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+
+        game.moves.clear();
+        assert_eq!(game.next_move().unwrap().to_lan(), "d4c6");
+
+        // The knight often took the pawn on e6 with a cutoff
+        game.moves.add_capture_history(WHITE_KNIGHT, E6, PAWN, 8);
+
+        game.moves.clear();
+        assert_eq!(game.next_move().unwrap().to_lan(), "d4e6");
+        assert_eq!(game.next_move().unwrap().to_lan(), "d4c6");
+    }
+
     #[test]
     fn test_moves_order_with_best_and_killer_moves() {
         let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
@@ -804,7 +1726,7 @@ mod tests {
         let good_capture  = game.move_from_lan("b5c6");
         let bad_capture_1 = game.move_from_lan("f3e5");
         let bad_capture_2 = game.move_from_lan("b5a6");
-        let quiet_move_1  = game.move_from_lan("a2a3");
+        let quiet_move_1  = game.move_from_lan("b5f1"); // Attacked by a6
         let killer_move_1 = game.move_from_lan("b5c4");
 
         game.moves.add_killer_move(killer_move_1);
@@ -836,7 +1758,7 @@ mod tests {
         let good_capture  = game.move_from_lan("b5c6");
         let bad_capture_1 = game.move_from_lan("f3e5");
         let bad_capture_2 = game.move_from_lan("b5a6");
-        let quiet_move_1  = game.move_from_lan("a2a3");
+        let quiet_move_1  = game.move_from_lan("b5f1"); // Attacked by a6
         let killer_move_1 = game.move_from_lan("b5c4");
 
         let best_move = bad_capture_2;
@@ -859,4 +1781,127 @@ mod tests {
         }
         assert_eq!(n, 32);
     }
+
+    #[test]
+    fn test_evasions_ordering() {
+        let fen = "4r2k/2N5/8/8/8/8/3B4/R3K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.moves.clear();
+        let mut moves = Vec::new();
+        while let Some(m) = game.next_move() {
+            moves.push(game.move_to_san(m));
+        }
+
+        // Capture of the checker
+        assert_eq!(moves[0], "Nxe8");
+
+        // Interpositions
+        moves[1..3].sort();
+        assert_eq!(moves[1..3], ["Be3", "Ne6"]);
+
+        // King moves to squares not attacked
+        moves[3..6].sort();
+        assert_eq!(moves[3..6], ["Kd1", "Kf1", "Kf2"]);
+        assert!(!moves.contains(&"Ke2".to_string()));
+    }
+
+    #[test]
+    fn test_atomic() {
+        let fen = "4k3/8/8/2nqb3/3P4/8/8/4K3 w - - 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::Atomic;
+        game.load_fen(fen).unwrap();
+
+        // The pawn explodes with the bishop and the queen but not the knight
+        let m = PieceMove::new(D4, E5, CAPTURE);
+        game.make_move(m);
+        assert_eq!(game.to_fen(), "4k3/8/8/2n5/8/8/8/4K3 b - - 0 1");
+        let hash = game.positions.top().hash;
+        game.undo_move(m);
+        assert_eq!(game.to_fen(), fen);
+        assert!(game.explosions.is_empty());
+        game.load_fen("4k3/8/8/2n5/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(game.positions.top().hash, hash);
+
+        // Kings cannot capture
+        game.load_fen("4k3/8/8/8/8/8/4q3/4K3 w - - 0 1").unwrap();
+        assert!(game.get_moves().is_empty());
+        assert!(game.is_mate());
+
+        // Captures cannot explode their own king but can explode the other
+        game.load_fen("4k3/3p4/8/8/8/8/2Kn4/3R4 w - - 0 1").unwrap();
+        let moves = game.get_moves();
+        assert!(!moves.contains(&PieceMove::new(D1, D2, CAPTURE)));
+        game.load_fen("3k4/3p4/8/8/8/8/2K5/3R4 w - - 0 1").unwrap();
+        let m = PieceMove::new(D1, D7, CAPTURE);
+        assert!(game.get_moves().contains(&m));
+        game.make_move(m);
+        assert_eq!(game.bitboard(BLACK_KING).count(), 0);
+        assert!(game.is_mate());
+    }
+
+    #[test]
+    fn test_antichess() {
+        let fen = "4k3/8/8/3p4/4P3/8/8/R3K3 w Q - 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::Antichess;
+        game.load_fen(fen).unwrap();
+
+        // Captures are compulsory
+        let m = PieceMove::new(E4, D5, CAPTURE);
+        assert_eq!(game.get_moves(), vec![m]);
+        assert!(!game.is_legal_move(PieceMove::new(E4, E5, QUIET_MOVE)));
+
+        // The king can be captured and cannot castle
+        game.load_fen("4k3/8/8/8/8/8/8/R3Kr2 w Q - 0 1").unwrap();
+        assert_eq!(game.get_moves(), vec![PieceMove::new(E1, F1, CAPTURE)]);
+        game.load_fen("4k3/8/8/8/8/8/8/R3K2r b Q - 0 1").unwrap();
+        assert!(game.get_moves().contains(&PieceMove::new(H1, E1, CAPTURE)));
+        game.load_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert!(!game.get_moves().contains(&PieceMove::new(E1, C1, QUEEN_CASTLE)));
+        assert!(!game.is_mate());
+    }
+
+    #[test]
+    fn test_crazyhouse() {
+        let fen = "4k2q~/8/8/8/8/8/8/4K2R[] w - - 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::Crazyhouse;
+        game.load_fen(fen).unwrap();
+
+        // The promoted queen goes into the hand as a pawn
+        let capture = PieceMove::new(H1, H8, CAPTURE);
+        game.make_move(capture);
+        assert_eq!(game.positions.top().hand(WHITE_PAWN), 1);
+        assert_eq!(game.positions.top().hand(WHITE_QUEEN), 0);
+        assert!(game.positions.top().promoted == 0);
+        let m = PieceMove::new(E8, E7, QUIET_MOVE);
+        game.make_move(m);
+
+        // Pawns cannot be dropped on the first and last ranks
+        let moves = game.get_moves();
+        let drop = PieceMove::new_drop(PAWN, E6);
+        assert!(moves.contains(&drop));
+        assert!(!moves.contains(&PieceMove::new_drop(PAWN, E1)));
+        assert!(!moves.contains(&PieceMove::new_drop(KNIGHT, E6)));
+        assert_eq!(moves.iter().filter(|m| m.is_drop()).count(), 48 - 1);
+        assert!(game.is_legal_move(drop));
+
+        game.make_move(drop);
+        assert_eq!(game.board[E6 as usize], WHITE_PAWN);
+        assert_eq!(game.positions.top().hand(WHITE_PAWN), 0);
+        assert_eq!(game.to_fen(), "7R/4k3/4P3/8/8/8/8/4K3[] b - - 2 2");
+        let hash = game.positions.top().hash;
+        game.load_fen(&game.to_fen()).unwrap();
+        assert_eq!(game.positions.top().hash, hash);
+
+        game.load_fen(fen).unwrap();
+        game.make_move(capture);
+        game.make_move(m);
+        game.make_move(drop);
+        game.undo_move(drop);
+        game.undo_move(m);
+        game.undo_move(capture);
+        assert_eq!(game.to_fen(), fen);
+    }
 }
@@ -8,8 +8,10 @@ use attack::*;
 use piece_move::*;
 use square::SquareExt;
 use bitboard::{Bitboard, BitboardExt, BitboardIterator};
-use hyperbola::bishop_attacks;
-use hyperbola::rook_attacks;
+use magic::bishop_attacks;
+use magic::rook_attacks;
+//use hyperbola::bishop_attacks;
+//use hyperbola::rook_attacks;
 //use dumb7fill::bishop_attacks;
 //use dumb7fill::rook_attacks;
 
@@ -32,7 +34,9 @@ pub enum PieceMoveListStage { // If we don't care about `PartialOrd` we could do
     Capture         = 1,      // =  4 (CAPTURE)
     KillerPieceMove = 2,      // = 17 (KILLER_MOVE)
     QuietPieceMove  = 3,      // =  0 (QUIET_MOVE < CAPTURE)
-    Done            = 4,
+    Evasion         = 4,      // Instead of the other stages when in check
+    QuietCheck      = 5,      // After the captures in quiescence search
+    Done            = 6,
 }
 
 // Convert `PieceMoveListStage::Capture` into `CAPTURE`
@@ -45,16 +49,48 @@ impl From<PieceMoveListStage> for PieceMoveType {
     }
 }
 
+// Number of kinds of pieces times the number of squares
+const PIECE_SQUARES: usize = 6 * 64;
+
+// Get the index of the given piece on the given square in the continuation
+// history, with the squares of black flipped to share the entries of white
+fn piece_square_index(piece: Piece, sq: Square) -> usize {
+    debug_assert!(piece != EMPTY);
+    (piece.kind() as usize / 2 - 1) * 64 + sq.flip(piece.color()) as usize
+}
+
 #[derive(Clone)]
 pub struct PieceMoveList {
     killers: [[PieceMove; MAX_KILLERS]; MAX_PLY],
 
+    // History of the quiet moves causing a beta cutoff indexed by their
+    // origin and destination squares.
+    history: [[u32; 64]; 64],
+
+    // History of the quiet moves causing a beta cutoff after the previous
+    // move of the same side, indexed by the kind of piece and the
+    // destination square of the previous move and then of the move.
+    continuation_history: Vec<[u32; PIECE_SQUARES]>,
+
+    // History of the captures causing a beta cutoff indexed by the kind of
+    // piece and the destination square of the move, and by the kind of the
+    // captured piece.
+    capture_history: [[u32; 6]; PIECE_SQUARES],
+
+    // Piece and destination square of the move made at a given ply, or
+    // `EMPTY` for a null move.
+    played: [(Piece, Square); MAX_PLY],
+
+    // Static evaluation of the position at a given ply, or `None` when it
+    // was not evaluated.
+    evals: [Option<Score>; MAX_PLY],
+
     // We store the generated moves for each ply in a two dimensional array
     // used by the recursive search function. It must be able to store any
     // ply up to `MAX_PLY`, the theoretical maximum number of plies in a chess
     // game. And likewise it must be able to store the generated moves up to
     // the maximum of any chess position `MAX_MOVES`.
-    lists: [[Scored<PieceMove, u8>; MAX_MOVES]; MAX_PLY],
+    lists: Vec<[Scored<PieceMove, u8>; MAX_MOVES]>,
 
     // Number of moves at a given ply.
     sizes: [usize; MAX_PLY],
@@ -64,6 +100,9 @@ pub struct PieceMoveList {
 
     stages: [PieceMoveListStage; MAX_PLY],
 
+    // Pieces of the opponent that can be captured at a given ply.
+    victims: [Bitboard; MAX_PLY],
+
     pub skip_ordering: bool,
     pub skip_killers: bool,
 
@@ -75,10 +114,16 @@ impl PieceMoveList {
     pub fn new() -> PieceMoveList {
         PieceMoveList {
             killers: [[PieceMove::new_null(); MAX_KILLERS]; MAX_PLY],
-            lists: [[Scored::new(PieceMove::new_null(), 0); MAX_MOVES]; MAX_PLY],
+            history: [[0; 64]; 64],
+            continuation_history: vec![[0; PIECE_SQUARES]; PIECE_SQUARES],
+            capture_history: [[0; 6]; PIECE_SQUARES],
+            played: [(EMPTY, OUT); MAX_PLY],
+            evals: [None; MAX_PLY],
+            lists: vec![[Scored::new(PieceMove::new_null(), 0); MAX_MOVES]; MAX_PLY],
             sizes: [0; MAX_PLY],
             indexes: [0; MAX_PLY],
             stages: [PieceMoveListStage::BestPieceMove; MAX_PLY],
+            victims: [!0; MAX_PLY],
             skip_ordering: false,
             skip_killers: false,
             ply: 0,
@@ -102,13 +147,23 @@ impl PieceMoveList {
         self.sizes[self.ply] = 0;
         self.indexes[self.ply] = 0;
         self.stages[self.ply] = PieceMoveListStage::BestPieceMove;
+        self.victims[self.ply] = !0;
     }
 
     pub fn clear_all(&mut self) {
-        self.killers = [[PieceMove::new_null(); MAX_KILLERS]; MAX_PLY];
+        self.clear_killers();
+        self.clear_history();
+        self.clear_plies();
+    }
+
+    /// Reset the lists of every ply without touching the killer moves and
+    /// the history heuristic
+    pub fn clear_plies(&mut self) {
         self.sizes = [0; MAX_PLY];
         self.indexes = [0; MAX_PLY];
         self.stages = [PieceMoveListStage::BestPieceMove; MAX_PLY];
+        self.victims = [!0; MAX_PLY];
+        self.evals = [None; MAX_PLY];
         self.ply = 0;
     }
 
@@ -130,10 +185,26 @@ impl PieceMoveList {
             PieceMoveListStage::Capture    => PieceMoveListStage::KillerPieceMove,
             PieceMoveListStage::KillerPieceMove => PieceMoveListStage::QuietPieceMove,
             PieceMoveListStage::QuietPieceMove  => PieceMoveListStage::Done,
+            PieceMoveListStage::Evasion    => PieceMoveListStage::Done,
+            PieceMoveListStage::QuietCheck => PieceMoveListStage::Done,
             PieceMoveListStage::Done       => panic!("no next stage")
         }
     }
 
+    pub fn set_stage(&mut self, stage: PieceMoveListStage) {
+        self.stages[self.ply] = stage;
+    }
+
+    /// Restrict the captures generated at the current ply to the given
+    /// pieces until the list is cleared
+    pub fn set_victims(&mut self, victims: Bitboard) {
+        self.victims[self.ply] = victims;
+    }
+
+    fn capture_targets(&self, bitboards: &[Bitboard], side: Color) -> Bitboard {
+        bitboards[(side ^ 1) as usize] & self.victims[self.ply]
+    }
+
     pub fn is_last_stage(&self) -> bool {
         // debug_assert(self.stages[self.ply] != PieceMoveListStage::Done);
         // self.stages[self.ply] == PieceMoveListStage::QuietPieceMove
@@ -170,6 +241,8 @@ impl PieceMoveList {
             PieceMoveListStage::Capture    => QUIET_MOVE_SCORE,
             PieceMoveListStage::KillerPieceMove => KILLER_MOVE_SCORE,
             PieceMoveListStage::QuietPieceMove  => QUIET_MOVE_SCORE,
+            PieceMoveListStage::Evasion    => QUIET_MOVE_SCORE,
+            PieceMoveListStage::QuietCheck => QUIET_MOVE_SCORE,
             PieceMoveListStage::Done       => panic!("last stage")
         };
 
@@ -225,9 +298,11 @@ impl PieceMoveList {
                     let targets = attackers.shift(dir);
                     //let epb = 1 << ep; // FIXME: 1 << 64 == 0
                     let epb = ((ep as u64 >> 6) ^ 1) << (ep % 64);
-                    self.add_moves(targets & epb, dir, EN_PASSANT);
+                    if ep == OUT || self.victims[self.ply].get(ep ^ 8) {
+                        self.add_moves(targets & epb, dir, EN_PASSANT);
+                    }
 
-                    let attacks = targets & bitboards[(side ^ 1) as usize];
+                    let attacks = targets & self.capture_targets(bitboards, side);
 
                     self.add_moves(attacks & !end_rank, dir, CAPTURE);
                     self.add_moves(attacks & end_rank, dir, KNIGHT_PROMOTION_CAPTURE);
@@ -249,7 +324,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let targets = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = pieces.next() {
@@ -264,7 +339,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let dests = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = knights.next() {
@@ -280,7 +355,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let dests = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = kings.next() {
@@ -296,7 +371,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let dests = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = bishops.next() {
@@ -311,7 +386,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let dests = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = rooks.next() {
@@ -326,7 +401,7 @@ impl PieceMoveList {
         let mt = PieceMoveType::from(self.stage());
         let dests = match self.stage() {
             PieceMoveListStage::QuietPieceMove => !occupied,
-            PieceMoveListStage::Capture        => bitboards[(side ^ 1) as usize],
+            PieceMoveListStage::Capture        => self.capture_targets(bitboards, side),
             _                                  => panic!("wrong generation stage")
         };
         while let Some(from) = queens.next() {
@@ -335,13 +410,28 @@ impl PieceMoveList {
         }
     }
 
-    pub fn add_king_castle(&mut self, side: Color) {
-        let m = PieceMove::new(E1.flip(side), G1.flip(side), KING_CASTLE);
-        self.add_move(m);
+    pub fn add_drops(&mut self, bitboards: &[Bitboard], hands: &[u8], side: Color) {
+        let occupied = bitboards[WHITE as usize] | bitboards[BLACK as usize];
+        for &p in &[PAWN, KNIGHT, BISHOP, ROOK, QUEEN] {
+            if hands[(side | p) as usize] == 0 {
+                continue;
+            }
+            let mut targets = match p {
+                PAWN => !occupied & !(RANK_1 | RANK_8),
+                _    => !occupied
+            };
+            while let Some(to) = targets.next() {
+                self.add_move(PieceMove::new_drop(p, to));
+            }
+        }
     }
 
-    pub fn add_queen_castle(&mut self, side: Color) {
-        let m = PieceMove::new(E1.flip(side), C1.flip(side), QUEEN_CASTLE);
+    pub fn add_castle(&mut self, side: Color, from: Square, wing: Piece) {
+        let m = if wing == KING {
+            PieceMove::new(from, G1.flip(side), KING_CASTLE)
+        } else {
+            PieceMove::new(from, C1.flip(side), QUEEN_CASTLE)
+        };
         self.add_move(m);
     }
 
@@ -349,6 +439,18 @@ impl PieceMoveList {
         self.lists[self.ply].swap(i, j);
     }
 
+    // Remove the moves left after the current index
+    pub fn truncate(&mut self) {
+        self.sizes[self.ply] = self.indexes[self.ply];
+    }
+
+    // Remove the move at the given index by replacing it with the last move
+    pub fn remove(&mut self, i: usize) {
+        let n = self.sizes[self.ply] - 1;
+        self.lists[self.ply].swap(i, n);
+        self.sizes[self.ply] = n;
+    }
+
     pub fn get_killer_move(&mut self, i: usize) -> PieceMove {
         self.killers[self.ply][i]
     }
@@ -360,6 +462,109 @@ impl PieceMoveList {
             self.killers[self.ply][0] = killer_move;
         }
     }
+
+    pub fn clear_killers(&mut self) {
+        self.killers = [[PieceMove::new_null(); MAX_KILLERS]; MAX_PLY];
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history = [[0; 64]; 64];
+        for row in self.continuation_history.iter_mut() {
+            *row = [0; PIECE_SQUARES];
+        }
+        self.capture_history = [[0; 6]; PIECE_SQUARES];
+    }
+
+    /// Halve the history scores to give more weight to the next search
+    pub fn age_history(&mut self) {
+        for h in self.history.iter_mut().flat_map(|row| row.iter_mut()) {
+            *h /= 2;
+        }
+        for h in self.continuation_history.iter_mut().flat_map(|row| row.iter_mut()) {
+            *h /= 2;
+        }
+        for h in self.capture_history.iter_mut().flat_map(|row| row.iter_mut()) {
+            *h /= 2;
+        }
+    }
+
+    /// Record the piece and the destination square of the move made at the
+    /// current ply, or `EMPTY` for a null move
+    pub fn set_played(&mut self, piece: Piece, to: Square) {
+        self.played[self.ply] = (piece, to);
+    }
+
+    /// Record the static evaluation of the position at the current ply, or
+    /// `None` when it was not evaluated
+    pub fn set_eval(&mut self, eval: Option<Score>) {
+        self.evals[self.ply] = eval;
+    }
+
+    /// Check if the static evaluation of the position at the current ply is
+    /// better than the one two plies before for the same side, assuming it
+    /// is when one of them is unknown
+    pub fn is_improving(&self) -> bool {
+        if self.ply < 2 {
+            return true;
+        }
+        match (self.evals[self.ply], self.evals[self.ply - 2]) {
+            (Some(eval), Some(previous)) => eval > previous,
+            _ => true
+        }
+    }
+
+    // Get the index in the continuation history of the previous move of
+    // the side to move, if any
+    fn previous_move_index(&self) -> Option<usize> {
+        if self.ply < 2 {
+            return None;
+        }
+        match self.played[self.ply - 2] {
+            (EMPTY, _) => None,
+            (piece, to) => Some(piece_square_index(piece, to))
+        }
+    }
+
+    /// Get the continuation history of the given piece moving to the given
+    /// square after the previous move of the same side
+    pub fn get_continuation_history(&self, piece: Piece, to: Square) -> u32 {
+        match self.previous_move_index() {
+            Some(i) => self.continuation_history[i][piece_square_index(piece, to)],
+            None => 0
+        }
+    }
+
+    pub fn add_continuation_history(&mut self, piece: Piece, to: Square, depth: Depth) {
+        if let Some(i) = self.previous_move_index() {
+            let bonus = (depth as u32) * (depth as u32);
+            let h = &mut self.continuation_history[i][piece_square_index(piece, to)];
+            *h = h.saturating_add(bonus);
+        }
+    }
+
+    /// Get the capture history of the given piece capturing a piece of the
+    /// given kind on the given square
+    pub fn get_capture_history(&self, piece: Piece, to: Square, captured: Piece) -> u32 {
+        debug_assert!(captured != EMPTY);
+        self.capture_history[piece_square_index(piece, to)][captured.kind() as usize / 2 - 1]
+    }
+
+    pub fn add_capture_history(&mut self, piece: Piece, to: Square, captured: Piece, depth: Depth) {
+        debug_assert!(captured != EMPTY);
+        let bonus = (depth as u32) * (depth as u32);
+        let h = &mut self.capture_history[piece_square_index(piece, to)][captured.kind() as usize / 2 - 1];
+        *h = h.saturating_add(bonus);
+    }
+
+    pub fn get_history(&self, m: PieceMove) -> u32 {
+        self.history[m.from() as usize][m.to() as usize]
+    }
+
+    pub fn add_history(&mut self, m: PieceMove, depth: Depth) {
+        let bonus = (depth as u32) * (depth as u32);
+        let h = &mut self.history[m.from() as usize][m.to() as usize];
+        *h = h.saturating_add(bonus);
+    }
 }
 
 impl Iterator for PieceMoveList {
@@ -426,6 +631,83 @@ mod tests {
         assert_eq!(moves.stage(), PieceMoveListStage::QuietPieceMove);
     }
 
+    #[test]
+    fn test_continuation_history() {
+        let mut moves = PieceMoveList::new();
+
+        // White knight to f3, black pawn to d5, white bishop to c4
+        moves.set_played(WHITE_KNIGHT, F3);
+        moves.inc();
+        moves.set_played(BLACK_PAWN, D5);
+        moves.inc();
+        moves.add_continuation_history(WHITE_BISHOP, C4, 3);
+        assert_eq!(moves.get_continuation_history(WHITE_BISHOP, C4), 9);
+        assert_eq!(moves.get_continuation_history(WHITE_BISHOP, B5), 0);
+
+        // The same moves by black share the entry of white
+        moves.clear_plies();
+        moves.set_played(BLACK_KNIGHT, F6);
+        moves.inc();
+        moves.set_played(WHITE_PAWN, D4);
+        moves.inc();
+        assert_eq!(moves.get_continuation_history(BLACK_BISHOP, C5), 9);
+
+        // No history after a null move or at the first plies
+        moves.clear_plies();
+        moves.set_played(EMPTY, OUT);
+        moves.inc();
+        moves.inc();
+        assert_eq!(moves.get_continuation_history(BLACK_BISHOP, C5), 0);
+        moves.clear_plies();
+        assert_eq!(moves.get_continuation_history(WHITE_BISHOP, C4), 0);
+
+        moves.age_history();
+        moves.set_played(WHITE_KNIGHT, F3);
+        moves.inc();
+        moves.inc();
+        assert_eq!(moves.get_continuation_history(WHITE_BISHOP, C4), 4);
+        moves.clear_history();
+        assert_eq!(moves.get_continuation_history(WHITE_BISHOP, C4), 0);
+    }
+
+    #[test]
+    fn test_is_improving() {
+        let mut moves = PieceMoveList::new();
+        moves.set_eval(Some(20));
+        moves.inc();
+        moves.set_eval(Some(-50));
+        moves.inc();
+        moves.set_eval(Some(10));
+        assert!(!moves.is_improving());
+        moves.set_eval(Some(30));
+        assert!(moves.is_improving());
+
+        // Unknown when in check or in a PV node
+        moves.set_eval(None);
+        assert!(moves.is_improving());
+        moves.inc();
+        moves.inc();
+        moves.set_eval(Some(-100));
+        assert!(moves.is_improving());
+    }
+
+    #[test]
+    fn test_capture_history() {
+        let mut moves = PieceMoveList::new();
+        moves.add_capture_history(WHITE_KNIGHT, E5, BLACK_PAWN, 3);
+        assert_eq!(moves.get_capture_history(WHITE_KNIGHT, E5, PAWN), 9);
+        assert_eq!(moves.get_capture_history(WHITE_KNIGHT, E5, KNIGHT), 0);
+        assert_eq!(moves.get_capture_history(WHITE_BISHOP, E5, PAWN), 0);
+
+        // The same capture by black shares the entry of white
+        assert_eq!(moves.get_capture_history(BLACK_KNIGHT, E4, PAWN), 9);
+
+        moves.age_history();
+        assert_eq!(moves.get_capture_history(WHITE_KNIGHT, E5, PAWN), 4);
+        moves.clear_history();
+        assert_eq!(moves.get_capture_history(WHITE_KNIGHT, E5, PAWN), 0);
+    }
+
     #[test]
     fn test_moves_ordering() {
         // TODO: rewrite this test
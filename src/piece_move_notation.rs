@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use regex::Regex;
 
 use attack::piece_attacks;
@@ -12,11 +14,15 @@ use square::*;
 use square::SquareExt;
 use search::Search;
 
-static RE_LAN: &str = r"^(?P<from>[a-h][1-8])(?P<to>[a-h][1-8])(?P<promotion>[nbrq])?$";
+static RE_LAN: &str = r"^(?P<from>[a-h][1-8])(?P<to>[a-h][1-8])(?P<promotion>[nbrq])?$|^[PNBRQ]@[a-h][1-8]$";
 static RE_SAN: &str = r"(?x)
-    ^(?P<piece>[NBRQK])?(?P<file>[a-h])?(?P<rank>[1-8])?(?P<capture>x)?(?P<to>[a-h][1-8])=?(?P<promotion>[KBRQ])?
-    |(?P<queen>O-O-O)
-    |(?P<king>O-O)";
+    ^(?P<piece>[NBRQK])?(?P<file>[a-h])?(?P<rank>[1-8])?(?P<capture>x)?(?P<to>[a-h][1-8])=?(?P<promotion>[NBRQ])?
+    |^(?P<queen>O-O-O|0-0-0)
+    |^(?P<king>O-O|0-0)";
+
+/// Choice of the piece kind of a promotion entered without it, or `None`
+/// to cancel the move
+pub type PromotionPiece = dyn Fn(&Game, PieceMove) -> Option<Piece> + Send + Sync;
 
 /// PieceMoveList generator
 pub trait PieceMoveNotation {
@@ -26,31 +32,55 @@ pub trait PieceMoveNotation {
     /// Get move from string in long algebraic notation (LAN)
     fn move_from_lan(&mut self, s: &str) -> PieceMove;
 
+    /// Get string in long algebraic notation (LAN) from move, with
+    /// castlings written as the king taking its rook in Chess960
+    fn move_to_lan(&self, m: PieceMove) -> String;
+
     /// Get move from string in standard algebraic notation (SAN)
     fn move_from_san(&mut self, s: &str) -> Option<PieceMove>;
 
     /// Get SAN string from move
     fn move_to_san(&mut self, m: PieceMove) -> String;
+
+    /// Parse a legal move entered by a user, with the piece of a promotion
+    /// given without it chosen by `auto_queen` or `promotion_piece`
+    fn enter_move(&mut self, s: &str) -> Option<PieceMove>;
 }
 
 trait PieceMoveNotationExt {
     fn move_from_lan_checked(&mut self, s: &str) -> Option<PieceMove>;
+    fn promotion_without_piece(&self, s: &str, m: PieceMove) -> bool;
 }
 
 impl PieceMoveNotation for Game {
     fn parse_move(&mut self, s: &str) -> Option<PieceMove> {
-        self.move_from_san(s).or(self.move_from_lan_checked(s))
+        self.move_from_lan_checked(s).or_else(|| self.move_from_san(s))
     }
 
     fn move_from_lan(&mut self, s: &str) -> PieceMove {
         debug_assert!(s.len() == 4 || s.len() == 5);
 
+        // Drop from the hand in Crazyhouse, like `N@f3`
+        if &s[1..2] == "@" {
+            let piece: Piece = PieceChar::from_char(s.chars().next().unwrap());
+            return PieceMove::new_drop(piece.kind(), Square::from_coord(&s[2..4]));
+        }
+
         let side = self.side();
         let from = Square::from_coord(&s[0..2]);
         let to = Square::from_coord(&s[2..4]);
         let piece = self.board[from as usize];
         let capture = self.board[to as usize];
 
+        // Castling written as the king taking its rook in Chess960
+        if piece.kind() == KING && capture == side | ROOK {
+            return if to.file() > from.file() {
+                PieceMove::new(from, G1.flip(side), KING_CASTLE)
+            } else {
+                PieceMove::new(from, C1.flip(side), QUEEN_CASTLE)
+            };
+        }
+
         let mt = if s.len() == 5 {
             let promotion = match s.chars().nth(4) {
                 Some('n') => KNIGHT_PROMOTION,
@@ -64,9 +94,9 @@ impl PieceMoveNotation for Game {
             } else {
                 promotion | CAPTURE
             }
-        } else if piece.kind() == KING && from == E1.flip(side) && to == G1.flip(side) {
+        } else if !self.is_chess960 && piece.kind() == KING && from == E1.flip(side) && to == G1.flip(side) {
             KING_CASTLE
-        } else if piece.kind() == KING && from == E1.flip(side) && to == C1.flip(side) {
+        } else if !self.is_chess960 && piece.kind() == KING && from == E1.flip(side) && to == C1.flip(side) {
             QUEEN_CASTLE
         } else if capture == EMPTY {
             let d = (to.flip(side) as Shift) - (from.flip(side) as Shift);
@@ -85,20 +115,15 @@ impl PieceMoveNotation for Game {
     }
 
     fn move_from_san(&mut self, s: &str) -> Option<PieceMove> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(RE_SAN).unwrap();
-        }
+        static RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(RE_SAN).unwrap());
         let caps = match RE.captures(s) {
             Some(caps) => caps,
             None => return None,
         };
 
-        let side = self.side();
-        if caps.name("queen").is_some() {
-            return Some(PieceMove::new(E1.flip(side), C1.flip(side), QUEEN_CASTLE));
-        }
-        if caps.name("king").is_some() {
-            return Some(PieceMove::new(E1.flip(side), G1.flip(side), KING_CASTLE));
+        if caps.name("queen").is_some() || caps.name("king").is_some() {
+            let kind = if caps.name("queen").is_some() { QUEEN_CASTLE } else { KING_CASTLE };
+            return self.get_moves().into_iter().find(|m| m.kind() == kind);
         }
 
         if caps.name("to").is_none() {
@@ -106,7 +131,7 @@ impl PieceMoveNotation for Game {
         }
         let to = Square::from_coord(&caps["to"]);
         for m in self.get_moves() {
-            if m.to() != to {
+            if m.to() != to || m.is_drop() {
                 continue;
             }
 
@@ -143,10 +168,24 @@ impl PieceMoveNotation for Game {
         None
     }
 
+    // NOTE: this function assumes that the move has not been played yet
+    fn move_to_lan(&self, m: PieceMove) -> String {
+        if self.is_chess960 && m.is_castle() {
+            let rook = self.positions.top().castling_rook(self.side(), m.castle_kind());
+            format!("{}{}", m.from().to_coord(), rook.to_coord())
+        } else {
+            m.to_lan()
+        }
+    }
+
     // NOTE: this function assumes that the move has not been played yet
     fn move_to_san(&mut self, m: PieceMove) -> String {
         let mut out = String::new();
 
+        if m.is_drop() {
+            return m.to_lan();
+        }
+
         if m.is_castle() {
             if m.castle_kind() == KING {
                 out.push_str("O-O");
@@ -192,19 +231,54 @@ impl PieceMoveNotation for Game {
 
         out
     }
+
+    fn enter_move(&mut self, s: &str) -> Option<PieceMove> {
+        let m = self.parse_move(s)?;
+        let moves = self.get_moves();
+
+        if !self.promotion_without_piece(s, m) {
+            return moves.into_iter().find(|&lm| lm == m);
+        }
+
+        let kind = if self.auto_queen {
+            QUEEN
+        } else if let Some(promotion_piece) = self.promotion_piece.clone() {
+            promotion_piece(self, m)?.kind()
+        } else {
+            return None;
+        };
+
+        moves.into_iter().find(|&lm| {
+            lm.from() == m.from() && lm.to() == m.to() &&
+            lm.is_promotion() && lm.promotion_kind() == kind
+        })
+    }
 }
 
 impl PieceMoveNotationExt for Game {
     fn move_from_lan_checked(&mut self, s: &str) -> Option<PieceMove> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(RE_LAN).unwrap();
-        }
+        static RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(RE_LAN).unwrap());
         if RE.is_match(s) {
             Some(self.move_from_lan(s))
         } else {
             None
         }
     }
+
+    // A pawn move to the last rank parsed from a string ending without the
+    // piece of the promotion
+    fn promotion_without_piece(&self, s: &str, m: PieceMove) -> bool {
+        if m.is_drop() || !self.board[m.from() as usize].is_pawn() {
+            return false;
+        }
+        if !(RANK_1 | RANK_8).get(m.to()) {
+            return false;
+        }
+        match s.trim_end_matches(['+', '#']).chars().last() {
+            Some(c) => !"nbrqNBRQ".contains(c),
+            None => false
+        }
+    }
 }
 
 
@@ -214,6 +288,8 @@ mod tests {
     use piece_move::PieceMove;
     use fen::FEN;
     use game::Game;
+    use piece_move_generator::PieceMoveGenerator;
+    use std::sync::Arc;
     use super::*;
 
     #[test]
@@ -237,10 +313,44 @@ mod tests {
         let m = game.move_from_lan_checked("g1f3");
         assert_eq!(m, Some(PieceMove::new(G1, F3, QUIET_MOVE)));
 
+        let m = game.move_from_lan_checked("N@f3");
+        assert_eq!(m, Some(PieceMove::new_drop(KNIGHT, F3)));
+
         let m = game.move_from_lan_checked("none");
         assert_eq!(m, None);
     }
 
+    #[test]
+    fn test_move_lan_chess960() {
+        let fen = "1r2k1r1/8/8/8/8/8/8/1R2K1R1 w KQkq - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+
+        let m = game.move_from_lan("e1b1");
+        assert_eq!(m, PieceMove::new(E1, C1, QUEEN_CASTLE));
+        assert_eq!(game.move_to_lan(m), "e1c1");
+        assert_eq!(game.move_to_san(m), "O-O-O");
+
+        game.is_chess960 = true;
+        assert_eq!(game.move_to_lan(m), "e1b1");
+        assert_eq!(game.move_from_san("O-O-O"), Some(m));
+
+        game.make_move(m);
+        assert_eq!(game.board[B1 as usize], EMPTY);
+        assert_eq!(game.board[C1 as usize], WHITE_KING);
+        assert_eq!(game.board[D1 as usize], WHITE_ROOK);
+        game.undo_move(m);
+        assert_eq!(game.to_fen(), fen);
+
+        // The king goes to the square of its rook
+        let m = game.move_from_lan("e1g1");
+        assert_eq!(m, PieceMove::new(E1, G1, KING_CASTLE));
+        game.make_move(m);
+        assert_eq!(game.board[F1 as usize], WHITE_ROOK);
+        assert_eq!(game.board[G1 as usize], WHITE_KING);
+        game.undo_move(m);
+        assert_eq!(game.to_fen(), fen);
+    }
+
     #[test]
     fn test_move_to_san() {
         let fen = "7k/3P1ppp/4PQ2/8/8/8/8/6RK w - - 0 1";
@@ -290,6 +400,10 @@ mod tests {
         assert_eq!(game.move_from_san("Qxh7"), Some(PieceMove::new(H6, H7, CAPTURE)));
         assert_eq!(game.move_from_san("Qg7!"), Some(PieceMove::new(H6, G7, QUIET_MOVE)));
         assert_eq!(game.move_from_san("Qxh7!"), Some(PieceMove::new(H6, H7, CAPTURE)));
+        assert_eq!(game.move_from_san("0-0"), Some(PieceMove::new(E1, G1, KING_CASTLE)));
+        assert_eq!(game.move_from_san("a8=N"), Some(PieceMove::new(A7, A8, KNIGHT_PROMOTION)));
+        assert_eq!(game.move_from_san("axb8=R"), Some(PieceMove::new(A7, B8, ROOK_PROMOTION_CAPTURE)));
+        assert_eq!(game.move_from_san("1.g3"), None);
         for m in game.get_moves() {
             let san = game.move_to_san(m);
             assert_eq!(game.move_from_san(&san), Some(m));
@@ -308,6 +422,7 @@ mod tests {
         let mut game = Game::from_fen(fen).unwrap();
         assert_eq!(game.move_from_san("R5a4"), Some(PieceMove::new(A5, A4, QUIET_MOVE)));
         assert_eq!(game.move_from_san("R1a4"), Some(PieceMove::new(A1, A4, QUIET_MOVE)));
+        assert_eq!(game.move_from_san("O-O"), None); // No castling rights
         for m in game.get_moves() {
             let san = game.move_to_san(m);
             assert_eq!(game.move_from_san(&san), Some(m));
@@ -324,5 +439,28 @@ mod tests {
         assert_eq!(game.parse_move("Ng1"), Some(PieceMove::new(F3, G1, QUIET_MOVE)));
         assert_eq!(game.parse_move("g2g3"), Some(PieceMove::new(G2, G3, QUIET_MOVE)));
         assert_eq!(game.parse_move("f3g1"), Some(PieceMove::new(F3, G1, QUIET_MOVE)));
+        assert_eq!(game.parse_move("a7b8r"), Some(PieceMove::new(A7, B8, ROOK_PROMOTION_CAPTURE)));
+    }
+
+    #[test]
+    fn test_enter_move() {
+        let fen = "1q3rk1/P5pp/8/8/8/8/6PP/6K1 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.enter_move("g2g3"), Some(PieceMove::new(G2, G3, QUIET_MOVE)));
+        assert_eq!(game.enter_move("g1f3"), None);
+        assert_eq!(game.enter_move("a7a8r"), Some(PieceMove::new(A7, A8, ROOK_PROMOTION)));
+        assert_eq!(game.enter_move("axb8=N+"), Some(PieceMove::new(A7, B8, KNIGHT_PROMOTION_CAPTURE)));
+        assert_eq!(game.enter_move("a7a8"), None);
+        assert_eq!(game.enter_move("a8"), None);
+
+        game.promotion_piece = Some(Arc::new(|_, m| {
+            if m.is_capture() { Some(BISHOP) } else { None }
+        }));
+        assert_eq!(game.enter_move("a7a8"), None);
+        assert_eq!(game.enter_move("a7b8"), Some(PieceMove::new(A7, B8, BISHOP_PROMOTION_CAPTURE)));
+
+        game.auto_queen = true;
+        assert_eq!(game.enter_move("a8"), Some(PieceMove::new(A7, A8, QUEEN_PROMOTION)));
+        assert_eq!(game.enter_move("axb8"), Some(PieceMove::new(A7, B8, QUEEN_PROMOTION_CAPTURE)));
     }
 }
@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use common::*;
 use color::*;
 use piece::*;
@@ -153,28 +155,26 @@ const WHITE_PIECES_ENDING: [[Score; 64]; 6] = [
     WHITE_KING_ENDING
 ];
 
-lazy_static! {
-    pub static ref PST: [[[Score; 2]; 64]; 14] = {
-        let mut pst_values = [[[0; 2]; 64]; 14];
+pub static PST: LazyLock<[[[Score; 2]; 64]; 14]> = LazyLock::new(|| {
+    let mut pst_values = [[[0; 2]; 64]; 14];
 
-        for c in 0..2 {
-            for p in 0..6 {
-                for s in 0..64 {
-                    let square = (s as Square).flip((c as Color) ^ 1);
-                    let piece = (c as Color) | PIECES[p];
+    for c in 0..2 {
+        for p in 0..6 {
+            for s in 0..64 {
+                let square = (s as Square).flip((c as Color) ^ 1);
+                let piece = (c as Color) | PIECES[p];
 
-                    let score = WHITE_PIECES_OPENING[p][s];
-                    pst_values[piece as usize][square as usize][0] = score;
+                let score = WHITE_PIECES_OPENING[p][s];
+                pst_values[piece as usize][square as usize][0] = score;
 
-                    let score = WHITE_PIECES_ENDING[p][s];
-                    pst_values[piece as usize][square as usize][1] = score;
-                }
+                let score = WHITE_PIECES_ENDING[p][s];
+                pst_values[piece as usize][square as usize][1] = score;
             }
         }
+    }
 
-        pst_values
-    };
-}
+    pst_values
+});
 
 #[cfg(test)]
 mod tests {
@@ -3,16 +3,27 @@ use std::ops::Index;
 use color::*;
 use piece::*;
 use square::*;
+use bitboard::Bitboard;
+use square::SquareExt;
+
+/// Test positions
+pub mod test_suite;
 
 #[derive(Copy, Clone)]
 pub struct Position {
     pub hash: u64,
+    pub pawn_hash: u64, // Hash of the pawns only
+    pub promoted: Bitboard, // Promoted pieces going back to the hand as pawns
+    pub hands: [u8; 14], // Pieces in hand indexed like the bitboards
     pub side: Color,
     pub capture: Piece, // TODO: use `Option<Piece>`?
     pub en_passant: Square, // TODO: use `Option<Square>`?
     pub null_move_right: bool,
     pub castling_rights: u8,
+    pub castling_files: u16, // Files of the castling rooks on 3 bits each
     pub halfmoves_count: u8,
+    pub explosions_count: u8, // Pieces removed by the explosion of a capture in Atomic
+    pub checks_count: u8, // Checks given by each side on 4 bits in Three-check
 }
 
 // WHITE == 0b0000 => 0b0000
@@ -27,12 +38,18 @@ impl Position {
     pub fn new() -> Position {
         Position {
             hash: 0, // TODO: is it a problem for the starting position?
+            pawn_hash: 0,
+            promoted: 0,
+            hands: [0; 14],
             side: WHITE,
             capture: EMPTY, // TODO: use `None`?
             en_passant: OUT, // TODO: use `None`?
             null_move_right: true,
             castling_rights: 0,
+            castling_files: 0b111_111_000_000, // Rooks on files A and H
             halfmoves_count: 0,
+            explosions_count: 0,
+            checks_count: 0,
         }
     }
 
@@ -50,21 +67,63 @@ impl Position {
         let i = castling_rights_index(side, wing);
         self.castling_rights &= !(1 << i);
     }
+
+    pub fn castling_file(&self, side: Color, wing: Piece) -> u8 {
+        let i = castling_rights_index(side, wing);
+        ((self.castling_files >> (3 * i)) & 0b111) as u8
+    }
+
+    pub fn set_castling_file(&mut self, side: Color, wing: Piece, file: u8) {
+        let i = castling_rights_index(side, wing);
+        self.castling_files &= !(0b111 << (3 * i));
+        self.castling_files |= (file as u16) << (3 * i);
+    }
+
+    /// Get the starting square of the rook castling on the given wing
+    pub fn castling_rook(&self, side: Color, wing: Piece) -> Square {
+        (self.castling_file(side, wing) as Square).flip(side)
+    }
+
+    /// Get the number of the given piece in hand
+    pub fn hand(&self, piece: Piece) -> u8 {
+        self.hands[piece as usize]
+    }
+
+    /// Get the number of checks given by the given side
+    pub fn checks(&self, side: Color) -> u8 {
+        (self.checks_count >> (4 * side)) & 0b1111
+    }
+
+    /// Count a check given by the given side
+    pub fn add_check(&mut self, side: Color) {
+        self.checks_count += 1 << (4 * side);
+    }
+
+    /// Check if the given side has any piece in hand
+    pub fn has_hand(&self, side: Color) -> bool {
+        PIECES.iter().any(|&p| self.hand(side | p) > 0)
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new()
+    }
 }
 
 const MAX_POSITIONS: usize = 1024;
 
 #[derive(Clone)]
 pub struct Positions {
-    stack: [Position; MAX_POSITIONS],
-    fullmoves_init: u8,
+    stack: Vec<Position>,
+    fullmoves_init: u16,
     ply: usize
 }
 
 impl Positions {
     pub fn new() -> Positions {
         Positions {
-            stack: [Position::new(); MAX_POSITIONS],
+            stack: vec![Position::new(); MAX_POSITIONS],
             fullmoves_init: 0,
             ply: 0
         }
@@ -88,17 +147,20 @@ impl Positions {
         &self.stack[self.ply - 1]
     }
 
-    #[allow(dead_code)]
     pub fn len(&self) -> usize {
         self.ply
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.ply == 0
+    }
+
     pub fn halfmoves(&self) -> u8 {
         self.top().halfmoves_count
     }
 
-    pub fn fullmoves(&self) -> u8 {
-        let n = self.fullmoves_init + (self.ply / 2) as u8;
+    pub fn fullmoves(&self) -> u16 {
+        let n = self.fullmoves_init + (self.ply / 2) as u16;
         let blacks_started = self.top().side == BLACK && self.ply % 2 == 0;
 
         if blacks_started { n - 1 } else { n }
@@ -108,7 +170,15 @@ impl Positions {
         self.stack[self.ply - 1].halfmoves_count = n;
     }
 
-    pub fn set_fullmoves(&mut self, n: u8) {
+    pub fn set_hash(&mut self, hash: u64) {
+        self.stack[self.ply - 1].hash = hash;
+    }
+
+    pub fn set_pawn_hash(&mut self, hash: u64) {
+        self.stack[self.ply - 1].pawn_hash = hash;
+    }
+
+    pub fn set_fullmoves(&mut self, n: u16) {
         self.fullmoves_init = n;
     }
 
@@ -118,10 +188,19 @@ impl Positions {
             return true; // Fifty-move rule
         }
 
+        self.is_repetition()
+    }
+
+    pub fn is_repetition(&self) -> bool {
+        self.is_repetition_at(self.len() - 1)
+    }
+
+    // Check if the position at the given index repeats an earlier one
+    pub fn is_repetition_at(&self, index: usize) -> bool {
         // Threefold repetitions
         let mut repetitions_count = 0;
-        let hash = self.top().hash;
-        let mut i = self.len() - 1;
+        let hash = self[index].hash;
+        let mut i = index;
         while i >= 2 {
             i -= 2;
             if self[i].hash == hash {
@@ -152,6 +231,12 @@ impl Positions {
     }
 }
 
+impl Default for Positions {
+    fn default() -> Self {
+        Positions::new()
+    }
+}
+
 impl Index<usize> for Positions {
     type Output = Position;
 
@@ -167,14 +252,16 @@ mod tests {
 
     #[test]
     fn test_size_of_position() {
-        assert_eq!(mem::size_of::<u64>(),       8); // x1
-        assert_eq!(mem::size_of::<u8>(),        1); // x2
+        assert_eq!(mem::size_of::<u64>(),       8); // x3
+        assert_eq!(mem::size_of::<u8>(),        1); // x3
         assert_eq!(mem::size_of::<bool>(),      1); // x1
         assert_eq!(mem::size_of::<Color>(),     1); // x1
         assert_eq!(mem::size_of::<Piece>(),     1); // x1
         assert_eq!(mem::size_of::<Square>(),    1); // x1
 
-        assert_eq!(mem::size_of::<Position>(), 16);
+        assert_eq!(mem::size_of::<u16>(),       2); // x1
+        assert_eq!(mem::size_of::<[u8; 14]>(), 14); // x1
+        assert_eq!(mem::size_of::<Position>(), 48);
     }
 
     #[test]
@@ -222,4 +309,18 @@ mod tests {
         assert_eq!(pos.castling_right(BLACK, KING),  false);
         assert_eq!(pos.castling_right(BLACK, QUEEN), false);
     }
+
+    #[test]
+    fn test_position_castling_files() {
+        let mut pos = Position::new();
+        assert_eq!(pos.castling_rook(WHITE, KING),  H1);
+        assert_eq!(pos.castling_rook(WHITE, QUEEN), A1);
+        assert_eq!(pos.castling_rook(BLACK, KING),  H8);
+        assert_eq!(pos.castling_rook(BLACK, QUEEN), A8);
+
+        pos.set_castling_file(BLACK, KING, 6);
+        assert_eq!(pos.castling_file(BLACK, KING), 6);
+        assert_eq!(pos.castling_rook(BLACK, KING), G8);
+        assert_eq!(pos.castling_rook(WHITE, KING), H1);
+    }
 }
@@ -0,0 +1,70 @@
+//! Test positions commonly used to check and benchmark the engine
+
+use common::{Depth, DEFAULT_FEN};
+
+/// Kiwipete position with a lot of tactics and special moves
+pub const KIWIPETE: &str =
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+/// Perft position 3 with en passant captures discovering checks
+pub const PERFT_POSITION_3: &str =
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+/// Perft position 4 with promotions and castlings out of check
+pub const PERFT_POSITION_4: &str =
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+
+/// Perft position 4 with colors reversed
+pub const PERFT_POSITION_4_MIRRORED: &str =
+    "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+
+/// Perft position 5 from TalkChess
+pub const PERFT_POSITION_5: &str =
+    "rnbqkb1r/pp1p1ppp/2p5/4P3/2B5/8/PPP1NnPP/RNBQK2R w KQkq - 0 6";
+
+/// Perft position 6 from TalkChess
+pub const PERFT_POSITION_6: &str =
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+/// Zugzwang position won by `Kh6` that null move pruning can miss
+pub const ZUGZWANG_1: &str =
+    "1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1";
+
+/// Zugzwang position won by `Rf1` that null move pruning can miss
+pub const ZUGZWANG_2: &str =
+    "8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1";
+
+/// Position 70 of Basic Chess Endings by Reuben Fine, won by `Kb1` only
+/// found at high depths with a transposition table
+pub const FINE_70: &str =
+    "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1";
+
+/// Positions searched by the benchmark, from the opening to the ending
+pub const BENCH_SUITE: [&str; 8] = [
+    DEFAULT_FEN,
+    KIWIPETE,
+    PERFT_POSITION_3,
+    PERFT_POSITION_4,
+    PERFT_POSITION_5,
+    PERFT_POSITION_6,
+    ZUGZWANG_1,
+    FINE_70
+];
+
+/// Tricky positions with their number of nodes at a given depth, for a
+/// quick check of the moves generation
+pub const PERFT_SUITE: [(&str, Depth, u64); 13] = [
+    (DEFAULT_FEN, 5, 4865609),
+    (KIWIPETE, 4, 4085603),
+    (PERFT_POSITION_3, 5, 674624),
+    (PERFT_POSITION_4, 4, 422333),
+    (PERFT_POSITION_4_MIRRORED, 4, 422333),
+    (PERFT_POSITION_5, 3, 53392),
+    (PERFT_POSITION_6, 4, 3894594),
+    ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", 4, 314346), // Castlings
+    ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 4, 182838), // Promotions
+    ("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1", 5, 62297), // En passant with a diagonal pin
+    ("8/8/8/KPp4r/8/8/8/7k w - c6 0 1", 5, 23591), // En passant with a horizontal pin
+    ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 5, 206379), // En passant giving check
+    ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 3, 12189) // Chess960
+];
@@ -7,8 +7,9 @@ use rustyline_derive::{Helper, Validator, Highlighter, Hinter};
 use std::io;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufReader, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
 use std::error::Error;
 
@@ -16,22 +17,38 @@ use version;
 use color::*;
 use common::*;
 use attack::Attack;
-use clock::Clock;
+use chess960;
+use chess960::CHESS960_POSITIONS_COUNT;
+use clock::{Clock, GameClock, TimeControl};
+use epd::EPD;
 use eval::Eval;
 use fen::FEN;
 use game::Game;
+use jobs;
+use params::{ClearPolicy, EVAL_TERMS};
+use perft_table::PerftTable;
 use piece_move_generator::PieceMoveGenerator;
 use piece_move_notation::PieceMoveNotation;
+use odds;
+use odds::Odds;
+use online::OnlineGame;
+use options::{Options, DEFAULT_PROFILE};
 use pgn::*;
+use piece::*;
+use piece_move::PieceMove;
+use positions::test_suite::{BENCH_SUITE, PERFT_SUITE};
 use protocols::xboard::XBoard;
 use protocols::uci::UCI;
-use search::Search;
+use search::{Search, mate_moves};
 
 #[derive(Clone)]
 pub struct CLI {
     pub game: Game,
     max_depth: Depth,
     play_side: Option<Color>,
+    game_clock: GameClock,
+    move_started_at: Instant, // Start of the turn of the side to move
+    pub jobs_count: usize, // Positions processed concurrently by suites
     pub show_board: bool,
     pub show_san: bool,
     pub prompt: String,
@@ -49,12 +66,19 @@ impl CLI {
         let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
 
         // Set default clock to 40 moves in 5 minutes
-        game.clock = Clock::new(40, 5 * 60 * 1000);
+        let game_clock = GameClock::new(&[TimeControl::new(40, 5 * 60 * 1000, 0)]);
+        game.clock = game_clock.search_clock(WHITE);
+
+        // Ask for the piece of a promotion entered without it
+        game.promotion_piece = Some(Arc::new(ask_promotion_piece));
 
         CLI {
             game,
             max_depth: (MAX_PLY - 10) as Depth,
             play_side: None,
+            game_clock,
+            move_started_at: Instant::now(),
+            jobs_count: jobs::default_jobs_count(),
             show_board: false,
             show_san: true,
             prompt: "> ".to_string(),
@@ -117,16 +141,22 @@ impl CLI {
                 "play" | "p"           => self.cmd_play(&args),
                 "hint"                 => self.cmd_hint(),
                 "eval" | "e"           => self.cmd_eval(),
+                "stats"                => self.cmd_stats(),
                 "undo" | "u"           => self.cmd_undo(),
                 "move" | "m"           => self.cmd_move(&args),
                 "time" | "t" | "level" => self.cmd_time(&args),
                 "show"                 => self.cmd_config(true, &args),
                 "hide"                 => self.cmd_config(false, &args),
                 "core" | "threads"     => self.cmd_threads(&args),
-                "hash" | "memory"      => self.cmd_memory(&args),
+                "jobs"                 => self.cmd_jobs(&args),
+                "hash"                 => self.cmd_hash(&args),
+                "memory"               => self.cmd_memory(&args),
                 "perft"                => self.cmd_perft(&args),
+                "perftstats"           => self.cmd_perftstats(&args),
                 "perftsuite"           => self.cmd_perftsuite(&args),
                 "testsuite"            => self.cmd_testsuite(&args),
+                "ablation"             => self.cmd_ablation(&args),
+                "bench"                => self.cmd_bench(&args),
                 "divide"               => self.cmd_divide(&args),
                 "uci"                  => self.cmd_uci(),
                 "xboard"               => self.cmd_xboard(),
@@ -171,17 +201,23 @@ impl CLI {
             "  play [<color>]            Search and play [<color>] move[s]",
             "  undo                      Undo the last move",
             "  move <move>               Play <move> on the board",
+            "  stats                     Display the statistics of the last search",
             "",
             "  show <feature>            Show <feature>",
             "  hide <feature>            Hide <feature>",
             "  time <moves> <time>       Set clock to <moves> in <time> (in seconds)",
-            "  hash <size>               Set the <size> of the memory (in MB)",
+            "  time <control>            Set clock to PGN time <control> like 300+2",
+            "  hash [<size>]             Display the hash of the position [or set memory <size> in MB]",
             "  core <number>             Set the <number> of threads",
+            "  jobs <number>             Set the <number> of positions run concurrently",
             "",
-            "  perft [<depth>]           Count the nodes at each depth",
-            "  perftsuite <epd>          Compare perft results to each position of <epd>",
+            "  perft [<depth>] [<size>]  Count the nodes at each depth [with <size> MB of hash]",
+            "  perftstats <depth>        Count the nodes of each kind up to <depth>",
+            "  perftsuite [<epd>]        Compare perft results to each position [of <epd>]",
             "  testsuite <epd> [<time>]  Search each position of <epd> [for <time>]",
-            "  divide <depth>            Count the nodes at <depth> for each moves",
+            "  ablation <epd> [<time>]   Run testsuite with each eval term disabled",
+            "  bench [<depth>]           Search the benchmark positions [to <depth>]",
+            "  divide <depth>            Count the nodes at <depth> after each move",
             "",
             "  uci                       Start UCI mode",
             "  xboard                    Start XBoard mode",
@@ -208,7 +244,8 @@ impl CLI {
             ["coord", "board coordinates"],
             ["debug", "debug output"],
             ["think", "search output"],
-            ["san  ", "standard algebraic notation"],
+            ["san", "standard algebraic notation"],
+            ["autoqueen", "promotion to queen without asking"],
         ];
 
         println!();
@@ -216,9 +253,9 @@ impl CLI {
         println!();
         for args in &cmds {
             if value {
-                println!("  show {}     Show {}", args[0], args[1]);
+                println!("  show {:<9}     Show {}", args[0], args[1]);
             } else {
-                println!("  hide {}     Hide {}", args[0], args[1]);
+                println!("  hide {:<9}     Hide {}", args[0], args[1]);
             }
         }
         println!();
@@ -231,6 +268,11 @@ impl CLI {
         println!();
         println!("  load fen <string>         Load game from FEN <string>");
         println!("  load pgn <file>           Load game from PGN <file>");
+        println!("  load 960 [<id>] [<id>]    Load Chess960 or DFRC start position");
+        println!("  load odds <p> [<color>]   Load start position without <p> [of <color>]");
+        println!("  load odds <p> <color> <t> Load it with time control <t> for <color>");
+        println!("  load url <url>            Load game from Lichess <url> or lichess:<id>");
+        println!("  load options [<profile>]  Load engine options from [<profile>]");
         println!();
         Ok(State::Running)
     }
@@ -240,7 +282,9 @@ impl CLI {
         println!("Subcommands:");
         println!();
         println!("  save fen                  Save game to FEN <string>");
+        println!("  save epd                  Save position and last search to EPD <string>");
         println!("  save pgn <file>           Save game to PGN <file>");
+        println!("  save options [<profile>]  Save engine options to [<profile>]");
         println!();
         Ok(State::Running)
     }
@@ -265,8 +309,9 @@ impl CLI {
 
     fn cmd_init(&mut self) -> Result<State, Box<dyn Error>> {
         self.max_depth = (MAX_PLY - 10) as Depth;
-        self.game.clear();
+        self.game.new_game();
         self.game.load_fen(DEFAULT_FEN)?;
+        self.reset_clock();
 
         if self.show_board {
             println!();
@@ -293,12 +338,72 @@ impl CLI {
                     return Err("no filename given".into());
                 }
                 let path = Path::new(args[2]);
-                let pgn_str = fs::read_to_string(path)?;
+                let reader = PgnReader::new(BufReader::new(File::open(path)?));
                 // TODO: Add cmd arg to select which game to load in PGN file
                 // that have more than one game. Right now the last one will
                 // be loaded.
-                let pgn = PGN::from(pgn_str);
-                self.game.load_pgn(pgn);
+                let pgn = reader.last().unwrap_or_else(|| Ok(PGN::new()))?;
+                self.game.load_pgn(pgn)?;
+            },
+            "url" => {
+                if args.len() == 2 {
+                    return Err("no url given".into());
+                }
+                let game = match OnlineGame::parse(args[2]) {
+                    Some(game) => game,
+                    None => return Err(format!("invalid game url '{}'", args[2]).into())
+                };
+                self.game.load_pgn(game.fetch()?)?;
+            },
+            "960" => {
+                // Random position unless given by its Scharnagl number,
+                // with another one for black in Double Fischer Random Chess
+                let white_id = match args.get(2) {
+                    Some(id) => id.parse()?,
+                    None => self.game.rng.next_below(CHESS960_POSITIONS_COUNT as u64) as u16
+                };
+                let black_id = match args.get(3) {
+                    Some(id) => id.parse()?,
+                    None => white_id
+                };
+                self.game.new_game();
+                self.game.load_fen(&chess960::start_fen(white_id, black_id)?)?;
+                self.game.is_chess960 = true;
+            },
+            "odds" => {
+                if args.len() == 2 {
+                    return Err("no odds given".into());
+                }
+                let odds = match Odds::from_name(args[2]) {
+                    Some(odds) => odds,
+                    None => return Err(format!("invalid odds '{}'", args[2]).into())
+                };
+                // The engine gives the odds unless the color is given
+                let side = match args.get(3) {
+                    Some(&"white") => WHITE,
+                    Some(&"black") => BLACK,
+                    Some(color) => return Err(format!("invalid color '{}'", color).into()),
+                    None => self.play_side.unwrap_or(WHITE)
+                };
+                // The side giving the odds can also be given less time
+                if let Some(control) = args.get(4) {
+                    let controls = TimeControl::from_pgn(control)?;
+                    let other_controls = self.game_clock.controls(side ^ 1).to_vec();
+                    self.game_clock = if side == WHITE {
+                        GameClock::with_odds(&controls, &other_controls)
+                    } else {
+                        GameClock::with_odds(&other_controls, &controls)
+                    };
+                }
+                self.game.new_game();
+                self.game.load_fen(&odds::start_fen(odds, side))?;
+            }
+            "options" => {
+                let profile = args.get(2).cloned().unwrap_or(DEFAULT_PROFILE);
+                if !self.game.load_options(profile)? {
+                    return Err(format!("unknown options profile '{}'", profile).into());
+                }
+                return Ok(State::Running);
             }
             "help" => {
                 return self.cmd_load_usage();
@@ -308,6 +413,8 @@ impl CLI {
             }
         }
 
+        self.reset_clock();
+
         if self.show_board {
             println!();
             println!("{}", self.game);
@@ -325,6 +432,9 @@ impl CLI {
             "fen" => {
                 println!("{}", self.game.to_fen());
             },
+            "epd" => {
+                println!("{}", self.game.to_epd());
+            },
             "pgn" => {
                 if args.len() == 2 {
                     return Err("no filename given".into());
@@ -340,6 +450,11 @@ impl CLI {
                 }
                 write!(buffer, "{}", pgn)?;
             }
+            "options" => {
+                let profile = args.get(2).cloned().unwrap_or(DEFAULT_PROFILE);
+                let path = self.game.save_options(profile)?;
+                println!("saved options to '{}'", path.display());
+            }
             "help" => {
                 return self.cmd_save_usage();
             }
@@ -379,6 +494,9 @@ impl CLI {
             "san" => {
                 self.show_san = value;
             }
+            "autoqueen" | "auto_queen" => {
+                self.game.auto_queen = value;
+            }
             "help" => {
                 return self.cmd_config_usage(value);
             }
@@ -436,13 +554,33 @@ impl CLI {
         Ok(State::Running)
     }
 
-    fn cmd_undo(&mut self) -> Result<State, Box<dyn Error>> {
-        if self.game.history.len() > 0 {
-            if let Some(m) = self.game.history.pop() {
-                self.game.undo_move(m);
-            }
+    fn cmd_stats(&mut self) -> Result<State, Box<dyn Error>> {
+        let stats = self.game.search_stats;
+        let n = stats.nodes_count();
+        if n == 0 {
+            return Err("no search to display the statistics of".into());
         }
 
+        let percent = |count: u64| (count as f64) * 100.0 / (n as f64);
+        println!("Statistics of the last search:");
+        println!();
+        println!("  {:20} {:>10}", "nodes:", n);
+        println!("  {:20} {:>10} ({:.2} %)", "pv nodes:", stats.pv_nodes_count, percent(stats.pv_nodes_count));
+        println!("  {:20} {:>10} ({:.2} %)", "non-pv nodes:", stats.non_pv_nodes_count, percent(stats.non_pv_nodes_count));
+        println!("  {:20} {:>10} ({:.2} %)", "quiescence nodes:", stats.quiescence_nodes_count, percent(stats.quiescence_nodes_count));
+        println!("  {:20} {:>10}", "tt probes:", stats.tt_probes_count);
+        println!("  {:20} {:>10} ({:.2} %)", "tt hits:", stats.tt_hits_count, stats.tt_hit_rate() * 100.0);
+        println!("  {:20} {:>10} ({:.2} %)", "tt cutoffs:", stats.tt_cutoffs_count, stats.tt_cutoff_rate() * 100.0);
+        println!("  {:20} {:>10}", "beta cutoffs:", stats.cutoffs_count);
+        println!("  {:20} {:>10} ({:.2} %)", "first move cutoffs:", stats.first_move_cutoffs_count, stats.first_move_cutoff_rate() * 100.0);
+        println!("  {:20} {:>10}", "null moves:", stats.null_moves_count);
+        println!("  {:20} {:>10} ({:.2} %)", "null move cutoffs:", stats.null_move_cutoffs_count, stats.null_move_cutoff_rate() * 100.0);
+        Ok(State::Running)
+    }
+
+    fn cmd_undo(&mut self) -> Result<State, Box<dyn Error>> {
+        self.game.takeback(1);
+
         if self.show_board {
             println!();
             println!("{}", self.game);
@@ -454,23 +592,15 @@ impl CLI {
         if args.len() < 2 {
             return Err("no <move> given".into());
         }
-        if let Some(parsed_move) = self.game.parse_move(args[1]) {
-            let mut is_valid = false;
+        if self.game.parse_move(args[1]).is_some() {
+            let parsed_move = match self.game.enter_move(args[1]) {
+                Some(m) => m,
+                None => return Err(format!("move '{}' is not valid", args[1]).into())
+            };
+
             let side = self.game.side();
-            self.game.moves.clear();
-            while let Some(m) = self.game.next_move() {
-                if m == parsed_move {
-                    self.game.make_move(m);
-                    if !self.game.is_check(side) {
-                        is_valid = true;
-                    }
-                    self.game.undo_move(m);
-                    break;
-                }
-            }
-            if !is_valid {
-                return Err(format!("move '{}' is not valid", args[1]).into());
-            }
+            let elapsed = self.move_started_at.elapsed().as_millis() as u64;
+            self.press_clock(side, elapsed);
 
             self.game.make_move(parsed_move);
             self.game.history.push(parsed_move);
@@ -496,44 +626,57 @@ impl CLI {
     }
 
     fn cmd_time(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
-        match args.len() {
-            1 => { return Err("no <moves> and <time> given".into()) },
-            2 => { return Err("no <moves>".into()) },
-            _ => {}
-        }
-        let moves = args[1].parse::<u16>()?;
-        let time = args[2].parse::<f64>()?;
-        self.game.clock = Clock::new(moves, (time * 1000.0).round() as u64);
+        let controls = match args.len() {
+            1 => { return Err("no <moves> and <time> or <control> given".into()) },
+            2 => TimeControl::from_pgn(args[1])?,
+            _ => {
+                let moves = args[1].parse::<u16>()?;
+                let time = args[2].parse::<f64>()?;
+                vec![TimeControl::new(moves, (time * 1000.0).round() as u64, 0)]
+            }
+        };
+        self.game_clock = GameClock::new(&controls);
+        self.reset_clock();
         Ok(State::Running)
     }
 
+    // Restart the clock of both sides at the beginning of a game
+    fn reset_clock(&mut self) {
+        self.game_clock.reset();
+        self.move_started_at = Instant::now();
+    }
+
+    // Stop the clock of the side having played a move in the given time
+    fn press_clock(&mut self, side: Color, elapsed: u64) {
+        if !self.game_clock.press(side, elapsed) {
+            println!("# {} ran out of time", if side == WHITE { "white" } else { "black" });
+        }
+        self.move_started_at = Instant::now();
+    }
+
     fn cmd_divide(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
         if args.len() != 2 {
             return Err("no <depth> given".into());
         }
         let d = args[1].parse::<Depth>()?;
+        if d < 1 {
+            return Err("<depth> must be at least 1".into());
+        }
 
         self.game.moves.skip_ordering = true;
         self.game.moves.skip_killers = true;
-        let mut moves_count = 0u64;
-        let mut nodes_count = 0u64;
 
-        let side = self.game.side();
-        self.game.moves.clear();
-        while let Some(m) = self.game.next_move() {
+        // The count of each root move is the number of nodes at `d - 1`
+        // after it, adding up to the perft at `d` like `go perft` in UCI
+        let mut divide = Vec::new();
+        let nodes_count = self.game.perft_with(d, |m, r| divide.push((m, r)));
+        for &(m, r) in &divide {
             let move_str = if self.show_san { self.game.move_to_san(m) } else { m.to_lan() };
-            self.game.make_move(m);
-            if !self.game.is_check(side) {
-                let r = self.game.perft(d);
-                println!("{} {}", move_str, r);
-                moves_count += 1;
-                nodes_count += r;
-            }
-            self.game.undo_move(m);
+            println!("{} {}", move_str, r);
         }
 
         println!();
-        println!("Moves: {}", moves_count);
+        println!("Moves: {}", divide.len());
         println!("Nodes: {}", nodes_count);
         Ok(State::Running)
     }
@@ -546,6 +689,42 @@ impl CLI {
         Ok(State::Running)
     }
 
+    fn cmd_jobs(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
+        if args.len() < 2 {
+            return Err("no <number> given".into());
+        }
+        let n = args[1].parse::<usize>()?;
+        if n == 0 {
+            return Err("<number> must be at least 1".into());
+        }
+        self.jobs_count = n;
+        Ok(State::Running)
+    }
+
+    fn cmd_hash(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
+        if args.len() > 1 {
+            return self.cmd_memory(args);
+        }
+
+        let hash = self.game.hash();
+        let pawn_hash = self.game.positions.top().pawn_hash;
+        println!("hash:      {:016X}", hash);
+        println!("pawn hash: {:016X}", pawn_hash);
+
+        // Compare the hashes updated incrementally with the ones computed
+        // from scratch to detect a bug in make_move or undo_move
+        let computed_hash = self.game.compute_hash();
+        if computed_hash != hash {
+            println!("# inconsistent hash, computed {:016X}", computed_hash);
+        }
+        let computed_pawn_hash = self.game.compute_pawn_hash();
+        if computed_pawn_hash != pawn_hash {
+            println!("# inconsistent pawn hash, computed {:016X}", computed_pawn_hash);
+        }
+
+        Ok(State::Running)
+    }
+
     fn cmd_memory(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
         if args.len() < 2 {
             return Err("no <size> given".into());
@@ -556,12 +735,20 @@ impl CLI {
     }
 
     fn cmd_perft(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
-        let mut depth = if args.len() == 2 {
+        let mut depth = if args.len() > 1 {
             args[1].parse::<Depth>()?
         } else {
             1
         };
 
+        // Optional hash table for the positions already counted
+        let mut table = if args.len() > 2 {
+            let memory = args[2].parse::<usize>()?; // In MB
+            Some(PerftTable::with_memory(memory << 20))
+        } else {
+            None
+        };
+
         if self.game.is_debug {
             println!("# FEN {}", self.game.to_fen());
             println!("# starting perft at depth {}", depth);
@@ -572,12 +759,15 @@ impl CLI {
 
         loop {
             let started_at = Instant::now();
-            let n = self.game.perft(depth);
+            let n = match table {
+                Some(ref mut table) => self.game.perft_hashed(depth, table),
+                None => self.game.perft(depth)
+            };
             let s = started_at.elapsed().as_secs_f64();
             let nps = (n as f64) / s;
             println!("perft {} -> {} ({:.2} s, {:.2e} nps)", depth, n, s, nps);
 
-            if args.len() == 2 {
+            if args.len() > 1 {
                 break;
             } else {
                 depth += 1;
@@ -586,39 +776,89 @@ impl CLI {
         Ok(State::Running)
     }
 
+    fn cmd_perftstats(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
+        if args.len() != 2 {
+            return Err("no <depth> given".into());
+        }
+        let depth = args[1].parse::<Depth>()?;
+
+        self.game.moves.skip_ordering = true;
+        self.game.moves.skip_killers = true;
+
+        println!("{:>5}  {:>12}  {:>10}  {:>8}  {:>8}  {:>10}  {:>8}  {:>10}",
+            "depth", "nodes", "captures", "e.p.", "castles", "promotions", "checks", "checkmates");
+        for d in 1..(depth + 1) {
+            let stats = self.game.perft_stats(d);
+            println!("{:>5}  {:>12}  {:>10}  {:>8}  {:>8}  {:>10}  {:>8}  {:>10}",
+                d, stats.nodes, stats.captures, stats.en_passants, stats.castles,
+                stats.promotions, stats.checks, stats.checkmates);
+        }
+        Ok(State::Running)
+    }
+
     fn cmd_perftsuite(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
         self.game.moves.skip_ordering = true;
         self.game.moves.skip_killers = true;
 
+        let mut positions = Vec::new();
         if args.len() == 1 {
-            return Err("no <epd> given".into());
+            // Bundled positions
+            for &(fen, d, n) in PERFT_SUITE.iter() {
+                positions.push((fen.to_string(), vec![(d, n)]));
+            }
         }
-        let path = Path::new(args[1]);
-        let file = fs::read_to_string(&path)?;
+        let file = if args.len() > 1 { fs::read_to_string(Path::new(args[1]))? } else { String::new() };
         for line in file.lines() {
             let mut fields = line.split(';');
             let fen = fields.next().unwrap().trim();
-            print!("{} -> ", fen);
-            self.game.load_fen(fen)?;
+            let mut perfts = Vec::new();
             for field in fields {
                 let field = field.trim();
                 if !field.starts_with("D") {
-                    println!("");
                     return Err("invalid perftsuite epd format".into());
                 }
                 let mut it = field.split(' ');
                 let d = it.next().unwrap()[1..].parse::<Depth>()?;
-                let n = it.next().unwrap().parse::<u64>()?;
-                if self.game.perft(d) == n {
-                    print!("{}", ".".bold().green());
-                    io::stdout().flush().unwrap();
+                let n = it.next().ok_or("invalid perftsuite epd format")?.parse::<u64>()?;
+                perfts.push((d, n));
+            }
+            positions.push((fen.to_string(), perfts));
+        }
+
+        let workers = vec![self.game.clone(); self.jobs_count];
+        let total_count = positions.len();
+        let mut passed_count = 0;
+        let mut res = Ok(());
+        jobs::run(workers, positions, |game, (fen, perfts)| {
+            let mut marks = String::new();
+            if let Err(e) = game.load_fen(&fen) {
+                return (fen, Err(e.to_string()));
+            }
+            let mut passed = true;
+            for (d, n) in perfts {
+                if game.perft(d) == n {
+                    marks.push_str(&".".bold().green().to_string());
                 } else {
-                    print!("{}", "x".bold().red());
+                    marks.push_str(&"x".bold().red().to_string());
+                    passed = false;
                     break;
                 }
             }
-            println!();
-        }
+            (fen, Ok((marks, passed)))
+        }, |(fen, result)| {
+            match result {
+                Ok((marks, passed)) => {
+                    println!("{} -> {}", fen, marks);
+                    if passed {
+                        passed_count += 1;
+                    }
+                },
+                Err(e) => if res.is_ok() { res = Err(e) }
+            }
+        });
+        res?;
+
+        println!("Result {}/{}", passed_count, total_count);
         Ok(State::Running)
     }
 
@@ -631,59 +871,197 @@ impl CLI {
         } else {
             10
         };
-        let path = Path::new(args[1]);
+        let (found_count, total_count) = self.testsuite(args[1], time, true)?;
+        println!("Result {}/{}", found_count, total_count);
+        Ok(State::Running)
+    }
+
+    fn cmd_ablation(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
+        if args.len() == 1 {
+            return Err("no <epd> given".into());
+        }
+        let time = if args.len() > 2 {
+            args[2].parse::<u64>()? // `time` is given in seconds
+        } else {
+            1
+        };
+
+        let params = self.game.eval_params;
+        print!("{:<12} ", "baseline");
+        let (base_count, total_count) = self.testsuite(args[1], time, false)?;
+        println!(" {}/{}", base_count, total_count);
+
+        let mut res = Ok(());
+        for name in &EVAL_TERMS {
+            self.game.eval_params = params;
+            *self.game.eval_params.term_mut(name).unwrap() = false;
+            print!("{:<12} ", name);
+            match self.testsuite(args[1], time, false) {
+                Ok((found_count, _)) => {
+                    let diff = found_count as i64 - base_count as i64;
+                    println!(" {}/{} ({:+})", found_count, total_count, diff);
+                },
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+        self.game.eval_params = params;
+        res?;
+
+        Ok(State::Running)
+    }
+
+    fn cmd_bench(&mut self, args: &[&str]) -> Result<State, Box<dyn Error>> {
+        let depth = if args.len() > 1 {
+            args[1].parse::<Depth>()?
+        } else {
+            8
+        };
+        if depth < 1 {
+            return Err("<depth> should be at least 1".into());
+        }
+
+        // The positions are searched one after the other by a single thread
+        // from empty tables to get the same number of nodes on every run
+        let mut game = self.game.clone();
+        game.tt_resize(self.game.tt_size());
+        game.threads_count = 1;
+        game.is_search_verbose = false;
+        game.is_debug = false;
+
+        let started_at = Instant::now();
+        let mut total_nodes_count = 0;
+        for fen in BENCH_SUITE.iter() {
+            game.load_fen(fen)?;
+            game.clear_search(ClearPolicy::Clear);
+            game.clock = Clock::new(1, 0);
+            game.clock.enable_infinite();
+            let best_move = game.search(1..(depth + 1)).map_or("-".into(), |m| game.move_to_san(m));
+            println!("{} -> {} ({} nodes)", fen, best_move, game.nodes_count);
+            total_nodes_count += game.nodes_count;
+        }
+        let s = started_at.elapsed().as_secs_f64();
+        let nps = (total_nodes_count as f64) / s;
+
+        println!("Features {}", game.search_params.features.signature());
+        println!("Result {} nodes ({:.2} s, {:.2e} nps)", total_nodes_count, s, nps);
+        Ok(State::Running)
+    }
+
+    // Search each position of the given EPD file and return the number of
+    // positions solved with the total number of positions
+    fn testsuite(&mut self, path: &str, time: u64, verbose: bool) -> Result<(usize, usize), Box<dyn Error>> {
+        let path = Path::new(path);
         let file = fs::read_to_string(&path)?;
-        let mut found_count = 0;
-        let mut total_count = 0;
+        let mut positions = Vec::new();
         for mut line in file.lines() {
             if let Some(i) = line.find(";") {
                 line = &line[0..i];
             }
-            if !line.contains(" am ") && !line.contains(" bm ") {
+            if !line.contains(" am ") && !line.contains(" bm ") && !line.contains(" dm ") {
                 return Err("invalid testsuite epd format".into());
             }
 
             let i = line.find("m ").unwrap() - 1;
             let (fen, rem) = line.split_at(i);
             let (mt, moves) = rem.split_at(2);
+            positions.push((fen, mt, moves));
+        }
+
+        // Each worker has its own transposition table and prints nothing
+        // while searching to keep the output of the positions in order
+        let mut workers = Vec::with_capacity(self.jobs_count);
+        for _ in 0..self.jobs_count {
+            let mut game = self.game.clone();
+            game.tt_resize(self.game.tt_size());
+            game.is_search_verbose = false;
+            game.is_debug = false;
+            workers.push(game);
+        }
 
-            print!("{}{}{} -> ", fen, mt, moves);
+        let n = self.max_depth;
+        let mut found_count = 0;
+        let mut total_count = 0;
+        let mut res = Ok(());
+        jobs::run(workers, positions, |game, (fen, mt, moves)| {
+            if let Err(e) = game.load_fen(fen) {
+                return Err(e.to_string());
+            }
+            game.clock = Clock::new(1, time * 1000);
 
-            self.game.load_fen(fen)?;
-            self.game.clock = Clock::new(1, time * 1000);
+            // The number of moves of a direct mate must be found exactly
+            let mate = match mt {
+                "dm" => match moves.trim().parse::<Score>() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err("invalid testsuite mate distance".into())
+                },
+                _ => None
+            };
 
-            let n = self.max_depth;
-            let best_move = self.game.search(1..n).unwrap();
-            let mut best_move_str = self.game.move_to_san(best_move);
+            let best_move = game.search(1..n).unwrap();
+            let mut best_move_str = game.move_to_san(best_move);
 
             // Add `+` to move in case of check
-            let side = self.game.side();
-            self.game.make_move(best_move);
-            if self.game.is_check(side ^ 1) {
+            let side = game.side();
+            game.make_move(best_move);
+            if game.is_check(side ^ 1) {
                 best_move_str.push('+');
             }
-            self.game.undo_move(best_move);
+            game.undo_move(best_move);
 
             let found = match mt {
                 "bm" => moves.contains(&best_move_str),
                 "am" => !moves.contains(&best_move_str),
+                "dm" => {
+                    let score = game.search_result.map_or(0, |res| res.score);
+                    let moves_to_mate = mate_moves(score);
+                    if let Some(n) = moves_to_mate {
+                        best_move_str.push_str(&format!(" #{}", n));
+                    }
+                    moves_to_mate == mate
+                },
                 _    => unreachable!()
             };
+            Ok((fen, mt, moves, best_move_str, found))
+        }, |result| {
+            let (fen, mt, moves, best_move_str, found) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                    return;
+                }
+            };
             if found {
                 found_count += 1;
-                println!("{}", best_move_str.bold().green());
+            }
+            if verbose {
+                print!("{}{}{} -> ", fen, mt, moves);
+                if found {
+                    println!("{}", best_move_str.bold().green());
+                } else {
+                    println!("{}", best_move_str.bold().red());
+                }
             } else {
-                println!("{}", best_move_str.bold().red());
+                let c = if found { ".".bold().green() } else { "x".bold().red() };
+                print!("{}", c);
+                io::stdout().flush().unwrap();
             }
             total_count += 1;
-        }
-        println!("Result {}/{}", found_count, total_count);
-        Ok(State::Running)
+        });
+        res?;
+
+        Ok((found_count, total_count))
     }
 
     fn think(&mut self, play: bool) {
         let c = if play { "<" } else { "#" };
         let n = self.max_depth;
+        let side = self.game.side();
+        self.game.clock = self.game_clock.search_clock(side);
         let r = self.game.search(1..n);
         if self.game.is_debug || self.game.is_search_verbose {
             println!();
@@ -692,6 +1070,8 @@ impl CLI {
             println!("{} move {}", c, if self.show_san { self.game.move_to_san(m) } else { m.to_lan() });
 
             if play {
+                let elapsed = self.game.clock.elapsed_time();
+                self.press_clock(side, elapsed);
                 self.game.make_move(m);
                 self.game.history.push(m);
 
@@ -719,6 +1099,20 @@ fn print_error(msg: &str) {
     println!("# {} {}", "error:".bold().red(), msg);
 }
 
+fn ask_promotion_piece(_game: &Game, _m: PieceMove) -> Option<Piece> {
+    print!("Promote to [q, r, b, n]: ");
+    io::stdout().flush().ok()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).ok()?;
+    match line.trim().to_lowercase().as_str() {
+        "q" => Some(QUEEN),
+        "r" => Some(ROOK),
+        "b" => Some(BISHOP),
+        "n" => Some(KNIGHT),
+        _ => None
+    }
+}
+
 fn history_path() -> Option<PathBuf> {
     if let Some(data_dir) = dirs::data_dir() {
         Some(data_dir.join("littlewing").join("history"))
@@ -738,13 +1132,13 @@ impl Completer for CommandHelper {
     fn complete(&self, line: &str, _pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<String>), ReadlineError> {
         let move_params = self.move_params.iter().map(AsRef::as_ref).collect();
         let play_params = vec!["black", "white", "none"];
-        let conf_params = vec!["board", "color", "coord", "debug", "think", "san"];
-        let load_params = vec!["fen", "pgn", "help"];
-        let save_params = vec!["fen", "pgn", "help"];
+        let conf_params = vec!["board", "color", "coord", "debug", "think", "san", "autoqueen"];
+        let load_params = vec!["fen", "pgn", "url", "960", "odds", "options", "help"];
+        let save_params = vec!["fen", "epd", "pgn", "options", "help"];
         let commands = vec![
             "help", "quit", "init", "load", "save", "play", "hint", "eval",
-            "undo", "move", "time", "show", "hide", "core", "hash", "perft",
-            "perftsuite", "testsuite", "divide", "xboard", "uci"
+            "undo", "move", "stats", "time", "show", "hide", "core", "jobs", "hash", "perft",
+            "perftstats", "perftsuite", "testsuite", "ablation", "bench", "divide", "xboard", "uci"
         ];
 
         let mut options = Vec::new();
@@ -774,6 +1168,7 @@ impl Completer for CommandHelper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::env;
 
     #[test]
     fn test_undo() {
@@ -795,11 +1190,100 @@ mod tests {
         assert!(true);
     }
 
+    #[test]
+    fn test_auto_queen() {
+        let mut cli = CLI::new();
+        cli.game.load_fen("1q3rk1/P5pp/8/8/8/8/6PP/6K1 w - - 0 1").unwrap();
+
+        // No prompt without a promotion to choose
+        cli.cmd_move(&["move", "a7a8r"]).unwrap();
+        cli.cmd_undo().unwrap();
+
+        cli.cmd_config(true, &["show", "autoqueen"]).unwrap();
+        cli.cmd_move(&["move", "axb8"]).unwrap();
+        assert_eq!(cli.game.history.last().unwrap().promotion_kind(), QUEEN);
+    }
+
+    #[test]
+    fn test_time() {
+        let mut cli = CLI::new();
+        cli.cmd_time(&["time", "2", "60"]).unwrap();
+        assert_eq!(cli.game_clock.moves_to_go(WHITE), 2);
+
+        cli.cmd_time(&["time", "1+1"]).unwrap();
+        assert_eq!(cli.game_clock.remaining_time(BLACK), 1000);
+        cli.cmd_move(&["move", "e4"]).unwrap();
+        cli.cmd_play(&[]).unwrap();
+        assert!(cli.game_clock.remaining_time(BLACK) > 1000);
+        assert!(cli.game_clock.remaining_time(BLACK) <= 2000);
+
+        assert!(cli.cmd_time(&["time"]).is_err());
+        assert!(cli.cmd_time(&["time", "?"]).is_err());
+    }
+
+    #[test]
+    fn test_load_odds() {
+        let mut cli = CLI::new();
+        cli.cmd_load(&["load", "odds", "knight", "black", "60+1"]).unwrap();
+        assert_eq!(cli.game.to_fen(), "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(cli.game_clock.remaining_time(WHITE), 5 * 60 * 1000);
+        assert_eq!(cli.game_clock.remaining_time(BLACK), 60 * 1000);
+        assert_eq!(cli.game_clock.control(BLACK).increment, 1000);
+
+        assert!(cli.cmd_load(&["load", "odds", "knight", "black", "?"]).is_err());
+    }
+
     #[test]
     fn test_divide() {
         let mut cli = CLI::new();
 
         cli.cmd_divide(&["divide", "2"]).unwrap();
-        assert!(true);
+        assert!(cli.cmd_divide(&["divide"]).is_err());
+        assert!(cli.cmd_divide(&["divide", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut cli = CLI::new();
+        assert!(cli.cmd_stats().is_err());
+
+        cli.game.clock = Clock::new(1, 60 * 1000);
+        cli.game.search(1..4);
+        cli.cmd_stats().unwrap();
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut cli = CLI::new();
+        cli.cmd_hash(&["hash"]).unwrap();
+
+        let memory = cli.game.tt_size();
+        cli.cmd_hash(&["hash", "4"]).unwrap();
+        assert_eq!(cli.game.tt_size(), 4 << 20);
+        assert_ne!(cli.game.tt_size(), memory);
+    }
+
+    #[test]
+    fn test_bench() {
+        let mut cli = CLI::new();
+
+        cli.cmd_bench(&["bench", "2"]).unwrap();
+        assert!(cli.cmd_bench(&["bench", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_testsuite_direct_mate() {
+        let path = env::temp_dir().join("littlewing_test_testsuite.epd");
+        let epd = "r6k/6pp/8/4N3/2Q5/8/8/6K1 w - - dm 4;\n\
+                   r6k/6pp/8/4N3/2Q5/8/8/6K1 w - - dm 3;\n";
+        fs::write(&path, epd).unwrap();
+
+        let mut cli = CLI::new();
+        cli.jobs_count = 1;
+        let res = cli.testsuite(path.to_str().unwrap(), 2, false).unwrap();
+        assert_eq!(res, (1, 2));
+
+        fs::write(&path, "r6k/6pp/8/4N3/2Q5/8/8/6K1 w - - dm x;\n").unwrap();
+        assert!(cli.testsuite(path.to_str().unwrap(), 1, false).is_err());
     }
 }
@@ -2,22 +2,27 @@ use std::io;
 use std::thread;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use color::*;
 use common::*;
 use clock::Clock;
 use fen::FEN;
 use game::Game;
+use options::{Options, OptionType, DEFAULT_PROFILE};
+use piece_move::PieceMove;
 use piece_move_generator::PieceMoveGenerator;
+use piece_move_list::PieceMoveList;
 use piece_move_notation::PieceMoveNotation;
 use search::Search;
+use transposition_table::TranspositionTable;
 use protocols::Protocol;
 use version;
 
 pub struct UCI {
     pub game: Game,
     max_depth: Depth,
-    searcher: Option<thread::JoinHandle<()>>,
+    searcher: Option<thread::JoinHandle<(TranspositionTable, PieceMoveList)>>,
     print_bestmove: Arc<AtomicBool>,
 }
 
@@ -33,8 +38,12 @@ impl UCI {
     pub fn run(&mut self) {
         self.game.protocol = Protocol::UCI;
         self.game.is_search_verbose = true;
+        if let Err(e) = self.game.load_options(DEFAULT_PROFILE) {
+            println!("info string {}", e);
+        }
         println!("id name {}", version());
         println!("id author Vincent Ollivier");
+        self.print_options();
         println!("uciok");
         loop {
             let mut cmd = String::new();
@@ -43,8 +52,10 @@ impl UCI {
             match args[0] {
                 "quit"       => break,
                 "stop"       => self.cmd_stop(),
+                "ponderhit"  => self.cmd_ponderhit(),
                 "isready"    => self.cmd_isready(),
                 "ucinewgame" => self.cmd_ucinewgame(),
+                "setoption"  => self.cmd_setoption(&args),
                 "position"   => self.cmd_position(&args),
                 "go"         => self.cmd_go(&args),
                 _            => continue, // Ignore unknown commands
@@ -57,27 +68,71 @@ impl UCI {
         self.stop_search();
     }
 
+    fn cmd_ponderhit(&mut self) {
+        self.game.clock.ponderhit();
+    }
+
     fn cmd_isready(&mut self) {
         println!("readyok");
     }
 
+    fn cmd_setoption(&mut self, args: &[&str]) {
+        self.abort_search();
+
+        // setoption name <id> [value <x>]
+        let mut name = Vec::new();
+        let mut value = Vec::new();
+        let mut is_value = false;
+        for &arg in args.iter().skip(2) {
+            if arg == "value" && !is_value {
+                is_value = true;
+            } else if is_value {
+                value.push(arg);
+            } else {
+                name.push(arg);
+            }
+        }
+
+        if let Err(e) = self.game.set_option(&name.join(" "), &value.join(" ")) {
+            println!("info string {}", e);
+        }
+    }
+
     fn cmd_ucinewgame(&mut self) {
         self.abort_search();
 
         self.max_depth = (MAX_PLY - 10) as Depth;
-        self.game.clear();
+        self.game.new_game();
     }
 
     fn cmd_go(&mut self, args: &[&str]) {
         self.abort_search();
 
+        // go perft <depth>
+        if args.len() > 1 && args[1] == "perft" {
+            match args.get(2).and_then(|arg| arg.parse::<Depth>().ok()) {
+                Some(depth) if depth > 0 => self.go_perft(depth),
+                _ => println!("info string no <depth> given"),
+            }
+            return;
+        }
+
         let side = self.game.side();
         let mut time = u64::max_value(); // Infinite time
         let mut moves = 1;
         let mut next_arg_is_time = false;
         let mut next_arg_is_moves = false;
+        let mut is_infinite = false;
+        let mut is_ponder = false;
+        let mut is_fixed_time = false;
         for &arg in args {
             match arg {
+                "infinite" => {
+                    is_infinite = true;
+                },
+                "ponder" => {
+                    is_ponder = true;
+                },
                 "wtime" => {
                     if side == WHITE {
                         next_arg_is_time = true;
@@ -90,6 +145,7 @@ impl UCI {
                 },
                 "movetime" => {
                     next_arg_is_time = true;
+                    is_fixed_time = true;
                 }
                 "movestogo" => {
                     next_arg_is_moves = true;
@@ -108,10 +164,38 @@ impl UCI {
         // FIXME: time increment is ignored
         self.game.clock = Clock::new(moves, time);
         self.game.clock.disable_level();
+        if is_infinite {
+            self.game.clock.enable_infinite();
+        }
+        if is_fixed_time {
+            self.game.clock.enable_fixed_time();
+        }
+        if is_ponder {
+            self.game.clock.enable_ponder();
+        }
         self.print_bestmove.store(true, Ordering::Relaxed);
         self.start_search();
     }
 
+    // Print the number of nodes at the given depth for each root move
+    fn go_perft(&mut self, depth: Depth) {
+        let skip_ordering = self.game.moves.skip_ordering;
+        let skip_killers = self.game.moves.skip_killers;
+        self.game.moves.skip_ordering = true;
+        self.game.moves.skip_killers = true;
+
+        let mut divide = Vec::new();
+        let nodes_count = self.game.perft_with(depth, |m, n| divide.push((m, n)));
+        for &(m, n) in &divide {
+            println!("{}: {}", self.game.move_to_lan(m), n);
+        }
+        println!();
+        println!("Nodes searched: {}", nodes_count);
+
+        self.game.moves.skip_ordering = skip_ordering;
+        self.game.moves.skip_killers = skip_killers;
+    }
+
     fn cmd_position(&mut self, args: &[&str]) {
         self.abort_search();
 
@@ -142,7 +226,10 @@ impl UCI {
             }
         }
 
-        self.game.load_fen(&fen.join(" ")).unwrap();
+        if let Err(e) = self.game.load_fen(&fen.join(" ")) {
+            println!("info string {}", e);
+            return;
+        }
 
         for s in moves {
             let m = self.game.move_from_lan(s);
@@ -151,6 +238,29 @@ impl UCI {
         }
     }
 
+    fn print_options(&self) {
+        for option in self.game.options() {
+            match option.kind {
+                OptionType::Check => {
+                    println!("option name {} type check default {}", option.name, option.value);
+                },
+                OptionType::Spin(min, max) => {
+                    println!("option name {} type spin default {} min {} max {}", option.name, option.value, min, max);
+                },
+                OptionType::Combo(values) => {
+                    let vars: Vec<String> = values.iter().map(|v| format!("var {}", v)).collect();
+                    println!("option name {} type combo default {} {}", option.name, option.value, vars.join(" "));
+                },
+                OptionType::String => {
+                    println!("option name {} type string default {}", option.name, option.value);
+                },
+                OptionType::Button => {
+                    println!("option name {} type button", option.name);
+                }
+            }
+        }
+    }
+
     fn start_search(&mut self) {
         let n = self.max_depth;
         let mut game = self.game.clone();
@@ -163,12 +273,30 @@ impl UCI {
         self.searcher = Some(builder.spawn(move || {
             let res = game.search(1..n);
 
+            // In infinite mode the best move must not be sent before the
+            // GUI tells us to stop, even if the search is already over, and
+            // likewise when pondering before the ponderhit.
+            if game.clock.is_infinite() || game.clock.is_pondering() {
+                while !game.clock.is_stopped() && (game.clock.is_infinite() || game.clock.is_pondering()) {
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+
             if print_bestmove.load(Ordering::Relaxed) {
                 match res {
-                    Some(m) => println!("bestmove {}", m.to_lan()),
-                    None    => println!("bestmove 0000")
+                    Some(m) => {
+                        let reply = if game.show_ponder { ponder_move(&mut game, m) } else { None };
+                        match reply {
+                            Some(reply) => println!("bestmove {} ponder {}", game.move_to_lan(m), reply),
+                            None        => println!("bestmove {}", game.move_to_lan(m))
+                        }
+                    },
+                    None => println!("bestmove 0000")
                 }
             }
+
+            // Give back the search tables to keep them between moves
+            (game.tt, game.moves)
         }).unwrap());
     }
 
@@ -177,7 +305,9 @@ impl UCI {
 
         // Wait for current search to end
         if let Some(searcher) = self.searcher.take() {
-            searcher.join().unwrap();
+            let (tt, moves) = searcher.join().unwrap();
+            self.game.tt = tt;
+            self.game.moves = moves;
         }
     }
 
@@ -186,3 +316,42 @@ impl UCI {
         self.stop_search();
     }
 }
+
+// Get the expected reply to the given move from the transposition table,
+// in LAN
+fn ponder_move(game: &mut Game, m: PieceMove) -> Option<String> {
+    game.make_move(m);
+    let hash = game.positions.top().hash;
+    let reply = game.tt.get(hash).map(|t| t.best_move()).
+        filter(|&reply| !reply.is_null() && game.get_moves().contains(&reply)).
+        map(|reply| game.move_to_lan(reply));
+    game.undo_move(m);
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_go_time_pressure() {
+        let mut uci = UCI::new();
+        uci.cmd_position(&["position", "startpos"]);
+        let hash = uci.game.positions.top().hash;
+
+        // A short time to move is searched normally, storing the root
+        uci.cmd_go(&["go", "movetime", "50"]);
+        assert!(!uci.game.clock.is_time_pressure());
+        let (mut tt, _) = uci.searcher.take().unwrap().join().unwrap();
+        assert!(tt.get(hash).is_some());
+
+        uci.cmd_go(&["go", "wtime", "50", "btime", "50"]);
+        assert!(uci.game.clock.is_time_pressure());
+        uci.abort_search();
+
+        uci.cmd_go(&["go", "ponder", "wtime", "50", "btime", "50"]);
+        assert!(!uci.game.clock.is_time_pressure());
+        uci.cmd_ponderhit();
+        uci.searcher.take().unwrap().join().unwrap();
+    }
+}
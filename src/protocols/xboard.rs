@@ -4,19 +4,23 @@ use regex::Regex;
 use color::*;
 use common::*;
 use attack::Attack;
-use clock::Clock;
+use clock::{GameClock, TimeControl};
 use fen::FEN;
 use game::Game;
+use options::{Options, DEFAULT_PROFILE};
 use piece_move_generator::PieceMoveGenerator;
 use piece_move_notation::PieceMoveNotation;
 use search::Search;
+use variant::{Variant, VARIANTS};
 use protocols::Protocol;
 use version;
 
 pub struct XBoard {
     pub game: Game,
     max_depth: Depth,
-    force: bool
+    force: bool,
+    game_clock: GameClock,
+    engine_time: Option<u64> // Time given by the last `time` command
 }
 
 impl XBoard {
@@ -24,12 +28,17 @@ impl XBoard {
         XBoard {
             game: Game::from_fen(DEFAULT_FEN).unwrap(),
             max_depth: (MAX_PLY - 10) as Depth,
-            force: false
+            force: false,
+            game_clock: GameClock::new(&[TimeControl::new(40, 5 * 60 * 1000, 0)]),
+            engine_time: None
         }
     }
     pub fn run(&mut self) {
         self.game.protocol = Protocol::XBoard;
         println!(""); // Acknowledge XBoard mode
+        if let Err(e) = self.game.load_options(DEFAULT_PROFILE) {
+            println!("# error: {}", e);
+        }
         loop {
             let mut line = String::new();
             io::stdin().read_line(&mut line).unwrap();
@@ -51,6 +60,8 @@ impl XBoard {
                 "sd"       => self.cmd_depth(&args),
                 "level"    => self.cmd_level(&args),
                 "protover" => self.cmd_protover(&args),
+                "result"   => self.cmd_result(&args),
+                "variant"  => self.cmd_variant(&args),
                 _          => self.parse_move(&args)
             }
         }
@@ -62,8 +73,22 @@ impl XBoard {
 
     fn cmd_new(&mut self) {
         self.max_depth = (MAX_PLY - 10) as Depth;
-        self.game.clear();
+        self.game.new_game();
+        self.game.variant = Variant::Standard;
         self.game.load_fen(DEFAULT_FEN).unwrap();
+        self.game_clock.reset();
+        self.engine_time = None;
+    }
+
+    fn cmd_variant(&mut self, args: &[&str]) {
+        if args.len() == 1 {
+            panic!("no variant given");
+        }
+
+        match Variant::from_name(args[1]) {
+            Some(variant) => self.game.variant = variant,
+            None => println!("Error (unsupported variant): {}", args[1])
+        }
     }
 
     fn cmd_go(&mut self) {
@@ -79,25 +104,26 @@ impl XBoard {
         self.game.is_search_verbose = false;
     }
 
+    // Take back the last move in force mode
     fn cmd_undo(&mut self) {
-        if self.game.history.len() > 0 {
-            let m = self.game.history.pop().unwrap();
-            self.game.undo_move(m);
+        if self.game.takeback(1) == 0 {
+            println!("Error (no move to undo): undo");
         }
     }
 
+    // Take back the last moves of both sides, keeping the color played by
+    // the engine
     fn cmd_remove(&mut self) {
-        let m = self.game.history.pop().unwrap();
-        self.game.undo_move(m);
-
-        let m = self.game.history.pop().unwrap();
-        self.game.undo_move(m);
+        if self.game.takeback(2) < 2 {
+            println!("Error (no move to remove): remove");
+        }
     }
 
     fn cmd_time(&mut self, args: &[&str]) {
-        // `time` is given in centiseconds
+        // `time` is given in centiseconds, for the side played by the engine
+        // on its next move
         let time = args[1].parse::<u64>().unwrap();
-        self.game.clock.set_time(time * 10);
+        self.engine_time = Some(time * 10);
     }
 
     fn cmd_ping(&mut self, args: &[&str]) {
@@ -112,18 +138,24 @@ impl XBoard {
         let fen = args[1..].join(" ");
 
         self.game.clear();
-        self.game.load_fen(&fen).unwrap();
+        if let Err(e) = self.game.load_fen(&fen) {
+            println!("tellusererror Illegal position: {}", e);
+        }
     }
 
-    fn cmd_level(&mut self, args: &[&str]) {
-        let mut moves = args[1].parse::<u16>().unwrap();
-
-        if moves == 0 {
-            // FIXME: 0 means "play the whole game in this time control period"
-            // which is unsupported by our time management so we set it to
-            // another value instead.
-            moves = 60;
+    fn cmd_result(&mut self, args: &[&str]) {
+        if self.game.learning.is_enabled && args.len() > 1 {
+            let game = self.game.clone();
+            self.game.learning.record(&game, args[1]);
+            if let Err(e) = self.game.learning.save() {
+                println!("# error: {}", e);
+            }
         }
+    }
+
+    fn cmd_level(&mut self, args: &[&str]) {
+        // 0 means "play the whole game in this time control period"
+        let moves = args[1].parse::<u16>().unwrap();
 
         // `time` is given in `mm:ss` or `ss`.
         let time = match args[2].find(':') {
@@ -132,9 +164,11 @@ impl XBoard {
             None    => args[2].parse::<u64>().unwrap()
         };
 
-        // FIXME: time increment is ignored
+        // `inc` is given in seconds
+        let inc = args.get(3).map_or(0.0, |inc| inc.parse::<f64>().unwrap());
 
-        self.game.clock = Clock::new(moves, time * 1000);
+        let control = TimeControl::new(moves, time * 1000, (inc * 1000.0).round() as u64);
+        self.game_clock = GameClock::new(&[control]);
     }
 
     fn cmd_depth(&mut self, args: &[&str]) {
@@ -153,6 +187,8 @@ impl XBoard {
     #[allow(unused_variables)] // TODO: remove that
     fn cmd_protover(&mut self, args: &[&str]) {
         println!("feature myname=\"{}\"", version());
+        let variants: Vec<&str> = VARIANTS.iter().map(|v| v.xboard_name()).collect();
+        println!("feature variants=\"{}\"", variants.join(","));
         println!("feature sigint=0 ping=1 setboard=1 memory=1 smp=1 done=1");
         // TODO: check that the features got accepted
     }
@@ -173,6 +209,12 @@ impl XBoard {
     }
 
     fn think(&mut self) {
+        let side = self.game.side();
+        if let Some(time) = self.engine_time.take() {
+            self.game_clock.set_remaining_time(side, time);
+        }
+        self.game.clock = self.game_clock.search_clock(side);
+
         let n = self.max_depth;
         match self.game.search(1..n) {
             None => {
@@ -185,6 +227,8 @@ impl XBoard {
                 }
             },
             Some(m) => {
+                let elapsed = self.game.clock.elapsed_time();
+                self.game_clock.press(side, elapsed);
                 self.game.make_move(m);
                 self.game.history.push(m);
 
@@ -0,0 +1,76 @@
+use common::*;
+use piece_move::PieceMove;
+
+/// A triangular table of the principal variations found by the search at
+/// each ply, the variation of a ply being made of its best move followed by
+/// the variation of the next ply
+#[derive(Clone)]
+pub struct PvTable {
+    moves: Vec<[PieceMove; MAX_PLY]>,
+    lengths: [usize; MAX_PLY + 1]
+}
+
+impl PvTable {
+    pub fn new() -> PvTable {
+        PvTable {
+            moves: vec![[PieceMove::new_null(); MAX_PLY]; MAX_PLY],
+            lengths: [0; MAX_PLY + 1]
+        }
+    }
+
+    /// Clear the variation of the given ply before searching it
+    pub fn clear(&mut self, ply: usize) {
+        if ply < MAX_PLY {
+            self.lengths[ply] = 0;
+        }
+    }
+
+    /// Set the variation of the given ply to the given move followed by the
+    /// variation of the next ply
+    pub fn update(&mut self, ply: usize, m: PieceMove) {
+        debug_assert!(ply < MAX_PLY);
+        let n = if ply + 1 < MAX_PLY { self.lengths[ply + 1] } else { 0 };
+        let (head, tail) = self.moves.split_at_mut(ply + 1);
+        head[ply][0] = m;
+        if n > 0 {
+            head[ply][1..(n + 1)].copy_from_slice(&tail[0][0..n]);
+        }
+        self.lengths[ply] = n + 1;
+    }
+
+    /// Get the variation of the given ply
+    pub fn get(&self, ply: usize) -> &[PieceMove] {
+        &self.moves[ply][0..self.lengths[ply]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use square::*;
+
+    #[test]
+    fn test_pv_table() {
+        let mut pv = PvTable::new();
+        let e4 = PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH);
+        let e5 = PieceMove::new(E7, E5, DOUBLE_PAWN_PUSH);
+        let nf3 = PieceMove::new(G1, F3, QUIET_MOVE);
+        let d5 = PieceMove::new(D7, D5, DOUBLE_PAWN_PUSH);
+
+        pv.clear(0);
+        pv.clear(1);
+        pv.clear(2);
+        pv.update(2, nf3);
+        pv.update(1, e5);
+        pv.update(0, e4);
+        assert_eq!(pv.get(0), &[e4, e5, nf3]);
+
+        // A better move found at the second ply after a new search of the
+        // third one
+        pv.clear(2);
+        pv.update(1, d5);
+        pv.update(0, e4);
+        assert_eq!(pv.get(0), &[e4, d5]);
+        assert_eq!(pv.get(2), &[]);
+    }
+}
@@ -0,0 +1,63 @@
+use rand::{self, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+/// Seedable pseudo-random number generator, giving the same numbers from
+/// the same seed on every platform
+#[derive(Clone, Debug)]
+pub struct Random {
+    seed: u64,
+    rng: XorShiftRng
+}
+
+impl Random {
+    /// Create a generator from the given seed
+    pub fn new(seed: u64) -> Random {
+        Random {
+            seed,
+            rng: XorShiftRng::seed_from_u64(seed)
+        }
+    }
+
+    /// Create a generator from a random seed, that can be given back by
+    /// `seed` to reproduce its numbers
+    pub fn from_entropy() -> Random {
+        // The seed is kept positive to fit in a spin option
+        Random::new(rand::random::<u64>() >> 1)
+    }
+
+    /// Get the seed of the generator
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get the next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    /// Get the next random number below the given bound
+    pub fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        let mut c = Random::new(43);
+        let numbers: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        assert_eq!(numbers, (0..10).map(|_| b.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(numbers, (0..10).map(|_| c.next_u64()).collect::<Vec<u64>>());
+        assert_eq!(a.seed(), 42);
+
+        assert!((0..100).all(|_| a.next_below(960) < 960));
+
+        let random = Random::from_entropy();
+        assert_eq!(random.seed() >> 63, 0);
+    }
+}
@@ -1,26 +1,207 @@
 use std::cmp;
 use std::thread;
-use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::ops::{AddAssign, Range};
 
 use color::*;
-use piece::*;
 use common::*;
-use attack::Attack;
+use piece::*;
+use attack::{Attack, piece_attacks};
 use bitboard::BitboardExt;
+use crash;
 use eval::Eval;
 use fen::FEN;
 use game::Game;
+use learning::LEARNING_MAX_PLY;
+use params::GamePhase;
+use perft_table::PerftTable;
 use piece_move::PieceMove;
 use piece_move_generator::PieceMoveGenerator;
+use piece_move_list::PieceMoveListStage;
 use piece_move_notation::PieceMoveNotation;
 use protocols::Protocol;
-use transposition::Bound;
+use trace::TraceRecord;
+use transposition::{Bound, Transposition};
+
+#[cfg(feature = "verify-search")]
+use params::SearchFeatures;
+#[cfg(feature = "verify-search")]
+use trace::SearchTrace;
+
+#[cfg(feature = "verify-search")]
+const VERIFY_SEARCH_INTERVAL: u64 = 64;
+
+#[cfg(feature = "verify-search")]
+const VERIFY_MAX_DEPTH: Depth = 3;
+
+/// Function given the root position with its legal moves in the order of
+/// the search and their ordering scores, to reorder them before searching
+/// them, the moves removed being left out of the search
+pub type RootOrdering = dyn Fn(&Game, &mut Vec<(PieceMove, Score)>) + Send + Sync;
+
+/// Result of the deepest iteration completed by the last search
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchResult {
+    pub hash: u64, // Position of the root
+    pub best_move: PieceMove,
+    pub score: Score,
+    pub depth: Depth,
+    pub nodes_count: u64
+}
+
+/// Statistics of a thread of the last search
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct ThreadStats {
+    pub nodes_count: u64,
+    pub tt_lookups_count: u64,
+    pub tt_hits_count: u64,
+    pub depth: Depth, // Deepest iteration completed
+    pub search_stats: SearchStats
+}
+
+impl ThreadStats {
+    /// Get the rate of the lookups in the transposition table finding the
+    /// position
+    pub fn tt_hit_rate(&self) -> f64 {
+        if self.tt_lookups_count == 0 {
+            return 0.0;
+        }
+        self.tt_hits_count as f64 / self.tt_lookups_count as f64
+    }
+}
+
+/// Statistics of the nodes visited by the last search, to evaluate the
+/// changes made to the search beyond their playing strength
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct SearchStats {
+    /// Number of nodes of the main search with an open window
+    pub pv_nodes_count: u64,
+
+    /// Number of nodes of the main search with a zero window
+    pub non_pv_nodes_count: u64,
+
+    /// Number of nodes of the quiescence search
+    pub quiescence_nodes_count: u64,
+
+    /// Number of lookups in the transposition table
+    pub tt_probes_count: u64,
+
+    /// Number of lookups finding the position
+    pub tt_hits_count: u64,
+
+    /// Number of nodes returning the score found in the transposition table
+    pub tt_cutoffs_count: u64,
+
+    /// Number of beta cutoffs produced by a move
+    pub cutoffs_count: u64,
+
+    /// Number of beta cutoffs produced by the first legal move searched
+    pub first_move_cutoffs_count: u64,
+
+    /// Number of null moves searched
+    pub null_moves_count: u64,
+
+    /// Number of null moves producing a beta cutoff
+    pub null_move_cutoffs_count: u64
+}
+
+impl SearchStats {
+    /// Get the number of nodes visited by the main and quiescence searches
+    pub fn nodes_count(&self) -> u64 {
+        self.pv_nodes_count + self.non_pv_nodes_count + self.quiescence_nodes_count
+    }
+
+    /// Get the share of the nodes visited by the quiescence search
+    pub fn quiescence_share(&self) -> f64 {
+        rate(self.quiescence_nodes_count, self.nodes_count())
+    }
+
+    /// Get the rate of the lookups in the transposition table finding the
+    /// position
+    pub fn tt_hit_rate(&self) -> f64 {
+        rate(self.tt_hits_count, self.tt_probes_count)
+    }
+
+    /// Get the rate of the lookups in the transposition table returning
+    /// the score of the position without searching it
+    pub fn tt_cutoff_rate(&self) -> f64 {
+        rate(self.tt_cutoffs_count, self.tt_probes_count)
+    }
+
+    /// Get the rate of beta cutoffs produced by the first move searched
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        rate(self.first_move_cutoffs_count, self.cutoffs_count)
+    }
+
+    /// Get the rate of null moves producing a beta cutoff
+    pub fn null_move_cutoff_rate(&self) -> f64 {
+        rate(self.null_move_cutoffs_count, self.null_moves_count)
+    }
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: SearchStats) {
+        self.pv_nodes_count += other.pv_nodes_count;
+        self.non_pv_nodes_count += other.non_pv_nodes_count;
+        self.quiescence_nodes_count += other.quiescence_nodes_count;
+        self.tt_probes_count += other.tt_probes_count;
+        self.tt_hits_count += other.tt_hits_count;
+        self.tt_cutoffs_count += other.tt_cutoffs_count;
+        self.cutoffs_count += other.cutoffs_count;
+        self.first_move_cutoffs_count += other.first_move_cutoffs_count;
+        self.null_moves_count += other.null_moves_count;
+        self.null_move_cutoffs_count += other.null_move_cutoffs_count;
+    }
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    count as f64 / total as f64
+}
+
+/// Number of leaf nodes of each kind found by perft at a given depth
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct PerftStats {
+    pub nodes: u64,
+    pub captures: u64, // Including en passant captures
+    pub en_passants: u64,
+    pub castles: u64,
+    pub promotions: u64,
+    pub checks: u64, // Including checkmates
+    pub checkmates: u64
+}
+
+impl AddAssign for PerftStats {
+    fn add_assign(&mut self, other: PerftStats) {
+        self.nodes += other.nodes;
+        self.captures += other.captures;
+        self.en_passants += other.en_passants;
+        self.castles += other.castles;
+        self.promotions += other.promotions;
+        self.checks += other.checks;
+        self.checkmates += other.checkmates;
+    }
+}
 
 /// Search the game
 pub trait Search {
     /// Search the number of legal moves at the given depth
     fn perft(&mut self, depth: Depth) -> u64;
 
+    /// Search the number of legal moves at the given depth, calling the
+    /// given function with each legal root move and its number of nodes
+    fn perft_with<F>(&mut self, depth: Depth, f: F) -> u64 where F: FnMut(PieceMove, u64);
+
+    /// Search the number of legal moves at the given depth, keeping the
+    /// number of nodes of the positions already counted in the given table
+    fn perft_hashed(&mut self, depth: Depth, table: &mut PerftTable) -> u64;
+
+    /// Search the number of legal moves of each kind at the given depth
+    fn perft_stats(&mut self, depth: Depth) -> PerftStats;
+
     /// Searh the best move at the given depth range
     fn search(&mut self, depths: Range<Depth>) -> Option<PieceMove>;
 
@@ -35,17 +216,44 @@ pub trait Search {
 
     fn is_mate(&mut self) -> bool;
     fn get_moves(&mut self) -> Vec<PieceMove>;
+
+    /// Get the legal moves of the root position in the order of the search
+    /// with their ordering scores
+    fn root_moves(&mut self) -> Vec<(PieceMove, Score)>;
 }
 
 trait SearchExt {
     fn print_debug_init(&self, depth: Depth);
+    fn search_time_pressure(&mut self) -> Option<PieceMove>;
     fn print_thinking_init(&self);
-    fn print_thinking(&mut self, depth: Depth, score: Score, m: PieceMove);
-    fn get_pv(&mut self, depth: Depth) -> String;
+    fn print_threads_stats(&self);
+    fn thread_stats(&self) -> ThreadStats;
+    fn probe_tt(&mut self, hash: u64) -> Option<Transposition>;
+    fn trace_record(&self, record: TraceRecord);
+    fn publish_nodes_count(&self);
+    fn total_nodes_count(&self) -> u64;
+    fn print_thinking(&mut self, depth: Depth, score: Score, bound: Bound, m: PieceMove);
+    fn print_san_pv(&mut self, depth: Depth);
+    fn get_pv(&mut self, is_san_format: bool) -> String;
+    fn allows_repetition(&mut self) -> bool;
+    fn has_upcoming_repetition(&self, ply: usize) -> bool;
+    fn repetition_adjustment(&mut self, score: Score) -> Score;
+    fn draw_score(&self, ply: usize) -> Score;
+
+    #[cfg(feature = "verify-search")]
+    fn verify_zero_window(&mut self, alpha: Score, depth: Depth, ply: usize);
 }
 
 impl Search for Game {
     fn perft(&mut self, depth: Depth) -> u64 {
+        match depth {
+            0 => 1,
+            1 => self.count_legal_moves(), // Bulk counting of the leaves
+            _ => self.perft_with(depth, |_, _| {})
+        }
+    }
+
+    fn perft_with<F>(&mut self, depth: Depth, mut f: F) -> u64 where F: FnMut(PieceMove, u64) {
         if depth == 0 {
             1
         } else {
@@ -55,7 +263,9 @@ impl Search for Game {
             while let Some(m) = self.next_move() {
                 self.make_move(m);
                 if !self.is_check(side) {
-                    r += self.perft(depth - 1);
+                    let n = self.perft(depth - 1);
+                    f(m, n);
+                    r += n;
                 }
                 self.undo_move(m);
             }
@@ -63,36 +273,118 @@ impl Search for Game {
         }
     }
 
+    fn perft_hashed(&mut self, depth: Depth, table: &mut PerftTable) -> u64 {
+        if depth < 2 {
+            return self.perft(depth);
+        }
+
+        let hash = self.positions.top().hash;
+        if let Some(n) = table.get(hash, depth) {
+            return n;
+        }
+
+        let side = self.side();
+        self.moves.clear();
+        let mut r = 0;
+        while let Some(m) = self.next_move() {
+            self.make_move(m);
+            if !self.is_check(side) {
+                r += self.perft_hashed(depth - 1, table);
+            }
+            self.undo_move(m);
+        }
+
+        table.set(hash, depth, r);
+        r
+    }
+
+    fn perft_stats(&mut self, depth: Depth) -> PerftStats {
+        let mut stats = PerftStats::default();
+        if depth == 0 {
+            stats.nodes = 1;
+            return stats;
+        }
+
+        let side = self.side();
+        self.moves.clear();
+        while let Some(m) = self.next_move() {
+            self.make_move(m);
+            if !self.is_check(side) {
+                if depth > 1 {
+                    stats += self.perft_stats(depth - 1);
+                } else {
+                    stats.nodes += 1;
+                    if m.is_capture() || m.is_en_passant() {
+                        stats.captures += 1;
+                    }
+                    if m.is_en_passant() {
+                        stats.en_passants += 1;
+                    }
+                    if m.is_castle() {
+                        stats.castles += 1;
+                    }
+                    if m.is_promotion() {
+                        stats.promotions += 1;
+                    }
+                    if self.is_check(side ^ 1) {
+                        stats.checks += 1;
+                        if self.is_mate() {
+                            stats.checkmates += 1;
+                        }
+                    }
+                }
+            }
+            self.undo_move(m);
+        }
+        stats
+    }
+
     fn search(&mut self, depths: Range<Depth>) -> Option<PieceMove> {
         self.nodes_count = 0;
-        self.tt.reset();
+        self.search_stats = SearchStats::default();
+        self.search_result = None;
+        self.clear_search(self.search_params.clear_between_moves);
 
-        // NOTE: `clear_all()` will zero everything internally, including
+        // NOTE: `clear_plies()` will zero every ply internally, including
         // ply counter, while `clear()` will just reset the counter for
         // the current ply.
-        // By using `clear_all()` we make sure that we can always search
+        // By using `clear_plies()` we make sure that we can always search
         // very deep, even at the end of a very long game. But we loose
         // the ability to undo moves outside of the search function unless
         // we make a special case in `undo_move` for the root. In that special
         // case we don't decrement the ply counter that is already at 0.
-        self.moves.clear_all();
+        self.moves.clear_plies();
 
         self.clock.start(self.positions.len());
+        crash::record_search(self, depths.clone());
 
         let n = self.threads_count;
 
         if self.is_debug {
             println!("# using {} threads", n);
+
+            // Recover from a corrupted game state before searching it
+            if !self.is_consistent() {
+                println!("# inconsistent game state, rebuilding it from the board");
+                self.rebuild_from_board();
+            }
         }
 
         if n == 0 {
-            return self.search_root(depths);
+            let best_move = self.search_root(depths);
+            self.threads_stats = vec![self.thread_stats()];
+            return best_move;
         }
 
         let mut children = Vec::with_capacity(n);
 
+        // The nodes of every thread are counted in the info printed by the
+        // first one
+        let threads_nodes = Arc::new((0..n).map(|_| AtomicU64::new(0)).collect());
         for i in 0..n {
             let mut clone = self.clone();
+            clone.thread_index = i;
+            clone.threads_nodes = Arc::clone(&threads_nodes);
             if i > 0 {
                 clone.is_search_verbose = false;
                 clone.is_debug = false;
@@ -106,7 +398,9 @@ impl Search for Game {
                 stack_size(4 << 20);
 
             children.push(builder.spawn(move || {
-                clone.search_root(min_depth..max_depth)
+                let best_move = clone.search_root(min_depth..max_depth);
+                let stats = clone.thread_stats();
+                (best_move, clone.moves, clone.search_result, stats)
             }).unwrap());
         }
 
@@ -114,8 +408,23 @@ impl Search for Game {
         for child in children {
             res.push(child.join().unwrap());
         }
+        self.threads_stats = res.iter().map(|&(_, _, _, stats)| stats).collect();
+        self.nodes_count = self.threads_stats.iter().map(|stats| stats.nodes_count).sum();
+        for stats in &self.threads_stats {
+            self.search_stats += stats.search_stats;
+        }
+        if self.is_debug {
+            self.print_threads_stats();
+        }
 
-        res[0] // best move found by the first thread
+        // Keep the killer moves and the history of the first thread for
+        // the next search
+        let (best_move, moves, search_result, _) = res.swap_remove(0);
+        self.moves = moves;
+        self.moves.clear_plies();
+        self.search_result = search_result;
+
+        best_move // best move found by the first thread
     }
 
     fn search_root(&mut self, depths: Range<Depth>) -> Option<PieceMove> {
@@ -131,26 +440,41 @@ impl Search for Game {
             self.print_thinking_init();
         }
 
+        // The game is already lost
+        if self.is_checks_limit_reached() {
+            return None;
+        }
+
+        // Play a move found by a very shallow search when there is almost
+        // no time left
+        if self.clock.is_time_pressure() {
+            return self.search_time_pressure();
+        }
+
         // Current best move
         let mut best_move = PieceMove::new_null();
-        let mut best_score = 0;
+        let mut best_score: Score = 0;
 
         // Keep track of previous values at shallower depths
         let mut best_moves = [PieceMove::new_null(); MAX_PLY];
         let mut best_scores = [0; MAX_PLY];
 
+        // Root moves reordered by the function given to the game, searched
+        // after the best move of the previous iteration
+        let root_moves = match self.root_ordering.clone() {
+            Some(f) => {
+                let mut moves = self.root_moves();
+                f(self, &mut moves);
+                Some(moves)
+            },
+            None => None
+        };
+
         debug_assert!(depths.start > 0);
         for depth in depths {
-            let mut alpha = -INF;
-            let beta = INF;
-
-            self.moves.clear();
-            if !best_move.is_null() {
-                self.moves.add_move(best_move);
-            }
-
-            // Mate pruning
-            if depth > 6 {
+            // Mate pruning (except in infinite mode where we must keep
+            // searching until we are told to stop)
+            if depth > 6 && !self.clock.is_infinite() {
                 // Stop the search if the position was mate at the 3 previous
                 // shallower depths.
                 let mut is_mate = true;
@@ -167,32 +491,109 @@ impl Search for Game {
                 }
             }
 
-            let mut has_legal_moves = false;
-            while let Some(m) = self.next_move() {
-                if self.clock.poll(self.nodes_count) {
-                    break; // Discard search at this depth if time is out
+            // Aspiration window around the score of the previous iteration,
+            // widened after each fail-low or fail-high until the score is
+            // inside of it
+            let mut delta = self.search_params.aspiration;
+            let is_aspiration = delta > 0 && depth >= ASPIRATION_MIN_DEPTH && best_score.abs() < DISTANCE_SCORE;
+            let (mut lower, mut upper) = if is_aspiration {
+                (cmp::max(best_score - delta, -INF), cmp::min(best_score + delta, INF))
+            } else {
+                (-INF, INF)
+            };
+
+            self.seldepth = 0;
+            let mut first_move = best_move;
+            let mut has_legal_moves;
+            loop {
+                let mut alpha = lower;
+                let beta = upper;
+                best_moves[depth as usize] = PieceMove::new_null();
+
+                self.pv.clear(ply);
+                self.moves.clear();
+                if !first_move.is_null() {
+                    self.moves.add_move(first_move);
+                }
+                if let Some(ref moves) = root_moves {
+                    for &(m, _) in moves {
+                        self.moves.add_move(m);
+                    }
+                    self.moves.set_stage(PieceMoveListStage::Done);
                 }
 
-                self.make_move(m);
-                let score = -self.search_node(-beta, -alpha, depth - 1, ply + 1);
-                if !self.is_check(side) {
-                    has_legal_moves = true;
-                    self.nodes_count += 1;
-                    if score > alpha {
-                        if self.is_search_verbose && !self.clock.poll(self.nodes_count) {
-                            // TODO: skip the first thousand nodes to gain time?
+                has_legal_moves = false;
+                while let Some(m) = self.next_move() {
+                    if self.clock.poll(self.nodes_count) {
+                        break; // Discard search at this depth if time is out
+                    }
 
-                            self.tt.set(hash, depth, score, m, Bound::Exact);
+                    self.make_move(m);
+                    let mut score = -self.search_node(-beta, -alpha, depth - 1, ply + 1);
+                    if !self.is_check(side) {
+                        has_legal_moves = true;
+                        self.nodes_count += 1;
 
-                            // Get the PV line from the TT.
-                            self.print_thinking(depth, score, m);
+                        // Avoid or seek repetitions depending on the score of
+                        // the previous iteration
+                        if self.search_params.repetition > 0 && self.allows_repetition() {
+                            score += self.repetition_adjustment(best_score);
+                        }
+
+                        // Avoid opening lines that were often lost in the past
+                        if self.learning.is_enabled && self.positions.len() <= LEARNING_MAX_PLY {
+                            score -= self.learning.penalty(self.positions.top().hash);
+                        }
+
+                        let reason = if score >= beta { Some("cutoff") } else { None };
+                        self.trace_record(TraceRecord { hash, ply, piece_move: m, depth, alpha, beta, score: Some(score), reason });
+
+                        if score > alpha {
+                            self.pv.update(ply, m);
+                            let bound = if score >= beta { Bound::Lower } else { Bound::Exact };
+                            if self.is_search_verbose && !self.clock.poll(self.nodes_count) {
+                                // TODO: skip the first thousand nodes to gain time?
+
+                                self.tt.set(hash, depth, score, m, bound);
+                                self.print_thinking(depth, score, bound, m);
+                            }
+                            alpha = score;
+                            best_scores[depth as usize] = score;
+                            best_moves[depth as usize] = m;
+
+                            if score >= beta {
+                                self.undo_move(m);
+                                break;
+                            }
                         }
-                        alpha = score;
-                        best_scores[depth as usize] = score;
-                        best_moves[depth as usize] = m;
                     }
+                    self.undo_move(m);
+                }
+
+                if !has_legal_moves || self.clock.poll(self.nodes_count) {
+                    break;
+                }
+
+                delta = delta.saturating_mul(2);
+                if best_moves[depth as usize].is_null() && lower > -INF {
+                    // Fail-low, the best move of the previous iteration is
+                    // searched first again
+                    if self.is_search_verbose && !first_move.is_null() {
+                        self.pv.clear(ply + 1);
+                        self.pv.update(ply, first_move);
+                        self.make_move(first_move);
+                        self.print_thinking(depth, lower, Bound::Upper, first_move);
+                        self.undo_move(first_move);
+                    }
+                    lower = cmp::max(lower.saturating_sub(delta), -INF);
+                } else if best_scores[depth as usize] >= upper && upper < INF {
+                    // Fail-high, the move refuting the window is searched
+                    // first
+                    first_move = best_moves[depth as usize];
+                    upper = cmp::min(upper.saturating_add(delta), INF);
+                } else {
+                    break;
                 }
-                self.undo_move(m);
             }
 
             // Save the best move only if we found one and if we still have
@@ -202,10 +603,22 @@ impl Search for Game {
                 best_score = best_scores[depth as usize];
 
                 self.tt.set(hash, depth, best_score, best_move, Bound::Exact);
+                crash::record_depth(depth, self.nodes_count, best_move);
+                self.search_result = Some(SearchResult {
+                    hash,
+                    best_move,
+                    score: best_score,
+                    depth,
+                    nodes_count: self.nodes_count
+                });
+
+                if self.is_search_verbose && self.show_san_pv && self.protocol == Protocol::UCI {
+                    self.print_san_pv(depth);
+                }
             }
 
             // No need to iterate if there's no legal moves to play
-            if !has_legal_moves {
+            if !has_legal_moves || self.clock.is_stopped() {
                 break;
             }
         }
@@ -223,6 +636,8 @@ impl Search for Game {
             self.tt.print_stats();
         }
 
+        self.trace.flush();
+
         if best_move.is_null() {
             None
         } else {
@@ -230,49 +645,79 @@ impl Search for Game {
         }
     }
 
-    fn search_node(&mut self, mut alpha: Score, mut beta: Score, depth: Depth, ply: usize) -> Score {
+    fn search_node(&mut self, mut alpha: Score, beta: Score, mut depth: Depth, ply: usize) -> Score {
         if self.clock.poll(self.nodes_count) {
             return 0;
         }
 
-        if depth == 0 {
+        if self.nodes_count & 0x3FF == 0 {
+            self.publish_nodes_count();
+        }
+
+        // The principal variation ends at the horizon
+        self.pv.clear(ply);
+
+        if ply > self.seldepth {
+            self.seldepth = ply;
+        }
+
+        if depth <= 0 {
             return self.quiescence(alpha, beta, depth - 1, ply + 1);
         }
 
         // Detect draw by threefold repetitions and fifty-moves rule
         if self.positions.is_draw() {
-            return 0;
+            return self.draw_score(ply);
+        }
+
+        // Detect loss by the last check given in Three-check
+        if self.is_checks_limit_reached() {
+            return -INF + (ply as Score);
         }
 
         let hash = self.positions.top().hash;
+        let mut best_score = -INF;
+
+        // The score is at least a draw if the side to move can go back to a
+        // previous position
+        let draw_score = self.draw_score(ply);
+        if ply > 0 && alpha < draw_score && self.has_upcoming_repetition(ply) {
+            alpha = draw_score;
+            best_score = draw_score;
+            if alpha >= beta {
+                self.trace_record(TraceRecord { hash, ply, piece_move: PieceMove::new_null(), depth, alpha, beta, score: Some(alpha), reason: Some("cycle") });
+                return alpha;
+            }
+        }
+
         let side = self.side();
         let is_null_move = !self.positions.top().null_move_right;
         let is_pv = alpha != beta - 1;
+        if is_pv {
+            self.search_stats.pv_nodes_count += 1;
+        } else {
+            self.search_stats.non_pv_nodes_count += 1;
+        }
 
         let mut best_move = PieceMove::new_null();
-        let mut best_score = alpha;
         let old_alpha = alpha; // To test if best score raise initial alpha
 
         // Try to get the best move from transposition_table table
-        if let Some(t) = self.tt.get(hash) {
+        let tt_entry = self.probe_tt(hash);
+        if let Some(t) = tt_entry {
             if !is_pv && t.depth() >= depth {
-                match t.bound() {
-                    Bound::Exact => {
-                        return t.score();
-                    },
-                    Bound::Lower => {
-                        if t.score() > alpha {
-                            alpha = t.score();
-                        }
-                    },
-                    Bound::Upper => {
-                        if t.score() < beta {
-                            beta = t.score();
-                        }
-                    }
-                }
-                if alpha >= beta {
-                    return t.score();
+                // NOTE: The window is not narrowed by the bounds because the
+                // scores outside of it are returned and stored as bounds.
+                let score = score_from_tt(t.score(), ply);
+                let is_cutoff = match t.bound() {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha
+                };
+                if is_cutoff {
+                    self.search_stats.tt_cutoffs_count += 1;
+                    self.trace_record(TraceRecord { hash, ply, piece_move: PieceMove::new_null(), depth, alpha, beta, score: Some(score), reason: Some("tt") });
+                    return score;
                 }
             }
 
@@ -281,42 +726,86 @@ impl Search for Game {
 
         let is_in_check = self.is_check(side);
 
-        // Null Move Pruning (NMP)
-        let pieces_count = self.bitboard(side).count();
-        let pawns_count = self.bitboard(side | PAWN).count();
-        let is_pawn_ending = pieces_count == pawns_count + 1; // pawns + king
+        // Search parameters depending on the phase of the game
+        let params = *self.search_params.phase(GamePhase::from_game(self));
+
+        // Static evaluation of the node, only needed by the pruning of the
+        // non PV nodes when the side to move is not in check
+        let eval = if is_pv || is_in_check { 0 } else { self.eval() };
+        self.moves.set_eval(if is_pv || is_in_check { None } else { Some(eval) });
+        let is_improving = self.moves.is_improving();
 
+        // Razoring
+        //
+        // When the static evaluation is far below alpha near the horizon,
+        // verify with the quiescence search that no capture can make up
+        // for it before pruning the node.
+        if !is_pv && !is_in_check {
+            if let Some(margin) = self.search_params.razoring_margin(depth) {
+                if eval.saturating_add(margin) < alpha {
+                    let score = self.quiescence(alpha, beta, -1, ply + 1);
+                    if score < alpha {
+                        self.trace_record(TraceRecord { hash, ply, piece_move: PieceMove::new_null(), depth, alpha, beta, score: Some(score), reason: Some("razoring") });
+                        return score;
+                    }
+                }
+            }
+        }
+
+        // Null Move Pruning (NMP)
         let nmp_allowed =
             !is_in_check &&
             !is_null_move &&
             !is_pv &&
-            !is_pawn_ending;
+            !self.variant.has_forced_captures() && // Zugzwang everywhere
+            self.search_params.features.nmp &&
+            params.nmp; // Disabled in pawn endings to avoid zugzwang
 
         if nmp_allowed {
-            let r = cmp::min(depth - 1, 3 + depth / 4);
+            // The reduction increases with the depth and with the margin of
+            // the static evaluation above beta
+            let margin = eval.saturating_sub(beta);
+            let r = cmp::min(depth - 1, self.search_params.null_move_reduction(depth, margin));
             let m = PieceMove::new_null();
+            self.search_stats.null_moves_count += 1;
             self.make_move(m);
             self.positions.disable_null_move();
             let score = -self.search_node(-beta, -beta + 1, depth - r - 1, ply + 1);
             self.positions.enable_null_move();
             self.undo_move(m);
 
+            if self.clock.is_stopped() {
+                return 0;
+            }
+
+            // The mate scores found without making a move are not proven
             if score >= beta {
-                return score;
+                self.search_stats.null_move_cutoffs_count += 1;
+                self.trace_record(TraceRecord { hash, ply, piece_move: PieceMove::new_null(), depth, alpha, beta, score: Some(score), reason: Some("nmp") });
+                return if score >= DISTANCE_SCORE { beta } else { score };
             }
         }
 
-        // Internal Iterative Deepening (IID)
+        // Internal Iterative Deepening (IID) and Reduction (IIR)
         //
         // If we didn't get a best move from the transposition_table table,
-        // get it by searching the position at a reduced depth.
-        let iid_allowed = is_pv && best_move.is_null();
-
-        if iid_allowed && depth > 3 {
-            self.search_node(-beta, -alpha, depth / 2, ply + 1);
+        // get it by searching the position at a reduced depth in PV nodes,
+        // and search the other nodes one ply shallower since their moves
+        // will be poorly ordered. Both need the transposition table.
+        if best_move.is_null() && self.search_params.features.tt {
+            if is_pv {
+                if let Some(iid_depth) = self.search_params.iid_search_depth(depth) {
+                    self.search_node(alpha, beta, iid_depth, ply);
+                    if self.clock.is_stopped() {
+                        return 0;
+                    }
 
-            if let Some(t) = self.tt.get(hash) {
-                best_move = t.best_move();
+                    if let Some(t) = self.tt.get(hash) {
+                        best_move = t.best_move();
+                    }
+                }
+            } else if !is_in_check {
+                depth -= self.search_params.iir_reduction(depth);
             }
         }
 
@@ -325,51 +814,116 @@ impl Search for Game {
             self.moves.add_move(best_move);
         }
 
-        let eval = self.eval_material(side) - self.eval_material(side ^ 1);
-
-        let mut has_legal_moves = false;
+        let mut legal_moves_count = 0;
         let mut is_first_move = true;
         while let Some(m) = self.next_move() {
+            let is_giving_check = self.gives_check(m);
+
+            // SEE Pruning
+            //
+            // Near the horizon, skip the moves losing more material in the
+            // exchanges on their destination square than a margin growing
+            // with the depth.
+            let see_allowed =
+                self.search_params.features.see &&
+                !is_pv &&
+                !is_in_check &&
+                !is_first_move &&
+                !is_giving_check &&
+                !m.is_promotion() &&
+                !m.is_castle() &&
+                !m.is_drop() &&
+                !self.variant.has_explosions();
+
+            if see_allowed {
+                if let Some(threshold) = self.search_params.see_threshold(depth, m.is_capture()) {
+                    if self.see(m) < threshold {
+                        self.trace_record(TraceRecord { hash, ply, piece_move: m, depth, alpha, beta, score: None, reason: Some("see") });
+                        continue;
+                    }
+                }
+            }
+
+            let piece = self.moved_piece(m);
+            let continuation_history = self.moves.get_continuation_history(piece, m.to());
             self.make_move(m);
+            self.tt.prefetch(self.positions.top().hash);
 
             if self.is_check(side) {
                 self.undo_move(m);
                 continue;
             }
 
+            debug_assert_eq!(is_giving_check, self.is_check(side ^ 1));
+
             self.nodes_count += 1;
-            has_legal_moves = true;
+            legal_moves_count += 1;
+            let is_first_legal_move = is_first_move;
+
+            // Check Extension
+            //
+            // Search the moves giving check one ply deeper, as long as the
+            // extended plies do not take the search too far from the root.
+            let mut new_depth = depth - 1;
+            if is_giving_check && ply + (depth as usize) < CHECK_EXTENSION_MAX_PLY {
+                new_depth += 1;
+            }
 
             let mut score;
             if is_first_move {
                 // Search the first move with the full window
-                score = -self.search_node(-beta, -alpha, depth - 1, ply + 1);
+                score = -self.search_node(-beta, -alpha, new_depth, ply + 1);
 
-                best_score = score;
                 best_move = m;
                 is_first_move = false;
             } else {
-                let is_giving_check = self.is_check(side ^ 1);
                 let mut r = 0; // Depth reduction
 
                 // Futility Pruning (FP)
                 let fp_allowed =
+                    self.search_params.features.futility &&
                     !is_pv &&
                     !is_in_check &&
                     !is_giving_check &&
                     !m.is_capture() &&
                     !m.is_promotion();
 
-                if fp_allowed && depth < 6 {
-                    let margin = 100 * depth as Score;
-                    if eval + margin < alpha {
-                        self.undo_move(m);
-                        continue;
+                if fp_allowed {
+                    if let Some(margin) = self.search_params.futility_margin(depth) {
+                        if eval.saturating_add(margin) < alpha {
+                            self.undo_move(m);
+                            self.trace_record(TraceRecord { hash, ply, piece_move: m, depth, alpha, beta, score: None, reason: Some("futility") });
+                            continue;
+                        }
+                    }
+                }
+
+                // Late Move Pruning (LMP)
+                //
+                // Near the horizon, skip the quiet moves coming after many
+                // others when the static evaluation is not improving.
+                let lmp_allowed =
+                    self.search_params.features.lmp &&
+                    !is_pv &&
+                    !is_in_check &&
+                    !is_improving &&
+                    !is_giving_check &&
+                    !m.is_capture() &&
+                    !m.is_promotion();
+
+                if lmp_allowed {
+                    if let Some(count) = self.search_params.late_move_count(depth) {
+                        if legal_moves_count > count {
+                            self.undo_move(m);
+                            self.trace_record(TraceRecord { hash, ply, piece_move: m, depth, alpha, beta, score: None, reason: Some("lmp") });
+                            continue;
+                        }
                     }
                 }
 
                 // Late Move Reduction (LMR)
                 let lmr_allowed =
+                    self.search_params.features.lmr &&
                     !is_pv &&
                     !is_in_check &&
                     !is_giving_check &&
@@ -381,45 +935,85 @@ impl Search for Game {
                     if depth > 4 {
                         r += depth / 4;
                     }
+                    r += params.lmr;
+
+                    // Reduce one ply less the moves often causing a cutoff
+                    // after the previous move of the same side
+                    if continuation_history >= CONTINUATION_HISTORY_LMR_MIN {
+                        r -= 1;
+                    }
+
+                    // Never reduce beyond the horizon
+                    r = cmp::min(r, new_depth);
                 }
 
                 // Search the other moves with the reduced window
-                score = -self.search_node(-alpha - 1, -alpha, depth - r - 1, ply + 1);
+                score = -self.search_node(-alpha - 1, -alpha, new_depth - r, ply + 1);
+
+                #[cfg(feature = "verify-search")]
+                self.verify_zero_window(-alpha - 1, new_depth - r, ply + 1);
 
                 // LMR re-search
                 if r > 0 && score > alpha {
-                    score = -self.search_node(-alpha - 1, -alpha, depth - 1, ply + 1);
+                    score = -self.search_node(-alpha - 1, -alpha, new_depth, ply + 1);
+
+                    #[cfg(feature = "verify-search")]
+                    self.verify_zero_window(-alpha - 1, new_depth, ply + 1);
                 }
 
                 // Re-search with the full window
                 if alpha < score && score < beta {
-                    score = -self.search_node(-beta, -alpha, depth - 1, ply + 1);
+                    score = -self.search_node(-beta, -alpha, new_depth, ply + 1);
                 }
             }
 
             self.undo_move(m);
 
+            // Unwind the search without storing the scores of the aborted
+            // nodes in the transposition table
+            if self.clock.is_stopped() {
+                return 0;
+            }
+
+            let reason = if score >= beta { Some("cutoff") } else { None };
+            self.trace_record(TraceRecord { hash, ply, piece_move: m, depth, alpha, beta, score: Some(score), reason });
+
+            if score > best_score {
+                best_score = score;
+            }
+
             if score > alpha {
                 if score >= beta {
+                    self.search_stats.cutoffs_count += 1;
+                    if is_first_legal_move {
+                        self.search_stats.first_move_cutoffs_count += 1;
+                    }
                     if !m.is_capture() {
                         self.moves.add_killer_move(m);
+                        self.moves.add_history(m, depth);
+                        self.moves.add_continuation_history(piece, m.to(), depth);
+                    } else {
+                        let captured = self.captured_kind(m);
+                        self.moves.add_capture_history(piece, m.to(), captured, depth);
                     }
-                    self.tt.set(hash, depth, score, m, Bound::Lower);
+                    self.tt.set(hash, depth, score_to_tt(score, ply), m, Bound::Lower);
                     return score;
                 }
 
                 alpha = score;
-                best_score = score;
                 best_move = m;
+                self.pv.update(ply, m);
             }
         }
 
         // TODO: could we just use `best_move.is_null()` ?
-        if !has_legal_moves { // End of game
-            if is_in_check {
+        if legal_moves_count == 0 { // End of game
+            if self.variant.has_losing_goal() {
+                return INF - (ply as Score); // Win by having no moves left
+            } else if is_in_check {
                 return -INF + (ply as Score); // Checkmate
             } else {
-                return 0; // Stalemate
+                return self.draw_score(ply); // Stalemate
             }
         }
 
@@ -429,10 +1023,10 @@ impl Search for Game {
             } else {
                 Bound::Upper
             };
-            self.tt.set(hash, depth, best_score, best_move, bound);
+            self.tt.set(hash, depth, score_to_tt(best_score, ply), best_move, bound);
         }
 
-        alpha
+        best_score
     }
 
     fn quiescence(&mut self, mut alpha: Score, mut beta: Score, depth: Depth, ply: usize) -> Score {
@@ -441,79 +1035,135 @@ impl Search for Game {
             return 0;
         }
 
-        // Static evaluation
-        let eval = self.eval();
+        self.search_stats.quiescence_nodes_count += 1;
+
+        if ply > self.seldepth {
+            self.seldepth = ply;
+        }
+
+        // Detect loss by the last check given in Three-check
+        if self.is_checks_limit_reached() {
+            return -INF + (ply as Score);
+        }
 
         // Maximum depth abort
         if ply >= MAX_PLY {
-            return eval;
+            return self.eval();
         }
 
-        // Delta pruning
-        let delta = 1000; // Queen value
-        if eval < alpha - delta {
-            return alpha;
-        }
+        let side = self.side();
+        let is_in_check = self.is_check(side);
+        let old_alpha = alpha;
+        let mut best_score = -INF;
+
+        // The quiet moves giving a check are only searched after the
+        // captures at the first ply of quiescence search
+        let with_checks = depth == -1 && !is_in_check;
+
+        // The static evaluation is not a lower bound of the score when in
+        // check, every evasion is searched instead
+        if !is_in_check {
+            let eval = self.eval();
+
+            // Delta pruning
+            //
+            // No capture can raise the score by more than a queen, but a
+            // check can give mate so the first ply is not pruned.
+            let delta = 1000; // Queen value
+            if self.search_params.features.futility && !with_checks && eval < alpha - delta {
+                return eval + delta;
+            }
 
-        // Stand pat pruning
-        if eval > alpha {
+            // Stand pat pruning
             if eval >= beta {
                 return eval;
             }
-
-            alpha = eval;
+            if eval > alpha {
+                alpha = eval;
+            }
+            best_score = eval;
         }
 
         let hash = self.positions.top().hash;
-        let side = self.side();
-        let old_alpha = alpha;
         let mut best_move = PieceMove::new_null();
 
-        if let Some(t) = self.tt.get(hash) {
+        let tt_entry = self.probe_tt(hash);
+        if let Some(t) = tt_entry {
             if t.depth() >= depth { // This node has already been searched
-                match t.bound() {
-                    Bound::Exact => {
-                        return t.score();
-                    },
+                let score = score_from_tt(t.score(), ply);
+                let is_cutoff = match t.bound() {
+                    Bound::Exact => true,
                     Bound::Lower => {
-                        if t.score() > alpha {
-                            alpha = t.score();
-                        }
+                        alpha = cmp::max(alpha, score);
+                        alpha >= beta
                     },
                     Bound::Upper => {
-                        if t.score() < beta {
-                            beta = t.score();
-                        }
+                        beta = cmp::min(beta, score);
+                        alpha >= beta
                     }
-                }
-                if alpha >= beta {
-                    return t.score();
+                };
+                if is_cutoff {
+                    self.search_stats.tt_cutoffs_count += 1;
+                    return score;
                 }
             }
 
             best_move = t.best_move();
         }
 
+        // The quiet evasions stored in the transposition table are only
+        // searched when in check, to avoid endless series of quiet checks
+        // and evasions without standing pat
+        let is_quiet_best_move = !best_move.is_capture() && !best_move.is_promotion();
+        if is_quiet_best_move && !is_in_check {
+            best_move = PieceMove::new_null();
+        }
+
         self.moves.clear();
         if !best_move.is_null() {
             self.moves.add_move(best_move);
         }
-        while let Some(m) = self.next_capture() {
+        let mut has_legal_moves = false;
+        loop {
+            let next_move = if is_in_check {
+                self.next_move() // Every evasion
+            } else if with_checks {
+                self.next_capture_or_check()
+            } else {
+                self.next_capture()
+            };
+            let m = match next_move {
+                Some(m) => m,
+                None => break
+            };
+
             self.make_move(m);
+            self.tt.prefetch(self.positions.top().hash);
 
             if self.is_check(side) {
                 self.undo_move(m);
                 continue;
             }
             self.nodes_count += 1;
+            has_legal_moves = true;
 
             let score = -self.quiescence(-beta, -alpha, depth - 1, ply + 1);
 
             self.undo_move(m);
 
+            // Unwind the search without storing the scores of the aborted
+            // nodes in the transposition table
+            if self.clock.is_stopped() {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+            }
+
             if score > alpha {
                 if score >= beta {
-                    self.tt.set(hash, depth, score, m, Bound::Lower);
+                    self.tt.set(hash, depth, score_to_tt(score, ply), m, Bound::Lower);
                     return score;
                 }
                 alpha = score;
@@ -521,16 +1171,24 @@ impl Search for Game {
             }
         }
 
+        if is_in_check && !has_legal_moves {
+            if self.variant.has_losing_goal() {
+                return INF - (ply as Score); // Win by having no moves left
+            } else {
+                return -INF + (ply as Score); // Checkmate
+            }
+        }
+
         if !best_move.is_null() {
-            let bound = if alpha > old_alpha {
+            let bound = if best_score > old_alpha {
                 Bound::Exact
             } else {
                 Bound::Upper
             };
-            self.tt.set(hash, depth, alpha, best_move, bound);
+            self.tt.set(hash, depth, score_to_tt(best_score, ply), best_move, bound);
         }
 
-        alpha
+        best_score
     }
 
     fn is_mate(&mut self) -> bool {
@@ -548,44 +1206,162 @@ impl Search for Game {
     }
 
     fn get_moves(&mut self) -> Vec<PieceMove> {
-        let mut res = Vec::new();
+        self.generate_legal_moves()
+    }
+
+    fn root_moves(&mut self) -> Vec<(PieceMove, Score)> {
+        let hash = self.positions.top().hash;
         let side = self.side();
+        let mut moves = Vec::new();
+
         self.moves.clear();
+        if let Some(t) = self.tt.get(hash) {
+            self.moves.add_move(t.best_move());
+        }
         while let Some(m) = self.next_move() {
+            let score = self.moves[self.moves.index() - 1].score as Score;
             self.make_move(m);
             if !self.is_check(side) {
-                res.push(m);
+                moves.push((m, score));
             }
             self.undo_move(m);
         }
-        res
+
+        moves
     }
 }
 
 impl SearchExt for Game {
-    fn print_debug_init(&self, depth: Depth) {
-        println!("# FEN {}", self.to_fen());
-        println!("# allocating {} ms to move", self.clock.allocated_time());
-        println!("# starting search at depth {}", depth);
-        println!();
-    }
+    // Search only the best move of the TT and the captures at depth 1 and 2,
+    // or all the moves if there are none of them
+    fn search_time_pressure(&mut self) -> Option<PieceMove> {
+        let hash = self.positions.top().hash;
+        let tt_move = self.tt.get(hash).map_or(PieceMove::new_null(), |t| t.best_move());
+
+        let moves = self.get_moves();
+        let mut candidates: Vec<PieceMove> = moves.iter().cloned().
+            filter(|&m| m == tt_move || m.is_capture() || m.is_en_passant()).
+            collect();
+        if candidates.is_empty() {
+            candidates = moves;
+        }
+        if let Some(i) = candidates.iter().position(|&m| m == tt_move) {
+            candidates.swap(0, i);
+        }
+
+        let mut best_move = *candidates.first()?;
+        for depth in 1..3 {
+            let mut best_score = -INF;
+            let mut best_depth_move = best_move;
+            for &m in &candidates {
+                self.make_move(m);
+                let score = -self.search_node(-INF, INF, depth - 1, 1);
+                self.undo_move(m);
+
+                if self.clock.is_stopped() {
+                    return Some(best_move); // Keep the move of the previous depth
+                }
+                if score > best_score {
+                    best_score = score;
+                    best_depth_move = m;
+                }
+            }
+            best_move = best_depth_move;
+        }
+
+        Some(best_move)
+    }
+
+    fn print_debug_init(&self, depth: Depth) {
+        println!("# FEN {}", self.to_fen());
+        println!("# allocating {} ms to move", self.clock.allocated_time());
+        println!("# starting search at depth {}", depth);
+        println!();
+    }
 
     fn print_thinking_init(&self) {
         if self.protocol != Protocol::UCI {
             println!("  {:>3}  {:>5}  {:>6}  {:>9}  {}", "ply", "score", "time", "nodes", "pv");
+        } else if self.is_blocked() {
+            println!("info string blocked position, no progress without sacrifice");
+        }
+    }
+
+    fn print_threads_stats(&self) {
+        let time = self.clock.elapsed_time();
+        let mut total = 0;
+        for (i, stats) in self.threads_stats.iter().enumerate() {
+            total += stats.nodes_count;
+            let name = format!("thread {}:", i);
+            let percent = stats.tt_hit_rate() * 100.0;
+            println!("# {:15} {:>8} nodes, depth {}, tt hits {:.2} %", name, stats.nodes_count, stats.depth, percent);
+        }
+        let nps = (total as f64) / ((time as f64) / 1000.0);
+        println!("# {:15} {:>8} ({:.2e} nps)", "total nodes:", total, nps);
+    }
+
+    fn thread_stats(&self) -> ThreadStats {
+        ThreadStats {
+            nodes_count: self.nodes_count,
+            tt_lookups_count: self.tt.lookups_count(),
+            tt_hits_count: self.tt.hits_count(),
+            depth: self.search_result.map_or(0, |res| res.depth),
+            search_stats: self.search_stats
+        }
+    }
+
+    // Get the entry of the position in the transposition table unless it is
+    // disabled, counting the lookups in the statistics of the search
+    fn probe_tt(&mut self, hash: u64) -> Option<Transposition> {
+        if !self.search_params.features.tt {
+            return None;
+        }
+        self.search_stats.tt_probes_count += 1;
+        let tt_entry = self.tt.get(hash);
+        if tt_entry.is_some() {
+            self.search_stats.tt_hits_count += 1;
+        }
+        tt_entry
+    }
+
+    // Write the given record to the trace file if the nodes of its ply are
+    // traced
+    fn trace_record(&self, record: TraceRecord) {
+        if self.trace.is_tracing(record.ply) {
+            self.trace.write(&record);
+        }
+    }
+
+    // Share the number of nodes searched by the thread with the others
+    fn publish_nodes_count(&self) {
+        if let Some(count) = self.threads_nodes.get(self.thread_index) {
+            count.store(self.nodes_count, Ordering::Relaxed);
+        }
+    }
+
+    // Get the number of nodes searched by every thread
+    fn total_nodes_count(&self) -> u64 {
+        self.publish_nodes_count();
+        if self.threads_nodes.is_empty() {
+            self.nodes_count
+        } else {
+            self.threads_nodes.iter().map(|count| count.load(Ordering::Relaxed)).sum()
         }
     }
 
-    fn print_thinking(&mut self, depth: Depth, score: Score, m: PieceMove) {
+    fn print_thinking(&mut self, depth: Depth, score: Score, bound: Bound, m: PieceMove) {
         self.undo_move(m);
 
         let time = self.clock.elapsed_time();
-        let nodes = self.nodes_count;
-        let mut pv = self.get_pv(depth);
+        let nodes = self.total_nodes_count();
+        let mut pv = self.get_pv(self.protocol != Protocol::UCI);
 
         match self.protocol {
             Protocol::UCI => {
-                println!("info depth {} score cp {} time {} nodes {} pv {}", depth, score, time, nodes, pv);
+                let score = uci_score(score, bound);
+                let nps = nodes * 1000 / cmp::max(time, 1);
+                let seldepth = cmp::max(self.seldepth, depth as usize);
+                println!("info depth {} seldepth {} score {} time {} nodes {} nps {} pv {}", depth, seldepth, score, time, nodes, nps, pv);
             },
             Protocol::XBoard | Protocol::CLI => {
                 if self.side() == BLACK {
@@ -614,6 +1390,16 @@ impl SearchExt for Game {
                     pv = lines.join(&format!("{:<34}", "\n"));
                 }
 
+                // Mate scores are given in moves, with the convention of
+                // XBoard or like `#3` in CLI mode
+                let score = match mate_moves(score) {
+                    Some(n) if self.protocol == Protocol::XBoard => {
+                        (if n > 0 { 100000 + n as i32 } else { -100000 + n as i32 }).to_string()
+                    },
+                    Some(n) => format!("#{}", n),
+                    None => score.to_string()
+                };
+
                 println!("  {:>3}  {:>5}  {:>6}  {:>9}  {}", depth, score, time / 10, nodes, pv);
             }
         }
@@ -621,71 +1407,258 @@ impl SearchExt for Game {
         self.make_move(m);
     }
 
-    fn get_pv(&mut self, depth: Depth) -> String {
-        let is_san_format = self.protocol != Protocol::UCI;
-
-        if depth == 0 {
-            return String::new();
+    // Print the PV of a completed iteration in SAN format in an UCI info
+    // string for the users and logs preferring it.
+    fn print_san_pv(&mut self, depth: Depth) {
+        let mut pv = self.get_pv(true);
+        if self.side() == BLACK {
+            let fm = self.positions.fullmoves();
+            pv = format!("{}. ... {}", fm, pv);
         }
+        println!("info string depth {} pv {}", depth, pv.trim());
+    }
 
+    fn get_pv(&mut self, is_san_format: bool) -> String {
+        let pv = self.pv.get(0).to_vec();
         let mut res = vec![];
-        let mut m = PieceMove::new_null();
-
-        let side = self.side();
-        let hash = self.positions.top().hash;
-        if let Some(t) = self.tt.get(hash) {
-            m = t.best_move();
-
+        for &m in &pv {
+            let side = self.side();
             if is_san_format && side == WHITE {
                 let fm = self.positions.fullmoves();
                 res.push(format!("{}.", fm));
             }
 
-            // TODO: put the rest of the code here (if the compiler allow it)
-        }
-
-        if !m.is_null() {
-            let cur = if is_san_format {
+            let mut cur = if is_san_format {
                 self.move_to_san(m)
             } else {
-                m.to_lan()
+                self.move_to_lan(m)
             };
             self.make_move(m);
+            if is_san_format && self.is_check(side ^ 1) {
+                cur.push(if self.is_mate() { '#' } else { '+' });
+            }
+            res.push(cur);
+        }
+        for &m in pv.iter().rev() {
+            self.undo_move(m);
+        }
 
-            let pv = &self.get_pv(depth - 1);
-            let sep = if is_san_format && self.is_check(side ^ 1) {
-                if pv == "#" { "" } else { "+ " }
-            } else {
-                " "
-            };
-            res.push(format!("{}{}{}", cur, sep, pv));
+        res.join(" ")
+    }
+
+    // Check if the side to move has a reversible move going back to a
+    // previous position, by looking up the difference between the hashes
+    // of the two positions in the cuckoo table.
+    fn has_upcoming_repetition(&self, ply: usize) -> bool {
+        if self.variant.has_forced_captures() {
+            return false;
+        }
+
+        let side = self.side();
+        let hash = self.positions.top().hash;
+        let occupied = self.bitboard(WHITE) | self.bitboard(BLACK);
+        let top = self.positions.len() - 1;
+        let mut i = 1;
+        while i <= top {
+            // Stop at the last irreversible move or null move
+            let pos = &self.positions[top + 1 - i];
+            if pos.halfmoves_count == 0 || !pos.null_move_right {
+                break;
+            }
 
+            // Only a position with the other side to move can be reached
+            // with a single move
+            if i >= 3 && i % 2 == 1 {
+                if let Some(e) = self.cuckoo.get(hash ^ self.positions[top - i].hash) {
+                    let (from, to) = if self.board[e.from as usize] == EMPTY {
+                        (e.to, e.from)
+                    } else {
+                        (e.from, e.to)
+                    };
+
+                    // Before the root the repetition counts only if the move
+                    // is played by the side to move and goes back to a
+                    // position that was already repeated
+                    if self.board[from as usize] == e.piece &&
+                        self.board[to as usize] == EMPTY &&
+                        piece_attacks(e.piece, from, occupied).get(to) &&
+                        (ply > i || (e.piece.color() == side && self.positions.is_repetition_at(top - i))) {
+                        return true;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        false
+    }
+
+    // Check if the last move played allows an immediate repetition, either
+    // by repeating a position or by letting the opponent repeat one.
+    fn allows_repetition(&mut self) -> bool {
+        if self.positions.is_repetition() {
+            return true;
+        }
+
+        let side = self.side();
+        let mut res = false;
+        self.moves.clear();
+        while let Some(m) = self.next_move() {
+            self.make_move(m);
+            res = !self.is_check(side) && self.positions.is_repetition();
             self.undo_move(m);
-        } else if self.is_check(side) {
-            if is_san_format {
-                res.push("#".into());
+            if res {
+                break;
             }
         }
 
-        res.join(" ")
+        res
+    }
+
+    // Re-search a node searched with a zero window with the full window and
+    // check that both searches fail on the same side of the window, like a
+    // plain alpha-beta search would. The pruning and the transposition table
+    // cutoffs depend on the window by design, so they are disabled in both
+    // searches, which are done on a copy of the game writing in its own
+    // transposition table to leave the main search unchanged.
+    #[cfg(feature = "verify-search")]
+    fn verify_zero_window(&mut self, alpha: Score, depth: Depth, ply: usize) {
+        if depth > VERIFY_MAX_DEPTH || self.nodes_count % VERIFY_SEARCH_INTERVAL != 0 {
+            return;
+        }
+
+        let mut game = self.clone();
+        game.tt = self.verify_tt.clone();
+        game.trace = SearchTrace::new();
+        game.search_params.features = SearchFeatures {
+            nmp: false, lmr: false, lmp: false, futility: false, see: false, tt: false
+        };
+        game.search_params.razor_depth = 0;
+
+        let score = game.search_node(alpha, alpha + 1, depth, ply);
+        let full_score = game.search_node(-INF, INF, depth, ply);
+
+        if game.clock.is_stopped() {
+            return; // Scores of aborted searches are meaningless
+        }
+
+        assert_eq!(score > alpha, full_score > alpha,
+            "zero-window score {} and full-window score {} are inconsistent with alpha {} in {}",
+            score, full_score, alpha, self.to_fen());
+    }
+
+    // Get the score of a draw at the given ply from the root, with the
+    // contempt of the current phase of the game
+    fn draw_score(&self, ply: usize) -> Score {
+        self.search_params.draw_score(ply, self.phase())
+    }
+
+    // Get the root score adjustment of a move allowing a repetition from the
+    // score of the previous iteration compared to the score of a draw.
+    fn repetition_adjustment(&mut self, score: Score) -> Score {
+        let draw_score = self.draw_score(0);
+        let adjustment = self.search_params.repetition;
+
+        if score > draw_score {
+            -adjustment // Avoid repetition when winning
+        } else if score < draw_score {
+            adjustment // Seek repetition when losing
+        } else {
+            0
+        }
+    }
+}
+
+// Scores above this bound are mates depending on the distance from the root
+const DISTANCE_SCORE: Score = INF - MAX_PLY as Score;
+
+// Shallowest iteration searched with an aspiration window
+const ASPIRATION_MIN_DEPTH: Depth = 5;
+
+// Deepest ply reached by the nodes searched with a check extension
+const CHECK_EXTENSION_MAX_PLY: usize = MAX_PLY / 2;
+
+// Continuation history from which a quiet move is reduced one ply less,
+// reached by a few cutoffs at medium depths
+const CONTINUATION_HISTORY_LMR_MIN: u32 = 128;
+
+// Convert a score relative to the root into a score relative to the node
+// at the given ply, to store mate scores in the transposition table
+fn score_to_tt(score: Score, ply: usize) -> Score {
+    if score >= DISTANCE_SCORE {
+        score + ply as Score
+    } else if score <= -DISTANCE_SCORE {
+        score - ply as Score
+    } else {
+        score
+    }
+}
+
+// Convert a score relative to the node at the given ply, as stored in the
+// transposition table, into a score relative to the root
+fn score_from_tt(score: Score, ply: usize) -> Score {
+    if score >= DISTANCE_SCORE {
+        score - ply as Score
+    } else if score <= -DISTANCE_SCORE {
+        score + ply as Score
+    } else {
+        score
+    }
+}
+
+/// Get the number of moves to mate of a mate score, negative when the side
+/// to move is getting mated
+pub fn mate_moves(score: Score) -> Option<Score> {
+    if score >= INF - MAX_PLY as Score {
+        Some((INF - score + 1) / 2)
+    } else if score <= -INF + MAX_PLY as Score {
+        Some(-(INF + score) / 2)
+    } else {
+        None
     }
 }
 
+// Format a score with its bound for UCI, mate scores being given in moves
+fn uci_score(score: Score, bound: Bound) -> String {
+    let mut res = match mate_moves(score) {
+        Some(n) => format!("mate {}", n),
+        None => format!("cp {}", score)
+    };
+    match bound {
+        Bound::Lower => res.push_str(" lowerbound"),
+        Bound::Upper => res.push_str(" upperbound"),
+        Bound::Exact => {}
+    }
+    res
+}
+
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::fs;
+
     use color::*;
     use piece::*;
     use square::*;
     use common::*;
+    use attack::Attack;
     use bitboard::BitboardExt;
     use clock::Clock;
     use eval;
     use fen::FEN;
     use game::Game;
+    use params::{GAME_PHASES, SEARCH_FEATURES};
+    use perft_table::PerftTable;
     use piece_move::PieceMove;
+    use positions::test_suite;
+    use random::Random;
     use piece_move_generator::PieceMoveGenerator;
     use piece_move_notation::PieceMoveNotation;
-    use search::Search;
+    use search::{PerftStats, Search, SearchExt, mate_moves, score_from_tt, score_to_tt, uci_score};
+    use transposition::Bound;
+    use std::sync::Arc;
+    use variant::Variant;
+    use params::ClearPolicy;
 
     #[test]
     fn test_perft() {
@@ -706,36 +1679,143 @@ mod tests {
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 17);
 
-        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -";
+        let fen = test_suite::PERFT_POSITION_3;
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 14);
         assert_eq!(game.perft(2), 191);
         assert_eq!(game.perft(3), 2812);
 
-        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        let fen = test_suite::PERFT_POSITION_4;
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 6);
         assert_eq!(game.perft(2), 264);
         assert_eq!(game.perft(3), 9467);
 
-        let fen = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+        let fen = test_suite::PERFT_POSITION_4_MIRRORED;
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 6);
         assert_eq!(game.perft(2), 264);
         assert_eq!(game.perft(3), 9467);
 
-        // Kiwipete position
-        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -";
+        let fen = test_suite::KIWIPETE;
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 48);
         assert_eq!(game.perft(2), 2039);
         assert_eq!(game.perft(3), 97862);
 
-        let fen = "rnbqkb1r/pp1p1ppp/2p5/4P3/2B5/8/PPP1NnPP/RNBQK2R w KQkq - 0 6";
+        let fen = test_suite::PERFT_POSITION_5;
         game.load_fen(fen).unwrap();
         assert_eq!(game.perft(1), 42);
         assert_eq!(game.perft(2), 1352);
         assert_eq!(game.perft(3), 53392);
+
+        let fen = test_suite::PERFT_POSITION_6;
+        game.load_fen(fen).unwrap();
+        assert_eq!(game.perft(1), 46);
+        assert_eq!(game.perft(2), 2079);    }
+
+    #[test]
+    fn test_perft_with() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        let mut divide = Vec::new();
+        let n = game.perft_with(3, |m, nodes| divide.push((m.to_lan(), nodes)));
+        assert_eq!(n, 8902);
+        assert_eq!(divide.len(), 20);
+        assert_eq!(divide.iter().map(|&(_, nodes)| nodes).sum::<u64>(), n);
+        assert!(divide.contains(&("e2e4".to_string(), 600)));
+        assert!(divide.contains(&("g1f3".to_string(), 440)));
+        assert_eq!(game.to_fen(), DEFAULT_FEN);
+    }
+
+    #[test]
+    fn test_perft_chess960() {
+        let mut game = Game::new();
+        let positions = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", [21, 807, 18002]),
+            ("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9", [20, 479, 10471]),
+            ("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9", [22, 593, 13440]),
+            ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", [28, 1120, 31058])
+        ];
+        for &(fen, counts) in positions.iter() {
+            game.load_fen(fen).unwrap();
+            for (i, &n) in counts.iter().enumerate() {
+                assert_eq!(game.perft(i as Depth + 1), n, "perft {} of {}", i + 1, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_en_passant_pins() {
+        let mut game = Game::new();
+        let positions = [
+            ("8/5bk1/8/2Pp4/8/1K6/8/8 w - d6 0 1", 62297),     // Diagonal pin
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 206379),   // Capture giving check
+            ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 185429),     // Horizontal pin later
+            ("8/8/8/KPp4r/8/8/8/7k w - c6 0 1", 23591),        // Horizontal pin
+            ("8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1", 117741)      // Horizontal pin
+        ];
+        for &(fen, n) in positions.iter() {
+            game.load_fen(fen).unwrap();
+            assert_eq!(game.perft(5), n, "perft 5 of {}", fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_suite() {
+        // Only the smallest counts to keep the test fast in debug mode
+        let mut game = Game::new();
+        for &(fen, depth, n) in test_suite::PERFT_SUITE.iter().filter(|&&(_, _, n)| n < 500_000) {
+            game.load_fen(fen).unwrap();
+            assert_eq!(game.perft(depth), n, "perft {} of {}", depth, fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_stats() {
+        // Reference counts from the Chess Programming Wiki
+        let positions = [
+            (DEFAULT_FEN, 4, [197281, 1576, 0, 0, 0, 469, 8]),
+            (test_suite::KIWIPETE, 3, [97862, 17102, 45, 3162, 0, 993, 1]),
+            (test_suite::PERFT_POSITION_3, 4, [43238, 3348, 123, 0, 0, 1680, 17]),
+            (test_suite::PERFT_POSITION_4, 3, [9467, 1021, 4, 0, 120, 38, 22])
+        ];
+        for &(fen, depth, counts) in &positions {
+            let mut game = Game::from_fen(fen).unwrap();
+            let stats = game.perft_stats(depth);
+            assert_eq!(stats, PerftStats {
+                nodes: counts[0],
+                captures: counts[1],
+                en_passants: counts[2],
+                castles: counts[3],
+                promotions: counts[4],
+                checks: counts[5],
+                checkmates: counts[6]
+            }, "perft {} of {}", depth, fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_hashed() {
+        let mut game = Game::new();
+        let fens = [
+            DEFAULT_FEN,
+            test_suite::KIWIPETE,
+            test_suite::PERFT_POSITION_3,
+            test_suite::PERFT_POSITION_4,
+            "8/8/8/KPp4r/8/8/8/7k w - c6 0 1", // En passant pin
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9" // Chess960
+        ];
+
+        // Wrong hashes of transpositions would give different counts, with
+        // a table small enough to replace its entries
+        for fen in &fens {
+            let mut table = PerftTable::with_capacity(1 << 10);
+            game.load_fen(fen).unwrap();
+            let n = game.perft(4);
+            assert_eq!(game.perft_hashed(4, &mut table), n, "perft 4 of {}", fen);
+            assert_eq!(game.perft_hashed(4, &mut table), n, "perft 4 of {}", fen);
+        }
     }
 
     #[test]
@@ -760,7 +1840,7 @@ mod tests {
 
     #[test]
     fn test_stalemate() {
-        let mut game = Game::from_fen("4k3/4P3/4K3/8/8/8/8/ b - - 0 1").unwrap();
+        let mut game = Game::from_fen("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1").unwrap();
 
         game.nodes_count = 0;
         game.clock = Clock::new(1, 5 * 1000); // 5 seconds
@@ -801,6 +1881,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_upcoming_repetition() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        for s in &["g1f3", "b8c6", "f3g1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        // The start position can be reached again by Nc6-b8, but before the
+        // root it is not a repetition until it is repeated once
+        assert!(!game.has_upcoming_repetition(0));
+        assert!(game.has_upcoming_repetition(4));
+
+        // The position after 1. Nf3 can be repeated with a move of the side
+        // not to move, only inside the search tree
+        for s in &["g8f6", "g1f3", "c6b8"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        assert!(!game.has_upcoming_repetition(0));
+        assert!(game.has_upcoming_repetition(6));
+
+        // No repetition across an irreversible move
+        let m = game.move_from_lan("e2e4");
+        game.make_move(m);
+        assert!(!game.has_upcoming_repetition(7));
+
+        // The start position was already repeated before the root
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        for s in &["g1f3", "b8c6", "f3g1", "c6b8", "g1f3", "b8c6", "f3g1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        assert!(game.has_upcoming_repetition(0)); // Nc6-b8
+    }
+
+    #[test]
+    fn test_search_upcoming_repetition() {
+        // Black is lost but can go back to a previous position with Kh8,
+        // which is not searched by the quiescence search at the horizon
+        let mut game = Game::from_fen("7k/8/8/8/8/8/8/KQ6 w - - 0 1").unwrap();
+        for s in &["b1c1", "h8g8", "c1b1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        game.clock = Clock::new(1, 60 * 1000);
+        game.clock.start(game.positions.len());
+        let score = game.search_node(-INF, INF, 1, 4);
+        assert_eq!(score, game.draw_score(4));
+    }
+
     #[test]
     fn test_fifty_move_rule() {
         // Timman vs Lutz (1995)
@@ -844,6 +1974,281 @@ mod tests {
         assert_eq!(game.search(1..10), None);
     }
 
+    #[test]
+    fn test_aspiration() {
+        // The mate found after the first iterations fails high
+        let fen = "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - -";
+        let mut results = Vec::new();
+        for &aspiration in &[0, 10] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 5 * 1000);
+            game.search_params.aspiration = aspiration;
+            let m = game.search(1..8).unwrap();
+            assert_eq!(m, PieceMove::new(H6, H7, CAPTURE));
+            results.push(game.search_result.unwrap().score);
+        }
+        assert_eq!(results[0], results[1]);
+        assert!(mate_moves(results[0]).is_some());
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.search(1..7);
+
+        let stats = game.search_stats;
+        assert!(stats.pv_nodes_count > 0);
+        assert!(stats.non_pv_nodes_count > stats.pv_nodes_count);
+        assert!(stats.quiescence_share() > 0.0 && stats.quiescence_share() < 1.0);
+        assert!(stats.tt_hits_count <= stats.tt_probes_count);
+        assert!(stats.tt_cutoffs_count <= stats.tt_hits_count);
+        assert!(stats.first_move_cutoffs_count <= stats.cutoffs_count);
+        assert!(stats.null_move_cutoffs_count <= stats.null_moves_count);
+        assert!(stats.first_move_cutoff_rate() > 0.5);
+
+        // The stats of every thread are added together
+        game.threads_count = 2;
+        game.search(1..5);
+        let n: u64 = game.threads_stats.iter().map(|stats| stats.search_stats.nodes_count()).sum();
+        assert_eq!(game.search_stats.nodes_count(), n);
+    }
+
+    #[test]
+    fn test_search_trace() {
+        let path = env::temp_dir().join("littlewing_test_trace.jsonl");
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.trace.path = Some(path.clone());
+        game.trace.max_ply = 2;
+        game.trace.open().unwrap();
+        game.search(1..6);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.lines().count() > 0);
+        for line in content.lines() {
+            assert!(line.starts_with("{\"hash\":"));
+            assert!(line.ends_with("}"));
+            assert!(!line.contains("\"ply\":3,"));
+        }
+        assert!(content.contains("\"reason\":\"cutoff\""));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_threads_stats() {
+        for &n in &[0, 2] {
+            let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+            game.clock = Clock::new(1, 5 * 1000);
+            game.threads_count = n;
+            game.search(1..5);
+            assert_eq!(game.threads_stats.len(), n.max(1));
+            for stats in &game.threads_stats {
+                assert_eq!(stats.depth, 4);
+                assert!(stats.nodes_count > 0);
+                assert!(stats.tt_hits_count <= stats.tt_lookups_count);
+            }
+            let nodes_count: u64 = game.threads_stats.iter().map(|stats| stats.nodes_count).sum();
+            assert_eq!(nodes_count, game.nodes_count);
+        }
+    }
+
+    #[test]
+    fn test_search_time_pressure() {
+        // Capture of the queen
+        let fen = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 50);
+        game.clock.disable_level();
+        assert_eq!(game.search(1..10), Some(PieceMove::new(D2, D5, CAPTURE)));
+        assert!(game.clock.elapsed_time() < 50);
+
+        // Best move of the TT without any capture
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        let m = PieceMove::new(G1, F3, QUIET_MOVE);
+        game.tt.set(game.positions.top().hash, 1, 0, m, Bound::Exact);
+        game.clock = Clock::new(1, 50);
+        game.clock.disable_level();
+        assert_eq!(game.search(1..10), Some(m));
+
+        // Without anything better all the moves are searched
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 50);
+        game.clock.disable_level();
+        assert!(game.search(1..10).is_some());
+    }
+
+    #[test]
+    fn test_search_crazyhouse() {
+        // Back rank mate with a rook dropped from the hand
+        let fen = "6k1/5ppp/8/8/8/8/8/4K3[R] w - - 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::Crazyhouse;
+        game.load_fen(fen).unwrap();
+        game.clock = Clock::new(1, 1000);
+        let m = game.search(1..4).unwrap();
+        assert!(m.is_drop());
+        assert_eq!(m.to().rank(), 7);
+    }
+
+    #[test]
+    fn test_search_three_check() {
+        // Giving the third check wins more than capturing the queen
+        let fen = "4k3/8/8/3q4/8/8/3Q4/4K3 w - - 1+3 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::ThreeCheck;
+        game.load_fen(fen).unwrap();
+        game.clock = Clock::new(1, 1000);
+        let m = game.search(1..4).unwrap();
+        game.make_move(m);
+        assert!(game.is_check(BLACK));
+        assert!(game.is_checks_limit_reached());
+        assert_eq!(game.search(1..4), None);
+    }
+
+    #[test]
+    fn test_search_antichess() {
+        // Giving the rook away to the pawn wins
+        let fen = "8/8/8/8/8/8/1p6/R7 w - - 0 1";
+        let mut game = Game::new();
+        game.variant = Variant::Antichess;
+        game.load_fen(fen).unwrap();
+        game.clock = Clock::new(1, 1000);
+        let m = game.search(1..4).unwrap();
+        assert_eq!(m, PieceMove::new(A1, C1, QUIET_MOVE));
+    }
+
+    #[test]
+    fn test_search_infinite() {
+        use std::thread;
+        use std::time::Duration;
+
+        // Mate in one
+        let fen = "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - -";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 1000); // 1 second
+        game.clock.enable_infinite();
+
+        let mut clock = game.clock.clone();
+        let builder = thread::Builder::new().stack_size(4 << 20);
+        let searcher = builder.spawn(move || {
+            game.search(1..100)
+        }).unwrap();
+
+        // The search should neither stop at the mate nor after its time
+        thread::sleep(Duration::from_millis(2000));
+        assert!(!clock.is_stopped());
+
+        clock.stop();
+        let m = searcher.join().unwrap();
+        assert_eq!(m, Some(PieceMove::new(H6, H7, CAPTURE)));
+    }
+
+    #[test]
+    fn test_clear_policy() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        let hash = game.positions.top().hash;
+        game.search_params.clear_between_moves = ClearPolicy::Keep;
+        game.search(1..5);
+        assert!(game.tt.get(hash).is_some());
+
+        let m = game.move_from_lan("e2e4");
+        let h = game.moves.get_history(m);
+        game.search(1..2);
+        assert!(game.moves.get_history(m) >= h);
+        let h = game.moves.get_history(m);
+
+        game.clear_search(ClearPolicy::Age);
+        assert!(game.tt.get(hash).is_some());
+        assert_eq!(game.moves.get_history(m), h / 2);
+
+        game.new_game();
+        assert!(game.tt.get(hash).is_none());
+        assert_eq!(game.moves.get_history(m), 0);
+    }
+
+    #[test]
+    fn test_repetition_adjustment() {
+        // White is winning
+        let mut game = Game::from_fen("k7/8/8/8/8/8/8/3Q2K1 w - - 0 1").unwrap();
+        for s in &["g1f2", "a8b8", "f2g1", "b8a8"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        game.search_params.repetition = 50;
+
+        let m = game.move_from_lan("g1f2");
+        game.make_move(m);
+        assert!(game.allows_repetition());
+        game.undo_move(m);
+
+        let m = game.move_from_lan("g1h2");
+        game.make_move(m);
+        assert!(!game.allows_repetition());
+        game.undo_move(m);
+
+        assert_eq!(game.repetition_adjustment(eval::QUEEN_VALUE), -50);
+
+        // Black is losing
+        let mut game = Game::from_fen("k7/8/8/8/8/8/8/3Q2K1 b - - 0 1").unwrap();
+        for s in &["a8b8", "g1f2", "b8a8", "f2g1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+        }
+        game.search_params.repetition = 50;
+
+        let m = game.move_from_lan("a8b8");
+        game.make_move(m);
+        assert!(game.allows_repetition());
+        game.undo_move(m);
+
+        let m = game.move_from_lan("a8a7");
+        game.make_move(m);
+        assert!(!game.allows_repetition());
+        game.undo_move(m);
+
+        assert_eq!(game.repetition_adjustment(-eval::QUEEN_VALUE), 50);
+
+        // With contempt a draw is worse than an equal position
+        game.search_params.contempt = 20;
+        game.search_params.contempt_ending = 20;
+        assert_eq!(game.repetition_adjustment(-10), -50);
+        assert_eq!(game.repetition_adjustment(-20), 0);
+        assert_eq!(game.repetition_adjustment(-30), 50);
+    }
+
+    #[test]
+    fn test_search_repetition() {
+        // White is winning and should not allow black to repeat the position
+        let fen = "k7/8/8/8/8/8/8/3Q2K1 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        for s in &["g1f2", "a8b8", "f2g1", "b8a8"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+            game.history.push(m);
+        }
+        game.search_params.repetition = 50;
+        game.clock = Clock::new(1, 1000); // 1 second
+        let m = game.search(1..10).unwrap();
+        assert!(m != game.move_from_lan("g1f2"));
+
+        // Black is losing and should seek the repetition
+        let fen = "k7/8/8/8/8/8/8/3Q2K1 b - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        for s in &["a8b8", "g1f2", "b8a8", "f2g1"] {
+            let m = game.move_from_lan(s);
+            game.make_move(m);
+            game.history.push(m);
+        }
+        game.search_params.repetition = 50;
+        game.clock = Clock::new(1, 1000); // 1 second
+        let m = game.search(1..10).unwrap();
+        assert_eq!(m, game.move_from_lan("a8b8"));
+    }
+
     #[test]
     fn test_bug_promotion() {
         let fen = "5n2/1k4P1/8/8/8/8/6K1/8 w - - 0 1";
@@ -889,10 +2294,34 @@ mod tests {
         assert!(m != m1);
     }
 
+    #[test]
+    fn test_fail_soft() {
+        // White is a rook up, the scores outside of the window are returned
+        let fen = "4k3/pppp4/8/8/8/8/PPPP4/3RK3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.clock.start(game.positions.len());
+
+        let score = game.search_node(0, 1, 3, 1);
+        assert!(score > 200);
+
+        let score = game.search_node(1000, 1001, 3, 1);
+        assert!(score < 800);
+
+        // Black is a queen down, the delta pruning returns an upper bound
+        // below alpha
+        let fen = "4k3/8/8/8/8/8/8/Q3K3 b - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.clock.start(game.positions.len());
+        let score = game.quiescence(500, 501, -2, 1);
+        assert!(score < 500);
+    }
+
     #[test]
     fn test_null_move_pruning() {
         // Zugzwang #1
-        let fen = "1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1";
+        let fen = test_suite::ZUGZWANG_1;
         let mut game = Game::from_fen(fen).unwrap();
         game.clock = Clock::new(1, 5000); // 1 second
         let m = game.search(1..100).unwrap();
@@ -901,7 +2330,7 @@ mod tests {
         // Zugzwang #2
         /*
         //FIXME: this position takes too long at the moment
-        let fen = "8/8/p1p5/1p5p/1P5p/8/PPP2K1p/4R1rk w - - 0 1";
+        let fen = test_suite::ZUGZWANG_2;
         let mut game = Game::from_fen(fen).unwrap();
         game.clock = Clock::new(1, 1000); // 1 second
         let m = game.search(1..100).unwrap();
@@ -909,6 +2338,231 @@ mod tests {
         */
     }
 
+    #[test]
+    fn test_futility_pruning() {
+        // Quiet moves are pruned without missing the mate
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        let m = game.search(1..7).unwrap();
+        assert_eq!(game.move_to_san(m), "Qxf7");
+
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+        let mut nodes_counts = Vec::new();
+        for &fp_depth in &[0, 6] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            game.search_params.fp_depth = fp_depth;
+            game.search(1..7);
+            nodes_counts.push(game.nodes_count);
+        }
+        assert!(nodes_counts[1] < nodes_counts[0]);
+    }
+
+    #[test]
+    fn test_razoring() {
+        let fen = "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 1 7";
+        let mut results = Vec::new();
+        for &razor_depth in &[0, 4] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            game.search_params.razor_depth = razor_depth;
+            let m = game.search(1..7).unwrap();
+            results.push((m, game.nodes_count));
+        }
+        assert_eq!(results[0].0, results[1].0);
+        assert!(results[1].1 < results[0].1);
+    }
+
+    #[test]
+    fn test_search_abort() {
+        // The game is left at the root position wherever the search stops
+        let mut rng = Random::new(42);
+        for fen in test_suite::BENCH_SUITE.iter() {
+            let mut game = Game::from_fen(fen).unwrap();
+            let fen = game.to_fen();
+            let hash = game.positions.top().hash;
+            let positions_count = game.positions.len();
+            for _ in 0..10 {
+                game.clock = Clock::new(1, 60 * 1000);
+                game.clock.enable_infinite();
+                game.clock.set_nodes_limit(1 + rng.next_below(20_000));
+                game.search(1..20);
+                assert_eq!(game.to_fen(), fen);
+                assert_eq!(game.positions.top().hash, hash);
+                assert_eq!(game.positions.len(), positions_count);
+                assert!(game.is_consistent());
+            }
+        }
+    }
+
+    #[test]
+    fn test_late_move_pruning() {
+        let fen = "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 1 7";
+        let mut results = Vec::new();
+        for &lmp_depth in &[0, 4] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            game.search_params.lmp_depth = lmp_depth;
+            game.search(1..8).unwrap();
+            results.push((game.search_result.unwrap().score, game.nodes_count));
+        }
+        // The pruning may choose another move of about the same score
+        assert!((results[0].0 - results[1].0).abs() < 20);
+        assert!(results[1].1 < results[0].1);
+    }
+
+    #[test]
+    fn test_see_pruning() {
+        let fen = "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 1 7";
+        let mut results = Vec::new();
+        for &see_depth in &[0, 4] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            game.search_params.see_depth = see_depth;
+            game.search(1..8).unwrap();
+            results.push((game.search_result.unwrap().score, game.nodes_count));
+        }
+        // The pruning may choose another move of about the same score
+        assert!((results[0].0 - results[1].0).abs() < 20);
+        assert!(results[1].1 < results[0].1);
+    }
+
+    #[test]
+    fn test_quiescence_in_check() {
+        // Checkmate
+        let fen = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+        let mut game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.quiescence(-INF, INF, -1, 0), -INF);
+
+        // The mate given by a capture at the horizon is found by searching
+        // the evasions instead of standing pat
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 2 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        let m = game.search(1..2).unwrap();
+        assert_eq!(game.move_to_san(m), "Qxf7");
+        assert_eq!(mate_moves(game.search_result.unwrap().score), Some(1));
+    }
+
+    #[test]
+    fn test_pv() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 2 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.search(1..4);
+        assert_eq!(game.get_pv(true), "4. Qxf7#");
+
+        // The PV is a sequence of legal moves starting with the best move
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        let best_move = game.search(1..8).unwrap();
+        let pv = game.pv.get(0).to_vec();
+        assert_eq!(pv[0], best_move);
+        assert!(pv.len() >= 4);
+        for m in pv {
+            assert!(game.get_moves().contains(&m));
+            game.make_move(m);
+        }
+    }
+
+    #[test]
+    fn test_seldepth() {
+        // The captures of the quiescence search go deeper than the depth
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.search(1..6);
+        assert!(game.seldepth > 5);
+        assert!(game.seldepth < MAX_PLY);
+    }
+
+    #[test]
+    fn test_root_moves() {
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        assert_eq!(game.root_moves().len(), 20);
+
+        // The capture of the queen is searched first, and the moves leaving
+        // the king in check are left out
+        let fen = "4k3/8/8/3q4/4P3/8/3r4/4K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        let moves = game.root_moves();
+        assert_eq!(moves[0].0, PieceMove::new(E4, D5, CAPTURE));
+        assert_eq!(moves.len(), game.get_moves().len());
+    }
+
+    #[test]
+    fn test_root_ordering() {
+        // The root moves left by the function are the only ones searched
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.root_ordering = Some(Arc::new(|_: &Game, moves: &mut Vec<(PieceMove, Score)>| {
+            moves.retain(|&(m, _)| m.to_lan() == "a2a3" || m.to_lan() == "h2h4");
+            moves.reverse();
+        }));
+        let m = game.search(1..5).unwrap();
+        assert!(m.to_lan() == "a2a3" || m.to_lan() == "h2h4");
+    }
+
+    #[test]
+    fn test_search_features() {
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        game.search(1..6);
+        let nodes_count = game.nodes_count;
+
+        for name in &SEARCH_FEATURES {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            *game.search_params.features.feature_mut(name).unwrap() = false;
+            assert!(game.search(1..6).is_some());
+            assert!(game.nodes_count != nodes_count, "{} disabled", name);
+        }
+    }
+
+    #[test]
+    fn test_search_max_lmr() {
+        // The reduction cannot go beyond the horizon
+        let mut game = Game::from_fen(DEFAULT_FEN).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        for &phase in &GAME_PHASES {
+            game.search_params.phase_mut(phase).lmr = 4;
+        }
+        assert!(game.search(1..8).is_some());
+    }
+
+    #[test]
+    fn test_internal_iterative_deepening() {
+        // The move ordering changes but not the best move
+        let fen = "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4";
+        let mut results = Vec::new();
+        for &(iid_depth, iir_depth) in &[(0, 0), (4, 0), (4, 4)] {
+            let mut game = Game::from_fen(fen).unwrap();
+            game.clock = Clock::new(1, 60 * 1000);
+            game.search_params.iid_depth = iid_depth;
+            game.search_params.iir_depth = iir_depth;
+            let m = game.search(1..10).unwrap();
+            results.push((m, game.nodes_count));
+        }
+        assert_eq!(results[0].0, results[1].0);
+        assert_eq!(results[0].0, results[2].0);
+        assert_ne!(results[0].1, results[2].1);
+    }
+
+    #[test]
+    fn test_check_extension() {
+        // Smothered mate in four moves, all giving check, found by an
+        // iteration shallower than its seven plies
+        let fen = "r6k/6pp/8/4N3/2Q5/8/8/6K1 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        game.clock = Clock::new(1, 60 * 1000);
+        let m = game.search(1..6).unwrap();
+        assert_eq!(m, PieceMove::new(E5, F7, QUIET_MOVE));
+        assert_eq!(game.search_result.unwrap().score, INF - 7);
+    }
+
     #[test]
     fn test_is_mate() {
         let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2";
@@ -923,4 +2577,54 @@ mod tests {
         let mut game = Game::from_fen("8/8/8/8/r7/1k6/8/K7 w - - 0 1").unwrap();
         assert_eq!(game.get_moves(), vec![PieceMove::new(A1, B1, QUIET_MOVE)])
     }
+
+    #[test]
+    fn test_score_tt() {
+        // Mate in 1 found at ply 2 is a mate in 1 at the root of the node
+        let score = INF - 3;
+        assert_eq!(score_to_tt(score, 2), INF - 1);
+        assert_eq!(score_from_tt(score_to_tt(score, 2), 2), score);
+        assert_eq!(score_from_tt(score_to_tt(score, 2), 4), INF - 5);
+        assert_eq!(score_from_tt(score_to_tt(-score, 2), 4), -INF + 5);
+
+        // Other scores are independent of the ply
+        assert_eq!(score_to_tt(42, 10), 42);
+        assert_eq!(score_from_tt(-42, 10), -42);
+    }
+
+    #[test]
+    fn test_mate_score_from_tt() {
+        // Mate in 1 from the root stored in the transposition table
+        let mut game = Game::from_fen("6k1/8/6K1/8/8/8/8/R7 w - - 0 1").unwrap();
+        let m = game.move_from_lan("a1a8");
+        let hash = game.positions.top().hash;
+        game.tt.set(hash, 10, score_to_tt(INF - 1, 0), m, Bound::Exact);
+
+        // The only legal move of black leads to the same position, where
+        // the mate found in the table is now two plies away from the root
+        game.load_fen("7k/8/6K1/8/8/8/8/R7 b - - 0 1").unwrap();
+        let alpha = -INF + 1;
+        let beta = -INF + 2;
+        assert_eq!(game.search_node(alpha, beta, 2, 0), -INF + 2);
+    }
+
+    #[test]
+    fn test_shortest_mate() {
+        // Mates in 1 with the rook or the queen, and many longer mates
+        let mut game = Game::from_fen("k7/8/1K6/8/8/8/8/1Q5R w - - 0 1").unwrap();
+        game.clock = Clock::new(1, 5 * 1000);
+        let m = game.search(1..10).unwrap();
+        assert!(m.to_lan() == "h1h8" || m.to_lan() == "b1b7");
+        let hash = game.positions.top().hash;
+        assert_eq!(game.tt.get(hash).unwrap().score(), INF - 1);
+        assert_eq!(mate_moves(INF - 1), Some(1));
+    }
+
+    #[test]
+    fn test_uci_score() {
+        assert_eq!(uci_score(42, Bound::Exact), "cp 42");
+        assert_eq!(uci_score(-42, Bound::Lower), "cp -42 lowerbound");
+        assert_eq!(uci_score(INF - 3, Bound::Upper), "mate 2 upperbound");
+        assert_eq!(uci_score(-INF + 4, Bound::Exact), "mate -2");
+    }
 }
@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 
+use std::cmp;
+
 use color::*;
+use common::*;
+use bitboard::{Bitboard, BitboardExt};
 
 pub type Square = u8;
 
@@ -113,9 +117,61 @@ impl SquareExt for Square {
     }
 }
 
+/// Get the number of king moves between the given squares
+pub fn distance(a: Square, b: Square) -> u8 {
+    let files = (a.file() as i8 - b.file() as i8).abs();
+    let ranks = (a.rank() as i8 - b.rank() as i8).abs();
+    cmp::max(files, ranks) as u8
+}
+
+/// Get the number of files plus the number of ranks between the given
+/// squares
+pub fn manhattan_distance(a: Square, b: Square) -> u8 {
+    let files = (a.file() as i8 - b.file() as i8).abs();
+    let ranks = (a.rank() as i8 - b.rank() as i8).abs();
+    (files + ranks) as u8
+}
+
+/// Get the direction going from the first square to the second one, if
+/// they are on the same file, rank, or diagonal
+pub fn direction_between(a: Square, b: Square) -> Option<Direction> {
+    let files = b.file() as i8 - a.file() as i8;
+    let ranks = b.rank() as i8 - a.rank() as i8;
+    if a == b || (files != 0 && ranks != 0 && files.abs() != ranks.abs()) {
+        return None;
+    }
+
+    let dir = match (files.signum(), ranks.signum()) {
+        ( 0,  1) => NORTH,
+        ( 0, -1) => SOUTH,
+        (-1,  0) => WEST,
+        ( 1,  0) => EAST,
+        (-1,  1) => NORTHWEST,
+        ( 1,  1) => NORTHEAST,
+        (-1, -1) => SOUTHWEST,
+        _        => SOUTHEAST
+    };
+    Some(dir)
+}
+
+/// Get the squares going from the given square to the edge of the board in
+/// the given direction, without the square itself
+pub fn ray(sq: Square, dir: Direction) -> Bitboard {
+    let mut ray = 0;
+    let mut bb: Bitboard = 1 << sq;
+    loop {
+        bb = bb.shift(DIRECTION_SHIFTS[dir]) & DIRECTION_MASKS[dir];
+        if bb == 0 {
+            return ray;
+        }
+        ray |= bb;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bitboard::BitboardIterator;
 
     #[test]
     fn test_from_coord() {
@@ -130,6 +186,51 @@ mod tests {
         assert_eq!(C6.to_coord(), "c6");
     }
 
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance(E4, E4), 0);
+        assert_eq!(distance(E4, F5), 1);
+        assert_eq!(distance(A1, H8), 7);
+        assert_eq!(distance(B1, C7), 6);
+
+        assert_eq!(manhattan_distance(E4, F5), 2);
+        assert_eq!(manhattan_distance(A1, H8), 14);
+        assert_eq!(manhattan_distance(B1, C7), 7);
+    }
+
+    #[test]
+    fn test_direction_between() {
+        assert_eq!(direction_between(E4, E8), Some(NORTH));
+        assert_eq!(direction_between(E4, E1), Some(SOUTH));
+        assert_eq!(direction_between(E4, A4), Some(WEST));
+        assert_eq!(direction_between(E4, H4), Some(EAST));
+        assert_eq!(direction_between(E4, B7), Some(NORTHWEST));
+        assert_eq!(direction_between(E4, H7), Some(NORTHEAST));
+        assert_eq!(direction_between(E4, B1), Some(SOUTHWEST));
+        assert_eq!(direction_between(E4, G2), Some(SOUTHEAST));
+        assert_eq!(direction_between(E4, F6), None);
+        assert_eq!(direction_between(E4, E4), None);
+    }
+
+    #[test]
+    fn test_ray() {
+        assert_eq!(ray(E4, NORTH), (1 << E5) | (1 << E6) | (1 << E7) | (1 << E8));
+        assert_eq!(ray(B2, WEST), 1 << A2);
+        assert_eq!(ray(A2, WEST), 0);
+        assert_eq!(ray(H1, EAST), 0);
+        assert_eq!(ray(F3, SOUTHEAST), (1 << G2) | (1 << H1));
+        assert_eq!(ray(C1, NORTHWEST), (1 << B2) | (1 << A3));
+        assert_eq!(ray(A1, NORTHEAST).count(), 7);
+
+        // The squares of a ray are in the direction between them
+        for &dir in &[NORTH, SOUTH, WEST, EAST, NORTHWEST, NORTHEAST, SOUTHWEST, SOUTHEAST] {
+            let mut squares = ray(D5, dir);
+            while let Some(sq) = squares.next() {
+                assert_eq!(direction_between(D5, sq), Some(dir));
+            }
+        }
+    }
+
     #[test]
     fn test_flip() {
         assert_eq!(E2.flip(WHITE), E2);
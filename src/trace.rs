@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use common::*;
+use piece_move::PieceMove;
+
+/// Deepest ply of the nodes traced by default
+pub const TRACE_MAX_PLY: usize = 4;
+
+/// A move searched or pruned in a node of the search, or the node itself
+/// when its score was found without searching its moves
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceRecord {
+    pub hash: u64,
+    pub ply: usize,
+    pub piece_move: PieceMove, // Null for the node itself
+    pub depth: Depth,
+    pub alpha: Score,
+    pub beta: Score,
+    pub score: Option<Score>, // None for a pruned move
+    pub reason: Option<&'static str> // Pruning or cutoff of the move or node
+}
+
+impl TraceRecord {
+    /// Get the record as a line of JSON
+    pub fn to_json(&self) -> String {
+        let m = if self.piece_move.is_null() {
+            "null".to_string()
+        } else {
+            format!("\"{}\"", self.piece_move.to_lan())
+        };
+        let score = self.score.map_or("null".to_string(), |score| score.to_string());
+        let reason = self.reason.map_or("null".to_string(), |reason| format!("\"{}\"", reason));
+        format!(
+            "{{\"hash\":\"{:016X}\",\"ply\":{},\"move\":{},\"depth\":{},\"alpha\":{},\"beta\":{},\"score\":{},\"reason\":{}}}",
+            self.hash, self.ply, m, self.depth, self.alpha, self.beta, score, reason
+        )
+    }
+}
+
+/// Trace of the nodes visited by the search, written to a file in JSON
+/// Lines with one record per line up to a given ply from the root
+///
+/// The file is shared by the threads of the search.
+#[derive(Clone, Debug)]
+pub struct SearchTrace {
+    pub path: Option<PathBuf>,
+    pub max_ply: usize,
+    writer: Option<Arc<Mutex<BufWriter<File>>>>
+}
+
+impl SearchTrace {
+    pub fn new() -> SearchTrace {
+        SearchTrace {
+            path: None,
+            max_ply: TRACE_MAX_PLY,
+            writer: None
+        }
+    }
+
+    /// Create the trace file, or stop tracing without a path
+    pub fn open(&mut self) -> io::Result<()> {
+        self.writer = match self.path {
+            Some(ref path) => Some(Arc::new(Mutex::new(BufWriter::new(File::create(path)?)))),
+            None => None
+        };
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    /// Check if the nodes at the given ply are traced
+    pub fn is_tracing(&self, ply: usize) -> bool {
+        self.writer.is_some() && ply <= self.max_ply
+    }
+
+    /// Write the given record to the trace file
+    pub fn write(&self, record: &TraceRecord) {
+        if let Some(ref writer) = self.writer {
+            if let Ok(mut writer) = writer.lock() {
+                writeln!(writer, "{}", record.to_json()).ok();
+            }
+        }
+    }
+
+    /// Flush the records written to the trace file
+    pub fn flush(&self) {
+        if let Some(ref writer) = self.writer {
+            if let Ok(mut writer) = writer.lock() {
+                writer.flush().ok();
+            }
+        }
+    }
+}
+
+impl Default for SearchTrace {
+    fn default() -> Self {
+        SearchTrace::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use piece_move::*;
+    use square::*;
+
+    #[test]
+    fn test_to_json() {
+        let record = TraceRecord {
+            hash: 0x463B96181691FC9C,
+            ply: 1,
+            piece_move: PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH),
+            depth: 3,
+            alpha: -INF,
+            beta: INF,
+            score: Some(12),
+            reason: None
+        };
+        let json = "{\"hash\":\"463B96181691FC9C\",\"ply\":1,\"move\":\"e2e4\",\"depth\":3,\"alpha\":-29999,\"beta\":29999,\"score\":12,\"reason\":null}";
+        assert_eq!(record.to_json(), json);
+
+        let record = TraceRecord {
+            piece_move: PieceMove::new_null(),
+            score: None,
+            reason: Some("nmp"),
+            ..record
+        };
+        let json = "{\"hash\":\"463B96181691FC9C\",\"ply\":1,\"move\":null,\"depth\":3,\"alpha\":-29999,\"beta\":29999,\"score\":null,\"reason\":\"nmp\"}";
+        assert_eq!(record.to_json(), json);
+    }
+}
@@ -1,5 +1,6 @@
 use common::*;
 use piece_move::PieceMove;
+use square::Square;
 
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -24,7 +25,7 @@ pub struct Transposition {
     // it as `i8`.
     //
     // TODO: we don't need to store the whole hash as the first part is the
-    // index of the entry: `entries[hash % size]`
+    // index of the bucket: `buckets[hash % size]`
 }
 
 impl Transposition {
@@ -66,6 +67,39 @@ impl Transposition {
     pub fn age(&self) -> u8 {
         self.age
     }
+
+    /// Pack the fields of the entry other than its hash in 64 bits
+    pub fn data(&self) -> u64 {
+        let m = self.best_move;
+        ((m.from() as u64) << 10) |
+        ((m.to() as u64) << 4) |
+        (m.kind() as u64) |
+        ((self.score as u16 as u64) << 16) |
+        ((self.depth as u8 as u64) << 32) |
+        ((self.bound as u64) << 40) |
+        ((self.age as u64) << 48)
+    }
+
+    /// Unpack the fields of the entry of the given hash from 64 bits
+    pub fn from_data(hash: u64, data: u64) -> Transposition {
+        let from = ((data >> 10) & 0x3F) as Square;
+        let to = ((data >> 4) & 0x3F) as Square;
+        let kind = (data & 0xF) as PieceMoveType;
+        let best_move = PieceMove::new(from, to, kind);
+        let bound = match (data >> 40) as u8 {
+            1 => Bound::Lower,
+            2 => Bound::Upper,
+            _ => Bound::Exact
+        };
+        Transposition {
+            hash,
+            best_move,
+            score: (data >> 16) as u16 as Score,
+            depth: (data >> 32) as u8 as Depth,
+            bound,
+            age: (data >> 48) as u8
+        }
+    }
 }
 
 #[cfg(test)]
@@ -73,7 +107,8 @@ mod tests {
     use std::mem;
 
     use super::*;
-    use piece_move::PieceMove;
+    use piece_move::*;
+    use square::*;
 
     #[test]
     fn test_size_of_transposition() {
@@ -84,4 +119,15 @@ mod tests {
 
         assert_eq!(mem::size_of::<Transposition>(), 16);
     }
+
+    #[test]
+    fn test_transposition_data() {
+        let m = PieceMove::new(E7, E8, QUEEN_PROMOTION);
+        let t = Transposition::new(42, -3, -INF + 1, m, Bound::Upper, 7);
+        assert_eq!(Transposition::from_data(42, t.data()), t);
+
+        let t = Transposition::new_null();
+        assert_eq!(t.data(), 0);
+        assert_eq!(Transposition::from_data(0, 0), t);
+    }
 }
@@ -1,14 +1,58 @@
+use std::alloc::{self, Layout};
 use std::mem;
-use std::cell::UnsafeCell;
+use std::slice;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use common::*;
 use piece_move::PieceMove;
 use transposition::{Transposition, Bound};
 
+/// Number of entries in a bucket of the table
+pub const BUCKET_SIZE: usize = 4;
+
+// Size of the huge pages used for large tables when enabled
+const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+// Depth given up by the replacement strategy for each search an entry is
+// older than the current one
+const AGE_DEPTH: i16 = 8;
+
+/// An entry of the table shared by the threads of the search without lock
+///
+/// The hash is stored XORed with the data of the entry to detect the data
+/// written by another thread between reading both words, because the entry
+/// will not match its hash anymore (Hyatt's lockless hashing).
+pub struct Entry {
+    key: AtomicU64,
+    data: AtomicU64
+}
+
+impl Entry {
+    fn load(&self) -> Transposition {
+        let key = self.key.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+        Transposition::from_data(key ^ data, data)
+    }
+
+    fn store(&self, t: Transposition) {
+        let data = t.data();
+        self.key.store(t.hash() ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// A group of entries sharing the same index in the table, fitting in a
+/// cache line of 64 bytes to be read with a single memory access
+#[repr(align(64))]
+pub struct Bucket {
+    entries: [Entry; BUCKET_SIZE]
+}
+
 #[derive(Clone)]
 pub struct TranspositionTable {
     entries: Arc<SharedTable>,
+    huge_pages: bool,
     age: u8,
     stats_lookups: u64,
     stats_inserts: u64,
@@ -25,7 +69,8 @@ impl TranspositionTable {
         };
 
         TranspositionTable {
-            entries: Arc::new(SharedTable::with_capacity(n)),
+            entries: Arc::new(SharedTable::with_capacity(bucket_count(n), false)),
+            huge_pages: false,
             age: 0,
             stats_lookups: 0,
             stats_inserts: 0,
@@ -40,53 +85,107 @@ impl TranspositionTable {
         TranspositionTable::with_capacity(capacity)
     }
 
-    pub fn get(&mut self, hash: u64) -> Option<&Transposition> {
+    pub fn get(&mut self, hash: u64) -> Option<Transposition> {
         self.stats_lookups += 1;
 
-        let h = self.entries.get();
-        let n = self.len() as u64;
-        let k = (hash % n) as usize; // TODO: hash & (n - 1)
-        let t = &h[k]; // TODO: use get_unchecked?
+        let bucket = &self.entries.get()[self.index(hash)];
 
         // TODO: how faster would it be to just also return null move?
-        if t.best_move().is_null() {
-            None
-        } else if t.hash() != hash {
+        let mut is_empty = true;
+        for entry in &bucket.entries {
+            let t = entry.load();
+            if t.best_move().is_null() {
+                continue;
+            }
+            if t.hash() == hash {
+                self.stats_hits += 1;
+                return Some(t);
+            }
+            is_empty = false;
+        }
+        if !is_empty {
             self.stats_collisions += 1;
-            None
-        } else {
-            debug_assert_eq!(t.hash(), hash);
-            self.stats_hits += 1;
-            Some(t)
         }
+        None
     }
 
     pub fn set(&mut self, hash: u64, depth: Depth, score: Score, best_move: PieceMove, bound: Bound) {
         let age = self.age;
-        let h = self.entries.get();
-        let n = self.len() as u64;
-        let k = (hash % n) as usize;
-
-        // Always replace entries from previous searches (entry.age < age)
-        // but use depth preferred replacement strategy for the current search.
-        if age > h[k].age() || (age == 0 && h[k].age() > 0) || depth >= h[k].depth() {
-            let t = Transposition::new(hash, depth, score, best_move, bound, age);
-            h[k] = t;
-            self.stats_inserts += 1;
+        let bucket = &self.entries.get()[self.index(hash)];
+
+        // Replace the entry of the same position unless it is much deeper
+        // and from the current search, otherwise an empty entry or the one
+        // with the lowest depth after taking off some for each search since
+        // it was stored.
+        let mut i = 0;
+        let mut lowest = i16::MAX;
+        for (j, entry) in bucket.entries.iter().enumerate() {
+            let t = entry.load();
+            if t.hash() == hash && !t.best_move().is_null() {
+                if bound != Bound::Exact && t.age() == age && depth + 4 <= t.depth() {
+                    return;
+                }
+                i = j;
+                break;
+            }
+            let value = if t.best_move().is_null() {
+                i16::MIN
+            } else {
+                (t.depth() as i16) - AGE_DEPTH * (age.wrapping_sub(t.age()) as i16)
+            };
+            if value < lowest {
+                lowest = value;
+                i = j;
+            }
+        }
+
+        bucket.entries[i].store(Transposition::new(hash, depth, score, best_move, bound, age));
+        self.stats_inserts += 1;
+    }
+
+    /// Fetch the bucket of the given position into the cache of the CPU
+    /// ahead of its lookup
+    #[inline]
+    pub fn prefetch(&self, hash: u64) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket = &self.entries.get()[self.index(hash)];
+            unsafe { _mm_prefetch(bucket as *const Bucket as *const i8, _MM_HINT_T0) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            let _ = hash;
         }
     }
 
+    // Index of the bucket of the given position
+    fn index(&self, hash: u64) -> usize {
+        (hash & (self.entries.get().len() as u64 - 1)) as usize
+    }
+
     pub fn reset(&mut self) {
         self.age = (self.age + 1) % u8::max_value();
         self.clear_stats();
     }
 
     pub fn clear(&mut self) {
-        let n = self.len();
-        self.entries = Arc::new(SharedTable::with_capacity(n));
+        let n = self.entries.get().len();
+        self.entries = Arc::new(SharedTable::with_capacity(n, self.huge_pages));
         self.clear_stats();
     }
 
+    /// Allocate the table again with huge pages when they are enabled and
+    /// available, to reduce the misses of the TLB on large tables
+    pub fn set_huge_pages(&mut self, enabled: bool) {
+        self.huge_pages = enabled;
+        self.clear();
+    }
+
+    pub fn has_huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
     fn clear_stats(&mut self) {
         self.stats_lookups = 0;
         self.stats_inserts = 0;
@@ -94,8 +193,19 @@ impl TranspositionTable {
         self.stats_collisions = 0;
     }
 
+    /// Get the number of entries of the table
     pub fn len(&self) -> usize {
-        self.entries.get().len()
+        self.entries.get().len() * BUCKET_SIZE
+    }
+
+    /// Get the number of lookups since the last reset
+    pub fn lookups_count(&self) -> u64 {
+        self.stats_lookups
+    }
+
+    /// Get the number of lookups finding the position since the last reset
+    pub fn hits_count(&self) -> u64 {
+        self.stats_hits
     }
 
     pub fn memory(&self) -> usize {
@@ -114,7 +224,8 @@ impl TranspositionTable {
         let mut exact_count = 0;
         let mut upper_count = 0;
         let mut lower_count = 0;
-        for t in self.entries.get() {
+        for entry in self.entries.get().iter().flat_map(|bucket| bucket.entries.iter()) {
+            let t = entry.load();
             if t.best_move().is_null() {
                 continue;
             }
@@ -151,35 +262,69 @@ impl TranspositionTable {
     }
 }
 
+// Number of buckets holding the given number of entries, at least one
+fn bucket_count(capacity: usize) -> usize {
+    (capacity / BUCKET_SIZE).max(1)
+}
+
+/// The buckets of the table shared by the threads of the search inside an
+/// `Arc`, their entries being read and written atomically
 pub struct SharedTable {
-    inner: UnsafeCell<Box<[Transposition]>>
+    ptr: *mut Bucket,
+    len: usize,
+    layout: Layout
 }
 
-// Tell the compiler than the transposition table can be shared between
-// threads inside an `Arc`, even if it's not really safe at all in reality :)
+// The buckets are only accessed through atomic operations
+unsafe impl Send for SharedTable {}
 unsafe impl Sync for SharedTable {}
 
 impl SharedTable {
-    pub fn with_capacity(capacity: usize) -> SharedTable {
-        SharedTable {
-            // NOTE: Transmuting a boxed slice of zeroed 128 bit integers into
-            // empty transpositions is much faster than creating a boxed slice
-            // of transitions directly.
-            // inner: UnsafeCell::new(vec![Transposition::new_null(); capacity].into_boxed_slice())
-            inner: UnsafeCell::new(unsafe {
-                mem::transmute::<Box<[u128]>, Box<[Transposition]>>(
-                    vec![0u128; capacity].into_boxed_slice()
-                )
-            })
+    pub fn with_capacity(capacity: usize, huge_pages: bool) -> SharedTable {
+        let mut layout = Layout::array::<Bucket>(capacity).unwrap();
+        let huge_pages = huge_pages && layout.size() >= HUGE_PAGE_SIZE;
+        if huge_pages {
+            layout = layout.align_to(HUGE_PAGE_SIZE).unwrap();
+        }
+
+        // NOTE: Allocating zeroed memory for the buckets is much faster
+        // than creating a boxed slice of empty buckets directly, and
+        // zeroed entries are empty.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) as *mut Bucket };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        if huge_pages {
+            advise_huge_pages(ptr as *mut u8, layout.size());
         }
+
+        SharedTable { ptr, len: capacity, layout }
+    }
+
+    pub fn get(&self) -> &[Bucket] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
     }
+}
 
-    // FIXME: mutable borrow from immutable input
-    pub fn get(&self) -> &mut [Transposition] {
-        unsafe { &mut *self.inner.get() }
+impl Drop for SharedTable {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr as *mut u8, self.layout) }
     }
 }
 
+// Ask the kernel to back the given memory with transparent huge pages,
+// keeping the normal pages if it fails
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(ptr: *mut u8, size: usize) {
+    unsafe {
+        libc::madvise(ptr as *mut libc::c_void, size, libc::MADV_HUGEPAGE);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_ptr: *mut u8, _size: usize) {
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Barrier};
@@ -217,6 +362,7 @@ mod tests {
         assert_eq!(t.score(), s);
         assert_eq!(t.depth(), d);
 
+        tt.prefetch(h);
         tt.set(h, d, s, m, b);
 
         assert_eq!(tt.get(h).unwrap().best_move(), m);
@@ -225,6 +371,53 @@ mod tests {
         assert_eq!(tt.get(h), None);
     }
 
+    #[test]
+    fn test_transposition_table_buckets() {
+        assert_eq!(mem::size_of::<Bucket>(), 64);
+        assert_eq!(mem::align_of::<Bucket>(), 64);
+
+        let mut tt = TranspositionTable::with_capacity(32); // 8 buckets
+        let m = PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH);
+        let b = Bound::Exact;
+
+        // A deep entry is kept after filling its bucket with shallow ones
+        tt.set(1, 10, 0, m, b);
+        for i in 1..10 {
+            tt.set(1 + 8 * i, -1, 0, m, b);
+        }
+        assert_eq!(tt.get(1).map(|t| t.depth()), Some(10));
+        assert!(tt.get(1 + 8 * 9).is_some()); // The last one replaced another
+
+        // The entry of the same position is updated
+        tt.set(1, 12, 42, m, b);
+        assert_eq!(tt.get(1).map(|t| t.score()), Some(42));
+
+        // Until it gets too old for its depth
+        for _ in 0..2 {
+            tt.reset();
+        }
+        for i in 10..14 {
+            tt.set(1 + 8 * i, 0, 0, m, b);
+        }
+        assert!(tt.get(1).is_none());
+    }
+
+    #[test]
+    fn test_transposition_table_huge_pages() {
+        let mut tt = TranspositionTable::with_memory(4 << 20);
+        let m = PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH);
+        tt.set(42, 8, 100, m, Bound::Exact);
+
+        // The table is cleared by the new allocation
+        tt.set_huge_pages(true);
+        assert!(tt.has_huge_pages());
+        assert_eq!(tt.memory(), 4 << 20);
+        assert_eq!(tt.get(42), None);
+
+        tt.set(42, 8, 100, m, Bound::Exact);
+        assert_eq!(tt.get(42).unwrap().best_move(), m);
+    }
+
     #[test]
     fn test_transposition_table_in_threads() {
         // Transposition content
@@ -255,4 +448,33 @@ mod tests {
             assert_eq!(child.join().unwrap(), m);
         }
     }
+
+    #[test]
+    fn test_transposition_table_lockless() {
+        let n = 4;
+        let mut children = Vec::with_capacity(n);
+        let shared_tt = TranspositionTable::with_capacity(32);
+        for i in 0..n {
+            let mut tt = shared_tt.clone();
+            children.push(thread::spawn(move || {
+                // Every thread writes entries with a score and a depth
+                // given by their hash in the same buckets as the others
+                for j in 0..10000 {
+                    let h = (i * 10000 + j) as u64;
+                    let m = PieceMove::new(E2, E4, DOUBLE_PAWN_PUSH);
+                    tt.set(h, (h % 100) as Depth, h as Score, m, Bound::Exact);
+                    for k in 0..64 {
+                        if let Some(t) = tt.get(k) {
+                            assert_eq!(t.score(), k as Score);
+                            assert_eq!(t.depth(), (k % 100) as Depth);
+                        }
+                    }
+                }
+            }));
+        }
+
+        for child in children {
+            child.join().unwrap();
+        }
+    }
 }
@@ -0,0 +1,110 @@
+/// Rules of the game played
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    Crazyhouse, // Captured pieces go into the hand and can be dropped
+    Atomic, // Captures explode the pieces around except the pawns
+    ThreeCheck, // Giving check three times wins the game
+    Antichess // Captures are compulsory and losing all the pieces wins the game
+}
+
+pub const VARIANTS: [Variant; 5] = [
+    Variant::Standard,
+    Variant::Crazyhouse,
+    Variant::Atomic,
+    Variant::ThreeCheck,
+    Variant::Antichess
+];
+
+/// Number of checks winning the game in Three-check
+pub const CHECKS_LIMIT: u8 = 3;
+
+impl Variant {
+    /// Get the name of the variant, as used by the `UCI_Variant` option
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard   => "chess",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::Atomic     => "atomic",
+            Variant::ThreeCheck => "3check",
+            Variant::Antichess  => "antichess"
+        }
+    }
+
+    /// Get the name of the variant, as used by the `variant` command of the
+    /// XBoard protocol
+    pub fn xboard_name(self) -> &'static str {
+        match self {
+            Variant::Standard  => "normal",
+            Variant::Antichess => "giveaway",
+            _                  => self.name()
+        }
+    }
+
+    /// Get the variant with the given name
+    pub fn from_name(name: &str) -> Option<Variant> {
+        let name = name.to_lowercase();
+        match name.as_str() {
+            "standard" | "normal" => Some(Variant::Standard),
+            "threecheck" | "three-check" => Some(Variant::ThreeCheck),
+            "giveaway" => Some(Variant::Antichess),
+            _ => VARIANTS.iter().cloned().find(|v| v.name() == name)
+        }
+    }
+
+    /// Check if the pieces captured can be dropped back on the board
+    pub fn has_drops(self) -> bool {
+        self == Variant::Crazyhouse
+    }
+
+    /// Check if the captures explode the pieces around them
+    pub fn has_explosions(self) -> bool {
+        self == Variant::Atomic
+    }
+
+    /// Check if the king must be kept out of check, otherwise it is an
+    /// ordinary piece that can be captured
+    pub fn has_royal_king(self) -> bool {
+        self != Variant::Antichess
+    }
+
+    /// Check if a capture must be played when possible
+    pub fn has_forced_captures(self) -> bool {
+        self == Variant::Antichess
+    }
+
+    /// Check if the checks given are counted to win the game
+    pub fn has_checks_limit(self) -> bool {
+        self == Variant::ThreeCheck
+    }
+
+    /// Check if the goal is to lose all the pieces, the side left without
+    /// legal moves winning the game
+    pub fn has_losing_goal(self) -> bool {
+        self == Variant::Antichess
+    }
+
+    /// Check if the endgame knowledge of the standard rules applies, like
+    /// the bitbases and the recognizers
+    pub fn has_standard_endgames(self) -> bool {
+        self == Variant::Standard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Variant::from_name("chess"), Some(Variant::Standard));
+        assert_eq!(Variant::from_name("Crazyhouse"), Some(Variant::Crazyhouse));
+        assert_eq!(Variant::from_name("three-check"), Some(Variant::ThreeCheck));
+        assert_eq!(Variant::from_name("shogi"), None);
+        for &v in &VARIANTS {
+            assert_eq!(Variant::from_name(v.name()), Some(v));
+            assert_eq!(Variant::from_name(v.xboard_name()), Some(v));
+        }
+    }
+}
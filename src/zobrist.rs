@@ -1,29 +1,42 @@
-use rand::{RngCore, SeedableRng};
-use rand_xorshift::XorShiftRng;
-use color::Color;
-use piece::Piece;
+use color::*;
+use common::MAX_HAND;
+use piece::*;
+use random::Random;
+use variant::CHECKS_LIMIT;
 
 #[derive(Clone)]
 pub struct Zobrist {
     pub pieces: [[u64; 64]; 14],
     pub en_passant: [u64; 64],
     pub castling_rights: [[u64; 2]; 2],
-    pub side: u64
+    pub side: u64,
+    pub hands: [[u64; MAX_HAND]; 14], // Indexed by the number of pieces in hand
+    pub checks: [[u64; CHECKS_LIMIT as usize]; 2] // Indexed by the number of checks given
 }
 
-const SEED: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+// The keys must stay the same between builds because they are saved in the
+// learning files
+const SEED: u64 = 0x0807060504030201;
+
+/// Number of keys in the random array of the Polyglot opening books
+pub const POLYGLOT_KEYS_COUNT: usize = 781;
 
 impl Zobrist {
     pub fn new() -> Zobrist {
+        Zobrist::from_rng(&mut Random::new(SEED))
+    }
+
+    /// Create the keys from the numbers of the given generator
+    pub fn from_rng(rng: &mut Random) -> Zobrist {
         let mut zobrist = Zobrist {
             pieces: [[0; 64]; 14],
             en_passant: [0; 64],
             castling_rights: [[0; 2]; 2],
-            side: 0
+            side: 0,
+            hands: [[0; MAX_HAND]; 14],
+            checks: [[0; CHECKS_LIMIT as usize]; 2]
         };
 
-        let mut rng = XorShiftRng::from_seed(SEED);
-
         for i in 0..14 {
             for j in 0..64 {
                 zobrist.pieces[i][j] = rng.next_u64();
@@ -38,6 +51,50 @@ impl Zobrist {
             }
         }
         zobrist.side = rng.next_u64();
+        for i in 0..14 {
+            for j in 0..MAX_HAND {
+                zobrist.hands[i][j] = rng.next_u64();
+            }
+        }
+        for i in 0..2 {
+            for j in 0..(CHECKS_LIMIT as usize) {
+                zobrist.checks[i][j] = rng.next_u64();
+            }
+        }
+
+        zobrist
+    }
+
+    /// Create the keys from the random array of the Polyglot opening books,
+    /// with the pieces, castling rights, en passant files, and side to move
+    /// indexed like in their key construction
+    ///
+    /// The key of the side to move is used for black instead of white, and
+    /// the key of an en passant file even without a pawn able to capture,
+    /// which is adjusted by `Game::polyglot_key`. The pieces in hand and the
+    /// checks keep their own keys.
+    pub fn from_polyglot(randoms: &[u64; POLYGLOT_KEYS_COUNT]) -> Zobrist {
+        let mut zobrist = Zobrist::new();
+
+        // Black pieces before white ones in the order of `PIECES`
+        for (i, &kind) in PIECES.iter().enumerate() {
+            for &c in &COLORS {
+                let k = 2 * i + if c == WHITE { 1 } else { 0 };
+                for sq in 0..64 {
+                    zobrist.pieces[(c | kind) as usize][sq] = randoms[64 * k + sq];
+                }
+            }
+        }
+        for &c in &COLORS {
+            for (i, &wing) in [KING, QUEEN].iter().enumerate() {
+                let k = 768 + 2 * (c as usize) + i;
+                zobrist.castling_rights[c as usize][(wing >> 3) as usize] = randoms[k];
+            }
+        }
+        for sq in 0..64 {
+            zobrist.en_passant[sq] = randoms[772 + sq % 8];
+        }
+        zobrist.side = randoms[780];
 
         zobrist
     }
@@ -45,15 +102,58 @@ impl Zobrist {
     pub fn castling_right(&self, side: Color, wing: Piece) -> u64 {
         self.castling_rights[side as usize][(wing >> 3) as usize]
     }
+
+    /// Get the key of the given piece added to a hand already holding `n`
+    /// of them
+    pub fn hand(&self, piece: Piece, n: u8) -> u64 {
+        self.hands[piece as usize][n as usize]
+    }
+
+    /// Get the key of a check given by the given side after `n` of them
+    pub fn check(&self, side: Color, n: u8) -> u64 {
+        self.checks[side as usize][n as usize]
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use zobrist::Zobrist;
+    use color::*;
+    use piece::*;
+    use random::Random;
+    use zobrist::*;
 
     #[test]
     fn test_new() {
         let zobrist = Zobrist::new();
         assert!(zobrist.pieces[0][0] != zobrist.pieces[7][42]);
+        assert_eq!(zobrist.side, Zobrist::new().side);
+    }
+
+    #[test]
+    fn test_from_polyglot() {
+        let mut rng = Random::new(42);
+        let mut randoms = [0; POLYGLOT_KEYS_COUNT];
+        for k in randoms.iter_mut() {
+            *k = rng.next_u64();
+        }
+        let zobrist = Zobrist::from_polyglot(&randoms);
+        assert_eq!(zobrist.pieces[BLACK_PAWN as usize][0], randoms[0]);
+        assert_eq!(zobrist.pieces[WHITE_PAWN as usize][8], randoms[64 + 8]);
+        assert_eq!(zobrist.pieces[WHITE_KING as usize][4], randoms[64 * 11 + 4]);
+        assert_eq!(zobrist.castling_right(WHITE, KING), randoms[768]);
+        assert_eq!(zobrist.castling_right(WHITE, QUEEN), randoms[769]);
+        assert_eq!(zobrist.castling_right(BLACK, KING), randoms[770]);
+        assert_eq!(zobrist.castling_right(BLACK, QUEEN), randoms[771]);
+        assert_eq!(zobrist.en_passant[44], randoms[772 + 4]);
+        assert_eq!(zobrist.side, randoms[780]);
+    }
+
+    #[test]
+    fn test_from_rng() {
+        let a = Zobrist::from_rng(&mut Random::new(42));
+        let b = Zobrist::from_rng(&mut Random::new(42));
+        let c = Zobrist::from_rng(&mut Random::new(43));
+        assert_eq!(a.pieces[7][42], b.pieces[7][42]);
+        assert_ne!(a.pieces[7][42], c.pieces[7][42]);
     }
 }